@@ -0,0 +1,9 @@
+//! Run with `cargo fuzz run parse_class`, `tests/*.class` make a good seed corpus
+#![no_main]
+
+use jerris::class::Class;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Class::from_bytes(data);
+});
@@ -1,3 +1,5 @@
+use std::fmt::Debug;
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -10,6 +12,33 @@ bitflags! {
         const ACC_SYNTHETIC = 0x1000;
         const ACC_ANNOTATION = 0x2000;
         const ACC_ENUM = 0x4000;
+        const ACC_MODULE = 0x8000;
+    }
+}
+
+/// Adds `from_bits_retain` to flags that are read from class files
+macro_rules! retain_bits {
+    ($($flags:ident),*) => {$(
+        impl $flags {
+            /// Keeps bits without a meaning too, which the JVM ignores, so they're written back unchanged
+            pub fn from_bits_retain(bits: u16) -> Self {
+                Self { bits }
+            }
+        }
+    )*};
+}
+
+impl ClassAccessFlags {
+    /// Flags with a meaning in class files of version `major`
+    pub fn defined_in(major: u16) -> Self {
+        let mut flags = Self::ACC_PUBLIC | Self::ACC_FINAL | Self::ACC_SUPER | Self::ACC_INTERFACE | Self::ACC_ABSTRACT;
+        if major >= 49 {
+            flags |= Self::ACC_SYNTHETIC | Self::ACC_ANNOTATION | Self::ACC_ENUM;
+        }
+        if major >= 53 {
+            flags |= Self::ACC_MODULE;
+        }
+        flags
     }
 }
 bitflags! {
@@ -25,6 +54,18 @@ bitflags! {
         const ACC_ENUM = 0x4000;
     }
 }
+
+impl FieldAccessFlags {
+    /// Flags with a meaning in class files of version `major`
+    pub fn defined_in(major: u16) -> Self {
+        let mut flags = Self::ACC_PUBLIC | Self::ACC_PRIVATE | Self::ACC_PROTECTED | Self::ACC_STATIC | Self::ACC_FINAL |
+            Self::ACC_VOLATILE | Self::ACC_TRANSIENT;
+        if major >= 49 {
+            flags |= Self::ACC_SYNTHETIC | Self::ACC_ENUM;
+        }
+        flags
+    }
+}
 bitflags! {
     pub struct MethodAccessFlags: u16 {
         const ACC_PUBLIC = 0x0001;
@@ -40,4 +81,88 @@ bitflags! {
         const ACC_STRICT = 0x0800;
         const ACC_SYNTHETIC = 0x1000;
     }
-}
\ No newline at end of file
+}
+
+impl MethodAccessFlags {
+    /// Flags with a meaning in class files of version `major`
+    ///
+    /// `ACC_STRICT` was added in Java 1.2 and lost its meaning when Java 17 made all floating point strict.
+    pub fn defined_in(major: u16) -> Self {
+        let mut flags = Self::ACC_PUBLIC | Self::ACC_PRIVATE | Self::ACC_PROTECTED | Self::ACC_STATIC | Self::ACC_FINAL |
+            Self::ACC_SYNCHRONIZED | Self::ACC_NATIVE | Self::ACC_ABSTRACT;
+        if major >= 49 {
+            flags |= Self::ACC_BRIDGE | Self::ACC_VARARGS | Self::ACC_SYNTHETIC;
+        }
+        if (46..=60).contains(&major) {
+            flags |= Self::ACC_STRICT;
+        }
+        flags
+    }
+}
+bitflags! {
+    pub struct InnerClassAccessFlags: u16 {
+        const ACC_PUBLIC = 0x0001;
+        const ACC_PRIVATE = 0x0002;
+        const ACC_PROTECTED = 0x0004;
+        const ACC_STATIC = 0x0008;
+        const ACC_FINAL = 0x0010;
+        const ACC_INTERFACE = 0x0200;
+        const ACC_ABSTRACT = 0x0400;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_ANNOTATION = 0x2000;
+        const ACC_ENUM = 0x4000;
+    }
+}
+bitflags! {
+    pub struct MethodParameterAccessFlags: u16 {
+        const ACC_FINAL = 0x0010;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+}
+bitflags! {
+    pub struct ModuleFlags: u16 {
+        const ACC_OPEN = 0x0020;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+}
+bitflags! {
+    pub struct RequiresFlags: u16 {
+        const ACC_TRANSITIVE = 0x0020;
+        const ACC_STATIC_PHASE = 0x0040;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+}
+bitflags! {
+    /// Flags of both the `exports` and `opens` tables of the `Module` attribute
+    pub struct ExportsFlags: u16 {
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_MANDATED = 0x8000;
+    }
+}
+
+retain_bits!(ClassAccessFlags, FieldAccessFlags, MethodAccessFlags, InnerClassAccessFlags, MethodParameterAccessFlags, ModuleFlags, RequiresFlags, ExportsFlags);
+
+/// Name of every bit set in `bits`, like `ACC_PUBLIC`, from the `Debug` output of the flags `from_bits_truncate` creates
+///
+/// Bits without a name are written in hex, like `0x0100`.
+pub(crate) fn flag_names<T: Debug>(bits: u16, from_bits_truncate: fn(u16) -> T) -> Vec<String> {
+    (0..16).map(|shift| 1 << shift).filter(|bit| bits & bit != 0).map(|bit| {
+        let name = format!("{:?}", from_bits_truncate(bit));
+        if name.starts_with("ACC_") {
+            name
+        } else {
+            format!("{:#06x}", bit)
+        }
+    }).collect()
+}
+
+/// The bit called `name` by [`flag_names`]
+pub(crate) fn flag_bit<T: Debug>(name: &str, from_bits_truncate: fn(u16) -> T) -> Option<u16> {
+    match name.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => (0..16).map(|shift| 1 << shift).find(|&bit| format!("{:?}", from_bits_truncate(bit)) == name),
+    }
+}
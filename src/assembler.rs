@@ -0,0 +1,503 @@
+//! Building bytecode out of [`Instruction`]s, with labels instead of hand-computed offsets
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::code::{CodeAttribute, ExceptionTableEntry};
+use crate::instruction::{Instruction, Opcode, encode_wide, switch_padding};
+
+/// A position in the code, created by [`Assembler::new_label`] and placed with [`Assembler::bind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AssembleError {
+    #[error("label {0:?} is used but never bound")]
+    UnboundLabel(Label),
+    #[error("label {0:?} comes from another assembler")]
+    UnknownLabel(Label),
+    #[error("label {0:?} is bound twice")]
+    LabelBoundTwice(Label),
+    #[error("{} isn't a branch instruction", .0.mnemonic())]
+    NotABranch(Opcode),
+    #[error("{} can't have the wide prefix", .0.mnemonic())]
+    NotWidenable(Opcode),
+    #[error("tableswitch needs at least one target")]
+    EmptyTableswitch,
+    #[error("tableswitch starting at {low} has too many targets")]
+    TableswitchOverflow { low: i32 },
+    #[error("lookupswitch has key {0} twice")]
+    DuplicateSwitchKey(i32),
+    #[error("code is {0} bytes long, it can't be longer than 65535")]
+    CodeTooLong(u32),
+    #[error("code is empty")]
+    EmptyCode,
+    #[error("exception handler range {start_pc}..{end_pc} is empty")]
+    EmptyTryRange { start_pc: u32, end_pc: u32 },
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Instruction(Instruction),
+    /// A local variable instruction with the `wide` prefix
+    Wide(Instruction),
+    Branch { opcode: Opcode, target: Label },
+    Tableswitch { low: i32, default: Label, targets: Vec<Label> },
+    Lookupswitch { default: Label, pairs: Vec<(i32, Label)> },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: u16,
+}
+
+/// Emits instructions into a code buffer, resolving labels into offsets when it's finished
+///
+/// Branches that can't reach their target with a 16 bit offset are made long: `goto` and `jsr` become `goto_w`
+/// and `jsr_w`, and conditional branches jump over a `goto_w` with the opposite condition.
+#[derive(Debug, Clone, Default)]
+pub struct Assembler {
+    items: Vec<Item>,
+    /// The index of the item each label is bound before, `None` if it isn't bound yet
+    labels: Vec<Option<usize>>,
+    handlers: Vec<Handler>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places `label` right before the next instruction
+    pub fn bind(&mut self, label: Label) -> Result<(), AssembleError> {
+        let slot = self.labels.get_mut(label.0).ok_or(AssembleError::UnknownLabel(label))?;
+        if slot.is_some() {
+            return Err(AssembleError::LabelBoundTwice(label));
+        }
+        *slot = Some(self.items.len());
+        Ok(())
+    }
+
+    /// Emits an instruction as it is
+    ///
+    /// Branch offsets in `instruction` are kept untouched, use [`Assembler::branch`] to jump to a label instead.
+    pub fn emit(&mut self, instruction: Instruction) {
+        self.items.push(Item::Instruction(instruction));
+    }
+
+    /// Emits a local variable instruction with the `wide` prefix, even if its operands fit without it
+    pub fn emit_wide(&mut self, instruction: Instruction) -> Result<(), AssembleError> {
+        if encode_wide(&instruction, &mut vec![]).is_none() {
+            return Err(AssembleError::NotWidenable(instruction.opcode()));
+        }
+        self.items.push(Item::Wide(instruction));
+        Ok(())
+    }
+
+    /// Emits `ldc`, or `ldc_w` if `index` doesn't fit in a byte
+    pub fn ldc(&mut self, index: u16) {
+        match u8::try_from(index) {
+            Ok(index) => self.emit(Instruction::Ldc(index)),
+            Err(_) => self.emit(Instruction::LdcW(index)),
+        }
+    }
+
+    /// Emits a branch instruction like `ifeq` or `goto` jumping to `target`
+    pub fn branch(&mut self, opcode: Opcode, target: Label) -> Result<(), AssembleError> {
+        if short_branch(opcode, 0).is_none() && !matches!(opcode, Opcode::GotoW | Opcode::JsrW) {
+            return Err(AssembleError::NotABranch(opcode));
+        }
+        self.items.push(Item::Branch { opcode, target });
+        Ok(())
+    }
+
+    /// Emits a `tableswitch` jumping to `targets[i]` for the value `low + i`
+    pub fn tableswitch(&mut self, low: i32, default: Label, targets: Vec<Label>) -> Result<(), AssembleError> {
+        if targets.is_empty() {
+            return Err(AssembleError::EmptyTableswitch);
+        }
+        i32::try_from(low as i64 + targets.len() as i64 - 1).map_err(|_| AssembleError::TableswitchOverflow { low })?;
+        self.items.push(Item::Tableswitch { low, default, targets });
+        Ok(())
+    }
+
+    /// Emits a `lookupswitch`, the pairs are sorted by key as the JVM requires
+    pub fn lookupswitch(&mut self, default: Label, mut pairs: Vec<(i32, Label)>) -> Result<(), AssembleError> {
+        pairs.sort_by_key(|(key, _)| *key);
+        if let Some(pair) = pairs.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(AssembleError::DuplicateSwitchKey(pair[0].0));
+        }
+        self.items.push(Item::Lookupswitch { default, pairs });
+        Ok(())
+    }
+
+    /// Adds an exception handler covering the code from `start` to `end`, exclusive
+    ///
+    /// `catch_type` points to the class of exceptions caught in the constant pool, or is 0 to catch everything.
+    /// Handlers are searched in the order they're added.
+    pub fn try_catch(&mut self, start: Label, end: Label, handler: Label, catch_type: u16) {
+        self.handlers.push(Handler { start, end, handler, catch_type });
+    }
+
+    /// Resolves every label and produces the `Code` attribute
+    pub fn finish(self, max_stack: u16, max_locals: u16) -> Result<CodeAttribute, AssembleError> {
+        self.finish_with_labels(max_stack, max_locals).map(|(code, _)| code)
+    }
+
+    /// Like [`Assembler::finish`], also giving the pc every bound label ended up at, for tables pointing into the code
+    pub fn finish_with_labels(self, max_stack: u16, max_locals: u16) -> Result<(CodeAttribute, HashMap<Label, u32>), AssembleError> {
+        let positions = self.label_positions()?;
+        let long = self.long_branches(&positions);
+        let pcs = self.layout(&long);
+        let code_len = pcs[self.items.len()];
+        if code_len == 0 {
+            return Err(AssembleError::EmptyCode);
+        }
+        if code_len > u16::MAX as u32 {
+            return Err(AssembleError::CodeTooLong(code_len));
+        }
+        let label_pc = |label: &Label| pcs[positions[label.0]];
+        let mut code = Vec::with_capacity(code_len as usize);
+        for (i, item) in self.items.iter().enumerate() {
+            let pc = pcs[i];
+            let offset = |label: &Label| label_pc(label) as i32 - pc as i32;
+            match item {
+                Item::Instruction(instruction) => instruction.encode(pc, &mut code),
+                Item::Wide(instruction) => {
+                    encode_wide(instruction, &mut code);
+                }
+                Item::Branch { opcode, target } => {
+                    let offset = offset(target);
+                    if !long[i] {
+                        short_branch(*opcode, offset as i16).unwrap().encode(pc, &mut code);
+                    } else if let Some(instruction) = long_branch(*opcode, offset) {
+                        instruction.encode(pc, &mut code);
+                    } else {
+                        // Skip the goto_w below when the condition doesn't hold
+                        short_branch(opposite(*opcode), 8).unwrap().encode(pc, &mut code);
+                        Instruction::GotoW(offset - 3).encode(pc + 3, &mut code);
+                    }
+                }
+                Item::Tableswitch { low, default, targets } => Instruction::Tableswitch {
+                    default: offset(default),
+                    low: *low,
+                    high: low + targets.len() as i32 - 1,
+                    offsets: targets.iter().map(offset).collect(),
+                }.encode(pc, &mut code),
+                Item::Lookupswitch { default, pairs } => Instruction::Lookupswitch {
+                    default: offset(default),
+                    pairs: pairs.iter().map(|(key, target)| (*key, offset(target))).collect(),
+                }.encode(pc, &mut code),
+            }
+        }
+        let mut exception_table = Vec::with_capacity(self.handlers.len());
+        for handler in &self.handlers {
+            let (start_pc, end_pc) = (label_pc(&handler.start), label_pc(&handler.end));
+            if start_pc >= end_pc {
+                return Err(AssembleError::EmptyTryRange { start_pc, end_pc });
+            }
+            exception_table.push(ExceptionTableEntry {
+                start_pc: start_pc as u16,
+                end_pc: end_pc as u16,
+                handler_pc: label_pc(&handler.handler) as u16,
+                catch_type: handler.catch_type,
+            });
+        }
+        let labels = self.labels.iter().enumerate()
+            .filter_map(|(label, position)| position.map(|position| (Label(label), pcs[position])))
+            .collect();
+        Ok((CodeAttribute {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes: vec![],
+        }, labels))
+    }
+
+    /// The item index of every label, failing if one that's used isn't bound or isn't one of this assembler's
+    fn label_positions(&self) -> Result<Vec<usize>, AssembleError> {
+        let mut used = vec![];
+        for item in &self.items {
+            match item {
+                Item::Instruction(_) | Item::Wide(_) => {}
+                Item::Branch { target, .. } => used.push(*target),
+                Item::Tableswitch { default, targets, .. } => {
+                    used.push(*default);
+                    used.extend(targets);
+                }
+                Item::Lookupswitch { default, pairs } => {
+                    used.push(*default);
+                    used.extend(pairs.iter().map(|(_, target)| *target));
+                }
+            }
+        }
+        for handler in &self.handlers {
+            used.extend([handler.start, handler.end, handler.handler]);
+        }
+        for label in used {
+            match self.labels.get(label.0) {
+                Some(Some(_)) => {}
+                Some(None) => return Err(AssembleError::UnboundLabel(label)),
+                None => return Err(AssembleError::UnknownLabel(label)),
+            }
+        }
+        // Unused labels don't matter, put them anywhere
+        Ok(self.labels.iter().map(|position| position.unwrap_or(0)).collect())
+    }
+
+    /// Decides which branches need the long form
+    ///
+    /// Making a branch long only ever moves code further apart, so this stops once no more branches change.
+    fn long_branches(&self, positions: &[usize]) -> Vec<bool> {
+        let mut long: Vec<bool> = self.items.iter()
+            .map(|item| matches!(item, Item::Branch { opcode: Opcode::GotoW | Opcode::JsrW, .. }))
+            .collect();
+        loop {
+            let pcs = self.layout(&long);
+            let mut changed = false;
+            for (i, item) in self.items.iter().enumerate() {
+                if let Item::Branch { target, .. } = item {
+                    let offset = pcs[positions[target.0]] as i64 - pcs[i] as i64;
+                    if !long[i] && i16::try_from(offset).is_err() {
+                        long[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return long;
+            }
+        }
+    }
+
+    /// The pc of every item, plus the length of the code at the end
+    fn layout(&self, long: &[bool]) -> Vec<u32> {
+        let mut pcs = Vec::with_capacity(self.items.len() + 1);
+        let mut pc = 0u32;
+        for (i, item) in self.items.iter().enumerate() {
+            pcs.push(pc);
+            pc += match item {
+                Item::Instruction(instruction) => instruction.encoded_len(pc),
+                Item::Wide(Instruction::Iinc { .. }) => 6,
+                Item::Wide(_) => 4,
+                Item::Branch { opcode, .. } => match (long[i], opcode) {
+                    (false, _) => 3,
+                    (true, Opcode::Goto | Opcode::Jsr | Opcode::GotoW | Opcode::JsrW) => 5,
+                    (true, _) => 8,
+                },
+                Item::Tableswitch { targets, .. } => 1 + switch_padding(pc) + 12 + 4 * targets.len() as u32,
+                Item::Lookupswitch { pairs, .. } => 1 + switch_padding(pc) + 8 + 8 * pairs.len() as u32,
+            };
+        }
+        pcs.push(pc);
+        pcs
+    }
+}
+
+/// The branch instruction for `opcode` with a 16 bit offset, `None` if it isn't one
+fn short_branch(opcode: Opcode, offset: i16) -> Option<Instruction> {
+    Some(match opcode {
+        Opcode::Ifeq => Instruction::Ifeq(offset),
+        Opcode::Ifne => Instruction::Ifne(offset),
+        Opcode::Iflt => Instruction::Iflt(offset),
+        Opcode::Ifge => Instruction::Ifge(offset),
+        Opcode::Ifgt => Instruction::Ifgt(offset),
+        Opcode::Ifle => Instruction::Ifle(offset),
+        Opcode::IfIcmpeq => Instruction::IfIcmpeq(offset),
+        Opcode::IfIcmpne => Instruction::IfIcmpne(offset),
+        Opcode::IfIcmplt => Instruction::IfIcmplt(offset),
+        Opcode::IfIcmpge => Instruction::IfIcmpge(offset),
+        Opcode::IfIcmpgt => Instruction::IfIcmpgt(offset),
+        Opcode::IfIcmple => Instruction::IfIcmple(offset),
+        Opcode::IfAcmpeq => Instruction::IfAcmpeq(offset),
+        Opcode::IfAcmpne => Instruction::IfAcmpne(offset),
+        Opcode::Goto => Instruction::Goto(offset),
+        Opcode::Jsr => Instruction::Jsr(offset),
+        Opcode::Ifnull => Instruction::Ifnull(offset),
+        Opcode::Ifnonnull => Instruction::Ifnonnull(offset),
+        _ => return None,
+    })
+}
+
+/// The unconditional branch for `opcode` with a 32 bit offset, `None` for conditional branches
+fn long_branch(opcode: Opcode, offset: i32) -> Option<Instruction> {
+    match opcode {
+        Opcode::Goto | Opcode::GotoW => Some(Instruction::GotoW(offset)),
+        Opcode::Jsr | Opcode::JsrW => Some(Instruction::JsrW(offset)),
+        _ => None,
+    }
+}
+
+/// The conditional branch that jumps exactly when `opcode` doesn't
+fn opposite(opcode: Opcode) -> Opcode {
+    match opcode {
+        Opcode::Ifeq => Opcode::Ifne,
+        Opcode::Ifne => Opcode::Ifeq,
+        Opcode::Iflt => Opcode::Ifge,
+        Opcode::Ifge => Opcode::Iflt,
+        Opcode::Ifgt => Opcode::Ifle,
+        Opcode::Ifle => Opcode::Ifgt,
+        Opcode::IfIcmpeq => Opcode::IfIcmpne,
+        Opcode::IfIcmpne => Opcode::IfIcmpeq,
+        Opcode::IfIcmplt => Opcode::IfIcmpge,
+        Opcode::IfIcmpge => Opcode::IfIcmplt,
+        Opcode::IfIcmpgt => Opcode::IfIcmple,
+        Opcode::IfIcmple => Opcode::IfIcmpgt,
+        Opcode::IfAcmpeq => Opcode::IfAcmpne,
+        Opcode::IfAcmpne => Opcode::IfAcmpeq,
+        Opcode::Ifnull => Opcode::Ifnonnull,
+        Opcode::Ifnonnull => Opcode::Ifnull,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssembleError, Assembler};
+    use crate::instruction::{Instruction, Instructions, Opcode};
+
+    fn decode(code: &[u8]) -> Vec<(u32, Instruction)> {
+        Instructions::new(code).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    pub fn resolves_forward_and_backward_branches() {
+        let mut asm = Assembler::new();
+        let top = asm.new_label();
+        let end = asm.new_label();
+        asm.bind(top).unwrap();
+        asm.emit(Instruction::Iload(0));
+        asm.branch(Opcode::Ifeq, end).unwrap();
+        asm.emit(Instruction::Iinc { index: 0, value: -1 });
+        asm.branch(Opcode::Goto, top).unwrap();
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+        let code = asm.finish(1, 1).unwrap();
+        assert_eq!(decode(&code.code), vec![
+            (0, Instruction::Iload(0)),
+            (2, Instruction::Ifeq(9)),
+            (5, Instruction::Iinc { index: 0, value: -1 }),
+            (8, Instruction::Goto(-8)),
+            (11, Instruction::Return),
+        ]);
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+    }
+
+    #[test]
+    pub fn reports_label_errors() {
+        let mut asm = Assembler::new();
+        let (bound, unbound) = (asm.new_label(), asm.new_label());
+        asm.bind(bound).unwrap();
+        assert_eq!(asm.bind(bound), Err(AssembleError::LabelBoundTwice(bound)));
+        asm.branch(Opcode::Goto, unbound).unwrap();
+        assert_eq!(asm.finish(0, 0), Err(AssembleError::UnboundLabel(unbound)));
+
+        let foreign = Assembler::new().new_label();
+        let mut other = Assembler::new();
+        assert_eq!(other.bind(foreign), Err(AssembleError::UnknownLabel(foreign)));
+        other.emit(Instruction::Return);
+        other.lookupswitch(foreign, vec![]).unwrap();
+        assert_eq!(other.finish(0, 0), Err(AssembleError::UnknownLabel(foreign)));
+    }
+
+    #[test]
+    pub fn switch_offsets_are_relative_to_the_switch() {
+        let mut asm = Assembler::new();
+        let (a, default) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::Iconst0);
+        asm.tableswitch(0, default, vec![a]).unwrap();
+        asm.bind(a).unwrap();
+        asm.emit(Instruction::Iconst0);
+        asm.lookupswitch(default, vec![(10, a), (-3, a)]).unwrap();
+        asm.bind(default).unwrap();
+        asm.emit(Instruction::Return);
+        let code = asm.finish(1, 0).unwrap();
+        assert_eq!(decode(&code.code), vec![
+            (0, Instruction::Iconst0),
+            (1, Instruction::Tableswitch { default: 47, low: 0, high: 0, offsets: vec![19] }),
+            (20, Instruction::Iconst0),
+            (21, Instruction::Lookupswitch { default: 27, pairs: vec![(-3, -1), (10, -1)] }),
+            (48, Instruction::Return),
+        ]);
+    }
+
+    #[test]
+    pub fn promotes_far_branches() {
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        asm.emit(Instruction::Iconst0);
+        asm.branch(Opcode::Ifne, end).unwrap();
+        asm.branch(Opcode::Goto, end).unwrap();
+        for _ in 0..40000 {
+            asm.emit(Instruction::Nop);
+        }
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+        let code = asm.finish(1, 0).unwrap();
+        let instructions = decode(&code.code);
+        assert_eq!(&instructions[..4], &[
+            (0, Instruction::Iconst0),
+            (1, Instruction::Ifeq(8)),
+            (4, Instruction::GotoW(40010)),
+            (9, Instruction::GotoW(40005)),
+        ]);
+        assert_eq!(instructions.last(), Some(&(40014, Instruction::Return)));
+    }
+
+    #[test]
+    pub fn builds_exception_table() {
+        let mut asm = Assembler::new();
+        let (start, end, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.bind(start).unwrap();
+        asm.emit(Instruction::Aload(300));
+        asm.emit(Instruction::Athrow);
+        asm.bind(end).unwrap();
+        asm.bind(handler).unwrap();
+        asm.emit(Instruction::Return);
+        asm.try_catch(start, end, handler, 7);
+        let code = asm.finish(1, 301).unwrap();
+        assert_eq!(code.code, vec![0xc4, 0x19, 0x01, 0x2c, 0xbf, 0xb1]);
+        assert_eq!(code.exception_table.len(), 1);
+        assert_eq!((code.exception_table[0].start_pc, code.exception_table[0].end_pc, code.exception_table[0].handler_pc), (0, 5, 5));
+        assert_eq!(code.exception_table[0].catch_type, 7);
+    }
+
+    #[test]
+    pub fn emits_wide_and_reports_label_pcs() {
+        let mut asm = Assembler::new();
+        let (start, end) = (asm.new_label(), asm.new_label());
+        asm.bind(start).unwrap();
+        asm.emit_wide(Instruction::Iload(1)).unwrap();
+        asm.emit_wide(Instruction::Iinc { index: 1, value: -1 }).unwrap();
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+        assert_eq!(asm.emit_wide(Instruction::Return), Err(AssembleError::NotWidenable(Opcode::Return)));
+        let (code, labels) = asm.finish_with_labels(1, 2).unwrap();
+        assert_eq!(code.code, vec![0xc4, 0x15, 0x00, 0x01, 0xc4, 0x84, 0x00, 0x01, 0xff, 0xff, 0xb1]);
+        assert_eq!((labels[&start], labels[&end]), (0, 10));
+    }
+
+    #[test]
+    pub fn reports_errors() {
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        assert_eq!(asm.branch(Opcode::Iadd, label), Err(AssembleError::NotABranch(Opcode::Iadd)));
+        assert_eq!(asm.tableswitch(0, label, vec![]), Err(AssembleError::EmptyTableswitch));
+        assert_eq!(asm.tableswitch(i32::MAX, label, vec![label, label]), Err(AssembleError::TableswitchOverflow { low: i32::MAX }));
+        assert_eq!(asm.lookupswitch(label, vec![(1, label), (1, label)]), Err(AssembleError::DuplicateSwitchKey(1)));
+        assert_eq!(Assembler::new().finish(0, 0), Err(AssembleError::EmptyCode));
+        asm.bind(label).unwrap();
+        asm.emit(Instruction::Return);
+        asm.try_catch(label, label, label, 0);
+        assert_eq!(asm.finish(0, 0), Err(AssembleError::EmptyTryRange { start_pc: 0, end_pc: 0 }));
+    }
+}
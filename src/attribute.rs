@@ -1,20 +1,397 @@
-use std::fs::File;
+use thiserror::Error;
 
+use crate::access_flags::{ExportsFlags, InnerClassAccessFlags, MethodParameterAccessFlags, ModuleFlags, RequiresFlags};
 use crate::class::{ParseClassError, read_u16, read_u32, read_u8};
+use crate::code::{CodeAttribute, ExceptionTableEntry, parse_code, write_code};
+use crate::constant_pool::{ConstantPool, ConstantPoolError};
+use crate::reader::{ClassReader, SliceCursor};
+use crate::stack_map::{parse_stack_map_table, StackMapFrame, write_stack_map_table};
+use crate::writer::{ClassWriter, WriteClassError, write_table, write_u16_table};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attribute {
+    /// Index of the name of this attribute in the constant pool, which decides how `info` was parsed
     pub attribute_name_index: u16,
-    pub info: Vec<u8>,
+    pub info: AttributeInfo,
 }
 
-pub(crate) fn parse_attribute(f: &mut File) -> Result<Attribute, ParseClassError> {
+/// The generic signature in the `Signature` attribute among `attributes`, if there's one
+pub(crate) fn signature<'a>(attributes: &[Attribute], constant_pool: &'a ConstantPool) -> Result<Option<&'a str>, ConstantPoolError> {
+    attributes.iter()
+        .find_map(|attribute| match attribute.info {
+            AttributeInfo::Signature { signature_index } => Some(signature_index),
+            _ => None
+        })
+        .map(|index| constant_pool.get_utf8(index))
+        .transpose()
+}
+
+impl Attribute {
+    pub fn name<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.attribute_name_index)
+    }
+}
+
+/// Contents of an attribute
+///
+/// See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeInfo {
+    ConstantValue {
+        /// Points to the value of the field in the constant pool
+        constantvalue_index: u16,
+    },
+    Code(CodeAttribute),
+    StackMapTable(Vec<StackMapFrame>),
+    /// Each entry points to a class in the constant pool that the method declares it throws
+    Exceptions(Vec<u16>),
+    InnerClasses(Vec<InnerClass>),
+    EnclosingMethod {
+        /// Points to the innermost class enclosing this one
+        class_index: u16,
+        /// Points to the name and type of the enclosing method, or 0 if this class isn't enclosed by a method
+        method_index: u16,
+    },
+    Synthetic,
+    Signature {
+        /// Points to a utf8 string with the generic signature
+        signature_index: u16,
+    },
+    SourceFile {
+        /// Points to a utf8 string with the name of the source file
+        sourcefile_index: u16,
+    },
+    /// Extended debugging information, in a format the JVM doesn't care about
+    SourceDebugExtension(Vec<u8>),
+    LineNumberTable(Vec<LineNumber>),
+    LocalVariableTable(Vec<LocalVariable>),
+    LocalVariableTypeTable(Vec<LocalVariableType>),
+    Deprecated,
+    BootstrapMethods(Vec<BootstrapMethod>),
+    MethodParameters(Vec<MethodParameter>),
+    Module(ModuleAttribute),
+    /// Each entry points to a package in the constant pool
+    ModulePackages(Vec<u16>),
+    ModuleMainClass {
+        /// Points to the main class of the module
+        main_class_index: u16,
+    },
+    NestHost {
+        /// Points to the class that hosts the nest this class belongs to
+        host_class_index: u16,
+    },
+    /// Each entry points to a class in the constant pool that belongs to the nest hosted by this class
+    NestMembers(Vec<u16>),
+    Record(Vec<RecordComponent>),
+    /// Each entry points to a class in the constant pool that may extend this sealed class
+    PermittedSubclasses(Vec<u16>),
+    /// An attribute this crate doesn't understand, with its contents untouched
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InnerClass {
+    /// Points to the inner class
+    pub inner_class_info_index: u16,
+    /// Points to the class this one is a member of, or 0 for local and anonymous classes
+    pub outer_class_info_index: u16,
+    /// Points to the simple name of the class, or 0 for anonymous classes
+    pub inner_name_index: u16,
+    pub inner_class_access_flags: InnerClassAccessFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineNumber {
+    /// First pc of the code of this line
+    pub start_pc: u16,
+    pub line_number: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalVariable {
+    /// First pc where the variable has a value
+    pub start_pc: u16,
+    /// Number of bytes of code, starting at `start_pc`, where the variable has a value
+    pub length: u16,
+    pub name_index: u16,
+    /// Points to the field descriptor of the variable
+    pub descriptor_index: u16,
+    /// Local variable slot of the variable
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalVariableType {
+    /// First pc where the variable has a value
+    pub start_pc: u16,
+    /// Number of bytes of code, starting at `start_pc`, where the variable has a value
+    pub length: u16,
+    pub name_index: u16,
+    /// Points to the generic signature of the variable
+    pub signature_index: u16,
+    /// Local variable slot of the variable
+    pub index: u16,
+}
+
+/// An entry of the `BootstrapMethods` attribute, used by [`Constant::InvokeDynamic`] and [`Constant::Dynamic`]
+///
+/// [`Constant::InvokeDynamic`]: crate::constant_pool::Constant::InvokeDynamic
+/// [`Constant::Dynamic`]: crate::constant_pool::Constant::Dynamic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapMethod {
+    /// Points to a method handle in the constant pool
+    pub bootstrap_method_ref: u16,
+    /// Each one points to a loadable constant in the constant pool
+    pub bootstrap_arguments: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodParameter {
+    /// Points to the name of the parameter, or 0 if it has no name
+    pub name_index: u16,
+    pub access_flags: MethodParameterAccessFlags,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordComponent {
+    pub name_index: u16,
+    /// Points to the field descriptor of the component
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute>,
+}
+
+impl RecordComponent {
+    pub fn name<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.descriptor_index)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleAttribute {
+    /// Points to the module in the constant pool
+    pub module_name_index: u16,
+    pub module_flags: ModuleFlags,
+    /// Points to the version of the module, or 0 if it has none
+    pub module_version_index: u16,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<ModuleExports>,
+    pub opens: Vec<ModuleExports>,
+    /// Each entry points to a service interface the module uses
+    pub uses_index: Vec<u16>,
+    pub provides: Vec<ModuleProvides>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleRequires {
+    /// Points to the required module
+    pub requires_index: u16,
+    pub requires_flags: RequiresFlags,
+    /// Points to the version of the required module, or 0 if it has none
+    pub requires_version_index: u16,
+}
+
+/// An entry of the `exports` or `opens` table of a module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleExports {
+    /// Points to the exported or opened package
+    pub package_index: u16,
+    pub flags: ExportsFlags,
+    /// Each entry points to a module the package is restricted to, it's available to every module if empty
+    pub to_index: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleProvides {
+    /// Points to the service interface
+    pub provides_index: u16,
+    /// Each entry points to a class implementing the service
+    pub provides_with_index: Vec<u16>,
+}
+
+#[derive(Error, Debug)]
+pub enum AttributeParseError {
+    #[error("contents of the {0} attribute don't match its length")]
+    InvalidLength(String),
+    #[error("code length {0} is out of range")]
+    InvalidCodeLength(u32),
+    #[error("exception table entry {0:?} is out of the code range")]
+    InvalidExceptionTableEntry(ExceptionTableEntry),
+    #[error("invalid verification type tag {0}")]
+    InvalidVerificationType(u8),
+    #[error("invalid stack map frame type {0}")]
+    InvalidStackMapFrameType(u8),
+}
+
+fn read_u16_table<R: ClassReader>(f: &mut R) -> Result<Vec<u16>, ParseClassError> {
+    let len = read_u16(f)?;
+    let mut table = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        table.push(read_u16(f)?);
+    }
+    Ok(table)
+}
+
+fn read_table<R: ClassReader, T>(f: &mut R, mut read: impl FnMut(&mut R) -> Result<T, ParseClassError>) -> Result<Vec<T>, ParseClassError> {
+    let len = read_u16(f)?;
+    let mut table = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        table.push(read(f)?);
+    }
+    Ok(table)
+}
+
+/// Parses the contents of a `BootstrapMethods` attribute
+pub(crate) fn parse_bootstrap_methods<R: ClassReader>(f: &mut R) -> Result<Vec<BootstrapMethod>, ParseClassError> {
+    let len = read_u16(f)?;
+    let mut methods = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        let bootstrap_method_ref = read_u16(f)?;
+        let argument_count = read_u16(f)?;
+        let mut bootstrap_arguments = Vec::with_capacity(argument_count as usize);
+        for _ in 0..argument_count {
+            bootstrap_arguments.push(read_u16(f)?);
+        }
+        methods.push(BootstrapMethod {
+            bootstrap_method_ref,
+            bootstrap_arguments,
+        });
+    }
+    Ok(methods)
+}
+
+fn parse_module_exports<R: ClassReader>(f: &mut R) -> Result<ModuleExports, ParseClassError> {
+    Ok(ModuleExports {
+        package_index: read_u16(f)?,
+        flags: ExportsFlags::from_bits_retain(read_u16(f)?),
+        to_index: read_u16_table(f)?,
+    })
+}
+
+fn parse_module<R: ClassReader>(f: &mut R) -> Result<ModuleAttribute, ParseClassError> {
+    Ok(ModuleAttribute {
+        module_name_index: read_u16(f)?,
+        module_flags: ModuleFlags::from_bits_retain(read_u16(f)?),
+        module_version_index: read_u16(f)?,
+        requires: read_table(f, |f| Ok(ModuleRequires {
+            requires_index: read_u16(f)?,
+            requires_flags: RequiresFlags::from_bits_retain(read_u16(f)?),
+            requires_version_index: read_u16(f)?,
+        }))?,
+        exports: read_table(f, parse_module_exports)?,
+        opens: read_table(f, parse_module_exports)?,
+        uses_index: read_u16_table(f)?,
+        provides: read_table(f, |f| Ok(ModuleProvides {
+            provides_index: read_u16(f)?,
+            provides_with_index: read_u16_table(f)?,
+        }))?,
+    })
+}
+
+/// Oldest major version of class files where the JVM knows the attribute called `name`, `None` for unknown attributes
+pub fn first_version(name: &str) -> Option<u16> {
+    Some(match name {
+        "ConstantValue" | "Code" | "Exceptions" | "InnerClasses" | "Synthetic" | "SourceFile" | "LineNumberTable" |
+        "LocalVariableTable" | "Deprecated" => 45,
+        "EnclosingMethod" | "Signature" | "SourceDebugExtension" | "LocalVariableTypeTable" | "RuntimeVisibleAnnotations" |
+        "RuntimeInvisibleAnnotations" | "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" |
+        "AnnotationDefault" => 49,
+        "StackMapTable" => 50,
+        "BootstrapMethods" => 51,
+        "MethodParameters" | "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => 52,
+        "Module" | "ModulePackages" | "ModuleMainClass" => 53,
+        "NestHost" | "NestMembers" => 55,
+        "Record" => 60,
+        "PermittedSubclasses" => 61,
+        _ => return None,
+    })
+}
+
+/// Parses the contents of the attribute called `name`
+fn parse_info(name: &str, f: &mut SliceCursor, constant_pool: &ConstantPool, major: u16) -> Result<AttributeInfo, ParseClassError> {
+    // Older JVMs don't know about newer attributes, so they're just unknown ones
+    if first_version(name).is_some_and(|since| major < since) {
+        return Ok(AttributeInfo::Unknown(f.read_slice(f.remaining().len())?.to_vec()));
+    }
+    Ok(match name {
+        "ConstantValue" => AttributeInfo::ConstantValue { constantvalue_index: read_u16(f)? },
+        "Code" => AttributeInfo::Code(parse_code(f, constant_pool, major)?),
+        "StackMapTable" => AttributeInfo::StackMapTable(parse_stack_map_table(f)?),
+        "Exceptions" => AttributeInfo::Exceptions(read_u16_table(f)?),
+        "InnerClasses" => AttributeInfo::InnerClasses(read_table(f, |f| Ok(InnerClass {
+            inner_class_info_index: read_u16(f)?,
+            outer_class_info_index: read_u16(f)?,
+            inner_name_index: read_u16(f)?,
+            inner_class_access_flags: InnerClassAccessFlags::from_bits_retain(read_u16(f)?),
+        }))?),
+        "EnclosingMethod" => AttributeInfo::EnclosingMethod {
+            class_index: read_u16(f)?,
+            method_index: read_u16(f)?,
+        },
+        "Synthetic" => AttributeInfo::Synthetic,
+        "Signature" => AttributeInfo::Signature { signature_index: read_u16(f)? },
+        "SourceFile" => AttributeInfo::SourceFile { sourcefile_index: read_u16(f)? },
+        "SourceDebugExtension" => AttributeInfo::SourceDebugExtension(f.read_slice(f.remaining().len())?.to_vec()),
+        "LineNumberTable" => AttributeInfo::LineNumberTable(read_table(f, |f| Ok(LineNumber {
+            start_pc: read_u16(f)?,
+            line_number: read_u16(f)?,
+        }))?),
+        "LocalVariableTable" => AttributeInfo::LocalVariableTable(read_table(f, |f| Ok(LocalVariable {
+            start_pc: read_u16(f)?,
+            length: read_u16(f)?,
+            name_index: read_u16(f)?,
+            descriptor_index: read_u16(f)?,
+            index: read_u16(f)?,
+        }))?),
+        "LocalVariableTypeTable" => AttributeInfo::LocalVariableTypeTable(read_table(f, |f| Ok(LocalVariableType {
+            start_pc: read_u16(f)?,
+            length: read_u16(f)?,
+            name_index: read_u16(f)?,
+            signature_index: read_u16(f)?,
+            index: read_u16(f)?,
+        }))?),
+        "Deprecated" => AttributeInfo::Deprecated,
+        "BootstrapMethods" => AttributeInfo::BootstrapMethods(parse_bootstrap_methods(f)?),
+        "MethodParameters" => {
+            // Unlike every other table, the length of this one is a single byte
+            let len = read_u8(f)?;
+            let mut parameters = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                parameters.push(MethodParameter {
+                    name_index: read_u16(f)?,
+                    access_flags: MethodParameterAccessFlags::from_bits_retain(read_u16(f)?),
+                });
+            }
+            AttributeInfo::MethodParameters(parameters)
+        }
+        "Module" => AttributeInfo::Module(parse_module(f)?),
+        "ModulePackages" => AttributeInfo::ModulePackages(read_u16_table(f)?),
+        "ModuleMainClass" => AttributeInfo::ModuleMainClass { main_class_index: read_u16(f)? },
+        "NestHost" => AttributeInfo::NestHost { host_class_index: read_u16(f)? },
+        "NestMembers" => AttributeInfo::NestMembers(read_u16_table(f)?),
+        "Record" => AttributeInfo::Record(read_table(f, |f| Ok(RecordComponent {
+            name_index: read_u16(f)?,
+            descriptor_index: read_u16(f)?,
+            attributes: parse_attributes(f, constant_pool, major)?,
+        }))?),
+        "PermittedSubclasses" => AttributeInfo::PermittedSubclasses(read_u16_table(f)?),
+        _ => AttributeInfo::Unknown(f.read_slice(f.remaining().len())?.to_vec()),
+    })
+}
+
+pub(crate) fn parse_attribute<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Attribute, ParseClassError> {
     let attribute_name_index = read_u16(f)?;
+    let name = constant_pool.get_utf8(attribute_name_index)?;
     let attr_len = read_u32(f)?;
-    let mut info = vec![];
-    info.reserve(attr_len as usize);
-    for _ in 0..attr_len {
-        info.push(read_u8(f)?);
+    let start = f.offset();
+    let bytes = f.read_bytes(attr_len as usize)?;
+    let mut cursor = SliceCursor::at(&bytes, start);
+    let info = parse_info(name, &mut cursor, constant_pool, major)?;
+    if !cursor.remaining().is_empty() {
+        return Err(AttributeParseError::InvalidLength(name.to_string()).into());
     }
     Ok(Attribute {
         info,
@@ -22,12 +399,123 @@ pub(crate) fn parse_attribute(f: &mut File) -> Result<Attribute, ParseClassError
     })
 }
 
-pub(crate) fn parse_attributes(f: &mut File) -> Result<Vec<Attribute>, ParseClassError> {
+/// Parses the attributes of a class file of version `major`
+pub(crate) fn parse_attributes<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Vec<Attribute>, ParseClassError> {
     let len = read_u16(f)?;
-    let mut attributes = vec![];
+    let mut attributes = Vec::with_capacity(len as usize);
     for _ in 0..len {
-        let attr = parse_attribute(f)?;
+        let attr = parse_attribute(f, constant_pool, major)?;
         attributes.push(attr);
     }
     Ok(attributes)
-}
\ No newline at end of file
+}
+
+fn write_module_exports(out: &mut Vec<u8>, exports: &ModuleExports) -> Result<(), WriteClassError> {
+    out.write_u16(exports.package_index);
+    out.write_u16(exports.flags.bits());
+    write_u16_table(out, "Module exports", &exports.to_index)
+}
+
+fn write_module(out: &mut Vec<u8>, module: &ModuleAttribute) -> Result<(), WriteClassError> {
+    out.write_u16(module.module_name_index);
+    out.write_u16(module.module_flags.bits());
+    out.write_u16(module.module_version_index);
+    write_table(out, "Module requires", &module.requires, |out, requires| {
+        out.write_u16(requires.requires_index);
+        out.write_u16(requires.requires_flags.bits());
+        out.write_u16(requires.requires_version_index);
+        Ok(())
+    })?;
+    write_table(out, "Module exports", &module.exports, write_module_exports)?;
+    write_table(out, "Module opens", &module.opens, write_module_exports)?;
+    write_u16_table(out, "Module uses", &module.uses_index)?;
+    write_table(out, "Module provides", &module.provides, |out, provides| {
+        out.write_u16(provides.provides_index);
+        write_u16_table(out, "Module provides", &provides.provides_with_index)
+    })
+}
+
+/// Writes the contents of an attribute, without its name and length
+fn write_info(out: &mut Vec<u8>, info: &AttributeInfo) -> Result<(), WriteClassError> {
+    match info {
+        AttributeInfo::ConstantValue { constantvalue_index } => out.write_u16(*constantvalue_index),
+        AttributeInfo::Code(code) => write_code(out, code)?,
+        AttributeInfo::StackMapTable(frames) => write_stack_map_table(out, frames)?,
+        AttributeInfo::Exceptions(exceptions) => write_u16_table(out, "Exceptions", exceptions)?,
+        AttributeInfo::InnerClasses(classes) => write_table(out, "InnerClasses", classes, |out, class| {
+            out.write_u16(class.inner_class_info_index);
+            out.write_u16(class.outer_class_info_index);
+            out.write_u16(class.inner_name_index);
+            out.write_u16(class.inner_class_access_flags.bits());
+            Ok(())
+        })?,
+        AttributeInfo::EnclosingMethod { class_index, method_index } => {
+            out.write_u16(*class_index);
+            out.write_u16(*method_index);
+        }
+        AttributeInfo::Synthetic | AttributeInfo::Deprecated => {}
+        AttributeInfo::Signature { signature_index } => out.write_u16(*signature_index),
+        AttributeInfo::SourceFile { sourcefile_index } => out.write_u16(*sourcefile_index),
+        AttributeInfo::SourceDebugExtension(bytes) | AttributeInfo::Unknown(bytes) => out.write_bytes(bytes),
+        AttributeInfo::LineNumberTable(lines) => write_table(out, "LineNumberTable", lines, |out, line| {
+            out.write_u16(line.start_pc);
+            out.write_u16(line.line_number);
+            Ok(())
+        })?,
+        AttributeInfo::LocalVariableTable(variables) => write_table(out, "LocalVariableTable", variables, |out, variable| {
+            out.write_u16(variable.start_pc);
+            out.write_u16(variable.length);
+            out.write_u16(variable.name_index);
+            out.write_u16(variable.descriptor_index);
+            out.write_u16(variable.index);
+            Ok(())
+        })?,
+        AttributeInfo::LocalVariableTypeTable(variables) => write_table(out, "LocalVariableTypeTable", variables, |out, variable| {
+            out.write_u16(variable.start_pc);
+            out.write_u16(variable.length);
+            out.write_u16(variable.name_index);
+            out.write_u16(variable.signature_index);
+            out.write_u16(variable.index);
+            Ok(())
+        })?,
+        AttributeInfo::BootstrapMethods(methods) => write_table(out, "BootstrapMethods", methods, |out, method| {
+            out.write_u16(method.bootstrap_method_ref);
+            write_u16_table(out, "BootstrapMethods arguments", &method.bootstrap_arguments)
+        })?,
+        AttributeInfo::MethodParameters(parameters) => {
+            let len = u8::try_from(parameters.len())
+                .map_err(|_| WriteClassError::TooManyEntries { what: "MethodParameters", len: parameters.len() })?;
+            out.write_u8(len);
+            for parameter in parameters {
+                out.write_u16(parameter.name_index);
+                out.write_u16(parameter.access_flags.bits());
+            }
+        }
+        AttributeInfo::Module(module) => write_module(out, module)?,
+        AttributeInfo::ModulePackages(packages) => write_u16_table(out, "ModulePackages", packages)?,
+        AttributeInfo::ModuleMainClass { main_class_index } => out.write_u16(*main_class_index),
+        AttributeInfo::NestHost { host_class_index } => out.write_u16(*host_class_index),
+        AttributeInfo::NestMembers(members) => write_u16_table(out, "NestMembers", members)?,
+        AttributeInfo::Record(components) => write_table(out, "Record", components, |out, component| {
+            out.write_u16(component.name_index);
+            out.write_u16(component.descriptor_index);
+            write_attributes(out, &component.attributes)
+        })?,
+        AttributeInfo::PermittedSubclasses(classes) => write_u16_table(out, "PermittedSubclasses", classes)?,
+    }
+    Ok(())
+}
+
+pub(crate) fn write_attribute(out: &mut Vec<u8>, attribute: &Attribute) -> Result<(), WriteClassError> {
+    let mut info = vec![];
+    write_info(&mut info, &attribute.info)?;
+    let len = u32::try_from(info.len()).map_err(|_| WriteClassError::TooLong { what: "attribute", len: info.len() })?;
+    out.write_u16(attribute.attribute_name_index);
+    out.write_u32(len);
+    out.write_bytes(&info);
+    Ok(())
+}
+
+pub(crate) fn write_attributes(out: &mut Vec<u8>, attributes: &[Attribute]) -> Result<(), WriteClassError> {
+    write_table(out, "attributes", attributes, write_attribute)
+}
@@ -1,24 +1,48 @@
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, Read, Write};
 use std::path::PathBuf;
-use std::string::FromUtf8Error;
 
 use thiserror::Error;
 
 use crate::{access_flags::ClassAccessFlags, constant_pool};
-use crate::attribute::{Attribute, parse_attributes};
+use crate::attribute::{Attribute, AttributeInfo, AttributeParseError, BootstrapMethod, parse_attributes, write_attributes};
 use crate::big_endian::ParseBigEndian;
-use crate::constant_pool::{Constant, ConstantPoolValidationError};
-use crate::field::{Field, FieldParseError, parse_fields};
-use crate::method::{Method, MethodParseError, parse_methods};
+use crate::class_hierarchy::ClassHierarchy;
+use crate::class_json::{self, ClassJsonError};
+use crate::class_text::{self, ClassTextError};
+use crate::constant_pool::{ConstantPool, ConstantPoolError, ConstantPoolValidationError};
+use crate::constant_pool_builder::ConstantPoolBuilder;
+use crate::format_check::{format_check, FormatViolation};
+use crate::frame_inference::StackMapError;
+use crate::field::{Field, parse_fields, write_fields};
+use crate::json::Json;
+use crate::method::{Method, parse_methods, write_methods};
+use crate::mutf8::ModifiedUtf8Error;
+use crate::reader::{ClassReader, IoReader, SliceCursor};
+use crate::stack_map::compress_frames;
+use crate::verifier::{verify_class, VerifyError};
+use crate::writer::{ClassWriter, WriteClassError, write_u16_table};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct JavaVersion {
     pub minor: u16,
     pub major: u16,
 }
 
+/// Java releases by major version, starting at 45
+const RELEASES: [&str; 21] = [
+    "1.1", "1.2", "1.3", "1.4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20", "21",
+];
+
 impl JavaVersion {
+    /// Oldest major version, used by Java 1.0.2 and 1.1
+    pub const MIN_MAJOR: u16 = 45;
+    /// Newest major version jerris knows about, used by Java 21
+    pub const LATEST_MAJOR: u16 = 65;
+    /// Minor version of classes that use preview features of their Java release
+    pub const PREVIEW_MINOR: u16 = 0xffff;
+
     pub fn parse(bytes: [u8; 4]) -> Self {
         let minor_b = [bytes[0], bytes[1]];
         let major_b = [bytes[2], bytes[3]];
@@ -27,6 +51,38 @@ impl JavaVersion {
             major: major_b.parse_big_endian(),
         }
     }
+
+    /// The Java release that introduced this version, like `1.4` or `17`, `None` if jerris doesn't know it
+    pub fn release(&self) -> Option<&'static str> {
+        RELEASES.get(self.major.checked_sub(Self::MIN_MAJOR)? as usize).copied()
+    }
+
+    /// Whether the class depends on the preview features of its Java release, which only exist from Java 12 on
+    pub fn is_preview(&self) -> bool {
+        self.major >= 56 && self.minor == Self::PREVIEW_MINOR
+    }
+}
+
+impl fmt::Display for JavaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Limits on the class files [`Class::parse_with`] accepts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Newest major version to accept, [`JavaVersion::LATEST_MAJOR`] by default
+    pub max_major: u16,
+}
+
+impl Default for ParseOptions {
+    /// Accepts versions up to Java 21, so classes compiled for Java 22 and newer (major 66 on) are rejected unless
+    /// `max_major` is raised, in which case jerris reads them like version 65 and keeps attributes it doesn't know as
+    /// [`AttributeInfo::Unknown`]
+    fn default() -> Self {
+        Self { max_major: JavaVersion::LATEST_MAJOR }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -34,15 +90,16 @@ pub struct Class {
     /// Java version that this class was compiled for
     pub java_version: JavaVersion,
     /// Contains a pool of constants, like class information, strings, etc...
-    pub constant_pool: Vec<Constant>,
+    pub constant_pool: ConstantPool,
     /// Class access flags
     pub access_flags: ClassAccessFlags,
     /// Points to a class in the constant pool that contains the class info for this class
     pub this_class: u16,
     /// Points to a class in the constant pool that contains the class info for the super class
     pub super_class: u16,
-    /// The names of the interfaces this class implements
-    pub interfaces: Vec<String>,
+    /// Each entry points to a class in the constant pool that contains the class info of an interface this class
+    /// implements, see [`Class::interfaces`] for their names
+    pub interface_indices: Vec<u16>,
     /// Fields of this class
     pub fields: Vec<Field>,
     /// Method of this class
@@ -50,99 +107,247 @@ pub struct Class {
     pub attributes: Vec<Attribute>,
 }
 
-pub(crate) fn io_err<T>(res: Result<T, std::io::Error>) -> Result<T, ParseClassError> {
-    res.map_err(ParseClassError::IoError)
+pub(crate) fn read_n_dyn<R: ClassReader>(f: &mut R, n: usize) -> Result<Vec<u8>, ParseClassError> {
+    Ok(f.read_bytes(n)?.into_owned())
 }
 
-pub(crate) fn read_n_dyn(f: &mut File, n: usize) -> Result<Vec<u8>, ParseClassError> {
-    let mut b = vec![0; n];
-    io_err(f.read_exact(&mut b))?;
-    Ok(b)
-}
-
-pub(crate) fn read_n<const N: usize>(f: &mut File) -> Result<[u8; N], ParseClassError> {
+pub(crate) fn read_n<const N: usize, R: ClassReader>(f: &mut R) -> Result<[u8; N], ParseClassError> {
     let mut b = [0u8; N];
-    io_err(f.read_exact(&mut b))?;
+    f.read_exact(&mut b)?;
     Ok(b)
 }
 
-pub(crate) fn read_u8(f: &mut File) -> Result<u8, ParseClassError> {
-    let mut b = [0u8; 1];
-    io_err(f.read_exact(&mut b))?;
+pub(crate) fn read_u8<R: ClassReader>(f: &mut R) -> Result<u8, ParseClassError> {
+    let b: [u8; 1] = read_n(f)?;
     Ok(b[0])
 }
 
-pub(crate) fn read_u16(f: &mut File) -> Result<u16, ParseClassError> {
-    let mut b = [0u8; 2];
-    io_err(f.read_exact(&mut b))?;
+pub(crate) fn read_u16<R: ClassReader>(f: &mut R) -> Result<u16, ParseClassError> {
+    let b: [u8; 2] = read_n(f)?;
     Ok(b.parse_big_endian())
 }
 
-pub(crate) fn read_u32(f: &mut File) -> Result<u32, ParseClassError> {
-    let mut b = [0u8; 4];
-    io_err(f.read_exact(&mut b))?;
+pub(crate) fn read_u32<R: ClassReader>(f: &mut R) -> Result<u32, ParseClassError> {
+    let b: [u8; 4] = read_n(f)?;
     Ok(b.parse_big_endian())
 }
 
 impl Class {
     pub const MAGIC: u32 = 0xcafebabe;
+
     pub fn from_file<P: Into<PathBuf>>(path: P) -> Result<Self, ParseClassError> {
         let path: PathBuf = path.into();
-        let mut file = io_err(File::open(path))?;
-        if read_u32(&mut file)? != Self::MAGIC {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Parses a class from any byte stream, like a jar entry or a socket
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, ParseClassError> {
+        Self::parse(&mut IoReader::new(reader))
+    }
+
+    /// Parses a class that's already in memory
+    ///
+    /// Use [`Class::parse`] with a [`SliceCursor`] instead if you need to know where parsing stopped.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseClassError> {
+        Self::parse(&mut SliceCursor::new(bytes))
+    }
+
+    /// Parses a class from a [`ClassReader`], leaving it positioned right after the last attribute
+    pub fn parse<R: ClassReader>(reader: &mut R) -> Result<Self, ParseClassError> {
+        Self::parse_with(reader, &ParseOptions::default())
+    }
+
+    /// Like [`Class::parse`], but with other limits on what's accepted
+    ///
+    /// Constants and attributes are checked against the class file version: constants newer than it are rejected,
+    /// like the JVM does, while attributes newer than it are kept as [`AttributeInfo::Unknown`].
+    pub fn parse_with<R: ClassReader>(reader: &mut R, options: &ParseOptions) -> Result<Self, ParseClassError> {
+        if read_u32(reader)? != Self::MAGIC {
             return Err(ParseClassError::InvalidMagicNumber);
         }
-        let version_bytes = read_n(&mut file)?;
+        let version_bytes = read_n(reader)?;
         let java_version = JavaVersion::parse(version_bytes);
-        let constant_pool_len: u16 = read_u16(&mut file)? - 1;
-        let mut constant_pool = vec![];
-        constant_pool.reserve(constant_pool_len as usize);
-        for _ in 0..constant_pool_len {
-            constant_pool.push(constant_pool::parse_constant(&mut file)?);
+        if java_version.major < JavaVersion::MIN_MAJOR || java_version.major > options.max_major {
+            return Err(ParseClassError::UnsupportedVersion { version: java_version, max_major: options.max_major });
         }
+        // From Java 12 on, the minor version only says whether preview features are used
+        if java_version.major >= 56 && java_version.minor != 0 && java_version.minor != JavaVersion::PREVIEW_MINOR {
+            return Err(ParseClassError::InvalidMinorVersion(java_version));
+        }
+        let constant_pool = constant_pool::parse_constant_pool(reader)?;
         constant_pool::validate_constant_pool(&constant_pool)?;
-        let access_flags = ClassAccessFlags::from_bits(read_u16(&mut file)?).unwrap();
-        let this_class = read_u16(&mut file)?;
-        let super_class = read_u16(&mut file)?;
-        let interfaces = io_err(get_interfaces(&mut file, &constant_pool))?;
-        let fields = parse_fields(&mut file)?;
-        let methods = parse_methods(&mut file)?;
-        let attributes = parse_attributes(&mut file)?;
-        Ok(Self {
+        let access_flags = ClassAccessFlags::from_bits_retain(read_u16(reader)?);
+        let is_module = access_flags.contains(ClassAccessFlags::ACC_MODULE);
+        constant_pool::validate_constant_versions(&constant_pool, java_version.major, is_module)?;
+        let this_class = read_u16(reader)?;
+        let super_class = read_u16(reader)?;
+        let interface_indices = get_interfaces(reader, &constant_pool)?;
+        let fields = parse_fields(reader, &constant_pool, java_version.major)?;
+        let methods = parse_methods(reader, &constant_pool, java_version.major)?;
+        let attributes = parse_attributes(reader, &constant_pool, java_version.major)?;
+        let class = Self {
             java_version,
             constant_pool,
             access_flags,
             this_class,
             super_class,
-            interfaces,
+            interface_indices,
             fields,
             methods,
             attributes,
-        })
+        };
+        constant_pool::validate_bootstrap_methods(&class.constant_pool, class.bootstrap_methods())?;
+        Ok(class)
+    }
+
+    /// Serializes the class back into the class file format
+    ///
+    /// Writing a class that was parsed and left untouched gives back the exact same bytes.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), WriteClassError> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
     }
-}
 
-fn get_interfaces(f: &mut File, constant_pool: &[Constant]) -> Result<Vec<String>, std::io::Error> {
-    fn read_u16(f: &mut File) -> Result<u16, std::io::Error> {
-        let mut b = [0u8; 2];
-        f.read_exact(&mut b)?;
-        Ok(b.parse_big_endian())
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WriteClassError> {
+        let mut out = vec![];
+        out.write_u32(Self::MAGIC);
+        out.write_u16(self.java_version.minor);
+        out.write_u16(self.java_version.major);
+        constant_pool::write_constant_pool(&mut out, &self.constant_pool)?;
+        out.write_u16(self.access_flags.bits());
+        out.write_u16(self.this_class);
+        out.write_u16(self.super_class);
+        write_u16_table(&mut out, "interfaces", &self.interface_indices)?;
+        write_fields(&mut out, &self.fields)?;
+        write_methods(&mut out, &self.methods)?;
+        write_attributes(&mut out, &self.attributes)?;
+        Ok(out)
     }
+
+    /// The whole class as a JSON document, see [`crate::class_json`] for its schema
+    pub fn to_json(&self) -> String {
+        class_json::to_json(self).to_string()
+    }
+
+    /// Reads a class from the JSON written by [`Class::to_json`], which may have been edited by hand
+    ///
+    /// The class isn't checked like parsing does, parse what [`Class::to_bytes`] writes to do that.
+    pub fn from_json(json: &str) -> Result<Self, ClassJsonError> {
+        class_json::from_json(&Json::parse(json)?)
+    }
+
+    /// The class in the assembly language of [`crate::class_text`]
+    pub fn to_text(&self) -> Result<String, ClassTextError> {
+        class_text::to_text(self)
+    }
+
+    /// Assembles a class from the text written by [`Class::to_text`], which may have been edited by hand
+    ///
+    /// Like [`Class::from_json`], the class isn't checked like parsing does.
+    pub fn from_text(text: &str) -> Result<Self, ClassTextError> {
+        class_text::from_text(text)
+    }
+
+    /// Entries of the `BootstrapMethods` attribute, empty if the class doesn't have one
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethod] {
+        self.attributes.iter()
+            .find_map(|attribute| match &attribute.info {
+                AttributeInfo::BootstrapMethods(methods) => Some(methods.as_slice()),
+                _ => None
+            })
+            .unwrap_or_default()
+    }
+
+    /// Name of this class, in internal form (`java/lang/Object`)
+    pub fn name(&self) -> Result<&str, ConstantPoolError> {
+        self.constant_pool.get_class_name(self.this_class)
+    }
+
+    /// Name of the super class, `None` only for `java/lang/Object`
+    pub fn super_class_name(&self) -> Result<Option<&str>, ConstantPoolError> {
+        if self.super_class == 0 {
+            Ok(None)
+        } else {
+            self.constant_pool.get_class_name(self.super_class).map(Some)
+        }
+    }
+
+    /// Names of the interfaces this class implements, in internal form
+    pub fn interfaces(&self) -> Result<Vec<&str>, ConstantPoolError> {
+        self.interface_indices.iter().map(|&index| self.constant_pool.get_class_name(index)).collect()
+    }
+
+    /// The generic signature from the `Signature` attribute, see [`crate::signature`]
+    pub fn signature(&self) -> Result<Option<&str>, ConstantPoolError> {
+        crate::attribute::signature(&self.attributes, &self.constant_pool)
+    }
+
+    /// Recomputes the `StackMapTable` of every method with code, which classes from version 50 on need to be loaded
+    ///
+    /// Methods that don't need any frame lose their `StackMapTable`. See [`crate::frame_inference`].
+    pub fn compute_stack_maps(&mut self, hierarchy: &dyn ClassHierarchy) -> Result<(), StackMapError> {
+        let this_class = self.name()?.to_string();
+        let mut constant_pool = ConstantPoolBuilder::from_pool(self.constant_pool.clone());
+        let mut tables = Vec::with_capacity(self.methods.len());
+        for method in &self.methods {
+            let invalid = |error| StackMapError::InvalidMethod {
+                method: format!("{}{}", method.name(&self.constant_pool).unwrap_or("?"), method.descriptor(&self.constant_pool).unwrap_or("")),
+                error,
+            };
+            let table = match method.infer_frames(&this_class, &self.constant_pool, hierarchy).map_err(invalid)? {
+                Some(frames) => {
+                    let initial = method.initial_frame(&this_class, &self.constant_pool).map_err(invalid)?;
+                    Some(compress_frames(&initial, &frames, &mut constant_pool)?)
+                }
+                None => None,
+            };
+            tables.push(table);
+        }
+        // The name is only looked up when some method has frames, so classes without any don't gain a constant
+        let needs_name = tables.iter().flatten().any(|table| !table.is_empty());
+        let name_index = if needs_name { constant_pool.utf8("StackMapTable")? } else { 0 };
+        self.constant_pool = constant_pool.build();
+        for (method, table) in self.methods.iter_mut().zip(tables) {
+            let (code, table) = match (method.code_mut(), table) {
+                (Some(code), Some(table)) => (code, table),
+                _ => continue,
+            };
+            let existing = code.attributes.iter().position(|attribute| matches!(attribute.info, AttributeInfo::StackMapTable(_)));
+            match (existing, table.is_empty()) {
+                (Some(i), true) => {
+                    code.attributes.remove(i);
+                }
+                (Some(i), false) => code.attributes[i].info = AttributeInfo::StackMapTable(table),
+                (None, true) => {}
+                (None, false) => code.attributes.push(Attribute { attribute_name_index: name_index, info: AttributeInfo::StackMapTable(table) }),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the structural rules parsing doesn't enforce, as of this class's version
+    ///
+    /// See [`crate::format_check`], which can also check for bytes after the class.
+    pub fn format_check(&self) -> Vec<FormatViolation> {
+        format_check(self, self.java_version.major)
+    }
+
+    /// Type checks the code of every method against its `StackMapTable`, returning an error for each method that fails
+    ///
+    /// See [`crate::verifier`].
+    pub fn verify(&self, hierarchy: &dyn ClassHierarchy) -> Vec<VerifyError> {
+        verify_class(self, hierarchy)
+    }
+}
+
+fn get_interfaces<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool) -> Result<Vec<u16>, ParseClassError> {
     let len = read_u16(f)?;
-    let mut interfaces = vec![];
-    interfaces.reserve(len as usize);
+    let mut interfaces = Vec::with_capacity(len as usize);
     for _ in 0..len {
         let class_index = read_u16(f)?;
-        let class_name = match &constant_pool[class_index as usize] {
-            Constant::Class {
-                name_index
-            } => match &constant_pool[*name_index as usize] {
-                Constant::UTF8String(class_name) => class_name.clone(),
-                _ => unreachable!()
-            },
-            _ => unreachable!()
-        };
-        interfaces.push(class_name);
+        constant_pool.get_class_name(class_index)?;
+        interfaces.push(class_index);
     }
     Ok(interfaces)
 }
@@ -151,16 +356,24 @@ fn get_interfaces(f: &mut File, constant_pool: &[Constant]) -> Result<Vec<String
 pub enum ParseClassError {
     #[error("couldn't read the class file: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("unexpected end of class file at byte {offset}")]
+    UnexpectedEof { offset: usize },
     #[error("expected magic number to be 0xcafebabe")]
     InvalidMagicNumber,
-    #[error("invalid utf8 string on constant pool: {0}")]
-    InvalidUTF8Constant(#[from] FromUtf8Error),
+    #[error("class file version {version} isn't supported, the major version must be between 45 and {max_major}")]
+    UnsupportedVersion { version: JavaVersion, max_major: u16 },
+    #[error("class file version {0} must have a minor version of 0, or 65535 for preview features")]
+    InvalidMinorVersion(JavaVersion),
+    #[error("invalid modified utf8 string on constant pool at byte {offset}: {error}")]
+    InvalidUTF8Constant { offset: usize, error: ModifiedUtf8Error },
+    #[error("unknown constant pool tag {tag} at byte {offset}")]
+    UnknownConstantTag { tag: u8, offset: usize },
     #[error("invalid method handle reference kind")]
     InvalidMethodHandleReferenceKind,
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
     #[error("invalid constant pool: {0}")]
     ConstantPoolValidationError(#[from] ConstantPoolValidationError),
-    #[error("failed to parse field: {0}")]
-    FieldParseError(#[from] FieldParseError),
-    #[error("failed to parse method: {0}")]
-    MethodParseError(#[from] MethodParseError),
+    #[error("failed to parse attribute: {0}")]
+    AttributeParseError(#[from] AttributeParseError),
 }
@@ -0,0 +1,178 @@
+//! Answering questions about classes that aren't being looked at, like which class another one extends
+//!
+//! Computing and verifying stack map frames needs the superclasses of the classes a method uses, which jerris can't
+//! know on its own, so it asks a [`ClassHierarchy`].
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::access_flags::ClassAccessFlags;
+use crate::class::Class;
+use crate::constant_pool::ConstantPoolError;
+
+pub const OBJECT: &str = "java/lang/Object";
+pub(crate) const THROWABLE: &str = "java/lang/Throwable";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClassHierarchyError {
+    #[error("class {0} isn't known")]
+    UnknownClass(String),
+    #[error("class {0} is its own superclass")]
+    CyclicHierarchy(String),
+}
+
+/// Source of superclasses of classes, in internal form like `java/lang/String`
+pub trait ClassHierarchy {
+    /// Superclass of `class`, `None` for `java/lang/Object`
+    fn super_class(&self, class: &str) -> Result<Option<String>, ClassHierarchyError>;
+
+    fn is_interface(&self, class: &str) -> Result<bool, ClassHierarchyError>;
+
+    /// The most specific class both `a` and `b` can be assigned to, which is where two paths with different types merge
+    ///
+    /// Like the JVM's verifier, interfaces are treated as `java/lang/Object`. Both names can also be array descriptors
+    /// like `[Ljava/lang/String;`.
+    fn common_super_class(&self, a: &str, b: &str) -> Result<String, ClassHierarchyError> {
+        if a == b {
+            return Ok(a.to_string());
+        }
+        if a.starts_with('[') || b.starts_with('[') {
+            return match (array_component(a), array_component(b)) {
+                (Some(a), Some(b)) => Ok(array_of(&self.common_super_class(a, b)?)),
+                _ => Ok(OBJECT.to_string()),
+            };
+        }
+        if self.is_interface(a)? || self.is_interface(b)? {
+            return Ok(OBJECT.to_string());
+        }
+        let supers_of_a = self.super_classes(a)?;
+        let common = self.super_classes(b)?.into_iter().find(|class| supers_of_a.contains(class));
+        Ok(common.unwrap_or_else(|| OBJECT.to_string()))
+    }
+
+    /// `class` followed by its superclasses up to the one without a superclass
+    fn super_classes(&self, class: &str) -> Result<Vec<String>, ClassHierarchyError> {
+        let mut classes = vec![class.to_string()];
+        while let Some(super_class) = self.super_class(classes.last().unwrap())? {
+            if classes.contains(&super_class) {
+                return Err(ClassHierarchyError::CyclicHierarchy(super_class));
+            }
+            classes.push(super_class);
+        }
+        Ok(classes)
+    }
+}
+
+/// Class name of the elements of an array of references, `None` if `array` isn't one
+pub(crate) fn array_component(array: &str) -> Option<&str> {
+    let component = array.strip_prefix('[')?;
+    if component.starts_with('[') {
+        Some(component)
+    } else {
+        component.strip_prefix('L')?.strip_suffix(';')
+    }
+}
+
+/// Name of an array with elements of class `component`
+pub(crate) fn array_of(component: &str) -> String {
+    if component.starts_with('[') {
+        format!("[{}", component)
+    } else {
+        format!("[L{};", component)
+    }
+}
+
+/// A [`ClassHierarchy`] that knows the classes added to it, and `java/lang/Object`
+#[derive(Debug, Clone, Default)]
+pub struct ClassHierarchyMap {
+    /// Superclass and whether it's an interface, for each known class
+    classes: HashMap<String, (Option<String>, bool)>,
+}
+
+impl ClassHierarchyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A map that knows `java/lang/Throwable`, `Exception`, `RuntimeException`, `Error` and `java/io/IOException`,
+    /// which is enough for code that only catches those
+    pub fn with_exceptions() -> Self {
+        let mut hierarchy = Self::new();
+        hierarchy.insert("java/lang/Throwable", Some(OBJECT), false);
+        hierarchy.insert("java/lang/Exception", Some("java/lang/Throwable"), false);
+        hierarchy.insert("java/lang/RuntimeException", Some("java/lang/Exception"), false);
+        hierarchy.insert("java/lang/Error", Some("java/lang/Throwable"), false);
+        hierarchy.insert("java/io/IOException", Some("java/lang/Exception"), false);
+        hierarchy
+    }
+
+    pub fn insert(&mut self, class: &str, super_class: Option<&str>, is_interface: bool) {
+        self.classes.insert(class.to_string(), (super_class.map(str::to_string), is_interface));
+    }
+
+    /// Adds a parsed class
+    pub fn add(&mut self, class: &Class) -> Result<(), ConstantPoolError> {
+        let is_interface = class.access_flags.contains(ClassAccessFlags::ACC_INTERFACE);
+        self.insert(class.name()?, class.super_class_name()?, is_interface);
+        Ok(())
+    }
+}
+
+impl ClassHierarchy for ClassHierarchyMap {
+    fn super_class(&self, class: &str) -> Result<Option<String>, ClassHierarchyError> {
+        match self.classes.get(class) {
+            Some((super_class, _)) => Ok(super_class.clone()),
+            None if class == OBJECT => Ok(None),
+            None => Err(ClassHierarchyError::UnknownClass(class.to_string())),
+        }
+    }
+
+    fn is_interface(&self, class: &str) -> Result<bool, ClassHierarchyError> {
+        match self.classes.get(class) {
+            Some((_, is_interface)) => Ok(*is_interface),
+            None if class == OBJECT => Ok(false),
+            None => Err(ClassHierarchyError::UnknownClass(class.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassHierarchy, ClassHierarchyError, ClassHierarchyMap};
+
+    fn hierarchy() -> ClassHierarchyMap {
+        let mut hierarchy = ClassHierarchyMap::with_exceptions();
+        hierarchy.insert("java/lang/Runnable", Some("java/lang/Object"), true);
+        hierarchy
+    }
+
+    #[test]
+    pub fn finds_common_super_classes() {
+        let hierarchy = hierarchy();
+        let common = |a, b| hierarchy.common_super_class(a, b).unwrap();
+        assert_eq!(common("java/io/IOException", "java/lang/RuntimeException"), "java/lang/Exception");
+        assert_eq!(common("java/io/IOException", "java/lang/Error"), "java/lang/Throwable");
+        assert_eq!(common("java/lang/Exception", "java/io/IOException"), "java/lang/Exception");
+        assert_eq!(common("java/lang/Runnable", "java/lang/Error"), "java/lang/Object");
+        assert_eq!(common("java/lang/Error", "java/lang/Object"), "java/lang/Object");
+        assert_eq!(common("[Ljava/io/IOException;", "[Ljava/lang/Error;"), "[Ljava/lang/Throwable;");
+        assert_eq!(common("[[Ljava/lang/Error;", "[[Ljava/lang/Error;"), "[[Ljava/lang/Error;");
+        assert_eq!(common("[[I", "[Ljava/lang/Error;"), "[Ljava/lang/Object;");
+        assert_eq!(common("[I", "[F"), "java/lang/Object");
+        assert_eq!(common("[I", "java/lang/Error"), "java/lang/Object");
+        assert_eq!(
+            hierarchy.common_super_class("java/lang/Error", "Foo"),
+            Err(ClassHierarchyError::UnknownClass("Foo".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn cyclic_hierarchies_are_errors() {
+        let mut hierarchy = hierarchy();
+        hierarchy.insert("A", Some("B"), false);
+        hierarchy.insert("B", Some("A"), false);
+        hierarchy.insert("C", Some("java/lang/Object"), false);
+        assert_eq!(hierarchy.common_super_class("A", "C"), Err(ClassHierarchyError::CyclicHierarchy("A".to_string())));
+        assert_eq!(hierarchy.common_super_class("C", "B"), Err(ClassHierarchyError::CyclicHierarchy("B".to_string())));
+    }
+}
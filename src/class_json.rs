@@ -0,0 +1,977 @@
+//! Converting classes to JSON and back
+//!
+//! The JSON mirrors the parsed model field by field, so [`from_json`] gives back a [`Class`] that
+//! [`Class::to_bytes`] writes into the same bytes it was read from. Members holding constant pool indices are named
+//! like the fields of the model (`name_index`, `class_index`...). Some members only resolve something for people
+//! reading the document and are ignored by [`from_json`]: they're marked *informational* below.
+//!
+//! # Schema, version 1
+//!
+//! The document is an object with these members:
+//!
+//! - `schema_version`: always `1`, it changes whenever the schema changes in an incompatible way
+//! - `java_version`: `{"major": 61, "minor": 0}`, plus the informational `release` (`"17"`) when it's known
+//! - `constant_pool`: the constants in order, every one an object with its `index`, which must be right, and a `tag`
+//!   that's the name of the constant in the specification without the `CONSTANT_` prefix (`Utf8`, `Methodref`...).
+//!   The other members are the fields of [`Constant`]: `Utf8`, `Integer`, `Long`, `Float` and `Double` have a
+//!   `value`, and non-finite floats are the strings `"NaN"`, `"Infinity"` and `"-Infinity"`. A `Utf8` with unpaired
+//!   surrogates, which a JSON string can't hold, has U+FFFD in their place in the `value`, and its modified UTF-8 as
+//!   the hex string `bytes`. The `reference_kind` of a `MethodHandle` is a [`MethodReferenceKind`] name like
+//!   `"InvokeStatic"`. Every other constant has an informational `resolved` string, like
+//!   `java/lang/Object.<init>:()V`. The slot after a `Long` or `Double` isn't listed.
+//! - `access_flags`: a list of flag names like `"ACC_PUBLIC"`, with bits that have no name as hex strings like
+//!   `"0x0100"`. Every other set of flags is written the same way.
+//! - `this_class`, `super_class` (`0` for `java/lang/Object`) and `interfaces`, with the informational `name`,
+//!   `super_class_name` and `interface_names`
+//! - `fields` and `methods`: objects with `access_flags`, `name_index`, `descriptor_index` and `attributes`, plus the
+//!   informational `name` and `descriptor`
+//! - `attributes`: see below
+//!
+//! Attributes are objects with a `name_index`, the informational `name`, and a `kind`, which is the name of the
+//! [`AttributeInfo`] variant. It's the same as the name of the attribute, except for `Unknown`, which is an attribute
+//! this crate doesn't understand or that's newer than the class file. The other members depend on the kind:
+//!
+//! - `ConstantValue`: `constantvalue_index`
+//! - `Code`: `max_stack`, `max_locals`, `instructions`, `exception_table` (objects with `start_pc`, `end_pc`,
+//!   `handler_pc` and `catch_type`) and `attributes`. Instructions are objects with the informational `pc`, the
+//!   `opcode` mnemonic and the operands of the [`Instruction`] variant: `value` for `bipush`, `sipush` and the
+//!   increment of `iinc`, `index` for local variables and constant pool indices, `offset` for branches, `default`,
+//!   `low`, `high` and `offsets` for `tableswitch`, `default` and `pairs` (`[[key, offset]...]`) for
+//!   `lookupswitch`, `count` for `invokeinterface`, `atype` (`"int"`...) for `newarray` and `dimensions` for
+//!   `multianewarray`. Instructions using constants have an informational `constant` too. Local variable
+//!   instructions with `"wide": true` get the `wide` prefix even if they don't need it. Code that can't be decoded
+//!   has the raw `code` as a hex string instead of `instructions`.
+//! - `StackMapTable`: `frames`, objects with the `type` of the [`StackMapFrame`] variant and its fields, where
+//!   verification types are objects with the `type` of the [`VerificationTypeInfo`] variant, plus `cpool_index` for
+//!   `Object` and `offset` for `Uninitialized`
+//! - `Exceptions`: `exception_index_table`
+//! - `InnerClasses`: `classes`, objects with `inner_class_info_index`, `outer_class_info_index`, `inner_name_index`
+//!   and `inner_class_access_flags`
+//! - `EnclosingMethod`: `class_index` and `method_index`
+//! - `Synthetic` and `Deprecated`: nothing else
+//! - `Signature`: `signature_index`
+//! - `SourceFile`: `sourcefile_index`
+//! - `SourceDebugExtension`: `debug_extension`, a hex string
+//! - `LineNumberTable`: `line_number_table`, objects with `start_pc` and `line_number`
+//! - `LocalVariableTable`: `local_variable_table`, objects with `start_pc`, `length`, `name_index`,
+//!   `descriptor_index` and `index`
+//! - `LocalVariableTypeTable`: `local_variable_type_table`, like `local_variable_table` with a `signature_index`
+//!   instead of the `descriptor_index`
+//! - `BootstrapMethods`: `bootstrap_methods`, objects with `bootstrap_method_ref` and `bootstrap_arguments`
+//! - `MethodParameters`: `parameters`, objects with `name_index` and `access_flags`
+//! - `Module`: the fields of [`ModuleAttribute`], with the fields of its tables
+//! - `ModulePackages`: `package_index`
+//! - `ModuleMainClass`: `main_class_index`
+//! - `NestHost`: `host_class_index`
+//! - `NestMembers` and `PermittedSubclasses`: `classes`
+//! - `Record`: `components`, objects like fields without `access_flags`
+//! - `Unknown`: `info`, a hex string
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::access_flags::{ClassAccessFlags, ExportsFlags, FieldAccessFlags, InnerClassAccessFlags, MethodAccessFlags, MethodParameterAccessFlags, ModuleFlags, RequiresFlags, flag_bit, flag_names};
+use crate::attribute::{Attribute, AttributeInfo, BootstrapMethod, InnerClass, LineNumber, LocalVariable, LocalVariableType, MethodParameter, ModuleAttribute, ModuleExports, ModuleProvides, ModuleRequires, RecordComponent};
+use crate::class::{Class, JavaVersion};
+use crate::code::{CodeAttribute, ExceptionTableEntry};
+use crate::constant_pool::{Constant, ConstantKind, ConstantPool, ConstantPoolError, MethodReferenceKind, MAX_SLOTS};
+use crate::field::Field;
+use crate::instruction::{ArrayType, Instruction, Opcode, encode_wide};
+use crate::json::{Json, JsonError};
+use crate::method::Method;
+use crate::mutf8;
+use crate::stack_map::{StackMapFrame, VerificationTypeInfo};
+
+/// Version of the schema written by [`to_json`], the only one [`from_json`] reads
+pub const SCHEMA_VERSION: i64 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClassJsonError {
+    #[error("invalid json: {0}")]
+    Json(#[from] JsonError),
+    #[error("schema version {0} isn't supported, only {SCHEMA_VERSION} is")]
+    UnsupportedSchemaVersion(i64),
+    #[error("{path}: missing member {name}")]
+    MissingMember { path: String, name: &'static str },
+    #[error("{path}: expected {expected}")]
+    InvalidValue { path: String, expected: String },
+    #[error("{path}: unknown {what} {name}")]
+    UnknownName { path: String, what: &'static str, name: String },
+}
+
+/// The class as a JSON document, following the schema in the [module documentation](self)
+pub fn to_json(class: &Class) -> Json {
+    let pool = &class.constant_pool;
+    let mut java_version = vec![("major", Json::int(class.java_version.major)), ("minor", Json::int(class.java_version.minor))];
+    if let Some(release) = class.java_version.release() {
+        java_version.push(("release", Json::string(release)));
+    }
+    Json::object([
+        ("schema_version", Json::int(SCHEMA_VERSION)),
+        ("java_version", Json::object(java_version)),
+        ("constant_pool", Json::Array(pool.iter().map(|(index, constant)| constant_json(pool, index, constant)).collect())),
+        ("access_flags", flags_json(class.access_flags.bits(), ClassAccessFlags::from_bits_truncate)),
+        ("this_class", Json::int(class.this_class)),
+        ("name", resolved(pool.get_class_name(class.this_class))),
+        ("super_class", Json::int(class.super_class)),
+        ("super_class_name", class.super_class_name().ok().flatten().map_or(Json::Null, Json::string)),
+        ("interfaces", indices_json(&class.interface_indices)),
+        ("interface_names", Json::Array(class.interface_indices.iter().map(|&index| resolved(pool.get_class_name(index))).collect())),
+        ("fields", Json::Array(class.fields.iter().map(|field| field_json(pool, field)).collect())),
+        ("methods", Json::Array(class.methods.iter().map(|method| method_json(pool, method)).collect())),
+        ("attributes", attributes_json(pool, &class.attributes)),
+    ])
+}
+
+/// Reads a class from a JSON document written by [`to_json`], possibly edited since
+///
+/// Only the shape of the document is checked, the class may still be invalid: parse what [`Class::to_bytes`]
+/// writes to check it.
+pub fn from_json(json: &Json) -> Result<Class, ClassJsonError> {
+    let node = Node { json, path: "$".to_string() };
+    let schema_version = node.int("schema_version")?;
+    if schema_version != SCHEMA_VERSION {
+        return Err(ClassJsonError::UnsupportedSchemaVersion(schema_version));
+    }
+    let java_version = node.get("java_version")?;
+    Ok(Class {
+        java_version: JavaVersion { major: java_version.int("major")?, minor: java_version.int("minor")? },
+        constant_pool: parse_constant_pool(&node.get("constant_pool")?)?,
+        access_flags: ClassAccessFlags::from_bits_retain(parse_flags(&node.get("access_flags")?, ClassAccessFlags::from_bits_truncate)?),
+        this_class: node.int("this_class")?,
+        super_class: node.int("super_class")?,
+        interface_indices: node.ints("interfaces")?,
+        fields: node.array("fields", |field| Ok(Field {
+            access_flags: FieldAccessFlags::from_bits_retain(parse_flags(&field.get("access_flags")?, FieldAccessFlags::from_bits_truncate)?),
+            name_index: field.int("name_index")?,
+            descriptor_index: field.int("descriptor_index")?,
+            attributes: parse_attributes(&field)?,
+        }))?,
+        methods: node.array("methods", |method| Ok(Method {
+            access_flags: MethodAccessFlags::from_bits_retain(parse_flags(&method.get("access_flags")?, MethodAccessFlags::from_bits_truncate)?),
+            name_index: method.int("name_index")?,
+            descriptor_index: method.int("descriptor_index")?,
+            attributes: parse_attributes(&method)?,
+        }))?,
+        attributes: parse_attributes(&node)?,
+    })
+}
+
+/// A string resolved from the constant pool for readers of the document, `null` if it couldn't be
+fn resolved<S: AsRef<str>>(string: Result<S, ConstantPoolError>) -> Json {
+    string.map(|string| Json::string(string.as_ref())).unwrap_or(Json::Null)
+}
+
+fn indices_json(indices: &[u16]) -> Json {
+    Json::Array(indices.iter().map(|&index| Json::int(index)).collect())
+}
+
+fn hex(bytes: &[u8]) -> Json {
+    Json::String(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The shortest text that reads back as `value`, or `NaN`, `Infinity` and `-Infinity`
+pub(crate) fn float_text<T: Debug + Into<f64> + Copy>(value: T) -> String {
+    let wide: f64 = value.into();
+    if wide.is_finite() {
+        format!("{:?}", value)
+    } else if wide.is_nan() {
+        "NaN".to_string()
+    } else if wide > 0.0 {
+        "Infinity".to_string()
+    } else {
+        "-Infinity".to_string()
+    }
+}
+
+fn float_json<T: Debug + Into<f64> + Copy>(value: T) -> Json {
+    let text = float_text(value);
+    if value.into().is_finite() {
+        Json::Number(text)
+    } else {
+        Json::String(text)
+    }
+}
+
+/// Names of the flags set in `bits`, see [`flag_names`]
+fn flags_json<T: Debug>(bits: u16, from_bits_truncate: fn(u16) -> T) -> Json {
+    Json::Array(flag_names(bits, from_bits_truncate).into_iter().map(Json::String).collect())
+}
+
+/// Names of the method or field a `Fieldref`, `Methodref` or `InterfaceMethodref` points to, like `java/lang/Object.<init>:()V`
+fn describe_member(pool: &ConstantPool, class_index: u16, name_and_type_index: u16) -> Result<String, ConstantPoolError> {
+    let (name, descriptor) = pool.get_name_and_type(name_and_type_index)?;
+    Ok(format!("{}.{}:{}", pool.get_class_name(class_index)?, name, descriptor))
+}
+
+/// What the constant at `index` stands for, as written in the `resolved` and `constant` members
+fn describe(pool: &ConstantPool, index: u16) -> Result<String, ConstantPoolError> {
+    Ok(match pool.get(index)? {
+        Constant::Class { name_index } |
+        Constant::String { string_index: name_index } |
+        Constant::MethodType { descriptor_index: name_index } |
+        Constant::Module { name_index } |
+        Constant::Package { name_index } => pool.get_utf8(*name_index)?.to_string(),
+        Constant::Field { class_index, name_and_type_index } |
+        Constant::Method { class_index, name_and_type_index } |
+        Constant::InterfaceMethod { class_index, name_and_type_index } => describe_member(pool, *class_index, *name_and_type_index)?,
+        Constant::NameAndType { name_index, descriptor_index } => {
+            format!("{}:{}", pool.get_utf8(*name_index)?, pool.get_utf8(*descriptor_index)?)
+        }
+        Constant::MethodHandle { reference_kind, reference_index } => match pool.get(*reference_index)? {
+            Constant::Field { class_index, name_and_type_index } |
+            Constant::Method { class_index, name_and_type_index } |
+            Constant::InterfaceMethod { class_index, name_and_type_index } => {
+                format!("{:?} {}", reference_kind, describe_member(pool, *class_index, *name_and_type_index)?)
+            }
+            other => return Err(ConstantPoolError::UnexpectedKind { index: *reference_index, expected: ConstantKind::Method, actual: other.kind() }),
+        },
+        Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+        Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            let (name, descriptor) = pool.get_name_and_type(*name_and_type_index)?;
+            format!("#{}:{}:{}", bootstrap_method_attr_index, name, descriptor)
+        }
+        Constant::Integer(value) => value.to_string(),
+        Constant::Long(value) => value.to_string(),
+        Constant::Float(value) => float_text(*value),
+        Constant::Double(value) => float_text(*value),
+        Constant::UTF8String(string) | Constant::UTF8Bytes { string, .. } => string.clone(),
+        Constant::Unusable => return Err(ConstantPoolError::UnusableIndex(index)),
+    })
+}
+
+fn constant_json(pool: &ConstantPool, index: u16, constant: &Constant) -> Json {
+    let kind = constant.kind().to_string();
+    let mut members = vec![("index", Json::int(index)), ("tag", Json::string(kind.trim_start_matches("CONSTANT_")))];
+    match constant {
+        Constant::Class { name_index } | Constant::Module { name_index } | Constant::Package { name_index } => {
+            members.push(("name_index", Json::int(*name_index)));
+        }
+        Constant::Field { class_index, name_and_type_index } |
+        Constant::Method { class_index, name_and_type_index } |
+        Constant::InterfaceMethod { class_index, name_and_type_index } => {
+            members.push(("class_index", Json::int(*class_index)));
+            members.push(("name_and_type_index", Json::int(*name_and_type_index)));
+        }
+        Constant::String { string_index } => members.push(("string_index", Json::int(*string_index))),
+        Constant::Integer(value) => members.push(("value", Json::int(*value))),
+        Constant::Float(value) => members.push(("value", float_json(*value))),
+        Constant::Long(value) => members.push(("value", Json::int(*value))),
+        Constant::Double(value) => members.push(("value", float_json(*value))),
+        Constant::NameAndType { name_index, descriptor_index } => {
+            members.push(("name_index", Json::int(*name_index)));
+            members.push(("descriptor_index", Json::int(*descriptor_index)));
+        }
+        Constant::UTF8String(string) => members.push(("value", Json::string(string))),
+        Constant::UTF8Bytes { string, bytes } => {
+            members.push(("value", Json::string(string)));
+            members.push(("bytes", hex(bytes)));
+        }
+        Constant::MethodHandle { reference_kind, reference_index } => {
+            members.push(("reference_kind", Json::String(format!("{:?}", reference_kind))));
+            members.push(("reference_index", Json::int(*reference_index)));
+        }
+        Constant::MethodType { descriptor_index } => members.push(("descriptor_index", Json::int(*descriptor_index))),
+        Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+        Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            members.push(("bootstrap_method_attr_index", Json::int(*bootstrap_method_attr_index)));
+            members.push(("name_and_type_index", Json::int(*name_and_type_index)));
+        }
+        Constant::Unusable => {}
+    }
+    let has_value = matches!(
+        constant,
+        Constant::UTF8String(_) | Constant::UTF8Bytes { .. } | Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_)
+    );
+    if !has_value {
+        members.push(("resolved", resolved(describe(pool, index))));
+    }
+    Json::object(members)
+}
+
+fn field_json(pool: &ConstantPool, field: &Field) -> Json {
+    Json::object([
+        ("access_flags", flags_json(field.access_flags.bits(), FieldAccessFlags::from_bits_truncate)),
+        ("name_index", Json::int(field.name_index)),
+        ("name", resolved(field.name(pool))),
+        ("descriptor_index", Json::int(field.descriptor_index)),
+        ("descriptor", resolved(field.descriptor(pool))),
+        ("attributes", attributes_json(pool, &field.attributes)),
+    ])
+}
+
+fn method_json(pool: &ConstantPool, method: &Method) -> Json {
+    Json::object([
+        ("access_flags", flags_json(method.access_flags.bits(), MethodAccessFlags::from_bits_truncate)),
+        ("name_index", Json::int(method.name_index)),
+        ("name", resolved(method.name(pool))),
+        ("descriptor_index", Json::int(method.descriptor_index)),
+        ("descriptor", resolved(method.descriptor(pool))),
+        ("attributes", attributes_json(pool, &method.attributes)),
+    ])
+}
+
+fn attributes_json(pool: &ConstantPool, attributes: &[Attribute]) -> Json {
+    Json::Array(attributes.iter().map(|attribute| attribute_json(pool, attribute)).collect())
+}
+
+pub(crate) fn attribute_kind(info: &AttributeInfo) -> &'static str {
+    match info {
+        AttributeInfo::ConstantValue { .. } => "ConstantValue",
+        AttributeInfo::Code(_) => "Code",
+        AttributeInfo::StackMapTable(_) => "StackMapTable",
+        AttributeInfo::Exceptions(_) => "Exceptions",
+        AttributeInfo::InnerClasses(_) => "InnerClasses",
+        AttributeInfo::EnclosingMethod { .. } => "EnclosingMethod",
+        AttributeInfo::Synthetic => "Synthetic",
+        AttributeInfo::Signature { .. } => "Signature",
+        AttributeInfo::SourceFile { .. } => "SourceFile",
+        AttributeInfo::SourceDebugExtension(_) => "SourceDebugExtension",
+        AttributeInfo::LineNumberTable(_) => "LineNumberTable",
+        AttributeInfo::LocalVariableTable(_) => "LocalVariableTable",
+        AttributeInfo::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+        AttributeInfo::Deprecated => "Deprecated",
+        AttributeInfo::BootstrapMethods(_) => "BootstrapMethods",
+        AttributeInfo::MethodParameters(_) => "MethodParameters",
+        AttributeInfo::Module(_) => "Module",
+        AttributeInfo::ModulePackages(_) => "ModulePackages",
+        AttributeInfo::ModuleMainClass { .. } => "ModuleMainClass",
+        AttributeInfo::NestHost { .. } => "NestHost",
+        AttributeInfo::NestMembers(_) => "NestMembers",
+        AttributeInfo::Record(_) => "Record",
+        AttributeInfo::PermittedSubclasses(_) => "PermittedSubclasses",
+        AttributeInfo::Unknown(_) => "Unknown",
+    }
+}
+
+fn attribute_json(pool: &ConstantPool, attribute: &Attribute) -> Json {
+    let mut members = vec![
+        ("name_index", Json::int(attribute.attribute_name_index)),
+        ("name", resolved(attribute.name(pool))),
+        ("kind", Json::string(attribute_kind(&attribute.info))),
+    ];
+    match &attribute.info {
+        AttributeInfo::ConstantValue { constantvalue_index } => members.push(("constantvalue_index", Json::int(*constantvalue_index))),
+        AttributeInfo::Code(code) => members.extend(code_json(pool, code)),
+        AttributeInfo::StackMapTable(frames) => {
+            members.push(("frames", Json::Array(frames.iter().map(frame_json).collect())));
+        }
+        AttributeInfo::Exceptions(exceptions) => members.push(("exception_index_table", indices_json(exceptions))),
+        AttributeInfo::InnerClasses(classes) => members.push(("classes", Json::Array(classes.iter().map(|class| Json::object([
+            ("inner_class_info_index", Json::int(class.inner_class_info_index)),
+            ("outer_class_info_index", Json::int(class.outer_class_info_index)),
+            ("inner_name_index", Json::int(class.inner_name_index)),
+            ("inner_class_access_flags", flags_json(class.inner_class_access_flags.bits(), InnerClassAccessFlags::from_bits_truncate)),
+        ])).collect()))),
+        AttributeInfo::EnclosingMethod { class_index, method_index } => {
+            members.push(("class_index", Json::int(*class_index)));
+            members.push(("method_index", Json::int(*method_index)));
+        }
+        AttributeInfo::Synthetic | AttributeInfo::Deprecated => {}
+        AttributeInfo::Signature { signature_index } => members.push(("signature_index", Json::int(*signature_index))),
+        AttributeInfo::SourceFile { sourcefile_index } => members.push(("sourcefile_index", Json::int(*sourcefile_index))),
+        AttributeInfo::SourceDebugExtension(bytes) => members.push(("debug_extension", hex(bytes))),
+        AttributeInfo::LineNumberTable(lines) => members.push(("line_number_table", Json::Array(lines.iter().map(|line| Json::object([
+            ("start_pc", Json::int(line.start_pc)),
+            ("line_number", Json::int(line.line_number)),
+        ])).collect()))),
+        AttributeInfo::LocalVariableTable(variables) => members.push(("local_variable_table", Json::Array(variables.iter().map(|variable| Json::object([
+            ("start_pc", Json::int(variable.start_pc)),
+            ("length", Json::int(variable.length)),
+            ("name_index", Json::int(variable.name_index)),
+            ("descriptor_index", Json::int(variable.descriptor_index)),
+            ("index", Json::int(variable.index)),
+        ])).collect()))),
+        AttributeInfo::LocalVariableTypeTable(variables) => members.push(("local_variable_type_table", Json::Array(variables.iter().map(|variable| Json::object([
+            ("start_pc", Json::int(variable.start_pc)),
+            ("length", Json::int(variable.length)),
+            ("name_index", Json::int(variable.name_index)),
+            ("signature_index", Json::int(variable.signature_index)),
+            ("index", Json::int(variable.index)),
+        ])).collect()))),
+        AttributeInfo::BootstrapMethods(methods) => members.push(("bootstrap_methods", Json::Array(methods.iter().map(|method| Json::object([
+            ("bootstrap_method_ref", Json::int(method.bootstrap_method_ref)),
+            ("bootstrap_arguments", indices_json(&method.bootstrap_arguments)),
+        ])).collect()))),
+        AttributeInfo::MethodParameters(parameters) => members.push(("parameters", Json::Array(parameters.iter().map(|parameter| Json::object([
+            ("name_index", Json::int(parameter.name_index)),
+            ("access_flags", flags_json(parameter.access_flags.bits(), MethodParameterAccessFlags::from_bits_truncate)),
+        ])).collect()))),
+        AttributeInfo::Module(module) => members.extend(module_json(module)),
+        AttributeInfo::ModulePackages(packages) => members.push(("package_index", indices_json(packages))),
+        AttributeInfo::ModuleMainClass { main_class_index } => members.push(("main_class_index", Json::int(*main_class_index))),
+        AttributeInfo::NestHost { host_class_index } => members.push(("host_class_index", Json::int(*host_class_index))),
+        AttributeInfo::NestMembers(classes) | AttributeInfo::PermittedSubclasses(classes) => members.push(("classes", indices_json(classes))),
+        AttributeInfo::Record(components) => members.push(("components", Json::Array(components.iter().map(|component| Json::object([
+            ("name_index", Json::int(component.name_index)),
+            ("name", resolved(component.name(pool))),
+            ("descriptor_index", Json::int(component.descriptor_index)),
+            ("descriptor", resolved(component.descriptor(pool))),
+            ("attributes", attributes_json(pool, &component.attributes)),
+        ])).collect()))),
+        AttributeInfo::Unknown(bytes) => members.push(("info", hex(bytes))),
+    }
+    Json::object(members)
+}
+
+fn module_json(module: &ModuleAttribute) -> Vec<(&'static str, Json)> {
+    let exports_json = |exports: &Vec<ModuleExports>| Json::Array(exports.iter().map(|exports| Json::object([
+        ("package_index", Json::int(exports.package_index)),
+        ("flags", flags_json(exports.flags.bits(), ExportsFlags::from_bits_truncate)),
+        ("to_index", indices_json(&exports.to_index)),
+    ])).collect());
+    vec![
+        ("module_name_index", Json::int(module.module_name_index)),
+        ("module_flags", flags_json(module.module_flags.bits(), ModuleFlags::from_bits_truncate)),
+        ("module_version_index", Json::int(module.module_version_index)),
+        ("requires", Json::Array(module.requires.iter().map(|requires| Json::object([
+            ("requires_index", Json::int(requires.requires_index)),
+            ("requires_flags", flags_json(requires.requires_flags.bits(), RequiresFlags::from_bits_truncate)),
+            ("requires_version_index", Json::int(requires.requires_version_index)),
+        ])).collect())),
+        ("exports", exports_json(&module.exports)),
+        ("opens", exports_json(&module.opens)),
+        ("uses_index", indices_json(&module.uses_index)),
+        ("provides", Json::Array(module.provides.iter().map(|provides| Json::object([
+            ("provides_index", Json::int(provides.provides_index)),
+            ("provides_with_index", indices_json(&provides.provides_with_index)),
+        ])).collect())),
+    ]
+}
+
+fn code_json(pool: &ConstantPool, code: &CodeAttribute) -> Vec<(&'static str, Json)> {
+    let mut members = vec![("max_stack", Json::int(code.max_stack)), ("max_locals", Json::int(code.max_locals))];
+    match instructions_json(pool, &code.code) {
+        Some(instructions) => members.push(("instructions", instructions)),
+        None => members.push(("code", hex(&code.code))),
+    }
+    members.push(("exception_table", Json::Array(code.exception_table.iter().map(|entry| Json::object([
+        ("start_pc", Json::int(entry.start_pc)),
+        ("end_pc", Json::int(entry.end_pc)),
+        ("handler_pc", Json::int(entry.handler_pc)),
+        ("catch_type", Json::int(entry.catch_type)),
+    ])).collect())));
+    members.push(("attributes", attributes_json(pool, &code.attributes)));
+    members
+}
+
+/// The decoded instructions, `None` if the code can't be decoded or encoding the instructions again doesn't give it back
+fn instructions_json(pool: &ConstantPool, code: &[u8]) -> Option<Json> {
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while (pc as usize) < code.len() {
+        let (instruction, next) = Instruction::decode(code, pc).ok()?;
+        let original = &code[pc as usize..next as usize];
+        let mut encoded = vec![];
+        instruction.encode(pc, &mut encoded);
+        let wide = encoded != original;
+        if wide {
+            encoded.clear();
+            encode_wide(&instruction, &mut encoded)?;
+            if encoded != original {
+                return None;
+            }
+        }
+        let mut members = vec![("pc", Json::int(pc)), ("opcode", Json::string(instruction.opcode().mnemonic()))];
+        members.extend(operands_json(&instruction));
+        if let Some(index) = constant_operand(&instruction) {
+            members.push(("constant", resolved(describe(pool, index))));
+        }
+        if wide {
+            members.push(("wide", Json::Bool(true)));
+        }
+        instructions.push(Json::object(members));
+        pc = next;
+    }
+    Some(Json::Array(instructions))
+}
+
+/// The constant pool index `instruction` uses, if it uses one
+fn constant_operand(instruction: &Instruction) -> Option<u16> {
+    Some(match instruction {
+        Instruction::Ldc(index) => *index as u16,
+        Instruction::LdcW(index) | Instruction::Ldc2W(index) | Instruction::Getstatic(index) | Instruction::Putstatic(index) |
+        Instruction::Getfield(index) | Instruction::Putfield(index) | Instruction::Invokevirtual(index) |
+        Instruction::Invokespecial(index) | Instruction::Invokestatic(index) | Instruction::New(index) |
+        Instruction::Anewarray(index) | Instruction::Checkcast(index) | Instruction::Instanceof(index) |
+        Instruction::Invokeinterface { index, .. } | Instruction::Invokedynamic { index } |
+        Instruction::Multianewarray { index, .. } => *index,
+        _ => return None,
+    })
+}
+
+fn operands_json(instruction: &Instruction) -> Vec<(&'static str, Json)> {
+    match instruction {
+        Instruction::Bipush(value) => vec![("value", Json::int(*value))],
+        Instruction::Sipush(value) => vec![("value", Json::int(*value))],
+        Instruction::Ldc(index) => vec![("index", Json::int(*index))],
+        Instruction::LdcW(index) | Instruction::Ldc2W(index) | Instruction::Iload(index) | Instruction::Lload(index) |
+        Instruction::Fload(index) | Instruction::Dload(index) | Instruction::Aload(index) | Instruction::Istore(index) |
+        Instruction::Lstore(index) | Instruction::Fstore(index) | Instruction::Dstore(index) | Instruction::Astore(index) |
+        Instruction::Ret(index) | Instruction::Getstatic(index) | Instruction::Putstatic(index) | Instruction::Getfield(index) |
+        Instruction::Putfield(index) | Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) |
+        Instruction::Invokestatic(index) | Instruction::New(index) | Instruction::Anewarray(index) |
+        Instruction::Checkcast(index) | Instruction::Instanceof(index) | Instruction::Invokedynamic { index } => {
+            vec![("index", Json::int(*index))]
+        }
+        Instruction::Iinc { index, value } => vec![("index", Json::int(*index)), ("value", Json::int(*value))],
+        Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) | Instruction::Ifge(offset) |
+        Instruction::Ifgt(offset) | Instruction::Ifle(offset) | Instruction::IfIcmpeq(offset) | Instruction::IfIcmpne(offset) |
+        Instruction::IfIcmplt(offset) | Instruction::IfIcmpge(offset) | Instruction::IfIcmpgt(offset) |
+        Instruction::IfIcmple(offset) | Instruction::IfAcmpeq(offset) | Instruction::IfAcmpne(offset) |
+        Instruction::Goto(offset) | Instruction::Jsr(offset) | Instruction::Ifnull(offset) | Instruction::Ifnonnull(offset) => {
+            vec![("offset", Json::int(*offset))]
+        }
+        Instruction::GotoW(offset) | Instruction::JsrW(offset) => vec![("offset", Json::int(*offset))],
+        Instruction::Tableswitch { default, low, high, offsets } => vec![
+            ("default", Json::int(*default)),
+            ("low", Json::int(*low)),
+            ("high", Json::int(*high)),
+            ("offsets", Json::Array(offsets.iter().map(|&offset| Json::int(offset)).collect())),
+        ],
+        Instruction::Lookupswitch { default, pairs } => vec![
+            ("default", Json::int(*default)),
+            ("pairs", Json::Array(pairs.iter().map(|&(key, offset)| Json::Array(vec![Json::int(key), Json::int(offset)])).collect())),
+        ],
+        Instruction::Invokeinterface { index, count } => vec![("index", Json::int(*index)), ("count", Json::int(*count))],
+        Instruction::Newarray { atype } => vec![("atype", Json::string(atype.name()))],
+        Instruction::Multianewarray { index, dimensions } => vec![("index", Json::int(*index)), ("dimensions", Json::int(*dimensions))],
+        _ => vec![],
+    }
+}
+
+fn verification_type_json(info: &VerificationTypeInfo) -> Json {
+    match info {
+        VerificationTypeInfo::Object { cpool_index } => Json::object([("type", Json::string("Object")), ("cpool_index", Json::int(*cpool_index))]),
+        VerificationTypeInfo::Uninitialized { offset } => Json::object([("type", Json::string("Uninitialized")), ("offset", Json::int(*offset))]),
+        other => Json::object([("type", Json::String(format!("{:?}", other)))]),
+    }
+}
+
+fn verification_types_json(infos: &[VerificationTypeInfo]) -> Json {
+    Json::Array(infos.iter().map(verification_type_json).collect())
+}
+
+fn frame_json(frame: &StackMapFrame) -> Json {
+    let (kind, mut members) = match frame {
+        StackMapFrame::SameFrame { .. } => ("SameFrame", vec![]),
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. } => ("SameLocals1StackItemFrame", vec![("stack", verification_type_json(stack))]),
+        StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+            ("SameLocals1StackItemFrameExtended", vec![("stack", verification_type_json(stack))])
+        }
+        StackMapFrame::ChopFrame { k, .. } => ("ChopFrame", vec![("k", Json::int(*k))]),
+        StackMapFrame::SameFrameExtended { .. } => ("SameFrameExtended", vec![]),
+        StackMapFrame::AppendFrame { locals, .. } => ("AppendFrame", vec![("locals", verification_types_json(locals))]),
+        StackMapFrame::FullFrame { locals, stack, .. } => {
+            ("FullFrame", vec![("locals", verification_types_json(locals)), ("stack", verification_types_json(stack))])
+        }
+    };
+    members.insert(0, ("offset_delta", Json::int(frame.offset_delta())));
+    members.insert(0, ("type", Json::string(kind)));
+    Json::object(members)
+}
+
+/// A value in the document along with where it is, for error messages
+struct Node<'a> {
+    json: &'a Json,
+    /// Like `$.methods[1].attributes[0]`
+    path: String,
+}
+
+impl<'a> Node<'a> {
+    fn invalid(&self, expected: impl Into<String>) -> ClassJsonError {
+        ClassJsonError::InvalidValue { path: self.path.clone(), expected: expected.into() }
+    }
+
+    fn unknown(&self, what: &'static str, name: &str) -> ClassJsonError {
+        ClassJsonError::UnknownName { path: self.path.clone(), what, name: name.to_string() }
+    }
+
+    fn member(&self, name: &str) -> Option<Node<'a>> {
+        self.json.get(name).map(|json| Node { json, path: format!("{}.{}", self.path, name) })
+    }
+
+    fn get(&self, name: &'static str) -> Result<Node<'a>, ClassJsonError> {
+        if !matches!(self.json, Json::Object(_)) {
+            return Err(self.invalid("an object"));
+        }
+        self.member(name).ok_or_else(|| ClassJsonError::MissingMember { path: self.path.clone(), name })
+    }
+
+    fn as_int<T: TryFrom<i64>>(&self) -> Result<T, ClassJsonError> {
+        match self.json {
+            Json::Number(number) => number.parse::<i64>().ok().and_then(|value| T::try_from(value).ok()),
+            _ => None,
+        }.ok_or_else(|| self.invalid(format!("a {}", std::any::type_name::<T>())))
+    }
+
+    fn as_str(&self) -> Result<&'a str, ClassJsonError> {
+        match self.json {
+            Json::String(string) => Ok(string),
+            _ => Err(self.invalid("a string")),
+        }
+    }
+
+    fn as_float<T: FromStr>(&self) -> Result<T, ClassJsonError> {
+        match self.json {
+            Json::Number(number) => number.parse().ok(),
+            Json::String(name) if matches!(name.as_str(), "NaN" | "Infinity" | "-Infinity") => name.parse().ok(),
+            _ => None,
+        }.ok_or_else(|| self.invalid("a number, \"NaN\", \"Infinity\" or \"-Infinity\""))
+    }
+
+    fn as_hex(&self) -> Result<Vec<u8>, ClassJsonError> {
+        let string = self.as_str()?;
+        if string.len() % 2 != 0 {
+            return Err(self.invalid("a hex string"));
+        }
+        (0..string.len()).step_by(2)
+            .map(|i| string.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.invalid("a hex string"))
+    }
+
+    fn items(&self) -> Result<Vec<Node<'a>>, ClassJsonError> {
+        match self.json {
+            Json::Array(items) => Ok(items.iter().enumerate().map(|(i, json)| Node { json, path: format!("{}[{}]", self.path, i) }).collect()),
+            _ => Err(self.invalid("an array")),
+        }
+    }
+
+    fn int<T: TryFrom<i64>>(&self, name: &'static str) -> Result<T, ClassJsonError> {
+        self.get(name)?.as_int()
+    }
+
+    fn str(&self, name: &'static str) -> Result<&'a str, ClassJsonError> {
+        self.get(name)?.as_str()
+    }
+
+    fn array<T>(&self, name: &'static str, parse: impl FnMut(Node<'a>) -> Result<T, ClassJsonError>) -> Result<Vec<T>, ClassJsonError> {
+        self.get(name)?.items()?.into_iter().map(parse).collect()
+    }
+
+    fn ints<T: TryFrom<i64>>(&self, name: &'static str) -> Result<Vec<T>, ClassJsonError> {
+        self.array(name, |item| item.as_int())
+    }
+}
+
+/// The bits of a list of flag names written by [`flags_json`]
+fn parse_flags<T: Debug>(node: &Node, from_bits_truncate: fn(u16) -> T) -> Result<u16, ClassJsonError> {
+    let mut bits = 0;
+    for item in node.items()? {
+        let name = item.as_str()?;
+        bits |= flag_bit(name, from_bits_truncate).ok_or_else(|| item.unknown("flag", name))?;
+    }
+    Ok(bits)
+}
+
+fn parse_constant_pool(node: &Node) -> Result<ConstantPool, ClassJsonError> {
+    let mut pool = ConstantPool::new();
+    for item in node.items()? {
+        let expected = pool.len() + 1;
+        if item.int::<usize>("index")? != expected {
+            return Err(item.get("index")?.invalid(format!("index {}", expected)));
+        }
+        pool.push(parse_constant(&item)?).map_err(|_| item.invalid(format!("at most {} constant pool slots", MAX_SLOTS)))?;
+    }
+    Ok(pool)
+}
+
+fn parse_constant(node: &Node) -> Result<Constant, ClassJsonError> {
+    let tag = node.str("tag")?;
+    Ok(match tag {
+        "Class" => Constant::Class { name_index: node.int("name_index")? },
+        "Fieldref" => Constant::Field { class_index: node.int("class_index")?, name_and_type_index: node.int("name_and_type_index")? },
+        "Methodref" => Constant::Method { class_index: node.int("class_index")?, name_and_type_index: node.int("name_and_type_index")? },
+        "InterfaceMethodref" => {
+            Constant::InterfaceMethod { class_index: node.int("class_index")?, name_and_type_index: node.int("name_and_type_index")? }
+        }
+        "String" => Constant::String { string_index: node.int("string_index")? },
+        "Integer" => Constant::Integer(node.int("value")?),
+        "Float" => Constant::Float(node.get("value")?.as_float()?),
+        "Long" => Constant::Long(node.int("value")?),
+        "Double" => Constant::Double(node.get("value")?.as_float()?),
+        "NameAndType" => Constant::NameAndType { name_index: node.int("name_index")?, descriptor_index: node.int("descriptor_index")? },
+        "Utf8" => match node.member("bytes") {
+            Some(bytes) => {
+                let raw = bytes.as_hex()?;
+                let units = mutf8::decode_utf16(&raw).map_err(|_| bytes.invalid("modified UTF-8"))?;
+                Constant::UTF8Bytes { string: String::from_utf16_lossy(&units), bytes: raw }
+            }
+            None => Constant::UTF8String(node.str("value")?.to_string()),
+        },
+        "MethodHandle" => {
+            let kind = node.get("reference_kind")?;
+            let name = kind.as_str()?;
+            let reference_kind = MethodReferenceKind::from_name(name).ok_or_else(|| kind.unknown("reference kind", name))?;
+            Constant::MethodHandle { reference_kind, reference_index: node.int("reference_index")? }
+        }
+        "MethodType" => Constant::MethodType { descriptor_index: node.int("descriptor_index")? },
+        "InvokeDynamic" => Constant::InvokeDynamic {
+            bootstrap_method_attr_index: node.int("bootstrap_method_attr_index")?,
+            name_and_type_index: node.int("name_and_type_index")?,
+        },
+        "Dynamic" => Constant::Dynamic {
+            bootstrap_method_attr_index: node.int("bootstrap_method_attr_index")?,
+            name_and_type_index: node.int("name_and_type_index")?,
+        },
+        "Module" => Constant::Module { name_index: node.int("name_index")? },
+        "Package" => Constant::Package { name_index: node.int("name_index")? },
+        _ => return Err(node.get("tag")?.unknown("constant tag", tag)),
+    })
+}
+
+/// The `attributes` member of `node`
+fn parse_attributes(node: &Node) -> Result<Vec<Attribute>, ClassJsonError> {
+    node.array("attributes", |attribute| Ok(Attribute {
+        attribute_name_index: attribute.int("name_index")?,
+        info: parse_attribute_info(&attribute)?,
+    }))
+}
+
+fn parse_attribute_info(node: &Node) -> Result<AttributeInfo, ClassJsonError> {
+    let kind = node.str("kind")?;
+    Ok(match kind {
+        "ConstantValue" => AttributeInfo::ConstantValue { constantvalue_index: node.int("constantvalue_index")? },
+        "Code" => AttributeInfo::Code(parse_code(node)?),
+        "StackMapTable" => AttributeInfo::StackMapTable(node.array("frames", |frame| parse_frame(&frame))?),
+        "Exceptions" => AttributeInfo::Exceptions(node.ints("exception_index_table")?),
+        "InnerClasses" => AttributeInfo::InnerClasses(node.array("classes", |class| Ok(InnerClass {
+            inner_class_info_index: class.int("inner_class_info_index")?,
+            outer_class_info_index: class.int("outer_class_info_index")?,
+            inner_name_index: class.int("inner_name_index")?,
+            inner_class_access_flags: InnerClassAccessFlags::from_bits_retain(parse_flags(&class.get("inner_class_access_flags")?, InnerClassAccessFlags::from_bits_truncate)?),
+        }))?),
+        "EnclosingMethod" => AttributeInfo::EnclosingMethod { class_index: node.int("class_index")?, method_index: node.int("method_index")? },
+        "Synthetic" => AttributeInfo::Synthetic,
+        "Signature" => AttributeInfo::Signature { signature_index: node.int("signature_index")? },
+        "SourceFile" => AttributeInfo::SourceFile { sourcefile_index: node.int("sourcefile_index")? },
+        "SourceDebugExtension" => AttributeInfo::SourceDebugExtension(node.get("debug_extension")?.as_hex()?),
+        "LineNumberTable" => AttributeInfo::LineNumberTable(node.array("line_number_table", |line| Ok(LineNumber {
+            start_pc: line.int("start_pc")?,
+            line_number: line.int("line_number")?,
+        }))?),
+        "LocalVariableTable" => AttributeInfo::LocalVariableTable(node.array("local_variable_table", |variable| Ok(LocalVariable {
+            start_pc: variable.int("start_pc")?,
+            length: variable.int("length")?,
+            name_index: variable.int("name_index")?,
+            descriptor_index: variable.int("descriptor_index")?,
+            index: variable.int("index")?,
+        }))?),
+        "LocalVariableTypeTable" => AttributeInfo::LocalVariableTypeTable(node.array("local_variable_type_table", |variable| Ok(LocalVariableType {
+            start_pc: variable.int("start_pc")?,
+            length: variable.int("length")?,
+            name_index: variable.int("name_index")?,
+            signature_index: variable.int("signature_index")?,
+            index: variable.int("index")?,
+        }))?),
+        "Deprecated" => AttributeInfo::Deprecated,
+        "BootstrapMethods" => AttributeInfo::BootstrapMethods(node.array("bootstrap_methods", |method| Ok(BootstrapMethod {
+            bootstrap_method_ref: method.int("bootstrap_method_ref")?,
+            bootstrap_arguments: method.ints("bootstrap_arguments")?,
+        }))?),
+        "MethodParameters" => AttributeInfo::MethodParameters(node.array("parameters", |parameter| Ok(MethodParameter {
+            name_index: parameter.int("name_index")?,
+            access_flags: MethodParameterAccessFlags::from_bits_retain(parse_flags(&parameter.get("access_flags")?, MethodParameterAccessFlags::from_bits_truncate)?),
+        }))?),
+        "Module" => AttributeInfo::Module(parse_module(node)?),
+        "ModulePackages" => AttributeInfo::ModulePackages(node.ints("package_index")?),
+        "ModuleMainClass" => AttributeInfo::ModuleMainClass { main_class_index: node.int("main_class_index")? },
+        "NestHost" => AttributeInfo::NestHost { host_class_index: node.int("host_class_index")? },
+        "NestMembers" => AttributeInfo::NestMembers(node.ints("classes")?),
+        "Record" => AttributeInfo::Record(node.array("components", |component| Ok(RecordComponent {
+            name_index: component.int("name_index")?,
+            descriptor_index: component.int("descriptor_index")?,
+            attributes: parse_attributes(&component)?,
+        }))?),
+        "PermittedSubclasses" => AttributeInfo::PermittedSubclasses(node.ints("classes")?),
+        "Unknown" => AttributeInfo::Unknown(node.get("info")?.as_hex()?),
+        _ => return Err(node.get("kind")?.unknown("attribute kind", kind)),
+    })
+}
+
+fn parse_module_exports(node: Node) -> Result<ModuleExports, ClassJsonError> {
+    Ok(ModuleExports {
+        package_index: node.int("package_index")?,
+        flags: ExportsFlags::from_bits_retain(parse_flags(&node.get("flags")?, ExportsFlags::from_bits_truncate)?),
+        to_index: node.ints("to_index")?,
+    })
+}
+
+fn parse_module(node: &Node) -> Result<ModuleAttribute, ClassJsonError> {
+    Ok(ModuleAttribute {
+        module_name_index: node.int("module_name_index")?,
+        module_flags: ModuleFlags::from_bits_retain(parse_flags(&node.get("module_flags")?, ModuleFlags::from_bits_truncate)?),
+        module_version_index: node.int("module_version_index")?,
+        requires: node.array("requires", |requires| Ok(ModuleRequires {
+            requires_index: requires.int("requires_index")?,
+            requires_flags: RequiresFlags::from_bits_retain(parse_flags(&requires.get("requires_flags")?, RequiresFlags::from_bits_truncate)?),
+            requires_version_index: requires.int("requires_version_index")?,
+        }))?,
+        exports: node.array("exports", parse_module_exports)?,
+        opens: node.array("opens", parse_module_exports)?,
+        uses_index: node.ints("uses_index")?,
+        provides: node.array("provides", |provides| Ok(ModuleProvides {
+            provides_index: provides.int("provides_index")?,
+            provides_with_index: provides.ints("provides_with_index")?,
+        }))?,
+    })
+}
+
+fn parse_code(node: &Node) -> Result<CodeAttribute, ClassJsonError> {
+    let code = match node.member("instructions") {
+        Some(instructions) => {
+            let mut code = vec![];
+            for item in instructions.items()? {
+                let instruction = parse_instruction(&item)?;
+                let wide = match item.member("wide") {
+                    Some(wide) => match wide.json {
+                        Json::Bool(wide) => *wide,
+                        _ => return Err(wide.invalid("a boolean")),
+                    },
+                    None => false,
+                };
+                if wide {
+                    encode_wide(&instruction, &mut code).ok_or_else(|| item.invalid("a local variable instruction, only they can be wide"))?;
+                } else {
+                    instruction.encode(code.len() as u32, &mut code);
+                }
+            }
+            code
+        }
+        None => node.get("code")?.as_hex()?,
+    };
+    Ok(CodeAttribute {
+        max_stack: node.int("max_stack")?,
+        max_locals: node.int("max_locals")?,
+        code,
+        exception_table: node.array("exception_table", |entry| Ok(ExceptionTableEntry {
+            start_pc: entry.int("start_pc")?,
+            end_pc: entry.int("end_pc")?,
+            handler_pc: entry.int("handler_pc")?,
+            catch_type: entry.int("catch_type")?,
+        }))?,
+        attributes: parse_attributes(node)?,
+    })
+}
+
+fn parse_instruction(node: &Node) -> Result<Instruction, ClassJsonError> {
+    let mnemonic = node.str("opcode")?;
+    let opcode = Opcode::from_mnemonic(mnemonic).ok_or_else(|| node.unknown("opcode", mnemonic))?;
+    let index = || node.int("index");
+    let offset = || node.int("offset");
+    Ok(match opcode {
+        Opcode::Bipush => Instruction::Bipush(node.int("value")?),
+        Opcode::Sipush => Instruction::Sipush(node.int("value")?),
+        Opcode::Ldc => Instruction::Ldc(node.int("index")?),
+        Opcode::LdcW => Instruction::LdcW(index()?),
+        Opcode::Ldc2W => Instruction::Ldc2W(index()?),
+        Opcode::Iload => Instruction::Iload(index()?),
+        Opcode::Lload => Instruction::Lload(index()?),
+        Opcode::Fload => Instruction::Fload(index()?),
+        Opcode::Dload => Instruction::Dload(index()?),
+        Opcode::Aload => Instruction::Aload(index()?),
+        Opcode::Istore => Instruction::Istore(index()?),
+        Opcode::Lstore => Instruction::Lstore(index()?),
+        Opcode::Fstore => Instruction::Fstore(index()?),
+        Opcode::Dstore => Instruction::Dstore(index()?),
+        Opcode::Astore => Instruction::Astore(index()?),
+        Opcode::Ret => Instruction::Ret(index()?),
+        Opcode::Iinc => Instruction::Iinc { index: index()?, value: node.int("value")? },
+        Opcode::Ifeq => Instruction::Ifeq(offset()?),
+        Opcode::Ifne => Instruction::Ifne(offset()?),
+        Opcode::Iflt => Instruction::Iflt(offset()?),
+        Opcode::Ifge => Instruction::Ifge(offset()?),
+        Opcode::Ifgt => Instruction::Ifgt(offset()?),
+        Opcode::Ifle => Instruction::Ifle(offset()?),
+        Opcode::IfIcmpeq => Instruction::IfIcmpeq(offset()?),
+        Opcode::IfIcmpne => Instruction::IfIcmpne(offset()?),
+        Opcode::IfIcmplt => Instruction::IfIcmplt(offset()?),
+        Opcode::IfIcmpge => Instruction::IfIcmpge(offset()?),
+        Opcode::IfIcmpgt => Instruction::IfIcmpgt(offset()?),
+        Opcode::IfIcmple => Instruction::IfIcmple(offset()?),
+        Opcode::IfAcmpeq => Instruction::IfAcmpeq(offset()?),
+        Opcode::IfAcmpne => Instruction::IfAcmpne(offset()?),
+        Opcode::Goto => Instruction::Goto(offset()?),
+        Opcode::Jsr => Instruction::Jsr(offset()?),
+        Opcode::Ifnull => Instruction::Ifnull(offset()?),
+        Opcode::Ifnonnull => Instruction::Ifnonnull(offset()?),
+        Opcode::GotoW => Instruction::GotoW(node.int("offset")?),
+        Opcode::JsrW => Instruction::JsrW(node.int("offset")?),
+        Opcode::Tableswitch => Instruction::Tableswitch {
+            default: node.int("default")?,
+            low: node.int("low")?,
+            high: node.int("high")?,
+            offsets: node.ints("offsets")?,
+        },
+        Opcode::Lookupswitch => Instruction::Lookupswitch {
+            default: node.int("default")?,
+            pairs: node.array("pairs", |pair| match pair.items()?.as_slice() {
+                [key, offset] => Ok((key.as_int()?, offset.as_int()?)),
+                _ => Err(pair.invalid("a pair of key and offset")),
+            })?,
+        },
+        Opcode::Getstatic => Instruction::Getstatic(index()?),
+        Opcode::Putstatic => Instruction::Putstatic(index()?),
+        Opcode::Getfield => Instruction::Getfield(index()?),
+        Opcode::Putfield => Instruction::Putfield(index()?),
+        Opcode::Invokevirtual => Instruction::Invokevirtual(index()?),
+        Opcode::Invokespecial => Instruction::Invokespecial(index()?),
+        Opcode::Invokestatic => Instruction::Invokestatic(index()?),
+        Opcode::Invokeinterface => Instruction::Invokeinterface { index: index()?, count: node.int("count")? },
+        Opcode::Invokedynamic => Instruction::Invokedynamic { index: index()? },
+        Opcode::New => Instruction::New(index()?),
+        Opcode::Newarray => {
+            let atype = node.get("atype")?;
+            let name = atype.as_str()?;
+            Instruction::Newarray { atype: ArrayType::from_name(name).ok_or_else(|| atype.unknown("array type", name))? }
+        }
+        Opcode::Anewarray => Instruction::Anewarray(index()?),
+        Opcode::Checkcast => Instruction::Checkcast(index()?),
+        Opcode::Instanceof => Instruction::Instanceof(index()?),
+        Opcode::Multianewarray => Instruction::Multianewarray { index: index()?, dimensions: node.int("dimensions")? },
+        _ => Instruction::from_opcode(opcode).ok_or_else(|| node.unknown("opcode", mnemonic))?,
+    })
+}
+
+fn parse_verification_type(node: &Node) -> Result<VerificationTypeInfo, ClassJsonError> {
+    let kind = node.str("type")?;
+    Ok(match kind {
+        "Top" => VerificationTypeInfo::Top,
+        "Integer" => VerificationTypeInfo::Integer,
+        "Float" => VerificationTypeInfo::Float,
+        "Double" => VerificationTypeInfo::Double,
+        "Long" => VerificationTypeInfo::Long,
+        "Null" => VerificationTypeInfo::Null,
+        "UninitializedThis" => VerificationTypeInfo::UninitializedThis,
+        "Object" => VerificationTypeInfo::Object { cpool_index: node.int("cpool_index")? },
+        "Uninitialized" => VerificationTypeInfo::Uninitialized { offset: node.int("offset")? },
+        _ => return Err(node.get("type")?.unknown("verification type", kind)),
+    })
+}
+
+fn parse_frame(node: &Node) -> Result<StackMapFrame, ClassJsonError> {
+    let kind = node.str("type")?;
+    let verification_types = |name| node.array(name, |info| parse_verification_type(&info));
+    Ok(match kind {
+        "SameFrame" => StackMapFrame::SameFrame { offset_delta: node.int("offset_delta")? },
+        "SameLocals1StackItemFrame" => StackMapFrame::SameLocals1StackItemFrame {
+            offset_delta: node.int("offset_delta")?,
+            stack: parse_verification_type(&node.get("stack")?)?,
+        },
+        "SameLocals1StackItemFrameExtended" => StackMapFrame::SameLocals1StackItemFrameExtended {
+            offset_delta: node.int("offset_delta")?,
+            stack: parse_verification_type(&node.get("stack")?)?,
+        },
+        "ChopFrame" => StackMapFrame::ChopFrame { offset_delta: node.int("offset_delta")?, k: node.int("k")? },
+        "SameFrameExtended" => StackMapFrame::SameFrameExtended { offset_delta: node.int("offset_delta")? },
+        "AppendFrame" => StackMapFrame::AppendFrame { offset_delta: node.int("offset_delta")?, locals: verification_types("locals")? },
+        "FullFrame" => StackMapFrame::FullFrame {
+            offset_delta: node.int("offset_delta")?,
+            locals: verification_types("locals")?,
+            stack: verification_types("stack")?,
+        },
+        _ => return Err(node.get("type")?.unknown("frame type", kind)),
+    })
+}
@@ -0,0 +1,222 @@
+//! Finding classes by name, like the JVM does with its class path
+//!
+//! A [`ClassPath`] is a list of directories, jars, jmods and jimages that are searched in order for the file of a
+//! class, so `java/lang/String` is `java/lang/String.class` in a directory or jar, `classes/java/lang/String.class` in
+//! a jmod, and `/java.base/java/lang/String.class` in a jimage. Archives and images are opened the first time they're
+//! searched and stay open.
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use crate::access_flags::ClassAccessFlags;
+use crate::class::{Class, ParseClassError};
+use crate::class_hierarchy::{ClassHierarchy, ClassHierarchyError};
+use crate::jimage::{self, JImage, JImageError};
+use crate::zip::{ZipArchive, ZipError};
+
+#[derive(Error, Debug)]
+pub enum ClassPathError {
+    #[error("{}: {error}", path.display())]
+    IoError { path: PathBuf, error: io::Error },
+    #[error("{}: {error}", path.display())]
+    ZipError { path: PathBuf, error: ZipError },
+    #[error("{}: {error}", path.display())]
+    JImageError { path: PathBuf, error: JImageError },
+    #[error("class {0} isn't on the class path")]
+    ClassNotFound(String),
+    #[error("class {class}: {error}")]
+    ParseClassError { class: String, error: ParseClassError },
+}
+
+#[derive(Debug)]
+enum Source {
+    Directory,
+    /// A jar or zip, or a jmod with its classes under `classes/`
+    Archive { prefix: &'static str, archive: Mutex<Option<Arc<ZipArchive>>> },
+    /// A jimage, like the `lib/modules` of a JDK
+    Image(Mutex<Option<Arc<JImage>>>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    source: Source,
+}
+
+impl Entry {
+    /// What's in `cache`, opening it if it isn't open yet, `None` if the file doesn't exist
+    fn cached<T, E>(cache: &Mutex<Option<Arc<T>>>, open: impl FnOnce() -> Result<T, E>, not_found: impl Fn(&E) -> bool) -> Result<Option<Arc<T>>, E> {
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.is_none() {
+            // Failures aren't cached, the file may be fixed by the next time it's searched
+            match open() {
+                Ok(opened) => *cache = Some(Arc::new(opened)),
+                Err(error) if not_found(&error) => return Ok(None),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(cache.clone())
+    }
+
+    fn find(&self, class: &str) -> Result<Option<Vec<u8>>, ClassPathError> {
+        match &self.source {
+            Source::Directory => {
+                let path = self.path.join(format!("{}.class", class));
+                match std::fs::read(&path) {
+                    Ok(bytes) => Ok(Some(bytes)),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                    Err(error) => Err(ClassPathError::IoError { path, error }),
+                }
+            }
+            Source::Archive { prefix, archive } => {
+                let zip_error = |error| ClassPathError::ZipError { path: self.path.clone(), error };
+                let not_found = |error: &ZipError| matches!(error, ZipError::IoError(error) if error.kind() == io::ErrorKind::NotFound);
+                match Self::cached(archive, || ZipArchive::open(&self.path), not_found).map_err(zip_error)? {
+                    Some(archive) => archive.read(&format!("{}{}.class", prefix, class)).map_err(zip_error),
+                    None => Ok(None),
+                }
+            }
+            Source::Image(image) => {
+                let image_error = |error| ClassPathError::JImageError { path: self.path.clone(), error };
+                let not_found = |error: &JImageError| matches!(error, JImageError::IoError(error) if error.kind() == io::ErrorKind::NotFound);
+                match Self::cached(image, || JImage::open(&self.path), not_found).map_err(image_error)? {
+                    Some(image) => image.find_class(class).map_err(image_error),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Directories, archives and images to look for classes in, searched in the order they were added
+///
+/// Like the JVM, entries that don't exist are skipped rather than being errors.
+#[derive(Debug, Default)]
+pub struct ClassPath {
+    entries: Vec<Entry>,
+}
+
+impl ClassPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A class path written like the `-cp` option of `java`, with entries separated by `:`, or `;` on Windows
+    ///
+    /// An entry that's a directory followed by `*` is every jar in that directory, in the order of their names, and
+    /// empty entries are the current directory.
+    pub fn parse(class_path: &str) -> Self {
+        let mut classes = ClassPath::new();
+        for path in std::env::split_paths(class_path) {
+            if path.as_os_str().is_empty() {
+                classes.push(".");
+            } else if path.file_name().is_some_and(|name| name == "*") {
+                let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                let mut jars: Vec<PathBuf> = std::fs::read_dir(directory).into_iter().flatten()
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jar")))
+                    .collect();
+                jars.sort();
+                for jar in jars {
+                    classes.push(jar);
+                }
+            } else {
+                classes.push(path);
+            }
+        }
+        classes
+    }
+
+    /// Adds a directory, a jar, zip or jmod archive, or a jimage, to the end of the class path
+    ///
+    /// A file that starts with the magic number of jimages is read as one, any other file is read as a zip archive,
+    /// and as a jmod if its name ends with `.jmod`.
+    pub fn push<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let source = if is_jimage(&path) {
+            Source::Image(Mutex::new(None))
+        } else if path.is_file() {
+            let jmod = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jmod"));
+            Source::Archive { prefix: if jmod { "classes/" } else { "" }, archive: Mutex::new(None) }
+        } else {
+            Source::Directory
+        };
+        self.entries.push(Entry { path, source });
+    }
+
+    /// Adds the runtime classes of the JDK installed at `java_home`, from its `lib/modules` image, or from its jmods
+    /// if it doesn't have one, like a JDK that was only built
+    pub fn push_jdk<P: AsRef<Path>>(&mut self, java_home: P) {
+        let java_home = java_home.as_ref();
+        let modules = java_home.join("lib").join("modules");
+        if modules.is_file() {
+            self.push(modules);
+            return;
+        }
+        let mut jmods: Vec<PathBuf> = std::fs::read_dir(java_home.join("jmods")).into_iter().flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "jmod"))
+            .collect();
+        jmods.sort();
+        for jmod in jmods {
+            self.push(jmod);
+        }
+    }
+
+    /// Adds the entries of `other` to the end of the class path
+    pub fn append(&mut self, other: ClassPath) {
+        self.entries.extend(other.entries);
+    }
+
+    /// Paths of the entries, in the order they're searched
+    pub fn paths(&self) -> impl Iterator<Item=&Path> {
+        self.entries.iter().map(|entry| entry.path.as_path())
+    }
+
+    /// The class file of the class called `class` in internal form, like `java/lang/String`, from the first entry
+    /// that has it
+    pub fn find(&self, class: &str) -> Result<Option<Vec<u8>>, ClassPathError> {
+        // Names that could reach outside of a directory aren't classes
+        let relative = Path::new(class).components().all(|component| matches!(component, Component::Normal(_)));
+        if class.is_empty() || !relative {
+            return Ok(None);
+        }
+        for entry in &self.entries {
+            if let Some(bytes) = entry.find(class)? {
+                return Ok(Some(bytes));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds and parses the class called `class`, failing if it isn't on the class path
+    pub fn load(&self, class: &str) -> Result<Class, ClassPathError> {
+        let bytes = self.find(class)?.ok_or_else(|| ClassPathError::ClassNotFound(class.to_string()))?;
+        Class::from_bytes(&bytes).map_err(|error| ClassPathError::ParseClassError { class: class.to_string(), error })
+    }
+}
+
+/// Whether the file at `path` starts with the magic number of jimages, in either byte order
+fn is_jimage(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    let read = File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+    read.is_ok() && (u32::from_le_bytes(magic) == jimage::MAGIC || u32::from_be_bytes(magic) == jimage::MAGIC)
+}
+
+/// Loads classes as they're asked about, any class that can't be loaded is unknown
+impl ClassHierarchy for ClassPath {
+    fn super_class(&self, class: &str) -> Result<Option<String>, ClassHierarchyError> {
+        let unknown = || ClassHierarchyError::UnknownClass(class.to_string());
+        let loaded = self.load(class).map_err(|_| unknown())?;
+        let super_class = loaded.super_class_name().map_err(|_| unknown())?;
+        Ok(super_class.map(str::to_string))
+    }
+
+    fn is_interface(&self, class: &str) -> Result<bool, ClassHierarchyError> {
+        let loaded = self.load(class).map_err(|_| ClassHierarchyError::UnknownClass(class.to_string()))?;
+        Ok(loaded.access_flags.contains(ClassAccessFlags::ACC_INTERFACE))
+    }
+}
@@ -0,0 +1,1679 @@
+//! A text format for whole classes, an assembly language in the spirit of Jasmin and Krakatau
+//!
+//! [`to_text`] disassembles a class and [`from_text`] assembles it again. The class assembled from what was
+//! disassembled means the same thing, and usually has the same bytes: a constant used through a duplicate in the pool
+//! may end up pointing to the first of the equal constants instead.
+//!
+//! ```text
+//! .version 61 0
+//!
+//! .const #1 = Methodref java/lang/Object <init> ()V
+//! .const #2 = Class java/lang/Object
+//! ...
+//!
+//! .class public super Main
+//! .super java/lang/Object
+//!
+//! .method public static main ([Ljava/lang/String;)V
+//!     .code stack 2 locals 1
+//!     L0:
+//!         getstatic Fieldref java/lang/System out Ljava/io/PrintStream;
+//!         ldc String "Hello World!"
+//!         invokevirtual Methodref java/io/PrintStream println (Ljava/lang/String;)V
+//!         return
+//!         .linenumbertable
+//!             L0 3
+//!         .end linenumbertable
+//!     .end code
+//! .end method
+//!
+//! .sourcefile Main.java
+//! .end class
+//! ```
+//!
+//! # Syntax
+//!
+//! Lines are made of tokens separated by whitespace, and a token starting with `;` comments out the rest of the line.
+//! Tokens that have whitespace or could be mistaken for something else are quoted, like `"Hello World!"`, with the
+//! escapes `\"`, `\\`, `\n`, `\r`, `\t` and `\uXXXX`.
+//!
+//! The file starts with `.version`, then a `.const` line for every constant in the pool, in order. Constants are
+//! written as the name of their tag in the specification without the `CONSTANT_` prefix, followed by what they point
+//! to, which is written by its contents too: `Methodref java/lang/Object <init> ()V` is a `Methodref` pointing to the
+//! class `java/lang/Object` and the name and type `<init> ()V`. Non-finite floats and doubles are `NaN`, `Infinity`
+//! and `-Infinity`, and `NaN`s other than the usual one are written as their bits in hex. A `MethodHandle` has its
+//! [`MethodReferenceKind`] and the `Fieldref`, `Methodref` or `InterfaceMethodref` it points to, `InvokeDynamic` and
+//! `Dynamic` have the index of their bootstrap method and a name and type. A utf8 constant with unpaired surrogates,
+//! which can't be written as a string, is `Utf8Bytes` followed by its modified UTF-8 in hex, and what points to it
+//! uses its index.
+//!
+//! Everything else uses constants the same way: `ldc` takes a whole constant like `String "Hello World!"`, places
+//! that must point to a class, a module or a package only take its name, a name and type is a name followed by a
+//! descriptor, and utf8 constants are just the string. Any of them can also be the index of a constant like `#12`,
+//! where `#0` means there's none. Assembling uses the first listed constant equal to what's written and adds the ones
+//! that aren't listed at the end of the pool, so hand-written code can use new constants without listing them.
+//!
+//! Access flags are lowercase names without the `ACC_` prefix, like `public` or `static_phase`, with bits that
+//! have no name written in hex like `0x0100`.
+//!
+//! After the constants come `.class`, with the flags and name of the class, `.super`, an `.implements` line for
+//! every interface, then `.field` and `.method` blocks with the flags, name and descriptor of the member, and the
+//! attributes of the class. Attributes are directives named after the attribute in lowercase, like `.sourcefile` or
+//! `.linenumbertable`, the ones holding tables are blocks with a row per entry, closed by `.end` like
+//! `.end linenumbertable`. Attributes this crate doesn't understand are `.attribute` with their name and contents in
+//! hex.
+//!
+//! # Code
+//!
+//! `.code` has the maximum stack size and number of locals, followed by the instructions, one per line. Instructions
+//! are their mnemonic followed by their operands, with constants written as above, and a `wide` prefix when they
+//! have it in the class file. Positions in the code are labels defined by a line like `L12:`, which branches,
+//! `.catch` lines and the attributes of the code point to. `tableswitch` and `lookupswitch` have a row like
+//! `12: L40` for every key, ending with one like `default: L52`. The frames of `.stackmaptable` are written with the
+//! label of the instruction they're for, so their offsets are worked out when assembling: `same` and
+//! `same_locals_1_stack_item` frames become extended frames if they need to, while `same_extended` and
+//! `same_locals_1_stack_item_extended` stay extended.
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Debug;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use thiserror::Error;
+
+use crate::access_flags::{ClassAccessFlags, ExportsFlags, FieldAccessFlags, InnerClassAccessFlags, MethodAccessFlags, MethodParameterAccessFlags, ModuleFlags, RequiresFlags, flag_bit, flag_names};
+use crate::assembler::{AssembleError, Assembler, Label};
+use crate::attribute::{Attribute, AttributeInfo, BootstrapMethod, InnerClass, LineNumber, LocalVariable, LocalVariableType, MethodParameter, ModuleAttribute, ModuleExports, ModuleProvides, ModuleRequires, RecordComponent};
+use crate::class::{Class, JavaVersion};
+use crate::class_json::{attribute_kind, float_text};
+use crate::code::CodeAttribute;
+use crate::constant_pool::{Constant, ConstantKind, ConstantPool, MethodReferenceKind};
+use crate::constant_pool_builder::MAX_SLOTS;
+use crate::field::Field;
+use crate::instruction::{ArrayType, Instruction, Opcode};
+use crate::method::Method;
+use crate::mutf8;
+use crate::stack_map::{StackMapFrame, VerificationTypeInfo};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClassTextError {
+    /// The class has something that can't be written as text, like code that doesn't decode
+    #[error("{owner}: {message}")]
+    Unrepresentable { owner: String, message: String },
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// The class as text, following the syntax in the [module documentation](self)
+pub fn to_text(class: &Class) -> Result<String, ClassTextError> {
+    let mut writer = Writer { pool: &class.constant_pool, text: String::new() };
+    writer.class(class)?;
+    Ok(writer.text)
+}
+
+/// Assembles a class from text written by [`to_text`], possibly edited since
+///
+/// Like with [`crate::class_json::from_json`], the class may still be invalid: parse what [`Class::to_bytes`]
+/// writes to check it.
+pub fn from_text(text: &str) -> Result<Class, ClassTextError> {
+    let lines = tokenize(text)?;
+    let mut parser = Parser { lines: &lines, pos: 0, pool: Pool::default() };
+    parser.class_file()
+}
+
+/// `text` as a single token, quoted if it couldn't be read back as it is
+fn word(text: &str) -> String {
+    let plain = !text.is_empty() &&
+        !text.starts_with(['#', ';', '.']) &&
+        !text.ends_with(':') &&
+        !text.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"');
+    if plain {
+        return text.to_string();
+    }
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Names of the flags set in `bits`, lowercase and without the `ACC_` prefix
+fn flag_words<T: Debug>(bits: u16, from_bits_truncate: fn(u16) -> T) -> Vec<String> {
+    flag_names(bits, from_bits_truncate).into_iter()
+        .map(|name| name.strip_prefix("ACC_").map(str::to_lowercase).unwrap_or(name))
+        .collect()
+}
+
+/// The utf8 constant a `Class`, `String`, `MethodType`, `Module` or `Package` points to
+fn name_index(constant: &Constant) -> Option<u16> {
+    match constant {
+        Constant::Class { name_index } |
+        Constant::String { string_index: name_index } |
+        Constant::MethodType { descriptor_index: name_index } |
+        Constant::Module { name_index } |
+        Constant::Package { name_index } => Some(*name_index),
+        _ => None,
+    }
+}
+
+/// The pc of every frame of a `StackMapTable`
+fn frame_pcs(frames: &[StackMapFrame]) -> Vec<u32> {
+    let mut pcs: Vec<u32> = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let pc = match pcs.last() {
+            Some(previous) => previous + frame.offset_delta() as u32 + 1,
+            None => frame.offset_delta() as u32,
+        };
+        pcs.push(pc);
+    }
+    pcs
+}
+
+struct Writer<'a> {
+    pool: &'a ConstantPool,
+    text: String,
+}
+
+impl<'a> Writer<'a> {
+    fn line(&mut self, depth: usize, line: impl AsRef<str>) {
+        for _ in 0..depth {
+            self.text.push_str("    ");
+        }
+        self.text.push_str(line.as_ref());
+        self.text.push('\n');
+    }
+
+    /// A directive with a row for each entry of a table, closed with `.end`
+    fn block(&mut self, depth: usize, directive: &str, rows: Vec<String>) {
+        self.line(depth, directive);
+        for row in rows {
+            self.line(depth + 1, row);
+        }
+        let name = directive.split(' ').next().unwrap_or_default();
+        self.line(depth, format!(".end {}", &name[1..]));
+    }
+
+    /// The utf8 constant at `index` if it can be written as a string, which isn't the case with unpaired surrogates
+    fn string(&self, index: u16) -> Option<&'a str> {
+        match self.pool.get(index) {
+            Ok(Constant::UTF8String(string)) => Some(string),
+            _ => None,
+        }
+    }
+
+    fn utf8(&self, index: u16) -> String {
+        self.string(index).map(word).unwrap_or_else(|| format!("#{}", index))
+    }
+
+    /// A constant of `kind` pointing to a utf8 constant, written as just that string
+    fn named(&self, index: u16, kind: ConstantKind) -> String {
+        match self.pool.get(index) {
+            Ok(constant) if constant.kind() == kind => name_index(constant).and_then(|index| self.string(index)).map(word),
+            _ => None,
+        }.unwrap_or_else(|| format!("#{}", index))
+    }
+
+    fn classes(&self, indices: &[u16]) -> Vec<String> {
+        indices.iter().map(|&index| self.named(index, ConstantKind::Class)).collect()
+    }
+
+    fn name_and_type(&self, index: u16) -> String {
+        match self.pool.get(index) {
+            Ok(Constant::NameAndType { name_index, descriptor_index }) => match (self.string(*name_index), self.string(*descriptor_index)) {
+                (Some(name), Some(descriptor)) => format!("{} {}", word(name), word(descriptor)),
+                _ => format!("#{}", index),
+            },
+            _ => format!("#{}", index),
+        }
+    }
+
+    fn constant(&self, index: u16) -> String {
+        match self.pool.get(index) {
+            Ok(constant) => self.symbol(constant),
+            Err(_) => format!("#{}", index),
+        }
+    }
+
+    /// A constant written as its tag followed by what it points to
+    fn symbol(&self, constant: &Constant) -> String {
+        let kind = constant.kind().to_string();
+        let tag = kind.trim_start_matches("CONSTANT_");
+        let value = match constant {
+            Constant::UTF8String(string) => word(string),
+            Constant::UTF8Bytes { bytes, .. } => return format!("Utf8Bytes {}", hex(bytes)),
+            Constant::Integer(value) => value.to_string(),
+            Constant::Long(value) => value.to_string(),
+            Constant::Float(value) if value.is_nan() && value.to_bits() != f32::NAN.to_bits() => format!("{:#010x}", value.to_bits()),
+            Constant::Float(value) => float_text(*value),
+            Constant::Double(value) if value.is_nan() && value.to_bits() != f64::NAN.to_bits() => format!("{:#018x}", value.to_bits()),
+            Constant::Double(value) => float_text(*value),
+            Constant::Class { name_index } |
+            Constant::String { string_index: name_index } |
+            Constant::MethodType { descriptor_index: name_index } |
+            Constant::Module { name_index } |
+            Constant::Package { name_index } => self.utf8(*name_index),
+            Constant::Field { class_index, name_and_type_index } |
+            Constant::Method { class_index, name_and_type_index } |
+            Constant::InterfaceMethod { class_index, name_and_type_index } => {
+                format!("{} {}", self.named(*class_index, ConstantKind::Class), self.name_and_type(*name_and_type_index))
+            }
+            Constant::NameAndType { name_index, descriptor_index } => format!("{} {}", self.utf8(*name_index), self.utf8(*descriptor_index)),
+            Constant::MethodHandle { reference_kind, reference_index } => {
+                let reference = match self.pool.get(*reference_index) {
+                    Ok(member @ (Constant::Field { .. } | Constant::Method { .. } | Constant::InterfaceMethod { .. })) => self.symbol(member),
+                    _ => format!("#{}", reference_index),
+                };
+                format!("{:?} {}", reference_kind, reference)
+            }
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+            Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("{} {}", bootstrap_method_attr_index, self.name_and_type(*name_and_type_index))
+            }
+            Constant::Unusable => unreachable!("unusable slots are never listed or looked up"),
+        };
+        format!("{} {}", tag, value)
+    }
+
+    fn class(&mut self, class: &Class) -> Result<(), ClassTextError> {
+        let pool = self.pool;
+        self.line(0, format!(".version {} {}", class.java_version.major, class.java_version.minor));
+        self.line(0, "");
+        for (index, constant) in pool.iter() {
+            self.line(0, format!(".const #{} = {}", index, self.symbol(constant)));
+        }
+        self.line(0, "");
+        let mut header = vec![".class".to_string()];
+        header.extend(flag_words(class.access_flags.bits(), ClassAccessFlags::from_bits_truncate));
+        header.push(self.named(class.this_class, ConstantKind::Class));
+        self.line(0, header.join(" "));
+        self.line(0, format!(".super {}", self.named(class.super_class, ConstantKind::Class)));
+        for interface in self.classes(&class.interface_indices) {
+            self.line(0, format!(".implements {}", interface));
+        }
+        for field in &class.fields {
+            self.line(0, "");
+            let mut header = vec![".field".to_string()];
+            header.extend(flag_words(field.access_flags.bits(), FieldAccessFlags::from_bits_truncate));
+            header.extend([self.utf8(field.name_index), self.utf8(field.descriptor_index)]);
+            self.line(0, header.join(" "));
+            let owner = format!("field {}", header[header.len() - 2]);
+            self.attributes(1, &field.attributes, &owner)?;
+            self.line(0, ".end field");
+        }
+        for method in &class.methods {
+            self.line(0, "");
+            let mut header = vec![".method".to_string()];
+            header.extend(flag_words(method.access_flags.bits(), MethodAccessFlags::from_bits_truncate));
+            header.extend([self.utf8(method.name_index), self.utf8(method.descriptor_index)]);
+            self.line(0, header.join(" "));
+            let owner = format!("method {}{}", header[header.len() - 2], header[header.len() - 1]);
+            self.attributes(1, &method.attributes, &owner)?;
+            self.line(0, ".end method");
+        }
+        if !class.attributes.is_empty() {
+            self.line(0, "");
+        }
+        self.attributes(0, &class.attributes, "class")?;
+        self.line(0, ".end class");
+        Ok(())
+    }
+
+    /// `owner` says where the attributes are for errors, like `method main([Ljava/lang/String;)V`
+    fn attributes(&mut self, depth: usize, attributes: &[Attribute], owner: &str) -> Result<(), ClassTextError> {
+        for attribute in attributes {
+            self.attribute(depth, attribute, owner)?;
+        }
+        Ok(())
+    }
+
+    fn attribute(&mut self, depth: usize, attribute: &Attribute, owner: &str) -> Result<(), ClassTextError> {
+        let directive = format!(".{}", attribute_kind(&attribute.info).to_lowercase());
+        let with = |values: Vec<String>| std::iter::once(directive.clone()).chain(values).collect::<Vec<_>>().join(" ");
+        match &attribute.info {
+            AttributeInfo::ConstantValue { constantvalue_index } => self.line(depth, with(vec![self.constant(*constantvalue_index)])),
+            AttributeInfo::Code(code) => {
+                self.code(depth, code, owner).map_err(|message| ClassTextError::Unrepresentable { owner: owner.to_string(), message })?;
+            }
+            AttributeInfo::StackMapTable(frames) => {
+                let rows = frame_pcs(frames).into_iter().zip(frames).map(|(pc, frame)| self.frame(pc, frame)).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::Exceptions(classes) |
+            AttributeInfo::NestMembers(classes) |
+            AttributeInfo::PermittedSubclasses(classes) => self.line(depth, with(self.classes(classes))),
+            AttributeInfo::InnerClasses(classes) => {
+                let rows = classes.iter().map(|class| {
+                    let mut row = vec![
+                        self.named(class.inner_class_info_index, ConstantKind::Class),
+                        self.named(class.outer_class_info_index, ConstantKind::Class),
+                        self.utf8(class.inner_name_index),
+                    ];
+                    row.extend(flag_words(class.inner_class_access_flags.bits(), InnerClassAccessFlags::from_bits_truncate));
+                    row.join(" ")
+                }).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::EnclosingMethod { class_index, method_index } => {
+                self.line(depth, with(vec![self.named(*class_index, ConstantKind::Class), self.name_and_type(*method_index)]));
+            }
+            AttributeInfo::Synthetic | AttributeInfo::Deprecated => self.line(depth, directive),
+            AttributeInfo::Signature { signature_index: index } | AttributeInfo::SourceFile { sourcefile_index: index } => {
+                self.line(depth, with(vec![self.utf8(*index)]));
+            }
+            AttributeInfo::SourceDebugExtension(bytes) => self.line(depth, with(vec![hex(bytes)]).trim_end()),
+            AttributeInfo::LineNumberTable(lines) => {
+                let rows = lines.iter().map(|line| format!("L{} {}", line.start_pc, line.line_number)).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::LocalVariableTable(variables) => {
+                let rows = variables.iter().map(|variable| format!(
+                    "{} is {} {} from L{} to L{}",
+                    variable.index,
+                    self.utf8(variable.name_index),
+                    self.utf8(variable.descriptor_index),
+                    variable.start_pc,
+                    variable.start_pc as u32 + variable.length as u32,
+                )).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::LocalVariableTypeTable(variables) => {
+                let rows = variables.iter().map(|variable| format!(
+                    "{} is {} {} from L{} to L{}",
+                    variable.index,
+                    self.utf8(variable.name_index),
+                    self.utf8(variable.signature_index),
+                    variable.start_pc,
+                    variable.start_pc as u32 + variable.length as u32,
+                )).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::BootstrapMethods(methods) => {
+                let rows = methods.iter().map(|method| {
+                    std::iter::once(method.bootstrap_method_ref).chain(method.bootstrap_arguments.iter().copied())
+                        .map(|index| self.constant(index))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                }).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::MethodParameters(parameters) => {
+                let rows = parameters.iter().map(|parameter| {
+                    let mut row = vec![self.utf8(parameter.name_index)];
+                    row.extend(flag_words(parameter.access_flags.bits(), MethodParameterAccessFlags::from_bits_truncate));
+                    row.join(" ")
+                }).collect();
+                self.block(depth, &directive, rows);
+            }
+            AttributeInfo::Module(module) => self.module(depth, module),
+            AttributeInfo::ModulePackages(packages) => {
+                self.line(depth, with(packages.iter().map(|&index| self.named(index, ConstantKind::Package)).collect()));
+            }
+            AttributeInfo::ModuleMainClass { main_class_index: index } | AttributeInfo::NestHost { host_class_index: index } => {
+                self.line(depth, with(vec![self.named(*index, ConstantKind::Class)]));
+            }
+            AttributeInfo::Record(components) => {
+                self.line(depth, &directive);
+                for component in components {
+                    self.line(depth + 1, format!(".component {} {}", self.utf8(component.name_index), self.utf8(component.descriptor_index)));
+                    self.attributes(depth + 2, &component.attributes, owner)?;
+                    self.line(depth + 1, ".end component");
+                }
+                self.line(depth, ".end record");
+            }
+            AttributeInfo::Unknown(bytes) => {
+                self.line(depth, format!(".attribute {} {}", self.utf8(attribute.attribute_name_index), hex(bytes)).trim_end());
+            }
+        }
+        Ok(())
+    }
+
+    fn module(&mut self, depth: usize, module: &ModuleAttribute) {
+        let mut header = vec![
+            ".module".to_string(),
+            self.named(module.module_name_index, ConstantKind::Module),
+            self.utf8(module.module_version_index),
+        ];
+        header.extend(flag_words(module.module_flags.bits(), ModuleFlags::from_bits_truncate));
+        let mut rows = vec![];
+        for requires in &module.requires {
+            let mut row = vec![
+                "requires".to_string(),
+                self.named(requires.requires_index, ConstantKind::Module),
+                self.utf8(requires.requires_version_index),
+            ];
+            row.extend(flag_words(requires.requires_flags.bits(), RequiresFlags::from_bits_truncate));
+            rows.push(row.join(" "));
+        }
+        for (keyword, table) in [("exports", &module.exports), ("opens", &module.opens)] {
+            for exports in table {
+                let mut row = vec![keyword.to_string(), self.named(exports.package_index, ConstantKind::Package)];
+                row.extend(flag_words(exports.flags.bits(), ExportsFlags::from_bits_truncate));
+                if !exports.to_index.is_empty() {
+                    row.push("to".to_string());
+                    row.extend(exports.to_index.iter().map(|&index| self.named(index, ConstantKind::Module)));
+                }
+                rows.push(row.join(" "));
+            }
+        }
+        for &uses in &module.uses_index {
+            rows.push(format!("uses {}", self.named(uses, ConstantKind::Class)));
+        }
+        for provides in &module.provides {
+            let with = self.classes(&provides.provides_with_index).join(" ");
+            rows.push(format!("provides {} with {}", self.named(provides.provides_index, ConstantKind::Class), with));
+        }
+        self.block(depth, &header.join(" "), rows);
+    }
+
+    fn verification_type(&self, info: &VerificationTypeInfo) -> String {
+        match info {
+            VerificationTypeInfo::Object { cpool_index } => format!("Object {}", self.named(*cpool_index, ConstantKind::Class)),
+            VerificationTypeInfo::Uninitialized { offset } => format!("Uninitialized L{}", offset),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn frame(&self, pc: u32, frame: &StackMapFrame) -> String {
+        let types = |infos: &[VerificationTypeInfo]| infos.iter().map(|info| self.verification_type(info)).collect::<Vec<_>>();
+        let mut row = vec![format!("L{}", pc)];
+        match frame {
+            StackMapFrame::SameFrame { .. } => row.push("same".to_string()),
+            StackMapFrame::SameFrameExtended { .. } => row.push("same_extended".to_string()),
+            StackMapFrame::SameLocals1StackItemFrame { stack, .. } => {
+                row.extend(["same_locals_1_stack_item".to_string(), self.verification_type(stack)]);
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+                row.extend(["same_locals_1_stack_item_extended".to_string(), self.verification_type(stack)]);
+            }
+            StackMapFrame::ChopFrame { k, .. } => row.extend(["chop".to_string(), k.to_string()]),
+            StackMapFrame::AppendFrame { locals, .. } => {
+                row.push("append".to_string());
+                row.extend(types(locals));
+            }
+            StackMapFrame::FullFrame { locals, stack, .. } => {
+                row.extend(["full".to_string(), "locals".to_string()]);
+                row.extend(types(locals));
+                row.push("stack".to_string());
+                row.extend(types(stack));
+            }
+        }
+        row.join(" ")
+    }
+
+    /// Writes the code with labels at every pc something points to, failing if one isn't the start of an instruction
+    fn code(&mut self, depth: usize, code: &CodeAttribute, owner: &str) -> Result<(), String> {
+        let mut instructions = vec![];
+        let mut pc = 0;
+        while (pc as usize) < code.code.len() {
+            let (instruction, next) = Instruction::decode(&code.code, pc).map_err(|e| e.to_string())?;
+            let wide = code.code[pc as usize] == Opcode::Wide as u8;
+            instructions.push((pc, instruction, wide));
+            pc = next;
+        }
+        let starts: HashSet<u32> = instructions.iter().map(|(pc, _, _)| *pc).chain([pc]).collect();
+        let mut labels = BTreeSet::new();
+        let mut label = |pc: u32| {
+            if !starts.contains(&pc) {
+                return Err(format!("pc {} isn't the start of an instruction", pc));
+            }
+            labels.insert(pc);
+            Ok(())
+        };
+        for (pc, instruction, _) in &instructions {
+            for target in instruction.branch_targets(*pc) {
+                label(target)?;
+            }
+        }
+        for entry in &code.exception_table {
+            for pc in [entry.start_pc, entry.end_pc, entry.handler_pc] {
+                label(pc as u32)?;
+            }
+        }
+        for attribute in &code.attributes {
+            match &attribute.info {
+                AttributeInfo::LineNumberTable(lines) => {
+                    for line in lines {
+                        label(line.start_pc as u32)?;
+                    }
+                }
+                AttributeInfo::LocalVariableTable(variables) => {
+                    for variable in variables {
+                        label(variable.start_pc as u32)?;
+                        label(variable.start_pc as u32 + variable.length as u32)?;
+                    }
+                }
+                AttributeInfo::LocalVariableTypeTable(variables) => {
+                    for variable in variables {
+                        label(variable.start_pc as u32)?;
+                        label(variable.start_pc as u32 + variable.length as u32)?;
+                    }
+                }
+                AttributeInfo::StackMapTable(frames) => {
+                    for (pc, frame) in frame_pcs(frames).into_iter().zip(frames) {
+                        label(pc)?;
+                        let types = match frame {
+                            StackMapFrame::SameLocals1StackItemFrame { stack, .. } |
+                            StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => std::slice::from_ref(stack),
+                            StackMapFrame::AppendFrame { locals, .. } => locals,
+                            StackMapFrame::FullFrame { locals, .. } => locals,
+                            _ => &[],
+                        };
+                        let stack = match frame {
+                            StackMapFrame::FullFrame { stack, .. } => stack.as_slice(),
+                            _ => &[],
+                        };
+                        for info in types.iter().chain(stack) {
+                            if let VerificationTypeInfo::Uninitialized { offset } = info {
+                                label(*offset as u32)?;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.line(depth, format!(".code stack {} locals {}", code.max_stack, code.max_locals));
+        for (pc, instruction, wide) in &instructions {
+            if labels.contains(pc) {
+                self.line(depth, format!("L{}:", pc));
+            }
+            self.instruction(depth + 1, *pc, instruction, *wide, &labels)?;
+        }
+        if labels.contains(&pc) {
+            self.line(depth, format!("L{}:", pc));
+        }
+        for entry in &code.exception_table {
+            self.line(depth + 1, format!(
+                ".catch {} from L{} to L{} using L{}",
+                self.named(entry.catch_type, ConstantKind::Class),
+                entry.start_pc,
+                entry.end_pc,
+                entry.handler_pc,
+            ));
+        }
+        self.attributes(depth + 1, &code.attributes, owner).map_err(|e| e.to_string())?;
+        self.line(depth, ".end code");
+        Ok(())
+    }
+
+    fn instruction(&mut self, depth: usize, pc: u32, instruction: &Instruction, wide: bool, labels: &BTreeSet<u32>) -> Result<(), String> {
+        let label = |offset: i32| {
+            let target = pc as i64 + offset as i64;
+            match u32::try_from(target) {
+                Ok(target) if labels.contains(&target) => Ok(format!("L{}", target)),
+                _ => Err(format!("the instruction at pc {} jumps to {}, outside of the code", pc, target)),
+            }
+        };
+        let mnemonic = instruction.opcode().mnemonic();
+        let operands = match instruction {
+            Instruction::Bipush(value) => value.to_string(),
+            Instruction::Sipush(value) => value.to_string(),
+            Instruction::Ldc(index) => self.constant(*index as u16),
+            Instruction::LdcW(index) | Instruction::Ldc2W(index) | Instruction::Getstatic(index) | Instruction::Putstatic(index) |
+            Instruction::Getfield(index) | Instruction::Putfield(index) | Instruction::Invokevirtual(index) |
+            Instruction::Invokespecial(index) | Instruction::Invokestatic(index) | Instruction::Invokedynamic { index } => self.constant(*index),
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) | Instruction::Dload(index) |
+            Instruction::Aload(index) | Instruction::Istore(index) | Instruction::Lstore(index) | Instruction::Fstore(index) |
+            Instruction::Dstore(index) | Instruction::Astore(index) | Instruction::Ret(index) => index.to_string(),
+            Instruction::Iinc { index, value } => format!("{} {}", index, value),
+            Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) | Instruction::Ifge(offset) |
+            Instruction::Ifgt(offset) | Instruction::Ifle(offset) | Instruction::IfIcmpeq(offset) | Instruction::IfIcmpne(offset) |
+            Instruction::IfIcmplt(offset) | Instruction::IfIcmpge(offset) | Instruction::IfIcmpgt(offset) |
+            Instruction::IfIcmple(offset) | Instruction::IfAcmpeq(offset) | Instruction::IfAcmpne(offset) |
+            Instruction::Goto(offset) | Instruction::Jsr(offset) | Instruction::Ifnull(offset) | Instruction::Ifnonnull(offset) => {
+                label(*offset as i32)?
+            }
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => label(*offset)?,
+            Instruction::Tableswitch { default, low, offsets, .. } => {
+                self.line(depth, mnemonic);
+                for (i, offset) in offsets.iter().enumerate() {
+                    self.line(depth + 1, format!("{}: {}", *low as i64 + i as i64, label(*offset)?));
+                }
+                self.line(depth + 1, format!("default: {}", label(*default)?));
+                return Ok(());
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                self.line(depth, mnemonic);
+                for (key, offset) in pairs {
+                    self.line(depth + 1, format!("{}: {}", key, label(*offset)?));
+                }
+                self.line(depth + 1, format!("default: {}", label(*default)?));
+                return Ok(());
+            }
+            Instruction::Invokeinterface { index, count } => format!("{} {}", self.constant(*index), count),
+            Instruction::New(index) | Instruction::Anewarray(index) | Instruction::Checkcast(index) | Instruction::Instanceof(index) => {
+                self.named(*index, ConstantKind::Class)
+            }
+            Instruction::Newarray { atype } => atype.name().to_string(),
+            Instruction::Multianewarray { index, dimensions } => format!("{} {}", self.named(*index, ConstantKind::Class), dimensions),
+            _ => String::new(),
+        };
+        let prefix = if wide { "wide " } else { "" };
+        self.line(depth, format!("{}{} {}", prefix, mnemonic, operands).trim_end());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    /// Quoted tokens are always taken literally, so `"#12"` is a string rather than an index
+    quoted: bool,
+}
+
+/// A line with at least one token
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    number: usize,
+    tokens: Vec<Token>,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Line>, ClassTextError> {
+    let mut lines = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let error = |message: &str| ClassTextError::Syntax { line: number, message: message.to_string() };
+        let mut tokens = vec![];
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek() {
+                None | Some(';') => break,
+                Some('"') => {
+                    chars.next();
+                    tokens.push(Token { text: quoted(&mut chars).map_err(error)?, quoted: true });
+                }
+                Some(_) => {
+                    let mut text = String::new();
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        text.push(c);
+                    }
+                    tokens.push(Token { text, quoted: false });
+                }
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push(Line { number, tokens });
+        }
+    }
+    Ok(lines)
+}
+
+/// The rest of a quoted string, after the opening quote
+fn quoted(chars: &mut Peekable<Chars>) -> Result<String, &'static str> {
+    let mut text = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(text),
+            '\\' => text.push(match chars.next().ok_or("unterminated string")? {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let digits: String = chars.by_ref().take(4).collect();
+                    u32::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == 4).and_then(char::from_u32).ok_or("invalid escape")?
+                }
+                _ => return Err("invalid escape"),
+            }),
+            c => text.push(c),
+        }
+    }
+}
+
+/// A constant as it's written, which the [`Pool`] turns into an index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Symbol {
+    /// A constant written as its index, like `#12`, which isn't checked
+    Index(u16),
+    Utf8(String),
+    /// A utf8 constant with unpaired surrogates, as its modified UTF-8
+    Utf8Bytes(Vec<u8>),
+    Integer(i32),
+    /// Floats and doubles by their bits, so different `NaN`s stay apart
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    Class(Box<Symbol>),
+    String(Box<Symbol>),
+    MethodType(Box<Symbol>),
+    Module(Box<Symbol>),
+    Package(Box<Symbol>),
+    Field(Box<Symbol>, Box<Symbol>),
+    Method(Box<Symbol>, Box<Symbol>),
+    InterfaceMethod(Box<Symbol>, Box<Symbol>),
+    NameAndType(Box<Symbol>, Box<Symbol>),
+    MethodHandle(MethodReferenceKind, Box<Symbol>),
+    InvokeDynamic(u16, Box<Symbol>),
+    Dynamic(u16, Box<Symbol>),
+}
+
+/// The constant pool being assembled
+///
+/// Constants from the `.const` lines keep their index, the ones used without being listed are added after them.
+#[derive(Debug, Default)]
+struct Pool {
+    /// What the `.const` lines say, with the line they're on, until [`Pool::seal`] resolves them
+    listed: Vec<(Symbol, usize)>,
+    constants: Vec<Constant>,
+    added: Vec<Constant>,
+    /// Index of the first constant equal to every symbol
+    indices: HashMap<Symbol, u16>,
+    /// Slots taken so far
+    slots: usize,
+}
+
+impl Pool {
+    fn take_slots(&mut self, symbol: &Symbol) -> Result<u16, String> {
+        let index = self.slots + 1;
+        self.slots += if matches!(symbol, Symbol::Long(_) | Symbol::Double(_)) { 2 } else { 1 };
+        if self.slots > MAX_SLOTS {
+            return Err(format!("the constant pool can't hold more than {} slots", MAX_SLOTS));
+        }
+        Ok(index as u16)
+    }
+
+    fn list(&mut self, index: u16, symbol: Symbol, line: usize) -> Result<(), String> {
+        if index as usize != self.slots + 1 {
+            return Err(format!("expected constant #{}", self.slots + 1));
+        }
+        self.take_slots(&symbol)?;
+        self.indices.entry(symbol.clone()).or_insert(index);
+        self.listed.push((symbol, line));
+        Ok(())
+    }
+
+    /// Resolves what the listed constants point to, once they're all known
+    fn seal(&mut self) -> Result<(), ClassTextError> {
+        for (symbol, line) in std::mem::take(&mut self.listed) {
+            let constant = self.constant(&symbol).map_err(|message| ClassTextError::Syntax { line, message })?;
+            self.constants.push(constant);
+        }
+        Ok(())
+    }
+
+    fn index(&mut self, symbol: &Symbol) -> Result<u16, String> {
+        if let Symbol::Index(index) = symbol {
+            return Ok(*index);
+        }
+        if let Some(index) = self.indices.get(symbol) {
+            return Ok(*index);
+        }
+        let constant = self.constant(symbol)?;
+        let index = self.take_slots(symbol)?;
+        self.added.push(constant);
+        self.indices.insert(symbol.clone(), index);
+        Ok(index)
+    }
+
+    fn constant(&mut self, symbol: &Symbol) -> Result<Constant, String> {
+        Ok(match symbol {
+            Symbol::Index(index) => return Err(format!("#{} isn't a constant", index)),
+            Symbol::Utf8(string) => Constant::UTF8String(string.clone()),
+            Symbol::Utf8Bytes(bytes) => {
+                let units = mutf8::decode_utf16(bytes).map_err(|error| format!("invalid utf8 constant: {}", error))?;
+                Constant::UTF8Bytes { string: String::from_utf16_lossy(&units), bytes: bytes.clone() }
+            }
+            Symbol::Integer(value) => Constant::Integer(*value),
+            Symbol::Float(bits) => Constant::Float(f32::from_bits(*bits)),
+            Symbol::Long(value) => Constant::Long(*value),
+            Symbol::Double(bits) => Constant::Double(f64::from_bits(*bits)),
+            Symbol::Class(name) => Constant::Class { name_index: self.index(name)? },
+            Symbol::String(string) => Constant::String { string_index: self.index(string)? },
+            Symbol::MethodType(descriptor) => Constant::MethodType { descriptor_index: self.index(descriptor)? },
+            Symbol::Module(name) => Constant::Module { name_index: self.index(name)? },
+            Symbol::Package(name) => Constant::Package { name_index: self.index(name)? },
+            Symbol::Field(class, name_and_type) => Constant::Field { class_index: self.index(class)?, name_and_type_index: self.index(name_and_type)? },
+            Symbol::Method(class, name_and_type) => Constant::Method { class_index: self.index(class)?, name_and_type_index: self.index(name_and_type)? },
+            Symbol::InterfaceMethod(class, name_and_type) => {
+                Constant::InterfaceMethod { class_index: self.index(class)?, name_and_type_index: self.index(name_and_type)? }
+            }
+            Symbol::NameAndType(name, descriptor) => Constant::NameAndType { name_index: self.index(name)?, descriptor_index: self.index(descriptor)? },
+            Symbol::MethodHandle(reference_kind, reference) => Constant::MethodHandle { reference_kind: *reference_kind, reference_index: self.index(reference)? },
+            Symbol::InvokeDynamic(bootstrap_method_attr_index, name_and_type) => Constant::InvokeDynamic {
+                bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                name_and_type_index: self.index(name_and_type)?,
+            },
+            Symbol::Dynamic(bootstrap_method_attr_index, name_and_type) => Constant::Dynamic {
+                bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                name_and_type_index: self.index(name_and_type)?,
+            },
+        })
+    }
+
+    fn finish(self) -> ConstantPool {
+        self.constants.into_iter().chain(self.added).collect()
+    }
+}
+
+/// Reads the tokens of a line one by one
+struct Cursor<'a> {
+    line: &'a Line,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn error(&self, message: impl Into<String>) -> ClassTextError {
+        ClassTextError::Syntax { line: self.line.number, message: message.into() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.line.tokens.len()
+    }
+
+    /// The next token if it isn't quoted, without moving past it
+    fn peek_bare(&self) -> Option<&'a str> {
+        self.line.tokens.get(self.pos).filter(|token| !token.quoted).map(|token| token.text.as_str())
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a Token, ClassTextError> {
+        let token = self.line.tokens.get(self.pos).ok_or_else(|| self.error(format!("expected {}", what)))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn word(&mut self, what: &str) -> Result<&'a str, ClassTextError> {
+        self.next(what).map(|token| token.text.as_str())
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ClassTextError> {
+        match self.peek_bare() {
+            Some(word) if word == keyword => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected {}", keyword))),
+        }
+    }
+
+    fn int<T: FromStr>(&mut self, what: &str) -> Result<T, ClassTextError> {
+        let word = self.word(what)?;
+        word.parse().map_err(|_| self.error(format!("expected {}, found {}", what, word)))
+    }
+
+    fn end(&self) -> Result<(), ClassTextError> {
+        match self.line.tokens.get(self.pos) {
+            Some(token) => Err(self.error(format!("unexpected {}", token.text))),
+            None => Ok(()),
+        }
+    }
+
+    /// The index of a token like `#12`, `None` for other tokens
+    fn as_index(&self, token: &Token) -> Result<Option<u16>, ClassTextError> {
+        match token.text.strip_prefix('#') {
+            Some(digits) if !token.quoted => digits.parse().map(Some).map_err(|_| self.error(format!("invalid index {}", token.text))),
+            _ => Ok(None),
+        }
+    }
+
+    fn utf8(&mut self) -> Result<Symbol, ClassTextError> {
+        let token = self.next("a string")?;
+        Ok(match self.as_index(token)? {
+            Some(index) => Symbol::Index(index),
+            None => Symbol::Utf8(token.text.clone()),
+        })
+    }
+
+    /// A class, module or package written as its name, `constant` being the variant of [`Symbol`] it is
+    fn named(&mut self, what: &str, constant: fn(Box<Symbol>) -> Symbol) -> Result<Symbol, ClassTextError> {
+        let token = self.next(what)?;
+        Ok(match self.as_index(token)? {
+            Some(index) => Symbol::Index(index),
+            None => constant(Box::new(Symbol::Utf8(token.text.clone()))),
+        })
+    }
+
+    fn name_and_type(&mut self) -> Result<Symbol, ClassTextError> {
+        let token = self.next("a name")?;
+        Ok(match self.as_index(token)? {
+            Some(index) => Symbol::Index(index),
+            None => Symbol::NameAndType(Box::new(Symbol::Utf8(token.text.clone())), Box::new(self.utf8()?)),
+        })
+    }
+
+    fn constant(&mut self) -> Result<Symbol, ClassTextError> {
+        let token = self.next("a constant")?;
+        match self.as_index(token)? {
+            Some(index) => Ok(Symbol::Index(index)),
+            None => {
+                self.pos -= 1;
+                self.listed_constant()
+            }
+        }
+    }
+
+    /// The bits of a float or double, written in hex or as a number that `from_text` parses
+    fn float<T>(&mut self, what: &str, from_text: fn(&str) -> Option<T>) -> Result<T, ClassTextError> {
+        let word = self.word(what)?;
+        from_text(word).ok_or_else(|| self.error(format!("expected {}, found {}", what, word)))
+    }
+
+    /// A constant written as its tag followed by what it points to, like in `.const` lines
+    fn listed_constant(&mut self) -> Result<Symbol, ClassTextError> {
+        let tag = self.word("a constant")?;
+        let boxed = |symbol: Result<Symbol, ClassTextError>| symbol.map(Box::new);
+        Ok(match tag {
+            "Utf8" => Symbol::Utf8(self.word("a string")?.to_string()),
+            "Utf8Bytes" => {
+                let word = self.word("hex")?;
+                Symbol::Utf8Bytes(self.hex_bytes(word)?)
+            }
+            "Integer" => Symbol::Integer(self.int("an integer")?),
+            "Float" => Symbol::Float(self.float("a float", |word| match word.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => word.parse().ok().map(f32::to_bits),
+            })?),
+            "Long" => Symbol::Long(self.int("a long")?),
+            "Double" => Symbol::Double(self.float("a double", |word| match word.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                None => word.parse().ok().map(f64::to_bits),
+            })?),
+            "Class" => Symbol::Class(boxed(self.utf8())?),
+            "String" => Symbol::String(boxed(self.utf8())?),
+            "MethodType" => Symbol::MethodType(boxed(self.utf8())?),
+            "Module" => Symbol::Module(boxed(self.utf8())?),
+            "Package" => Symbol::Package(boxed(self.utf8())?),
+            "Fieldref" => Symbol::Field(boxed(self.named("a class", Symbol::Class))?, boxed(self.name_and_type())?),
+            "Methodref" => Symbol::Method(boxed(self.named("a class", Symbol::Class))?, boxed(self.name_and_type())?),
+            "InterfaceMethodref" => Symbol::InterfaceMethod(boxed(self.named("a class", Symbol::Class))?, boxed(self.name_and_type())?),
+            "NameAndType" => Symbol::NameAndType(boxed(self.utf8())?, boxed(self.utf8())?),
+            "MethodHandle" => {
+                let name = self.word("a reference kind")?;
+                let reference_kind = MethodReferenceKind::from_name(name).ok_or_else(|| self.error(format!("unknown reference kind {}", name)))?;
+                // Only members, so handles can't nest
+                let reference = match self.peek_bare() {
+                    Some("Fieldref" | "Methodref" | "InterfaceMethodref") => self.listed_constant()?,
+                    _ => match self.next("a field or method").and_then(|token| self.as_index(token))? {
+                        Some(index) => Symbol::Index(index),
+                        None => return Err(self.error("expected a Fieldref, Methodref or InterfaceMethodref")),
+                    },
+                };
+                Symbol::MethodHandle(reference_kind, Box::new(reference))
+            }
+            "InvokeDynamic" => Symbol::InvokeDynamic(self.int("a bootstrap method index")?, boxed(self.name_and_type())?),
+            "Dynamic" => Symbol::Dynamic(self.int("a bootstrap method index")?, boxed(self.name_and_type())?),
+            _ => return Err(self.error(format!("unknown constant {}", tag))),
+        })
+    }
+
+    /// The bits of the flags before the last `keep` tokens
+    fn flags<T: Debug>(&mut self, keep: usize, from_bits_truncate: fn(u16) -> T) -> Result<u16, ClassTextError> {
+        let mut bits = 0;
+        while self.line.tokens.len() - self.pos > keep {
+            let word = self.word("a flag")?;
+            let bit = match word.starts_with("0x") {
+                true => flag_bit(word, from_bits_truncate),
+                false => flag_bit(&format!("ACC_{}", word.to_uppercase()), from_bits_truncate),
+            };
+            bits |= bit.ok_or_else(|| self.error(format!("unknown flag {}", word)))?;
+        }
+        Ok(bits)
+    }
+
+    /// The bytes written in hex in the rest of the line
+    fn hex(&mut self) -> Result<Vec<u8>, ClassTextError> {
+        let mut text = String::new();
+        while !self.is_empty() {
+            text.push_str(self.word("hex")?);
+        }
+        self.hex_bytes(&text)
+    }
+
+    fn hex_bytes(&self, text: &str) -> Result<Vec<u8>, ClassTextError> {
+        if !text.len().is_multiple_of(2) {
+            return Err(self.error("expected an even number of hex digits"));
+        }
+        (0..text.len()).step_by(2)
+            .map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.error("invalid hex"))
+    }
+
+    /// The pc of the label in the next token
+    fn label_pc(&mut self, labels: Option<&HashMap<&str, u32>>) -> Result<u32, ClassTextError> {
+        let name = self.word("a label")?;
+        let labels = labels.ok_or_else(|| self.error("labels can only be used in code"))?;
+        labels.get(name).copied().ok_or_else(|| self.error(format!("undefined label {}", name)))
+    }
+}
+
+/// Directives of the blocks attributes can have, which the first pass over code skips
+const BLOCKS: [&str; 11] = [
+    ".code", ".stackmaptable", ".innerclasses", ".linenumbertable", ".localvariabletable", ".localvariabletypetable",
+    ".bootstrapmethods", ".methodparameters", ".module", ".record", ".component",
+];
+
+/// Labels of the code being assembled
+struct CodeLabels<'a> {
+    assembler: Assembler,
+    labels: HashMap<&'a str, Label>,
+    bound: HashSet<&'a str>,
+    /// Every label used by an instruction or `.catch`, with the line it's used on
+    used: Vec<(&'a str, usize)>,
+}
+
+impl<'a> CodeLabels<'a> {
+    fn get(&mut self, name: &'a str) -> Label {
+        let assembler = &mut self.assembler;
+        *self.labels.entry(name).or_insert_with(|| assembler.new_label())
+    }
+
+    fn use_label(&mut self, line: &mut Cursor<'a>) -> Result<Label, ClassTextError> {
+        let name = line.word("a label")?;
+        self.used.push((name, line.line.number));
+        Ok(self.get(name))
+    }
+
+    fn bind(&mut self, line: &Cursor<'a>, name: &'a str) -> Result<(), ClassTextError> {
+        let label = self.get(name);
+        self.bound.insert(name);
+        self.assembler.bind(label).map_err(|_| line.error(format!("label {} is defined twice", name)))
+    }
+}
+
+struct Parser<'a> {
+    lines: &'a [Line],
+    pos: usize,
+    pool: Pool,
+}
+
+impl<'a> Parser<'a> {
+    fn next_line(&mut self) -> Result<Cursor<'a>, ClassTextError> {
+        let lines = self.lines;
+        let line = lines.get(self.pos).ok_or_else(|| ClassTextError::Syntax {
+            line: lines.last().map_or(1, |line| line.number + 1),
+            message: "unexpected end of file".to_string(),
+        })?;
+        self.pos += 1;
+        Ok(Cursor { line, pos: 0 })
+    }
+
+    fn peek_directive(&self) -> Option<&'a str> {
+        self.lines.get(self.pos).and_then(|line| Cursor { line, pos: 0 }.peek_bare())
+    }
+
+    /// The next line, which must start with `directive`
+    fn directive(&mut self, directive: &str) -> Result<Cursor<'a>, ClassTextError> {
+        let mut line = self.next_line()?;
+        line.keyword(directive)?;
+        Ok(line)
+    }
+
+    fn resolve(&mut self, line: &Cursor, symbol: Result<Symbol, ClassTextError>) -> Result<u16, ClassTextError> {
+        self.pool.index(&symbol?).map_err(|message| line.error(message))
+    }
+
+    fn utf8(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.utf8();
+        self.resolve(line, symbol)
+    }
+
+    fn class(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.named("a class", Symbol::Class);
+        self.resolve(line, symbol)
+    }
+
+    fn classes(&mut self, line: &mut Cursor) -> Result<Vec<u16>, ClassTextError> {
+        let mut classes = vec![];
+        while !line.is_empty() {
+            classes.push(self.class(line)?);
+        }
+        Ok(classes)
+    }
+
+    fn module(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.named("a module", Symbol::Module);
+        self.resolve(line, symbol)
+    }
+
+    fn package(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.named("a package", Symbol::Package);
+        self.resolve(line, symbol)
+    }
+
+    fn name_and_type(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.name_and_type();
+        self.resolve(line, symbol)
+    }
+
+    fn constant(&mut self, line: &mut Cursor) -> Result<u16, ClassTextError> {
+        let symbol = line.constant();
+        self.resolve(line, symbol)
+    }
+
+    fn class_file(&mut self) -> Result<Class, ClassTextError> {
+        let mut line = self.directive(".version")?;
+        let java_version = JavaVersion { major: line.int("a major version")?, minor: line.int("a minor version")? };
+        line.end()?;
+        while self.peek_directive() == Some(".const") {
+            let mut line = self.directive(".const")?;
+            let token = line.next("an index")?;
+            let index = line.as_index(token)?.ok_or_else(|| line.error(format!("expected an index like #1, found {}", token.text)))?;
+            line.keyword("=")?;
+            let symbol = line.listed_constant()?;
+            line.end()?;
+            self.pool.list(index, symbol, line.line.number).map_err(|message| line.error(message))?;
+        }
+        self.pool.seal()?;
+
+        let mut line = self.directive(".class")?;
+        let access_flags = ClassAccessFlags::from_bits_retain(line.flags(1, ClassAccessFlags::from_bits_truncate)?);
+        let this_class = self.class(&mut line)?;
+        line.end()?;
+        let mut line = self.directive(".super")?;
+        let super_class = self.class(&mut line)?;
+        line.end()?;
+        let mut interfaces = vec![];
+        while self.peek_directive() == Some(".implements") {
+            let mut line = self.directive(".implements")?;
+            interfaces.push(self.class(&mut line)?);
+            line.end()?;
+        }
+
+        let mut fields = vec![];
+        let mut methods = vec![];
+        let mut attributes = vec![];
+        loop {
+            let mut line = self.next_line()?;
+            match line.word("a directive")? {
+                ".field" => {
+                    let access_flags = FieldAccessFlags::from_bits_retain(line.flags(2, FieldAccessFlags::from_bits_truncate)?);
+                    let name_index = self.utf8(&mut line)?;
+                    let descriptor_index = self.utf8(&mut line)?;
+                    line.end()?;
+                    fields.push(Field { access_flags, name_index, descriptor_index, attributes: self.attributes_until("field")? });
+                }
+                ".method" => {
+                    let access_flags = MethodAccessFlags::from_bits_retain(line.flags(2, MethodAccessFlags::from_bits_truncate)?);
+                    let name_index = self.utf8(&mut line)?;
+                    let descriptor_index = self.utf8(&mut line)?;
+                    line.end()?;
+                    methods.push(Method { access_flags, name_index, descriptor_index, attributes: self.attributes_until("method")? });
+                }
+                ".end" => {
+                    line.keyword("class")?;
+                    line.end()?;
+                    break;
+                }
+                directive => attributes.push(self.attribute(directive, &mut line, None)?),
+            }
+        }
+        if let Some(line) = self.lines.get(self.pos) {
+            return Err(ClassTextError::Syntax { line: line.number, message: "unexpected text after .end class".to_string() });
+        }
+        Ok(Class {
+            java_version,
+            constant_pool: std::mem::take(&mut self.pool).finish(),
+            access_flags,
+            this_class,
+            super_class,
+            interface_indices: interfaces,
+            fields,
+            methods,
+            attributes,
+        })
+    }
+
+    /// Attributes up to the `.end` line closing the block called `end`
+    fn attributes_until(&mut self, end: &str) -> Result<Vec<Attribute>, ClassTextError> {
+        let mut attributes = vec![];
+        loop {
+            let mut line = self.next_line()?;
+            match line.word("a directive")? {
+                ".end" => {
+                    line.keyword(end)?;
+                    line.end()?;
+                    return Ok(attributes);
+                }
+                directive => attributes.push(self.attribute(directive, &mut line, None)?),
+            }
+        }
+    }
+
+    /// A row for every line up to the `.end` line closing the block called `end`
+    fn rows<T>(&mut self, end: &str, mut row: impl FnMut(&mut Self, &mut Cursor<'a>) -> Result<T, ClassTextError>) -> Result<Vec<T>, ClassTextError> {
+        let mut rows = vec![];
+        loop {
+            let mut line = self.next_line()?;
+            if line.peek_bare() == Some(".end") {
+                line.keyword(".end")?;
+                line.keyword(end)?;
+                line.end()?;
+                return Ok(rows);
+            }
+            rows.push(row(self, &mut line)?);
+            line.end()?;
+        }
+    }
+
+    /// The attribute of the `directive` that starts `line`, `labels` being the pcs of the labels of the code it's in
+    fn attribute(&mut self, directive: &str, line: &mut Cursor<'a>, labels: Option<&HashMap<&'a str, u32>>) -> Result<Attribute, ClassTextError> {
+        let info = match directive {
+            ".constantvalue" => AttributeInfo::ConstantValue { constantvalue_index: self.constant(line)? },
+            ".code" => AttributeInfo::Code(self.code(line)?),
+            ".stackmaptable" => AttributeInfo::StackMapTable(self.frames(line, labels)?),
+            ".exceptions" => AttributeInfo::Exceptions(self.classes(line)?),
+            ".innerclasses" => {
+                line.end()?;
+                AttributeInfo::InnerClasses(self.rows("innerclasses", |parser, line| Ok(InnerClass {
+                    inner_class_info_index: parser.class(line)?,
+                    outer_class_info_index: parser.class(line)?,
+                    inner_name_index: parser.utf8(line)?,
+                    inner_class_access_flags: InnerClassAccessFlags::from_bits_retain(line.flags(0, InnerClassAccessFlags::from_bits_truncate)?),
+                }))?)
+            }
+            ".enclosingmethod" => AttributeInfo::EnclosingMethod { class_index: self.class(line)?, method_index: self.name_and_type(line)? },
+            ".synthetic" => AttributeInfo::Synthetic,
+            ".signature" => AttributeInfo::Signature { signature_index: self.utf8(line)? },
+            ".sourcefile" => AttributeInfo::SourceFile { sourcefile_index: self.utf8(line)? },
+            ".sourcedebugextension" => AttributeInfo::SourceDebugExtension(line.hex()?),
+            ".linenumbertable" => {
+                line.end()?;
+                AttributeInfo::LineNumberTable(self.rows("linenumbertable", |_, line| Ok(LineNumber {
+                    start_pc: line.label_pc(labels)? as u16,
+                    line_number: line.int("a line number")?,
+                }))?)
+            }
+            ".localvariabletable" => {
+                line.end()?;
+                AttributeInfo::LocalVariableTable(self.rows("localvariabletable", |parser, line| {
+                    let (index, name_index, descriptor_index, start_pc, length) = parser.local_variable(line, labels)?;
+                    Ok(LocalVariable { start_pc, length, name_index, descriptor_index, index })
+                })?)
+            }
+            ".localvariabletypetable" => {
+                line.end()?;
+                AttributeInfo::LocalVariableTypeTable(self.rows("localvariabletypetable", |parser, line| {
+                    let (index, name_index, signature_index, start_pc, length) = parser.local_variable(line, labels)?;
+                    Ok(LocalVariableType { start_pc, length, name_index, signature_index, index })
+                })?)
+            }
+            ".deprecated" => AttributeInfo::Deprecated,
+            ".bootstrapmethods" => {
+                line.end()?;
+                AttributeInfo::BootstrapMethods(self.rows("bootstrapmethods", |parser, line| {
+                    let bootstrap_method_ref = parser.constant(line)?;
+                    let mut bootstrap_arguments = vec![];
+                    while !line.is_empty() {
+                        bootstrap_arguments.push(parser.constant(line)?);
+                    }
+                    Ok(BootstrapMethod { bootstrap_method_ref, bootstrap_arguments })
+                })?)
+            }
+            ".methodparameters" => {
+                line.end()?;
+                AttributeInfo::MethodParameters(self.rows("methodparameters", |parser, line| Ok(MethodParameter {
+                    name_index: parser.utf8(line)?,
+                    access_flags: MethodParameterAccessFlags::from_bits_retain(line.flags(0, MethodParameterAccessFlags::from_bits_truncate)?),
+                }))?)
+            }
+            ".module" => AttributeInfo::Module(self.module_attribute(line)?),
+            ".modulepackages" => {
+                let mut packages = vec![];
+                while !line.is_empty() {
+                    packages.push(self.package(line)?);
+                }
+                AttributeInfo::ModulePackages(packages)
+            }
+            ".modulemainclass" => AttributeInfo::ModuleMainClass { main_class_index: self.class(line)? },
+            ".nesthost" => AttributeInfo::NestHost { host_class_index: self.class(line)? },
+            ".nestmembers" => AttributeInfo::NestMembers(self.classes(line)?),
+            ".record" => {
+                line.end()?;
+                AttributeInfo::Record(self.rows("record", |parser, line| {
+                    line.keyword(".component")?;
+                    let name_index = parser.utf8(line)?;
+                    let descriptor_index = parser.utf8(line)?;
+                    line.end()?;
+                    Ok(RecordComponent { name_index, descriptor_index, attributes: parser.attributes_until("component")? })
+                })?)
+            }
+            ".permittedsubclasses" => AttributeInfo::PermittedSubclasses(self.classes(line)?),
+            ".attribute" => {
+                let attribute_name_index = self.utf8(line)?;
+                return Ok(Attribute { attribute_name_index, info: AttributeInfo::Unknown(line.hex()?) });
+            }
+            _ => return Err(line.error(format!("unknown directive {}", directive))),
+        };
+        line.end()?;
+        let attribute_name_index = self.pool.index(&Symbol::Utf8(attribute_kind(&info).to_string())).map_err(|message| line.error(message))?;
+        Ok(Attribute { attribute_name_index, info })
+    }
+
+    /// A row of `.localvariabletable` or `.localvariabletypetable`: the index, name, descriptor or signature, start pc
+    /// and length of the variable
+    fn local_variable(&mut self, line: &mut Cursor<'a>, labels: Option<&HashMap<&'a str, u32>>) -> Result<(u16, u16, u16, u16, u16), ClassTextError> {
+        let index = line.int("a local variable")?;
+        line.keyword("is")?;
+        let name_index = self.utf8(line)?;
+        let descriptor_index = self.utf8(line)?;
+        line.keyword("from")?;
+        let start_pc = line.label_pc(labels)?;
+        line.keyword("to")?;
+        let end_pc = line.label_pc(labels)?;
+        let length = end_pc.checked_sub(start_pc).ok_or_else(|| line.error("the variable ends before it starts"))?;
+        Ok((index, name_index, descriptor_index, start_pc as u16, length as u16))
+    }
+
+    fn module_attribute(&mut self, line: &mut Cursor<'a>) -> Result<ModuleAttribute, ClassTextError> {
+        let mut module = ModuleAttribute {
+            module_name_index: self.module(line)?,
+            module_version_index: self.utf8(line)?,
+            module_flags: ModuleFlags::from_bits_retain(line.flags(0, ModuleFlags::from_bits_truncate)?),
+            requires: vec![],
+            exports: vec![],
+            opens: vec![],
+            uses_index: vec![],
+            provides: vec![],
+        };
+        line.end()?;
+        self.rows("module", |parser, line| {
+            match line.word("requires, exports, opens, uses or provides")? {
+                "requires" => module.requires.push(ModuleRequires {
+                    requires_index: parser.module(line)?,
+                    requires_version_index: parser.utf8(line)?,
+                    requires_flags: RequiresFlags::from_bits_retain(line.flags(0, RequiresFlags::from_bits_truncate)?),
+                }),
+                keyword @ ("exports" | "opens") => {
+                    let package_index = parser.package(line)?;
+                    let to = line.line.tokens[line.pos..].iter().position(|token| !token.quoted && token.text == "to");
+                    let flags = ExportsFlags::from_bits_retain(line.flags(to.map_or(0, |to| line.line.tokens.len() - line.pos - to), ExportsFlags::from_bits_truncate)?);
+                    let mut to_index = vec![];
+                    if to.is_some() {
+                        line.keyword("to")?;
+                        while !line.is_empty() {
+                            to_index.push(parser.module(line)?);
+                        }
+                    }
+                    let table = if keyword == "exports" { &mut module.exports } else { &mut module.opens };
+                    table.push(ModuleExports { package_index, flags, to_index });
+                }
+                "uses" => module.uses_index.push(parser.class(line)?),
+                "provides" => {
+                    let provides_index = parser.class(line)?;
+                    line.keyword("with")?;
+                    module.provides.push(ModuleProvides { provides_index, provides_with_index: parser.classes(line)? });
+                }
+                other => return Err(line.error(format!("unknown module directive {}", other))),
+            }
+            Ok(())
+        })?;
+        Ok(module)
+    }
+
+    fn verification_type(&mut self, line: &mut Cursor<'a>, labels: Option<&HashMap<&'a str, u32>>) -> Result<VerificationTypeInfo, ClassTextError> {
+        let name = line.word("a verification type")?;
+        Ok(match name {
+            "Top" => VerificationTypeInfo::Top,
+            "Integer" => VerificationTypeInfo::Integer,
+            "Float" => VerificationTypeInfo::Float,
+            "Double" => VerificationTypeInfo::Double,
+            "Long" => VerificationTypeInfo::Long,
+            "Null" => VerificationTypeInfo::Null,
+            "UninitializedThis" => VerificationTypeInfo::UninitializedThis,
+            "Object" => VerificationTypeInfo::Object { cpool_index: self.class(line)? },
+            "Uninitialized" => VerificationTypeInfo::Uninitialized { offset: line.label_pc(labels)? as u16 },
+            _ => return Err(line.error(format!("unknown verification type {}", name))),
+        })
+    }
+
+    fn frames(&mut self, line: &mut Cursor<'a>, labels: Option<&HashMap<&'a str, u32>>) -> Result<Vec<StackMapFrame>, ClassTextError> {
+        line.end()?;
+        let mut previous = None;
+        self.rows("stackmaptable", |parser, line| {
+            let pc = line.label_pc(labels)?;
+            let delta = match previous {
+                None => pc,
+                Some(previous) if pc > previous => pc - previous - 1,
+                Some(_) => return Err(line.error("frames must be in the order of their pcs")),
+            };
+            previous = Some(pc);
+            // Code is never longer than 65535 bytes
+            let offset_delta = delta as u16;
+            let kind = line.word("a frame type")?;
+            Ok(match kind {
+                "same" if delta < 64 => StackMapFrame::SameFrame { offset_delta: delta as u8 },
+                "same" | "same_extended" => StackMapFrame::SameFrameExtended { offset_delta },
+                "same_locals_1_stack_item" if delta < 64 => {
+                    StackMapFrame::SameLocals1StackItemFrame { offset_delta: delta as u8, stack: parser.verification_type(line, labels)? }
+                }
+                "same_locals_1_stack_item" | "same_locals_1_stack_item_extended" => {
+                    StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack: parser.verification_type(line, labels)? }
+                }
+                "chop" => StackMapFrame::ChopFrame { offset_delta, k: line.int("a number of locals")? },
+                "append" => {
+                    let mut locals = vec![];
+                    while !line.is_empty() {
+                        locals.push(parser.verification_type(line, labels)?);
+                    }
+                    StackMapFrame::AppendFrame { offset_delta, locals }
+                }
+                "full" => {
+                    line.keyword("locals")?;
+                    let mut locals = vec![];
+                    while line.peek_bare() != Some("stack") {
+                        locals.push(parser.verification_type(line, labels)?);
+                    }
+                    line.keyword("stack")?;
+                    let mut stack = vec![];
+                    while !line.is_empty() {
+                        stack.push(parser.verification_type(line, labels)?);
+                    }
+                    StackMapFrame::FullFrame { offset_delta, locals, stack }
+                }
+                _ => return Err(line.error(format!("unknown frame type {}", kind))),
+            })
+        })
+    }
+
+    /// Skips the lines of the block opened by `directive`, if it opens one
+    fn skip_block(&mut self, directive: &str) -> Result<(), ClassTextError> {
+        let mut depth = if BLOCKS.contains(&directive) { 1 } else { 0 };
+        while depth > 0 {
+            let line = self.next_line()?;
+            match line.peek_bare() {
+                Some(".end") => depth -= 1,
+                Some(directive) if BLOCKS.contains(&directive) => depth += 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles the instructions of a `.code` block, then reads its attributes in a second pass once the pc of
+    /// every label is known
+    fn code(&mut self, header: &mut Cursor<'a>) -> Result<CodeAttribute, ClassTextError> {
+        header.keyword("stack")?;
+        let max_stack = header.int("the maximum stack size")?;
+        header.keyword("locals")?;
+        let max_locals = header.int("the number of locals")?;
+        header.end()?;
+        let start = self.pos;
+        let mut code = CodeLabels { assembler: Assembler::new(), labels: HashMap::new(), bound: HashSet::new(), used: vec![] };
+        let end = loop {
+            let mut line = self.next_line()?;
+            match line.peek_bare() {
+                Some(".end") => {
+                    line.keyword(".end")?;
+                    line.keyword("code")?;
+                    line.end()?;
+                    break line;
+                }
+                Some(".catch") => {
+                    line.keyword(".catch")?;
+                    let catch_type = self.class(&mut line)?;
+                    line.keyword("from")?;
+                    let start = code.use_label(&mut line)?;
+                    line.keyword("to")?;
+                    let end = code.use_label(&mut line)?;
+                    line.keyword("using")?;
+                    let handler = code.use_label(&mut line)?;
+                    line.end()?;
+                    code.assembler.try_catch(start, end, handler, catch_type);
+                }
+                Some(directive) if directive.starts_with('.') => self.skip_block(directive)?,
+                Some(label) if label.len() > 1 && label.ends_with(':') => {
+                    line.pos += 1;
+                    code.bind(&line, &label[..label.len() - 1])?;
+                    if !line.is_empty() {
+                        self.instruction(&mut line, &mut code)?;
+                    }
+                }
+                _ => self.instruction(&mut line, &mut code)?,
+            }
+        };
+        if let Some((name, number)) = code.used.iter().find(|(name, _)| !code.bound.contains(name)) {
+            return Err(ClassTextError::Syntax { line: *number, message: format!("undefined label {}", name) });
+        }
+        let (mut attribute, pcs) = code.assembler.finish_with_labels(max_stack, max_locals).map_err(|e| end.error(e.to_string()))?;
+        let labels: HashMap<&str, u32> = code.labels.iter().filter_map(|(name, label)| pcs.get(label).map(|pc| (*name, *pc))).collect();
+
+        let after = self.pos;
+        self.pos = start;
+        while self.pos < after - 1 {
+            let mut line = self.next_line()?;
+            match line.peek_bare() {
+                Some(directive) if directive.starts_with('.') && directive != ".catch" => {
+                    line.pos += 1;
+                    attribute.attributes.push(self.attribute(directive, &mut line, Some(&labels))?);
+                }
+                _ => {}
+            }
+        }
+        self.pos = after;
+        Ok(attribute)
+    }
+
+    fn instruction(&mut self, line: &mut Cursor<'a>, code: &mut CodeLabels<'a>) -> Result<(), ClassTextError> {
+        let mut mnemonic = line.word("an instruction")?;
+        let wide = mnemonic == "wide";
+        if wide {
+            mnemonic = line.word("an instruction")?;
+        }
+        let opcode = Opcode::from_mnemonic(mnemonic).ok_or_else(|| line.error(format!("unknown instruction {}", mnemonic)))?;
+        let not_wide = |line: &Cursor| match wide {
+            true => Err(line.error(AssembleError::NotWidenable(opcode).to_string())),
+            false => Ok(()),
+        };
+        let instruction = match opcode {
+            Opcode::Ifeq | Opcode::Ifne | Opcode::Iflt | Opcode::Ifge | Opcode::Ifgt | Opcode::Ifle | Opcode::IfIcmpeq |
+            Opcode::IfIcmpne | Opcode::IfIcmplt | Opcode::IfIcmpge | Opcode::IfIcmpgt | Opcode::IfIcmple | Opcode::IfAcmpeq |
+            Opcode::IfAcmpne | Opcode::Goto | Opcode::Jsr | Opcode::Ifnull | Opcode::Ifnonnull | Opcode::GotoW | Opcode::JsrW => {
+                not_wide(line)?;
+                let target = code.use_label(line)?;
+                code.assembler.branch(opcode, target).map_err(|e| line.error(e.to_string()))?;
+                return line.end();
+            }
+            Opcode::Tableswitch | Opcode::Lookupswitch => {
+                not_wide(line)?;
+                line.end()?;
+                return self.switch(opcode, code);
+            }
+            Opcode::Bipush => Instruction::Bipush(line.int("a byte")?),
+            Opcode::Sipush => Instruction::Sipush(line.int("a short")?),
+            Opcode::Ldc => {
+                let index = self.constant(line)?;
+                Instruction::Ldc(u8::try_from(index).map_err(|_| line.error(format!("ldc can't load constant #{}, use ldc_w", index)))?)
+            }
+            Opcode::LdcW => Instruction::LdcW(self.constant(line)?),
+            Opcode::Ldc2W => Instruction::Ldc2W(self.constant(line)?),
+            Opcode::Iload => Instruction::Iload(line.int("a local variable")?),
+            Opcode::Lload => Instruction::Lload(line.int("a local variable")?),
+            Opcode::Fload => Instruction::Fload(line.int("a local variable")?),
+            Opcode::Dload => Instruction::Dload(line.int("a local variable")?),
+            Opcode::Aload => Instruction::Aload(line.int("a local variable")?),
+            Opcode::Istore => Instruction::Istore(line.int("a local variable")?),
+            Opcode::Lstore => Instruction::Lstore(line.int("a local variable")?),
+            Opcode::Fstore => Instruction::Fstore(line.int("a local variable")?),
+            Opcode::Dstore => Instruction::Dstore(line.int("a local variable")?),
+            Opcode::Astore => Instruction::Astore(line.int("a local variable")?),
+            Opcode::Ret => Instruction::Ret(line.int("a local variable")?),
+            Opcode::Iinc => Instruction::Iinc { index: line.int("a local variable")?, value: line.int("an increment")? },
+            Opcode::Getstatic => Instruction::Getstatic(self.constant(line)?),
+            Opcode::Putstatic => Instruction::Putstatic(self.constant(line)?),
+            Opcode::Getfield => Instruction::Getfield(self.constant(line)?),
+            Opcode::Putfield => Instruction::Putfield(self.constant(line)?),
+            Opcode::Invokevirtual => Instruction::Invokevirtual(self.constant(line)?),
+            Opcode::Invokespecial => Instruction::Invokespecial(self.constant(line)?),
+            Opcode::Invokestatic => Instruction::Invokestatic(self.constant(line)?),
+            Opcode::Invokeinterface => Instruction::Invokeinterface { index: self.constant(line)?, count: line.int("an argument count")? },
+            Opcode::Invokedynamic => Instruction::Invokedynamic { index: self.constant(line)? },
+            Opcode::New => Instruction::New(self.class(line)?),
+            Opcode::Newarray => {
+                let name = line.word("an array type")?;
+                Instruction::Newarray { atype: ArrayType::from_name(name).ok_or_else(|| line.error(format!("unknown array type {}", name)))? }
+            }
+            Opcode::Anewarray => Instruction::Anewarray(self.class(line)?),
+            Opcode::Checkcast => Instruction::Checkcast(self.class(line)?),
+            Opcode::Instanceof => Instruction::Instanceof(self.class(line)?),
+            Opcode::Multianewarray => Instruction::Multianewarray { index: self.class(line)?, dimensions: line.int("a number of dimensions")? },
+            _ => Instruction::from_opcode(opcode).ok_or_else(|| line.error(format!("unknown instruction {}", mnemonic)))?,
+        };
+        line.end()?;
+        if wide {
+            code.assembler.emit_wide(instruction).map_err(|e| line.error(e.to_string()))
+        } else {
+            code.assembler.emit(instruction);
+            Ok(())
+        }
+    }
+
+    /// The rows of a `tableswitch` or `lookupswitch`, up to the `default` one
+    fn switch(&mut self, opcode: Opcode, code: &mut CodeLabels<'a>) -> Result<(), ClassTextError> {
+        let mut pairs = vec![];
+        let (default, line) = loop {
+            let mut line = self.next_line()?;
+            let key = line.word("a key")?;
+            if key == "default:" {
+                let default = code.use_label(&mut line)?;
+                line.end()?;
+                break (default, line);
+            }
+            let value = key.strip_suffix(':').and_then(|key| key.parse::<i32>().ok())
+                .ok_or_else(|| line.error(format!("expected a key like 1:, found {}", key)))?;
+            if opcode == Opcode::Tableswitch && pairs.last().is_some_and(|&(last, _): &(i32, Label)| last as i64 + 1 != value as i64) {
+                return Err(line.error("tableswitch keys must go up one at a time"));
+            }
+            pairs.push((value, code.use_label(&mut line)?));
+            line.end()?;
+        };
+        let result = match opcode {
+            Opcode::Tableswitch => {
+                let low = pairs.first().map_or(0, |(key, _)| *key);
+                code.assembler.tableswitch(low, default, pairs.into_iter().map(|(_, target)| target).collect())
+            }
+            _ => code.assembler.lookupswitch(default, pairs),
+        };
+        result.map_err(|e| line.error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, word, Token};
+
+    #[test]
+    pub fn quotes_words_that_need_it() {
+        let texts = ["java/lang/Object", "Hello World!", "", "#12", ".end", "L1:", "a\"b\\c\nd\u{1}", "é😀"];
+        let line = texts.iter().map(|text| word(text)).collect::<Vec<_>>().join(" ");
+        assert_eq!(line, r##"java/lang/Object "Hello World!" "" "#12" ".end" "L1:" "a\"b\\c\nd\u0001" é😀"##);
+        let tokens = &tokenize(&format!("{} ; a comment", line)).unwrap()[0].tokens;
+        assert_eq!(tokens.iter().map(|token| token.text.as_str()).collect::<Vec<_>>(), texts);
+        assert_eq!(tokens[0], Token { text: "java/lang/Object".to_string(), quoted: false });
+        assert!(tokens[1..7].iter().all(|token| token.quoted));
+    }
+}
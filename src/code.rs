@@ -0,0 +1,150 @@
+//! The `Code` attribute, which holds the bytecode of a method
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.7.3
+use std::collections::BTreeMap;
+
+use crate::attribute::{Attribute, AttributeInfo, AttributeParseError, LineNumber, parse_attributes, write_attributes};
+use crate::class::{ParseClassError, read_n_dyn, read_u16, read_u32};
+use crate::constant_pool::{ConstantPool, ConstantPoolError};
+use crate::instruction::Instructions;
+use crate::reader::ClassReader;
+use crate::stack_map::{decode_frames, DecodeFrameError, Frame, StackMapFrame};
+use crate::writer::{ClassWriter, WriteClassError, write_table};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeAttribute {
+    /// Maximum depth of the operand stack at any point of the method
+    pub max_stack: u16,
+    /// Number of local variable slots, including the ones used for the parameters
+    pub max_locals: u16,
+    /// The bytecode itself
+    pub code: Vec<u8>,
+    /// Exception handlers, in the order they're searched
+    pub exception_table: Vec<ExceptionTableEntry>,
+    /// Attributes of the code, like `LineNumberTable` or `StackMapTable`
+    pub attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionTableEntry {
+    /// Start of the range of code covered by the handler, inclusive
+    pub start_pc: u16,
+    /// End of the range of code covered by the handler, exclusive
+    pub end_pc: u16,
+    /// Where the handler starts
+    pub handler_pc: u16,
+    /// Points to the class of exceptions caught by this handler, or 0 if it catches everything (like `finally`)
+    pub catch_type: u16,
+}
+
+impl CodeAttribute {
+    /// Decodes the bytecode, yielding every instruction along with its pc
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(&self.code)
+    }
+
+    /// Entries of the `LineNumberTable` attributes of the code, there can be more than one
+    pub fn line_numbers(&self) -> impl Iterator<Item=&LineNumber> {
+        self.attributes.iter()
+            .filter_map(|attribute| match &attribute.info {
+                AttributeInfo::LineNumberTable(lines) => Some(lines.iter()),
+                _ => None
+            })
+            .flatten()
+    }
+
+    /// Source line of the instruction at `pc`, if the code has line numbers
+    pub fn line_number(&self, pc: u16) -> Option<u16> {
+        self.line_numbers()
+            .filter(|line| line.start_pc <= pc)
+            .max_by_key(|line| line.start_pc)
+            .map(|line| line.line_number)
+    }
+
+    /// Frames of the `StackMapTable` attribute as stored in the class file, if the code has one
+    pub fn stack_map_table(&self) -> Option<&[StackMapFrame]> {
+        self.attributes.iter().find_map(|attribute| match &attribute.info {
+            AttributeInfo::StackMapTable(frames) => Some(frames.as_slice()),
+            _ => None
+        })
+    }
+
+    /// The full frame at each pc that has one in the `StackMapTable`, empty if the code doesn't have one
+    ///
+    /// `initial` is the frame the method starts with, see [`crate::method::Method::initial_frame`].
+    pub fn frames(&self, initial: &Frame, constant_pool: &ConstantPool) -> Result<BTreeMap<u32, Frame>, DecodeFrameError> {
+        let frames = decode_frames(initial, self.stack_map_table().unwrap_or_default(), constant_pool)?;
+        Ok(frames.into_iter().collect())
+    }
+
+    /// Handlers that cover `pc`, in the order the JVM tries them
+    pub fn handlers_at(&self, pc: u16) -> impl Iterator<Item=&ExceptionTableEntry> {
+        self.exception_table.iter().filter(move |entry| entry.covers(pc))
+    }
+}
+
+impl ExceptionTableEntry {
+    pub fn covers(&self, pc: u16) -> bool {
+        self.start_pc <= pc && pc < self.end_pc
+    }
+
+    /// Name of the class of exceptions caught by this handler, `None` if it catches everything
+    pub fn catch_type_name<'a>(&self, constant_pool: &'a ConstantPool) -> Result<Option<&'a str>, ConstantPoolError> {
+        if self.catch_type == 0 {
+            Ok(None)
+        } else {
+            constant_pool.get_class_name(self.catch_type).map(Some)
+        }
+    }
+}
+
+pub(crate) fn parse_code<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<CodeAttribute, ParseClassError> {
+    let max_stack = read_u16(f)?;
+    let max_locals = read_u16(f)?;
+    let code_len = read_u32(f)?;
+    // pcs are 16 bits wide everywhere else, so the code can't be longer than that
+    if code_len == 0 || code_len > u16::MAX as u32 {
+        return Err(AttributeParseError::InvalidCodeLength(code_len).into());
+    }
+    let code = read_n_dyn(f, code_len as usize)?;
+    let exception_table_len = read_u16(f)?;
+    let mut exception_table = Vec::with_capacity(exception_table_len as usize);
+    for _ in 0..exception_table_len {
+        let entry = ExceptionTableEntry {
+            start_pc: read_u16(f)?,
+            end_pc: read_u16(f)?,
+            handler_pc: read_u16(f)?,
+            catch_type: read_u16(f)?,
+        };
+        // end_pc is exclusive, so it may be equal to the code length
+        if entry.start_pc >= entry.end_pc || entry.end_pc as u32 > code_len || entry.handler_pc as u32 >= code_len {
+            return Err(AttributeParseError::InvalidExceptionTableEntry(entry).into());
+        }
+        entry.catch_type_name(constant_pool)?;
+        exception_table.push(entry);
+    }
+    let attributes = parse_attributes(f, constant_pool, major)?;
+    Ok(CodeAttribute {
+        max_stack,
+        max_locals,
+        code,
+        exception_table,
+        attributes,
+    })
+}
+
+pub(crate) fn write_code(out: &mut Vec<u8>, code: &CodeAttribute) -> Result<(), WriteClassError> {
+    out.write_u16(code.max_stack);
+    out.write_u16(code.max_locals);
+    let code_len = u32::try_from(code.code.len()).map_err(|_| WriteClassError::TooLong { what: "code", len: code.code.len() })?;
+    out.write_u32(code_len);
+    out.write_bytes(&code.code);
+    write_table(out, "exception table", &code.exception_table, |out, entry| {
+        out.write_u16(entry.start_pc);
+        out.write_u16(entry.end_pc);
+        out.write_u16(entry.handler_pc);
+        out.write_u16(entry.catch_type);
+        Ok(())
+    })?;
+    write_attributes(out, &code.attributes)
+}
@@ -1,12 +1,16 @@
-use std::fs::File;
+use std::fmt;
 
 use num_traits::FromPrimitive;
 use thiserror::*;
 
+use crate::attribute::BootstrapMethod;
 use crate::class;
 use crate::class::ParseClassError;
+use crate::mutf8::{self, ModifiedUtf8Error};
+use crate::reader::ClassReader;
+use crate::writer::{ClassWriter, WriteClassError};
 
-#[derive(FromPrimitive, ToPrimitive, Debug, PartialEq, Eq)]
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MethodReferenceKind {
     GetField = 1,
     GetStatic,
@@ -19,7 +23,14 @@ pub enum MethodReferenceKind {
     InvokeInterface,
 }
 
-#[derive(Debug, PartialEq)]
+impl MethodReferenceKind {
+    /// The kind named like its variant, such as `InvokeStatic`
+    pub fn from_name(name: &str) -> Option<MethodReferenceKind> {
+        (1..=9).filter_map(MethodReferenceKind::from_u8).find(|kind| format!("{:?}", kind) == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Constant {
     Class {
         /// This index references to a utf8 string in the constant pool
@@ -59,6 +70,10 @@ pub enum Constant {
         descriptor_index: u16,
     },
     UTF8String(String),
+    /// A utf8 constant with unpaired surrogates, which a `String` can't hold
+    ///
+    /// `string` has U+FFFD in their place, and `bytes` is the modified UTF-8 it was read from, which is what's written.
+    UTF8Bytes { string: String, bytes: Vec<u8> },
     MethodHandle {
         reference_kind: MethodReferenceKind,
         /// ## From the java specification:
@@ -86,218 +101,576 @@ pub enum Constant {
         /// Points to a name and type in the constant pool
         name_and_type_index: u16,
     },
+    /// A dynamically-computed constant, produced by a bootstrap method the first time it's loaded
+    Dynamic {
+        /// The value of the bootstrap_method_attr_index item must be a valid index into the bootstrap_methods array of the bootstrap method table (§4.7.23) of this class file.
+        bootstrap_method_attr_index: u16,
+        /// Points to a name and type in the constant pool, whose descriptor must be a field descriptor
+        name_and_type_index: u16,
+    },
+    /// A module, only allowed in the constant pool of `module-info`
+    Module {
+        /// Points to a utf8 string with the module name, like `java.base`
+        name_index: u16,
+    },
+    /// A package exported or opened by a module, only allowed in the constant pool of `module-info`
+    Package {
+        /// Points to a utf8 string with the package name in internal form, like `java/lang`
+        name_index: u16,
+    },
+    /// The slot right after a [`Constant::Long`] or [`Constant::Double`]
+    ///
+    /// The specification says this slot must be considered valid but unusable, it's never present in a class file.
+    Unusable,
+}
+
+/// The kind of a [`Constant`], without its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstantKind {
+    Class,
+    Field,
+    Method,
+    InterfaceMethod,
+    String,
+    Integer,
+    Float,
+    Long,
+    Double,
+    NameAndType,
+    UTF8String,
+    MethodHandle,
+    MethodType,
+    InvokeDynamic,
+    Dynamic,
+    Module,
+    Package,
+    Unusable,
+}
+
+impl fmt::Display for ConstantKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConstantKind::Class => "CONSTANT_Class",
+            ConstantKind::Field => "CONSTANT_Fieldref",
+            ConstantKind::Method => "CONSTANT_Methodref",
+            ConstantKind::InterfaceMethod => "CONSTANT_InterfaceMethodref",
+            ConstantKind::String => "CONSTANT_String",
+            ConstantKind::Integer => "CONSTANT_Integer",
+            ConstantKind::Float => "CONSTANT_Float",
+            ConstantKind::Long => "CONSTANT_Long",
+            ConstantKind::Double => "CONSTANT_Double",
+            ConstantKind::NameAndType => "CONSTANT_NameAndType",
+            ConstantKind::UTF8String => "CONSTANT_Utf8",
+            ConstantKind::MethodHandle => "CONSTANT_MethodHandle",
+            ConstantKind::MethodType => "CONSTANT_MethodType",
+            ConstantKind::InvokeDynamic => "CONSTANT_InvokeDynamic",
+            ConstantKind::Dynamic => "CONSTANT_Dynamic",
+            ConstantKind::Module => "CONSTANT_Module",
+            ConstantKind::Package => "CONSTANT_Package",
+            ConstantKind::Unusable => "unusable slot",
+        };
+        f.write_str(name)
+    }
+}
+
+impl ConstantKind {
+    /// Oldest major version of class files that can have this kind of constant
+    pub fn since(&self) -> u16 {
+        match self {
+            ConstantKind::MethodHandle | ConstantKind::MethodType | ConstantKind::InvokeDynamic => 51,
+            ConstantKind::Module | ConstantKind::Package => 53,
+            ConstantKind::Dynamic => 55,
+            _ => 45,
+        }
+    }
+}
+
+impl Constant {
+    pub fn kind(&self) -> ConstantKind {
+        match self {
+            Constant::Class { .. } => ConstantKind::Class,
+            Constant::Field { .. } => ConstantKind::Field,
+            Constant::Method { .. } => ConstantKind::Method,
+            Constant::InterfaceMethod { .. } => ConstantKind::InterfaceMethod,
+            Constant::String { .. } => ConstantKind::String,
+            Constant::Integer(_) => ConstantKind::Integer,
+            Constant::Float(_) => ConstantKind::Float,
+            Constant::Long(_) => ConstantKind::Long,
+            Constant::Double(_) => ConstantKind::Double,
+            Constant::NameAndType { .. } => ConstantKind::NameAndType,
+            Constant::UTF8String(_) | Constant::UTF8Bytes { .. } => ConstantKind::UTF8String,
+            Constant::MethodHandle { .. } => ConstantKind::MethodHandle,
+            Constant::MethodType { .. } => ConstantKind::MethodType,
+            Constant::InvokeDynamic { .. } => ConstantKind::InvokeDynamic,
+            Constant::Dynamic { .. } => ConstantKind::Dynamic,
+            Constant::Module { .. } => ConstantKind::Module,
+            Constant::Package { .. } => ConstantKind::Package,
+            Constant::Unusable => ConstantKind::Unusable,
+        }
+    }
+
+    /// Whether this constant can be pushed on the stack by `ldc`, and so be a bootstrap method argument
+    pub fn is_loadable(&self) -> bool {
+        matches!(
+            self,
+            Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_) |
+            Constant::Class { .. } | Constant::String { .. } | Constant::MethodHandle { .. } |
+            Constant::MethodType { .. } | Constant::Dynamic { .. }
+        )
+    }
+
+    /// Whether this constant takes two slots in the constant pool
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Constant::Long(_) | Constant::Double(_))
+    }
+}
+
+/// Highest number of slots a constant pool can have, since `constant_pool_count` is a u16 holding the slots plus one
+pub const MAX_SLOTS: usize = u16::MAX as usize - 1;
+
+/// The constant pool of a class, indexed like the specification does
+///
+/// Valid indices go from 1 to [`ConstantPool::len`], index 0 is never valid and the slot after every
+/// [`Constant::Long`] and [`Constant::Double`] holds [`Constant::Unusable`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConstantPool {
+    /// `constants[i]` is the constant at index `i + 1`
+    constants: Vec<Constant>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConstantPoolError {
+    #[error("constant pool is full, it can't hold more than {MAX_SLOTS} slots")]
+    Full,
+    #[error("constant pool index {0} is out of range")]
+    IndexOutOfRange(u16),
+    #[error("constant pool index {0} is the unusable slot after a long or double")]
+    UnusableIndex(u16),
+    #[error("unusable slots can't be added on their own, they come with the long or double before them")]
+    UnusableConstant,
+    #[error("constant pool index {0} can't be replaced by a constant taking a different number of slots")]
+    SlotsMismatch(u16),
+    #[error("expected constant pool index {index} to be a {expected}, found {actual}")]
+    UnexpectedKind {
+        index: u16,
+        expected: ConstantKind,
+        actual: ConstantKind,
+    },
 }
 
-#[derive(Error, Debug)]
+impl ConstantPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of slots in the pool, the class file stores this plus one as `constant_pool_count`
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// Appends a constant, followed by an unusable slot if it's a long or a double, and returns its index
+    ///
+    /// Fails if that would take more than [`MAX_SLOTS`] slots or `constant` is [`Constant::Unusable`], then the pool is
+    /// left as it was.
+    pub fn push(&mut self, constant: Constant) -> Result<u16, ConstantPoolError> {
+        if matches!(constant, Constant::Unusable) {
+            return Err(ConstantPoolError::UnusableConstant);
+        }
+        let wide = constant.is_wide();
+        if self.constants.len() + if wide { 2 } else { 1 } > MAX_SLOTS {
+            return Err(ConstantPoolError::Full);
+        }
+        self.constants.push(constant);
+        let index = self.constants.len() as u16;
+        if wide {
+            self.constants.push(Constant::Unusable);
+        }
+        Ok(index)
+    }
+
+    /// Replaces the constant at `index`, which must take as many slots as the one it replaces so other indices stay valid
+    pub fn set(&mut self, index: u16, constant: Constant) -> Result<(), ConstantPoolError> {
+        if matches!(constant, Constant::Unusable) {
+            return Err(ConstantPoolError::UnusableConstant);
+        }
+        if self.get(index)?.is_wide() != constant.is_wide() {
+            return Err(ConstantPoolError::SlotsMismatch(index));
+        }
+        self.constants[index as usize - 1] = constant;
+        Ok(())
+    }
+
+    pub fn get(&self, index: u16) -> Result<&Constant, ConstantPoolError> {
+        if index == 0 {
+            return Err(ConstantPoolError::IndexOutOfRange(index));
+        }
+        match self.constants.get(index as usize - 1) {
+            Some(Constant::Unusable) => Err(ConstantPoolError::UnusableIndex(index)),
+            Some(constant) => Ok(constant),
+            None => Err(ConstantPoolError::IndexOutOfRange(index)),
+        }
+    }
+
+    fn unexpected(&self, index: u16, expected: ConstantKind, actual: &Constant) -> ConstantPoolError {
+        ConstantPoolError::UnexpectedKind { index, expected, actual: actual.kind() }
+    }
+
+    pub fn get_utf8(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        match self.get(index)? {
+            Constant::UTF8String(string) | Constant::UTF8Bytes { string, .. } => Ok(string),
+            other => Err(self.unexpected(index, ConstantKind::UTF8String, other)),
+        }
+    }
+
+    /// Name of the [`Constant::Class`] at `index`, in internal form (`java/lang/Object`)
+    pub fn get_class_name(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        match self.get(index)? {
+            Constant::Class { name_index } => self.get_utf8(*name_index),
+            other => Err(self.unexpected(index, ConstantKind::Class, other)),
+        }
+    }
+
+    /// Name and descriptor of the [`Constant::NameAndType`] at `index`
+    pub fn get_name_and_type(&self, index: u16) -> Result<(&str, &str), ConstantPoolError> {
+        match self.get(index)? {
+            Constant::NameAndType { name_index, descriptor_index } => {
+                Ok((self.get_utf8(*name_index)?, self.get_utf8(*descriptor_index)?))
+            }
+            other => Err(self.unexpected(index, ConstantKind::NameAndType, other)),
+        }
+    }
+
+    /// Iterates over every usable constant along with its index
+    pub fn iter(&self) -> impl Iterator<Item=(u16, &Constant)> {
+        self.constants.iter()
+            .enumerate()
+            .filter(|(_, constant)| !matches!(constant, Constant::Unusable))
+            .map(|(i, constant)| (i as u16 + 1, constant))
+    }
+}
+
+/// Panics if the constants take more than [`MAX_SLOTS`] slots or one of them is [`Constant::Unusable`]
+impl FromIterator<Constant> for ConstantPool {
+    fn from_iter<T: IntoIterator<Item=Constant>>(iter: T) -> Self {
+        let mut pool = Self::new();
+        for constant in iter {
+            if let Err(error) = pool.push(constant) {
+                panic!("{}", error);
+            }
+        }
+        pool
+    }
+}
+
+/// A reference from one constant to another that doesn't point at the kind of constant it should
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidReference {
+    /// Index of the constant holding the reference
+    pub index: u16,
+    /// The index being referenced
+    pub target: u16,
+    /// What kind of constant `target` should be
+    pub expected: ConstantKind,
+    /// What kind of constant `target` actually is, `None` if it's out of range
+    pub actual: Option<ConstantKind>,
+}
+
+impl fmt::Display for InvalidReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "constant #{} references #{}, expected a {} but ", self.index, self.target, self.expected)?;
+        match self.actual {
+            Some(actual) => write!(f, "found a {actual}"),
+            None => f.write_str("it's out of range"),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum ConstantPoolValidationError {
-    #[error("expected name_index of class to point to a string")]
-    ClassWithInvalidNameIndex,
-    #[error("method has invalid class index")]
-    MethodWithInvalidClassIndex,
-    #[error("method has invalid name and type index")]
-    MethodWithInvalidNameAndTypeIndex,
-    #[error("field has invalid class index")]
-    FieldWithInvalidClassIndex,
-    #[error("field has invalid name and type index")]
-    FieldWithInvalidNameAndTypeIndex,
-    #[error("interface method has invalid class index")]
-    InterfaceMethodWithInvalidClassIndex,
-    #[error("interface method has invalid name and type index")]
-    InterfaceMethodWithInvalidNameAndTypeIndex,
-    #[error("string object has invalid utf8 string index")]
-    StringWithInvalidUTF8Index,
-    #[error("name and type has invalid name index")]
-    NameAndTypeWithInvalidNameIndex,
-    #[error("name and type has invalid descriptor index")]
-    NameAndTypeWithInvalidDescriptorIndex,
-    #[error("invoke dynamic has invalid name and type index")]
-    InvokeDynamicWithInvalidNameAndType,
-    #[error("invoke dynamic has invalid bootstrap method index")]
-    InvokeDynamicWithInvalidBootstrapMethodIndex,
-    #[error("method type has invalid descriptor index")]
-    MethodTypeWithInvalidDescriptorIndex,
-    #[error("invalid method handle")]
-    InvalidMethodHandle,
+    #[error("expected name_index of class to point to a string: {0}")]
+    ClassWithInvalidNameIndex(InvalidReference),
+    #[error("method has invalid class index: {0}")]
+    MethodWithInvalidClassIndex(InvalidReference),
+    #[error("method has invalid name and type index: {0}")]
+    MethodWithInvalidNameAndTypeIndex(InvalidReference),
+    #[error("field has invalid class index: {0}")]
+    FieldWithInvalidClassIndex(InvalidReference),
+    #[error("field has invalid name and type index: {0}")]
+    FieldWithInvalidNameAndTypeIndex(InvalidReference),
+    #[error("interface method has invalid class index: {0}")]
+    InterfaceMethodWithInvalidClassIndex(InvalidReference),
+    #[error("interface method has invalid name and type index: {0}")]
+    InterfaceMethodWithInvalidNameAndTypeIndex(InvalidReference),
+    #[error("string object has invalid utf8 string index: {0}")]
+    StringWithInvalidUTF8Index(InvalidReference),
+    #[error("name and type has invalid name index: {0}")]
+    NameAndTypeWithInvalidNameIndex(InvalidReference),
+    #[error("name and type has invalid descriptor index: {0}")]
+    NameAndTypeWithInvalidDescriptorIndex(InvalidReference),
+    #[error("invoke dynamic has invalid name and type index: {0}")]
+    InvokeDynamicWithInvalidNameAndType(InvalidReference),
+    #[error("invoke dynamic #{index} has invalid bootstrap method index {bootstrap_method_attr_index}")]
+    InvokeDynamicWithInvalidBootstrapMethodIndex {
+        index: u16,
+        bootstrap_method_attr_index: u16,
+    },
+    #[error("dynamic constant #{index} has invalid bootstrap method index {bootstrap_method_attr_index}")]
+    DynamicWithInvalidBootstrapMethodIndex {
+        index: u16,
+        bootstrap_method_attr_index: u16,
+    },
+    #[error("bootstrap method {bootstrap_method} references #{target}, expected a method handle but found {}", display_kind(actual))]
+    BootstrapMethodWithInvalidMethodHandle {
+        bootstrap_method: u16,
+        target: u16,
+        /// `None` if `target` is out of range
+        actual: Option<ConstantKind>,
+    },
+    #[error("bootstrap method {bootstrap_method} has argument #{target}, expected a loadable constant but found {}", display_kind(actual))]
+    BootstrapMethodWithInvalidArgument {
+        bootstrap_method: u16,
+        target: u16,
+        /// `None` if `target` is out of range
+        actual: Option<ConstantKind>,
+    },
+    #[error("dynamic constant has invalid name and type index: {0}")]
+    DynamicWithInvalidNameAndType(InvalidReference),
+    #[error("module has invalid name index: {0}")]
+    ModuleWithInvalidNameIndex(InvalidReference),
+    #[error("package has invalid name index: {0}")]
+    PackageWithInvalidNameIndex(InvalidReference),
+    #[error("method type has invalid descriptor index: {0}")]
+    MethodTypeWithInvalidDescriptorIndex(InvalidReference),
+    #[error("invalid method handle: {0}")]
+    InvalidMethodHandle(InvalidReference),
+    #[error("method handle #{index} of kind {reference_kind:?} can't reference a method named {name}")]
+    MethodHandleWithInvalidMethodName {
+        index: u16,
+        reference_kind: MethodReferenceKind,
+        name: String,
+    },
+    #[error("the last constant is a long or double, which doesn't fit in the constant pool")]
+    WideConstantInLastSlot,
+    #[error("constant #{index} is a {kind}, which needs version {} but the class has version {major}", kind.since())]
+    ConstantTooNew {
+        index: u16,
+        kind: ConstantKind,
+        major: u16,
+    },
+    #[error("constant #{index} is a {kind}, which is only allowed in module-info")]
+    ConstantOutsideModule {
+        index: u16,
+        kind: ConstantKind,
+    },
 }
 
-fn validate_constant(constant: &Constant, pool: &[Constant]) -> Result<(), ConstantPoolValidationError> {
+fn display_kind(kind: &Option<ConstantKind>) -> String {
+    match kind {
+        Some(kind) => format!("a {kind}"),
+        None => "nothing, it's out of range".to_string(),
+    }
+}
+
+/// Kind of the constant in the slot at `index`, including unusable ones
+fn kind_at(pool: &ConstantPool, index: u16) -> Option<ConstantKind> {
+    pool.constants.get((index as usize).wrapping_sub(1)).map(Constant::kind)
+}
+
+/// Checks that `target` points at a constant of the `expected` kind
+fn check_reference(pool: &ConstantPool, index: u16, target: u16, expected: ConstantKind) -> Result<&Constant, InvalidReference> {
+    let constant = pool.get(target).ok();
+    match constant {
+        Some(constant) if constant.kind() == expected => Ok(constant),
+        _ => Err(InvalidReference {
+            index,
+            target,
+            expected,
+            // The unusable slot after a long or double is reported as such, not as out of range
+            actual: kind_at(pool, target),
+        }),
+    }
+}
+
+fn validate_constant(index: u16, constant: &Constant, pool: &ConstantPool) -> Result<(), ConstantPoolValidationError> {
+    use ConstantKind as Kind;
+    use ConstantPoolValidationError as E;
     match constant {
         Constant::Class { name_index } => {
-            let name_constant = &pool[*name_index as usize];
-            if matches!(name_constant, Constant::UTF8String(_)) {
-                validate_constant(name_constant, pool)?;
-                Ok(())
-            } else {
-                Err(ConstantPoolValidationError::ClassWithInvalidNameIndex)
-            }
+            check_reference(pool, index, *name_index, Kind::UTF8String).map_err(E::ClassWithInvalidNameIndex)?;
         }
         Constant::Method { class_index, name_and_type_index } => {
-            let class_constant = &pool[*class_index as usize];
-            if matches!(class_constant, Constant::Class {..}) {
-                validate_constant(class_constant, pool)?;
-                let nat_constant = &pool[*name_and_type_index as usize];
-                if matches!(nat_constant, Constant::NameAndType {..}) {
-                    validate_constant(nat_constant, pool)?;
-                    Ok(())
-                } else {
-                    Err(ConstantPoolValidationError::MethodWithInvalidNameAndTypeIndex)
-                }
-            } else {
-                Err(ConstantPoolValidationError::MethodWithInvalidClassIndex)
-            }
+            check_reference(pool, index, *class_index, Kind::Class).map_err(E::MethodWithInvalidClassIndex)?;
+            check_reference(pool, index, *name_and_type_index, Kind::NameAndType).map_err(E::MethodWithInvalidNameAndTypeIndex)?;
         }
         Constant::Field { class_index, name_and_type_index } => {
-            let class_constant = &pool[*class_index as usize];
-            if matches!(class_constant, Constant::Class {..}) {
-                validate_constant(class_constant, pool)?;
-                let nat_constant = &pool[*name_and_type_index as usize];
-                if matches!(nat_constant, Constant::NameAndType {..}) {
-                    validate_constant(nat_constant, pool)?;
-                    Ok(())
-                } else {
-                    Err(ConstantPoolValidationError::FieldWithInvalidNameAndTypeIndex)
-                }
-            } else {
-                Err(ConstantPoolValidationError::FieldWithInvalidClassIndex)
-            }
+            check_reference(pool, index, *class_index, Kind::Class).map_err(E::FieldWithInvalidClassIndex)?;
+            check_reference(pool, index, *name_and_type_index, Kind::NameAndType).map_err(E::FieldWithInvalidNameAndTypeIndex)?;
         }
         Constant::InterfaceMethod { class_index, name_and_type_index } => {
-            let class_constant = &pool[*class_index as usize];
-            if matches!(class_constant, Constant::Class {..}) {
-                validate_constant(class_constant, pool)?;
-                let nat_constant = &pool[*name_and_type_index as usize];
-                if matches!(nat_constant, Constant::NameAndType {..}) {
-                    validate_constant(nat_constant, pool)?;
-                    Ok(())
-                } else {
-                    Err(ConstantPoolValidationError::InterfaceMethodWithInvalidNameAndTypeIndex)
-                }
-            } else {
-                Err(ConstantPoolValidationError::InterfaceMethodWithInvalidClassIndex)
-            }
+            check_reference(pool, index, *class_index, Kind::Class).map_err(E::InterfaceMethodWithInvalidClassIndex)?;
+            check_reference(pool, index, *name_and_type_index, Kind::NameAndType).map_err(E::InterfaceMethodWithInvalidNameAndTypeIndex)?;
         }
         // Just assume they're good, nothing to check here
-        Constant::Integer(_) | Constant::Long(_) | Constant::Float(_) | Constant::Double(_) => Ok(()),
-        Constant::UTF8String(_) => Ok(()),
+        Constant::Integer(_) | Constant::Long(_) | Constant::Float(_) | Constant::Double(_) => {}
+        Constant::UTF8String(_) | Constant::UTF8Bytes { .. } | Constant::Unusable => {}
         Constant::String { string_index } => {
-            let string_constant = &pool[*string_index as usize];
-            if matches!(string_constant, Constant::UTF8String(_)) {
-                validate_constant(string_constant, pool)?;
-                Ok(())
-            } else {
-                Err(ConstantPoolValidationError::StringWithInvalidUTF8Index)
-            }
+            check_reference(pool, index, *string_index, Kind::UTF8String).map_err(E::StringWithInvalidUTF8Index)?;
         }
         Constant::NameAndType { name_index, descriptor_index } => {
-            let name_constant = &pool[*name_index as usize];
-            if matches!(name_constant, Constant::UTF8String(_)) {
-                validate_constant(name_constant, pool)?;
-                let descriptor_constant = &pool[*descriptor_index as usize];
-                if matches!(descriptor_constant, Constant::UTF8String(_)) {
-                    validate_constant(descriptor_constant, pool)?;
-                    Ok(())
-                } else {
-                    Err(ConstantPoolValidationError::NameAndTypeWithInvalidDescriptorIndex)
-                }
-            } else {
-                Err(ConstantPoolValidationError::NameAndTypeWithInvalidNameIndex)
-            }
+            check_reference(pool, index, *name_index, Kind::UTF8String).map_err(E::NameAndTypeWithInvalidNameIndex)?;
+            check_reference(pool, index, *descriptor_index, Kind::UTF8String).map_err(E::NameAndTypeWithInvalidDescriptorIndex)?;
         }
         Constant::InvokeDynamic { name_and_type_index, .. } => {
-            let nat_constant = &pool[*name_and_type_index as usize];
-            if matches!(nat_constant, Constant::NameAndType{..}) {
-                validate_constant(nat_constant, pool)?;
-                eprintln!("FIXME!: Implement bootstrap method attr index check");
-                Ok(())
-            } else {
-                Err(ConstantPoolValidationError::InvokeDynamicWithInvalidNameAndType)
-            }
+            check_reference(pool, index, *name_and_type_index, Kind::NameAndType).map_err(E::InvokeDynamicWithInvalidNameAndType)?;
+        }
+        Constant::Dynamic { name_and_type_index, .. } => {
+            check_reference(pool, index, *name_and_type_index, Kind::NameAndType).map_err(E::DynamicWithInvalidNameAndType)?;
+        }
+        Constant::Module { name_index } => {
+            check_reference(pool, index, *name_index, Kind::UTF8String).map_err(E::ModuleWithInvalidNameIndex)?;
+        }
+        Constant::Package { name_index } => {
+            check_reference(pool, index, *name_index, Kind::UTF8String).map_err(E::PackageWithInvalidNameIndex)?;
         }
         Constant::MethodType { descriptor_index } => {
-            let descriptor_constant = &pool[*descriptor_index as usize];
-            if matches!(descriptor_constant, Constant::UTF8String(_)) {
-                validate_constant(descriptor_constant, pool)?;
-                Ok(())
-            } else {
-                Err(ConstantPoolValidationError::MethodTypeWithInvalidDescriptorIndex)
-            }
+            check_reference(pool, index, *descriptor_index, Kind::UTF8String).map_err(E::MethodTypeWithInvalidDescriptorIndex)?;
         }
         Constant::MethodHandle { reference_index, reference_kind } => {
             use MethodReferenceKind::*;
-            match reference_kind {
-                GetField | GetStatic | PutField | PutStatic => {
-                    let field_constant = &pool[*reference_index as usize];
-                    if matches!(field_constant, Constant::Field{..}) {
-                        validate_constant(field_constant, pool)?;
-                        Ok(())
-                    } else {
-                        Err(ConstantPoolValidationError::InvalidMethodHandle)
-                    }
-                }
-                InvokeSpecial | InvokeVirtual | InvokeStatic => {
-                    let method_constant = &pool[*reference_index as usize];
-                    if matches!(method_constant, Constant::Method{..}) {
-                        validate_constant(method_constant, pool)?;
-                        Ok(())
-                    } else {
-                        Err(ConstantPoolValidationError::InvalidMethodHandle)
-                    }
-                }
-                InvokeInterface => {
-                    let interface_method_constant = &pool[*reference_index as usize];
-                    if matches!(interface_method_constant, Constant::InterfaceMethod{..}) {
-                        validate_constant(interface_method_constant, pool)?;
-                        Ok(())
-                    } else {
-                        Err(ConstantPoolValidationError::InvalidMethodHandle)
-                    }
-                }
-                NewInvokeSpecial => {
-                    let diamond_init_method_constant = &pool[*reference_index as usize];
-                    if matches!(diamond_init_method_constant, Constant::Method{..}) {
-                        validate_constant(diamond_init_method_constant, pool)?;
-                        let name_and_type = match diamond_init_method_constant {
-                            Constant::Method { name_and_type_index, .. } => &pool[*name_and_type_index as usize],
-                            _ => unreachable!()
-                        };
-                        let name = match name_and_type {
-                            Constant::NameAndType { name_index, .. } => &pool[*name_index as usize],
-                            _ => unreachable!()
-                        };
-                        let name = match name {
-                            Constant::UTF8String(name) => name,
-                            _ => unreachable!()
-                        };
-                        if &**name == "<init>" {
-                            Ok(())
-                        } else {
-                            Err(ConstantPoolValidationError::InvalidMethodHandle)
-                        }
-                    } else {
-                        Err(ConstantPoolValidationError::InvalidMethodHandle)
+            let reference = match reference_kind {
+                GetField | GetStatic | PutField | PutStatic => check_reference(pool, index, *reference_index, Kind::Field),
+                InvokeVirtual | NewInvokeSpecial => check_reference(pool, index, *reference_index, Kind::Method),
+                // Since Java 8 static and special method handles can point at interface methods too
+                InvokeStatic | InvokeSpecial => check_reference(pool, index, *reference_index, Kind::Method)
+                    .or_else(|e| check_reference(pool, index, *reference_index, Kind::InterfaceMethod).map_err(|_| e)),
+                InvokeInterface => check_reference(pool, index, *reference_index, Kind::InterfaceMethod),
+            }.map_err(E::InvalidMethodHandle)?;
+            if let Constant::Method { name_and_type_index, .. } | Constant::InterfaceMethod { name_and_type_index, .. } = reference {
+                // A broken name and type is reported when the method itself is validated
+                if let Ok((name, _)) = pool.get_name_and_type(*name_and_type_index) {
+                    let valid = match reference_kind {
+                        NewInvokeSpecial => name == "<init>",
+                        _ => name != "<init>" && name != "<clinit>",
+                    };
+                    if !valid {
+                        return Err(E::MethodHandleWithInvalidMethodName {
+                            index,
+                            reference_kind: *reference_kind,
+                            name: name.to_string(),
+                        });
                     }
                 }
             }
         }
     }
+    Ok(())
 }
 
-pub fn validate_constant_pool(constant_pool: &[Constant]) -> Result<(), ParseClassError> {
-    for constant in constant_pool {
-        validate_constant(constant, constant_pool).map_err(ParseClassError::ConstantPoolValidationError)?;
+pub fn validate_constant_pool(constant_pool: &ConstantPool) -> Result<(), ParseClassError> {
+    for (index, constant) in constant_pool.iter() {
+        validate_constant(index, constant, constant_pool).map_err(ParseClassError::ConstantPoolValidationError)?;
     }
     Ok(())
 }
 
-pub fn parse_constant(f: &mut File) -> Result<Constant, ParseClassError> {
+/// Checks that every constant is allowed in a class of version `major`, and module constants only in `module-info`
+pub fn validate_constant_versions(constant_pool: &ConstantPool, major: u16, is_module: bool) -> Result<(), ConstantPoolValidationError> {
+    for (index, constant) in constant_pool.iter() {
+        let kind = constant.kind();
+        if major < kind.since() {
+            return Err(ConstantPoolValidationError::ConstantTooNew { index, kind, major });
+        }
+        if matches!(kind, ConstantKind::Module | ConstantKind::Package) && !is_module {
+            return Err(ConstantPoolValidationError::ConstantOutsideModule { index, kind });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the constant pool against the class' `BootstrapMethods` attribute
+///
+/// This has to be a second pass since the attributes come after the constant pool in the class file.
+pub fn validate_bootstrap_methods(constant_pool: &ConstantPool, bootstrap_methods: &[BootstrapMethod]) -> Result<(), ConstantPoolValidationError> {
+    use ConstantPoolValidationError as E;
+    for (index, constant) in constant_pool.iter() {
+        match constant {
+            Constant::InvokeDynamic { bootstrap_method_attr_index, .. } if *bootstrap_method_attr_index as usize >= bootstrap_methods.len() => {
+                return Err(E::InvokeDynamicWithInvalidBootstrapMethodIndex {
+                    index,
+                    bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                });
+            }
+            Constant::Dynamic { bootstrap_method_attr_index, .. } if *bootstrap_method_attr_index as usize >= bootstrap_methods.len() => {
+                return Err(E::DynamicWithInvalidBootstrapMethodIndex {
+                    index,
+                    bootstrap_method_attr_index: *bootstrap_method_attr_index,
+                });
+            }
+            _ => {}
+        }
+    }
+    for (i, method) in bootstrap_methods.iter().enumerate() {
+        let bootstrap_method = i as u16;
+        if !matches!(constant_pool.get(method.bootstrap_method_ref), Ok(Constant::MethodHandle { .. })) {
+            return Err(E::BootstrapMethodWithInvalidMethodHandle {
+                bootstrap_method,
+                target: method.bootstrap_method_ref,
+                actual: kind_at(constant_pool, method.bootstrap_method_ref),
+            });
+        }
+        for &target in &method.bootstrap_arguments {
+            if !matches!(constant_pool.get(target), Ok(constant) if constant.is_loadable()) {
+                return Err(E::BootstrapMethodWithInvalidArgument {
+                    bootstrap_method,
+                    target,
+                    actual: kind_at(constant_pool, target),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses `constant_pool_count` followed by the constants themselves
+pub fn parse_constant_pool<R: ClassReader>(f: &mut R) -> Result<ConstantPool, ParseClassError> {
+    let slots = class::read_u16(f)?.saturating_sub(1) as usize;
+    let mut pool = ConstantPool::new();
+    let wide_in_last_slot = || ParseClassError::ConstantPoolValidationError(ConstantPoolValidationError::WideConstantInLastSlot);
+    while pool.len() < slots {
+        // `slots` fits in the pool, so it's only ever full after a long or double in the last slot
+        pool.push(parse_constant(f)?).map_err(|_| wide_in_last_slot())?;
+    }
+    if pool.len() > slots {
+        return Err(wide_in_last_slot());
+    }
+    Ok(pool)
+}
+
+pub fn parse_constant<R: ClassReader>(f: &mut R) -> Result<Constant, ParseClassError> {
+    let offset = f.offset();
     let tag = class::read_u8(f)?;
     match tag {
         // UTF8
         1 => {
             let len = class::read_u16(f)?;
-            let bytes = class::read_n_dyn(f, len as usize)?;
-            let string = String::from_utf8(bytes).map_err(ParseClassError::InvalidUTF8Constant)?;
-            Ok(Constant::UTF8String(string))
+            let start = f.offset();
+            let bytes = f.read_bytes(len as usize)?;
+            let invalid = |error: ModifiedUtf8Error| ParseClassError::InvalidUTF8Constant {
+                offset: start + error.index(),
+                error,
+            };
+            match mutf8::decode(&bytes) {
+                Ok(string) => Ok(Constant::UTF8String(string)),
+                Err(ModifiedUtf8Error::UnpairedSurrogate(_)) => {
+                    let units = mutf8::decode_utf16(&bytes).map_err(invalid)?;
+                    Ok(Constant::UTF8Bytes { string: String::from_utf16_lossy(&units), bytes: bytes.into_owned() })
+                }
+                Err(error) => Err(invalid(error)),
+            }
         }
         // Method handle
         15 => {
@@ -305,7 +678,7 @@ pub fn parse_constant(f: &mut File) -> Result<Constant, ParseClassError> {
                 Some(rk) => Ok(rk),
                 None => Err(ParseClassError::InvalidMethodHandleReferenceKind)
             }?;
-            let reference_index = class::read_u16(f)? - 1;
+            let reference_index = class::read_u16(f)?;
             Ok(Constant::MethodHandle {
                 reference_kind,
                 reference_index,
@@ -314,22 +687,43 @@ pub fn parse_constant(f: &mut File) -> Result<Constant, ParseClassError> {
         // Method Type
         16 => {
             Ok(Constant::MethodType {
-                descriptor_index: class::read_u16(f)? - 1
+                descriptor_index: class::read_u16(f)?
             })
         }
         // Invoke Dynamic
         18 => {
-            let bootstrap_method_attr_index = class::read_u16(f)? - 1;
-            let name_and_type_index = class::read_u16(f)? - 1;
+            let bootstrap_method_attr_index = class::read_u16(f)?;
+            let name_and_type_index = class::read_u16(f)?;
             Ok(Constant::InvokeDynamic {
                 bootstrap_method_attr_index,
                 name_and_type_index,
             })
         }
+        // Dynamic
+        17 => {
+            let bootstrap_method_attr_index = class::read_u16(f)?;
+            let name_and_type_index = class::read_u16(f)?;
+            Ok(Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            })
+        }
+        // Module
+        19 => {
+            Ok(Constant::Module {
+                name_index: class::read_u16(f)?
+            })
+        }
+        // Package
+        20 => {
+            Ok(Constant::Package {
+                name_index: class::read_u16(f)?
+            })
+        }
         // Name And Type
         12 => {
-            let name_index = class::read_u16(f)? - 1;
-            let descriptor_index = class::read_u16(f)? - 1;
+            let name_index = class::read_u16(f)?;
+            let descriptor_index = class::read_u16(f)?;
             Ok(Constant::NameAndType {
                 descriptor_index,
                 name_index,
@@ -358,33 +752,156 @@ pub fn parse_constant(f: &mut File) -> Result<Constant, ParseClassError> {
         // Class
         7 => {
             Ok(Constant::Class {
-                name_index: class::read_u16(f)? - 1
+                name_index: class::read_u16(f)?
             })
         }
         // String
         8 => {
             Ok(Constant::String {
-                string_index: class::read_u16(f)? - 1
+                string_index: class::read_u16(f)?
             })
         }
         // Field
         9 => {
-            let class_index = class::read_u16(f)? - 1;
-            let name_and_type_index = class::read_u16(f)? - 1;
+            let class_index = class::read_u16(f)?;
+            let name_and_type_index = class::read_u16(f)?;
             Ok(Constant::Field { class_index, name_and_type_index })
         }
         // Method
         10 => {
-            let class_index = class::read_u16(f)? - 1;
-            let name_and_type_index = class::read_u16(f)? - 1;
+            let class_index = class::read_u16(f)?;
+            let name_and_type_index = class::read_u16(f)?;
             Ok(Constant::Method { class_index, name_and_type_index })
         }
         // Interface Method
         11 => {
-            let class_index = class::read_u16(f)? - 1;
-            let name_and_type_index = class::read_u16(f)? - 1;
+            let class_index = class::read_u16(f)?;
+            let name_and_type_index = class::read_u16(f)?;
             Ok(Constant::InterfaceMethod { class_index, name_and_type_index })
         }
-        _ => todo!()
+        _ => Err(ParseClassError::UnknownConstantTag { tag, offset })
+    }
+}
+
+/// Writes `constant_pool_count` followed by the constants themselves
+pub(crate) fn write_constant_pool(out: &mut Vec<u8>, constant_pool: &ConstantPool) -> Result<(), WriteClassError> {
+    out.write_len("constant pool", constant_pool.len() + 1)?;
+    let mut after_wide = false;
+    for (i, constant) in constant_pool.constants.iter().enumerate() {
+        match constant {
+            // Never written, the slot is implied by the long or double before it
+            Constant::Unusable if after_wide => {}
+            Constant::Unusable => return Err(WriteClassError::StrayUnusableSlot(i as u16 + 1)),
+            _ => write_constant(out, constant)?,
+        }
+        after_wide = constant.is_wide();
+    }
+    Ok(())
+}
+
+pub(crate) fn write_constant(out: &mut Vec<u8>, constant: &Constant) -> Result<(), WriteClassError> {
+    match constant {
+        Constant::UTF8String(string) => {
+            out.write_u8(1);
+            let bytes = mutf8::encode(string);
+            let len = u16::try_from(bytes.len()).map_err(|_| WriteClassError::TooLong { what: "utf8 constant", len: bytes.len() })?;
+            out.write_u16(len);
+            out.write_bytes(&bytes);
+        }
+        Constant::UTF8Bytes { bytes, .. } => {
+            out.write_u8(1);
+            let len = u16::try_from(bytes.len()).map_err(|_| WriteClassError::TooLong { what: "utf8 constant", len: bytes.len() })?;
+            out.write_u16(len);
+            out.write_bytes(bytes);
+        }
+        Constant::Integer(value) => {
+            out.write_u8(3);
+            out.write_u32(*value as u32);
+        }
+        Constant::Float(value) => {
+            out.write_u8(4);
+            out.write_u32(value.to_bits());
+        }
+        Constant::Long(value) => {
+            out.write_u8(5);
+            out.write_bytes(&value.to_be_bytes());
+        }
+        Constant::Double(value) => {
+            out.write_u8(6);
+            out.write_bytes(&value.to_bits().to_be_bytes());
+        }
+        Constant::Class { name_index } => {
+            out.write_u8(7);
+            out.write_u16(*name_index);
+        }
+        Constant::String { string_index } => {
+            out.write_u8(8);
+            out.write_u16(*string_index);
+        }
+        Constant::Field { class_index, name_and_type_index } => {
+            out.write_u8(9);
+            out.write_u16(*class_index);
+            out.write_u16(*name_and_type_index);
+        }
+        Constant::Method { class_index, name_and_type_index } => {
+            out.write_u8(10);
+            out.write_u16(*class_index);
+            out.write_u16(*name_and_type_index);
+        }
+        Constant::InterfaceMethod { class_index, name_and_type_index } => {
+            out.write_u8(11);
+            out.write_u16(*class_index);
+            out.write_u16(*name_and_type_index);
+        }
+        Constant::NameAndType { name_index, descriptor_index } => {
+            out.write_u8(12);
+            out.write_u16(*name_index);
+            out.write_u16(*descriptor_index);
+        }
+        Constant::MethodHandle { reference_kind, reference_index } => {
+            out.write_u8(15);
+            out.write_u8(*reference_kind as u8);
+            out.write_u16(*reference_index);
+        }
+        Constant::MethodType { descriptor_index } => {
+            out.write_u8(16);
+            out.write_u16(*descriptor_index);
+        }
+        Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            out.write_u8(17);
+            out.write_u16(*bootstrap_method_attr_index);
+            out.write_u16(*name_and_type_index);
+        }
+        Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            out.write_u8(18);
+            out.write_u16(*bootstrap_method_attr_index);
+            out.write_u16(*name_and_type_index);
+        }
+        Constant::Module { name_index } => {
+            out.write_u8(19);
+            out.write_u16(*name_index);
+        }
+        Constant::Package { name_index } => {
+            out.write_u8(20);
+            out.write_u16(*name_index);
+        }
+        Constant::Unusable => unreachable!("unusable slots are skipped by write_constant_pool"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_constant_pool, Constant, ConstantPool};
+    use crate::writer::WriteClassError;
+
+    #[test]
+    pub fn stray_unusable_slots_are_not_written() {
+        let pool = ConstantPool { constants: vec![Constant::Long(1), Constant::Unusable, Constant::Unusable] };
+        assert!(matches!(write_constant_pool(&mut vec![], &pool), Err(WriteClassError::StrayUnusableSlot(3))));
+        let pool = ConstantPool { constants: vec![Constant::Long(1), Constant::Unusable] };
+        let mut out = vec![];
+        write_constant_pool(&mut out, &pool).unwrap();
+        assert_eq!(out, vec![0, 3, 5, 0, 0, 0, 0, 0, 0, 0, 1]);
     }
 }
@@ -0,0 +1,262 @@
+//! Building constant pools for generated or rewritten classes
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+pub use crate::constant_pool::MAX_SLOTS;
+use crate::constant_pool::{Constant, ConstantKind, ConstantPool, MethodReferenceKind};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConstantPoolBuilderError {
+    #[error("constant pool is full, it can't hold more than {MAX_SLOTS} slots")]
+    Full,
+    #[error("unusable slots can't be added on their own, they come with the long or double before them")]
+    UnusableSlot,
+}
+
+/// What makes two constants the same, floats are compared bit by bit so `NaN`s can be deduplicated too
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Utf8(String),
+    /// A utf8 constant with unpaired surrogates, only the same bytes make the same constant
+    Utf8Bytes(Vec<u8>),
+    Integer(i32),
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    /// Any other constant, which is just a couple of numbers
+    Other(ConstantKind, u16, u16),
+}
+
+impl Key {
+    fn of(constant: &Constant) -> Option<Key> {
+        Some(match constant {
+            Constant::UTF8String(string) => Key::Utf8(string.clone()),
+            Constant::UTF8Bytes { bytes, .. } => Key::Utf8Bytes(bytes.clone()),
+            Constant::Integer(value) => Key::Integer(*value),
+            Constant::Float(value) => Key::Float(value.to_bits()),
+            Constant::Long(value) => Key::Long(*value),
+            Constant::Double(value) => Key::Double(value.to_bits()),
+            Constant::Class { name_index } |
+            Constant::String { string_index: name_index } |
+            Constant::MethodType { descriptor_index: name_index } |
+            Constant::Module { name_index } |
+            Constant::Package { name_index } => Key::Other(constant.kind(), *name_index, 0),
+            Constant::Field { class_index, name_and_type_index } |
+            Constant::Method { class_index, name_and_type_index } |
+            Constant::InterfaceMethod { class_index, name_and_type_index } => Key::Other(constant.kind(), *class_index, *name_and_type_index),
+            Constant::NameAndType { name_index, descriptor_index } => Key::Other(constant.kind(), *name_index, *descriptor_index),
+            Constant::MethodHandle { reference_kind, reference_index } => Key::Other(constant.kind(), *reference_kind as u16, *reference_index),
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+            Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                Key::Other(constant.kind(), *bootstrap_method_attr_index, *name_and_type_index)
+            }
+            Constant::Unusable => return None,
+        })
+    }
+}
+
+/// Adds constants to a pool, reusing the index of an equal constant if there's one already
+///
+/// Constants are only ever appended, so indices handed out before stay valid.
+#[derive(Debug, Clone, Default)]
+pub struct ConstantPoolBuilder {
+    pool: ConstantPool,
+    indices: HashMap<Key, u16>,
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from an existing pool, keeping every constant at the index it already has
+    ///
+    /// If the pool has duplicates, the first one is reused.
+    pub fn from_pool(pool: ConstantPool) -> Self {
+        let mut indices = HashMap::new();
+        for (index, constant) in pool.iter() {
+            if let Some(key) = Key::of(constant) {
+                indices.entry(key).or_insert(index);
+            }
+        }
+        Self { pool, indices }
+    }
+
+    pub fn pool(&self) -> &ConstantPool {
+        &self.pool
+    }
+
+    pub fn build(self) -> ConstantPool {
+        self.pool
+    }
+
+    /// Index of `constant`, adding it to the pool if it isn't there yet
+    ///
+    /// The indices `constant` holds aren't checked, use the typed methods like [`ConstantPoolBuilder::method_ref`]
+    /// to add the constants it depends on too.
+    pub fn add(&mut self, constant: Constant) -> Result<u16, ConstantPoolBuilderError> {
+        let key = match Key::of(&constant) {
+            Some(key) => key,
+            None => return Err(ConstantPoolBuilderError::UnusableSlot),
+        };
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let index = self.pool.push(constant).map_err(|_| ConstantPoolBuilderError::Full)?;
+        self.indices.insert(key, index);
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, string: &str) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::UTF8String(string.to_string()))
+    }
+
+    pub fn integer(&mut self, value: i32) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::Integer(value))
+    }
+
+    pub fn float(&mut self, value: f32) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::Float(value))
+    }
+
+    /// Adds a long, which takes two slots
+    pub fn long(&mut self, value: i64) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::Long(value))
+    }
+
+    /// Adds a double, which takes two slots
+    pub fn double(&mut self, value: f64) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::Double(value))
+    }
+
+    /// A `java.lang.String` constant, as loaded by `ldc`
+    pub fn string(&mut self, value: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let string_index = self.utf8(value)?;
+        self.add(Constant::String { string_index })
+    }
+
+    /// A class, with its name in internal form like `java/lang/Object`
+    pub fn class(&mut self, name: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_index = self.utf8(name)?;
+        self.add(Constant::Class { name_index })
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_index = self.utf8(name)?;
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(Constant::NameAndType { name_index, descriptor_index })
+    }
+
+    pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(Constant::Field { class_index, name_and_type_index })
+    }
+
+    pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(Constant::Method { class_index, name_and_type_index })
+    }
+
+    pub fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let class_index = self.class(class)?;
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(Constant::InterfaceMethod { class_index, name_and_type_index })
+    }
+
+    /// A method handle, `reference_index` points at the field or method ref it refers to
+    pub fn method_handle(&mut self, reference_kind: MethodReferenceKind, reference_index: u16) -> Result<u16, ConstantPoolBuilderError> {
+        self.add(Constant::MethodHandle { reference_kind, reference_index })
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let descriptor_index = self.utf8(descriptor)?;
+        self.add(Constant::MethodType { descriptor_index })
+    }
+
+    /// The call site of an `invokedynamic`, `bootstrap_method_attr_index` indexes the `BootstrapMethods` attribute
+    pub fn invoke_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index })
+    }
+
+    /// A dynamically-computed constant, `bootstrap_method_attr_index` indexes the `BootstrapMethods` attribute
+    pub fn dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_and_type_index = self.name_and_type(name, descriptor)?;
+        self.add(Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index })
+    }
+
+    pub fn module(&mut self, name: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_index = self.utf8(name)?;
+        self.add(Constant::Module { name_index })
+    }
+
+    /// A package, with its name in internal form like `java/lang`
+    pub fn package(&mut self, name: &str) -> Result<u16, ConstantPoolBuilderError> {
+        let name_index = self.utf8(name)?;
+        self.add(Constant::Package { name_index })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstantPoolBuilder, ConstantPoolBuilderError, MAX_SLOTS};
+    use crate::constant_pool::{Constant, ConstantPool, ConstantPoolError, MethodReferenceKind};
+
+    #[test]
+    pub fn deduplicates_constants() {
+        let mut builder = ConstantPoolBuilder::new();
+        let println = builder.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V").unwrap();
+        assert_eq!(builder.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V"), Ok(println));
+        let class = builder.class("java/io/PrintStream").unwrap();
+        assert_eq!(builder.pool().get(println), Ok(&Constant::Method { class_index: class, name_and_type_index: 5 }));
+        assert_eq!(builder.utf8("println"), Ok(3));
+        assert_eq!(builder.float(f32::NAN), builder.float(f32::NAN));
+        let pool = builder.build();
+        assert_eq!(pool.len(), 7);
+        assert_eq!(pool.get_class_name(class), Ok("java/io/PrintStream"));
+    }
+
+    #[test]
+    pub fn wide_constants_take_two_slots() {
+        let mut builder = ConstantPoolBuilder::new();
+        assert_eq!(builder.long(1), Ok(1));
+        assert_eq!(builder.double(2.0), Ok(3));
+        assert_eq!(builder.integer(3), Ok(5));
+        assert_eq!(builder.long(1), Ok(1));
+        assert_eq!(builder.pool().get(2), Err(ConstantPoolError::UnusableIndex(2)));
+        assert_eq!(builder.add(Constant::Unusable), Err(ConstantPoolBuilderError::UnusableSlot));
+    }
+
+    #[test]
+    pub fn starts_from_existing_pool() {
+        let pool: ConstantPool = [
+            Constant::UTF8String("Foo".to_string()),
+            Constant::Class { name_index: 1 },
+            Constant::Long(5),
+            Constant::UTF8String("Foo".to_string()),
+        ].into_iter().collect();
+        let mut builder = ConstantPoolBuilder::from_pool(pool);
+        assert_eq!(builder.class("Foo"), Ok(2));
+        assert_eq!(builder.utf8("Foo"), Ok(1));
+        assert_eq!(builder.long(5), Ok(3));
+        assert_eq!(builder.string("Foo"), Ok(6));
+        let handle = builder.method_handle(MethodReferenceKind::InvokeStatic, 2).unwrap();
+        assert_eq!(handle, 7);
+    }
+
+    #[test]
+    pub fn fails_when_full() {
+        let mut builder = ConstantPoolBuilder::new();
+        for i in 0..MAX_SLOTS - 1 {
+            builder.integer(i as i32).unwrap();
+        }
+        assert_eq!(builder.long(0), Err(ConstantPoolBuilderError::Full));
+        assert_eq!(builder.integer(-1), Ok(MAX_SLOTS as u16));
+        assert_eq!(builder.integer(-2), Err(ConstantPoolBuilderError::Full));
+        // Existing constants can still be looked up
+        assert_eq!(builder.integer(0), Ok(1));
+    }
+}
@@ -0,0 +1,355 @@
+//! Field and method descriptors, like `I` or `([Ljava/lang/String;)V`
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.3
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+/// The dimensions of an array type can't go over this
+pub const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    pub fn from_descriptor_char(c: char) -> Option<Self> {
+        Some(match c {
+            'B' => BaseType::Byte,
+            'C' => BaseType::Char,
+            'D' => BaseType::Double,
+            'F' => BaseType::Float,
+            'I' => BaseType::Int,
+            'J' => BaseType::Long,
+            'S' => BaseType::Short,
+            'Z' => BaseType::Boolean,
+            _ => return None,
+        })
+    }
+
+    pub fn descriptor_char(&self) -> char {
+        match self {
+            BaseType::Byte => 'B',
+            BaseType::Char => 'C',
+            BaseType::Double => 'D',
+            BaseType::Float => 'F',
+            BaseType::Int => 'I',
+            BaseType::Long => 'J',
+            BaseType::Short => 'S',
+            BaseType::Boolean => 'Z',
+        }
+    }
+
+    /// The keyword used for this type in java source code
+    pub fn java_name(&self) -> &'static str {
+        match self {
+            BaseType::Byte => "byte",
+            BaseType::Char => "char",
+            BaseType::Double => "double",
+            BaseType::Float => "float",
+            BaseType::Int => "int",
+            BaseType::Long => "long",
+            BaseType::Short => "short",
+            BaseType::Boolean => "boolean",
+        }
+    }
+
+    /// Number of local variable or operand stack slots taken by a value of this type
+    pub fn slot_size(&self) -> u16 {
+        match self {
+            BaseType::Long | BaseType::Double => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Type of a field, parameter or return value
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Base(BaseType),
+    /// An instance of a class, with its name in internal form like `java/lang/String`
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser::new(descriptor);
+        let field_type = parser.field_type()?;
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    /// Number of local variable or operand stack slots taken by a value of this type
+    pub fn slot_size(&self) -> u16 {
+        match self {
+            FieldType::Base(base) => base.slot_size(),
+            _ => 1,
+        }
+    }
+
+    /// Number of array dimensions, 0 if this isn't an array
+    pub fn dimensions(&self) -> usize {
+        match self {
+            FieldType::Array(component) => 1 + component.dimensions(),
+            _ => 0,
+        }
+    }
+
+    /// How this type is written in java source code, like `java.lang.String[]`, or `String[]` if not `qualified`
+    pub fn java_name(&self, qualified: bool) -> String {
+        match self {
+            FieldType::Base(base) => base.java_name().to_string(),
+            FieldType::Object(name) => java_class_name(name, qualified),
+            FieldType::Array(component) => format!("{}[]", component.java_name(qualified)),
+        }
+    }
+}
+
+impl Display for FieldType {
+    /// Writes the type back in descriptor form
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Base(base) => write!(f, "{}", base.descriptor_char()),
+            FieldType::Object(name) => write!(f, "L{};", name),
+            FieldType::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub params: Vec<FieldType>,
+    /// `None` if the method returns `void`
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Result<Self, DescriptorError> {
+        let mut parser = Parser::new(descriptor);
+        parser.expect('(')?;
+        let mut params = vec![];
+        while parser.peek() != Some(')') {
+            params.push(parser.field_type()?);
+        }
+        parser.expect(')')?;
+        let return_type = if parser.peek() == Some('V') {
+            parser.next()?;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.end()?;
+        Ok(Self { params, return_type })
+    }
+
+    /// Number of local variable slots taken by the parameters, not counting `this`
+    pub fn param_slots(&self) -> u16 {
+        self.params.iter().map(FieldType::slot_size).sum()
+    }
+
+    /// Number of operand stack slots taken by the return value
+    pub fn return_slots(&self) -> u16 {
+        self.return_type.as_ref().map_or(0, FieldType::slot_size)
+    }
+
+    /// How a method with this descriptor is declared in java source code, like `void main(String[])`
+    pub fn java_signature(&self, name: &str, qualified: bool) -> String {
+        let return_type = match &self.return_type {
+            Some(return_type) => return_type.java_name(qualified),
+            None => "void".to_string(),
+        };
+        let params: Vec<String> = self.params.iter().map(|param| param.java_name(qualified)).collect();
+        format!("{} {}({})", return_type, name, params.join(", "))
+    }
+}
+
+impl Display for MethodDescriptor {
+    /// Writes the method descriptor back in descriptor form
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for param in &self.params {
+            write!(f, "{}", param)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type),
+            None => write!(f, "V"),
+        }
+    }
+}
+
+/// Turns an internal class name like `java/lang/Map$Entry` into `java.lang.Map$Entry`, or `Map$Entry` if not `qualified`
+pub fn java_class_name(internal_name: &str, qualified: bool) -> String {
+    if qualified {
+        internal_name.replace('/', ".")
+    } else {
+        internal_name.rsplit('/').next().unwrap_or(internal_name).to_string()
+    }
+}
+
+/// Errors point at the character where parsing failed, counted in bytes from the start of the descriptor
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DescriptorError {
+    #[error("descriptor ends unexpectedly at {position}")]
+    UnexpectedEnd { position: usize },
+    #[error("unexpected character {found:?} at {position}")]
+    UnexpectedCharacter { position: usize, found: char },
+    #[error("invalid class name at {position}")]
+    InvalidClassName { position: usize },
+    #[error("array at {position} has more than 255 dimensions")]
+    TooManyDimensions { position: usize },
+    #[error("unexpected characters after the end of the descriptor at {position}")]
+    TrailingCharacters { position: usize },
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, position: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, DescriptorError> {
+        let c = self.peek().ok_or(DescriptorError::UnexpectedEnd { position: self.position })?;
+        self.position += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DescriptorError> {
+        let position = self.position;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            found => Err(DescriptorError::UnexpectedCharacter { position, found }),
+        }
+    }
+
+    fn end(&self) -> Result<(), DescriptorError> {
+        if self.position != self.s.len() {
+            return Err(DescriptorError::TrailingCharacters { position: self.position });
+        }
+        Ok(())
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, DescriptorError> {
+        let start = self.position;
+        let mut dimensions = 0;
+        while self.peek() == Some('[') {
+            self.next()?;
+            dimensions += 1;
+        }
+        if dimensions > MAX_ARRAY_DIMENSIONS {
+            return Err(DescriptorError::TooManyDimensions { position: start });
+        }
+        let position = self.position;
+        let mut field_type = match self.next()? {
+            'L' => FieldType::Object(self.class_name()?),
+            c => match BaseType::from_descriptor_char(c) {
+                Some(base) => FieldType::Base(base),
+                None => return Err(DescriptorError::UnexpectedCharacter { position, found: c }),
+            },
+        };
+        for _ in 0..dimensions {
+            field_type = FieldType::Array(Box::new(field_type));
+        }
+        Ok(field_type)
+    }
+
+    /// Reads a class name up to the `;` that ends it
+    fn class_name(&mut self) -> Result<String, DescriptorError> {
+        let start = self.position;
+        let len = self.s[start..].find(';').ok_or(DescriptorError::UnexpectedEnd { position: self.s.len() })?;
+        let name = &self.s[start..start + len];
+        if !is_valid_class_name(name) {
+            return Err(DescriptorError::InvalidClassName { position: start });
+        }
+        self.position += len + 1;
+        Ok(name.to_string())
+    }
+}
+
+/// Checks a binary class name in internal form, see https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.2.1
+pub(crate) fn is_valid_class_name(name: &str) -> bool {
+    name.split('/').all(|part| !part.is_empty() && !part.contains(['.', ';', '[']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BaseType, DescriptorError, FieldType, MethodDescriptor};
+
+    fn object(name: &str) -> FieldType {
+        FieldType::Object(name.to_string())
+    }
+
+    #[test]
+    pub fn parses_field_types() {
+        assert_eq!(FieldType::parse("I").unwrap(), FieldType::Base(BaseType::Int));
+        assert_eq!(FieldType::parse("Ljava/lang/String;").unwrap(), object("java/lang/String"));
+        assert_eq!(
+            FieldType::parse("[[J").unwrap(),
+            FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Base(BaseType::Long)))))
+        );
+    }
+
+    #[test]
+    pub fn parses_method_descriptors() {
+        let descriptor = MethodDescriptor::parse("(IDLjava/lang/Thread;)Ljava/lang/Object;").unwrap();
+        assert_eq!(descriptor.params, vec![FieldType::Base(BaseType::Int), FieldType::Base(BaseType::Double), object("java/lang/Thread")]);
+        assert_eq!(descriptor.return_type, Some(object("java/lang/Object")));
+        assert_eq!(MethodDescriptor::parse("()V").unwrap(), MethodDescriptor { params: vec![], return_type: None });
+    }
+
+    #[test]
+    pub fn displays_as_descriptor() {
+        for descriptor in ["()V", "([Ljava/lang/String;)V", "(IDLjava/lang/Thread;)[[Ljava/lang/Object;", "(JZ)C"] {
+            assert_eq!(MethodDescriptor::parse(descriptor).unwrap().to_string(), descriptor);
+        }
+        assert_eq!(FieldType::parse("[Ljava/util/Map$Entry;").unwrap().to_string(), "[Ljava/util/Map$Entry;");
+    }
+
+    #[test]
+    pub fn java_signatures() {
+        let main = MethodDescriptor::parse("([Ljava/lang/String;)V").unwrap();
+        assert_eq!(main.java_signature("main", false), "void main(String[])");
+        assert_eq!(main.java_signature("main", true), "void main(java.lang.String[])");
+        let compare = MethodDescriptor::parse("(JJ)I").unwrap();
+        assert_eq!(compare.java_signature("compare", false), "int compare(long, long)");
+    }
+
+    #[test]
+    pub fn slot_sizes() {
+        let descriptor = MethodDescriptor::parse("(IJLjava/lang/Object;D[J)J").unwrap();
+        assert_eq!(descriptor.param_slots(), 7);
+        assert_eq!(descriptor.return_slots(), 2);
+        assert_eq!(MethodDescriptor::parse("()V").unwrap().return_slots(), 0);
+    }
+
+    #[test]
+    pub fn reports_error_positions() {
+        assert_eq!(MethodDescriptor::parse("(IX)V"), Err(DescriptorError::UnexpectedCharacter { position: 2, found: 'X' }));
+        assert_eq!(MethodDescriptor::parse("(I"), Err(DescriptorError::UnexpectedEnd { position: 2 }));
+        assert_eq!(MethodDescriptor::parse("I)V"), Err(DescriptorError::UnexpectedCharacter { position: 0, found: 'I' }));
+        assert_eq!(MethodDescriptor::parse("()VI"), Err(DescriptorError::TrailingCharacters { position: 3 }));
+        assert_eq!(FieldType::parse("Ljava/lang/String"), Err(DescriptorError::UnexpectedEnd { position: 17 }));
+        assert_eq!(FieldType::parse("L;"), Err(DescriptorError::InvalidClassName { position: 1 }));
+        assert_eq!(FieldType::parse("Ljava//String;"), Err(DescriptorError::InvalidClassName { position: 1 }));
+        assert_eq!(FieldType::parse("V"), Err(DescriptorError::UnexpectedCharacter { position: 0, found: 'V' }));
+        let deep = format!("{}I", "[".repeat(256));
+        assert_eq!(FieldType::parse(&deep), Err(DescriptorError::TooManyDimensions { position: 0 }));
+    }
+}
@@ -1,10 +1,9 @@
-use std::fs::File;
-
-use thiserror::Error;
-
 use crate::access_flags::FieldAccessFlags;
-use crate::attribute::{Attribute, parse_attributes};
+use crate::attribute::{self, Attribute, parse_attributes, write_attributes};
 use crate::class::{ParseClassError, read_u16};
+use crate::constant_pool::{ConstantPool, ConstantPoolError};
+use crate::reader::ClassReader;
+use crate::writer::{ClassWriter, WriteClassError, write_table};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Field {
@@ -14,25 +13,30 @@ pub struct Field {
     pub name_index: u16,
     /// The type index on the constant pool of this field
     pub descriptor_index: u16,
-    /// TODO: attribute field docs
+    /// Attributes of this field, like its `ConstantValue` or `Signature`
     pub attributes: Vec<Attribute>,
 }
 
-#[derive(Error, Debug)]
-pub enum FieldParseError {
-    #[error("field has invalid access flags")]
-    InvalidAccessFlags,
+impl Field {
+    pub fn name<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.descriptor_index)
+    }
+
+    /// The generic signature from the `Signature` attribute, see [`crate::signature`]
+    pub fn signature<'a>(&self, constant_pool: &'a ConstantPool) -> Result<Option<&'a str>, ConstantPoolError> {
+        attribute::signature(&self.attributes, constant_pool)
+    }
 }
 
-fn parse_field(f: &mut File) -> Result<Field, ParseClassError> {
-    let access_flags = FieldAccessFlags::from_bits(read_u16(f)?);
-    let access_flags = match access_flags {
-        Some(af) => Ok(af),
-        None => Err(ParseClassError::FieldParseError(FieldParseError::InvalidAccessFlags))
-    }?;
+fn parse_field<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Field, ParseClassError> {
+    let access_flags = FieldAccessFlags::from_bits_retain(read_u16(f)?);
     let name_index = read_u16(f)?;
     let descriptor_index = read_u16(f)?;
-    let attributes = parse_attributes(f)?;
+    let attributes = parse_attributes(f, constant_pool, major)?;
     Ok(Field {
         name_index,
         descriptor_index,
@@ -41,12 +45,22 @@ fn parse_field(f: &mut File) -> Result<Field, ParseClassError> {
     })
 }
 
-pub(crate) fn parse_fields(f: &mut File) -> Result<Vec<Field>, ParseClassError> {
+pub(crate) fn parse_fields<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Vec<Field>, ParseClassError> {
     let len = read_u16(f)?;
-    let mut result = vec![];
-    result.reserve(len as usize);
+    let mut result = Vec::with_capacity(len as usize);
     for _ in 0..len {
-        result.push(parse_field(f)?);
+        result.push(parse_field(f, constant_pool, major)?);
     }
     Ok(result)
-}
\ No newline at end of file
+}
+
+fn write_field(out: &mut Vec<u8>, field: &Field) -> Result<(), WriteClassError> {
+    out.write_u16(field.access_flags.bits());
+    out.write_u16(field.name_index);
+    out.write_u16(field.descriptor_index);
+    write_attributes(out, &field.attributes)
+}
+
+pub(crate) fn write_fields(out: &mut Vec<u8>, fields: &[Field]) -> Result<(), WriteClassError> {
+    write_table(out, "fields", fields, write_field)
+}
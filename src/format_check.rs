@@ -0,0 +1,260 @@
+//! Structural rules of the class file format that parsing doesn't enforce
+//!
+//! The parser only rejects what it can't represent, so a parsed class can still have access flags the JVM doesn't
+//! allow together or two methods with the same name and descriptor. [`format_check`] finds every such violation, with
+//! the section of the JVMS that states the rule. Which flags exist and some of the rules depend on the class file
+//! version, which can be given explicitly to check a class as if it had another version. Flags that aren't defined in
+//! that version are ignored, like the JVM does.
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.8
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
+use crate::class::{Class, ParseClassError};
+use crate::constant_pool::ConstantPool;
+use crate::reader::{ClassReader, SliceCursor};
+
+/// A broken rule, with the section of the JVMS that states it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatViolation {
+    /// Section like `4.1`
+    pub section: &'static str,
+    pub kind: FormatViolationKind,
+}
+
+impl Display for FormatViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (JVMS §{})", self.kind, self.section)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FormatViolationKind {
+    #[error("interface isn't abstract")]
+    InterfaceNotAbstract,
+    #[error("interface has access flags {flags:#06x} that interfaces can't have")]
+    InvalidInterfaceFlags { flags: u16 },
+    #[error("annotation isn't an interface")]
+    AnnotationNotInterface,
+    #[error("class is both final and abstract")]
+    FinalAndAbstract,
+    #[error("module has access flags {flags:#06x} besides ACC_MODULE")]
+    InvalidModuleFlags { flags: u16 },
+    #[error("{member} has more than one of public, private and protected")]
+    ConflictingVisibility { member: String },
+    #[error("field {field} is both final and volatile")]
+    FinalAndVolatile { field: String },
+    #[error("interface field {field} has access flags {flags:#06x} instead of public, static and final")]
+    InvalidInterfaceField { field: String, flags: u16 },
+    #[error("interface method {method} has access flags {flags:#06x}, which interface methods can't have in this version")]
+    InvalidInterfaceMethod { method: String, flags: u16 },
+    #[error("abstract method {method} has access flags {flags:#06x} that abstract methods can't have")]
+    InvalidAbstractMethod { method: String, flags: u16 },
+    #[error("constructor {method} has access flags {flags:#06x} that constructors can't have")]
+    InvalidConstructorFlags { method: String, flags: u16 },
+    #[error("class initialization method {method} isn't static")]
+    ClassInitializerNotStatic { method: String },
+    #[error("field {name} {descriptor} is declared more than once")]
+    DuplicateField { name: String, descriptor: String },
+    #[error("method {name}{descriptor} is declared more than once")]
+    DuplicateMethod { name: String, descriptor: String },
+    #[error("{count} bytes after the last attribute")]
+    TrailingBytes { count: usize },
+}
+
+/// Checks `class` as a class file of version `major`, returning every violation found
+pub fn format_check(class: &Class, major: u16) -> Vec<FormatViolation> {
+    let mut violations = vec![];
+    let mut violation = |section, kind| violations.push(FormatViolation { section, kind });
+    let is_interface = class.access_flags.contains(ClassAccessFlags::ACC_INTERFACE);
+    for kind in check_class_flags(class.access_flags, major) {
+        violation("4.1", kind);
+    }
+
+    let mut fields = HashSet::new();
+    for field in &class.fields {
+        let (name, descriptor) = member_name(&class.constant_pool, field.name_index, field.descriptor_index);
+        for kind in check_field_flags(field.access_flags, format!("{} {}", name, descriptor), is_interface, major) {
+            violation("4.5", kind);
+        }
+        if !fields.insert((name, descriptor)) {
+            violation("4.5", FormatViolationKind::DuplicateField { name: name.to_string(), descriptor: descriptor.to_string() });
+        }
+    }
+
+    let mut methods = HashSet::new();
+    for method in &class.methods {
+        let (name, descriptor) = member_name(&class.constant_pool, method.name_index, method.descriptor_index);
+        for kind in check_method_flags(method.access_flags, name, format!("{}{}", name, descriptor), is_interface, major) {
+            violation("4.6", kind);
+        }
+        if !methods.insert((name, descriptor)) {
+            violation("4.6", FormatViolationKind::DuplicateMethod { name: name.to_string(), descriptor: descriptor.to_string() });
+        }
+    }
+    violations
+}
+
+/// Parses a class file and checks it as the version it declares, including that nothing follows the last attribute
+pub fn format_check_bytes(bytes: &[u8]) -> Result<Vec<FormatViolation>, ParseClassError> {
+    let mut cursor = SliceCursor::new(bytes);
+    let class = Class::parse(&mut cursor)?;
+    let mut violations = format_check(&class, class.java_version.major);
+    if cursor.offset() < bytes.len() {
+        violations.push(FormatViolation { section: "4.8", kind: FormatViolationKind::TrailingBytes { count: bytes.len() - cursor.offset() } });
+    }
+    Ok(violations)
+}
+
+/// Name and descriptor of a field or method, with placeholders for invalid indices
+fn member_name(constant_pool: &ConstantPool, name_index: u16, descriptor_index: u16) -> (&str, &str) {
+    (constant_pool.get_utf8(name_index).unwrap_or("?"), constant_pool.get_utf8(descriptor_index).unwrap_or("?"))
+}
+
+fn check_class_flags(flags: ClassAccessFlags, major: u16) -> Vec<FormatViolationKind> {
+    let flags = flags & ClassAccessFlags::defined_in(major);
+    let mut violations = vec![];
+    if flags.contains(ClassAccessFlags::ACC_MODULE) {
+        if flags != ClassAccessFlags::ACC_MODULE {
+            violations.push(FormatViolationKind::InvalidModuleFlags { flags: (flags - ClassAccessFlags::ACC_MODULE).bits() });
+        }
+    } else if flags.contains(ClassAccessFlags::ACC_INTERFACE) {
+        // Before Java 6, the JVM treats every interface as abstract
+        if !flags.contains(ClassAccessFlags::ACC_ABSTRACT) && major >= 50 {
+            violations.push(FormatViolationKind::InterfaceNotAbstract);
+        }
+        let invalid = flags & (ClassAccessFlags::ACC_FINAL | ClassAccessFlags::ACC_SUPER | ClassAccessFlags::ACC_ENUM);
+        if !invalid.is_empty() {
+            violations.push(FormatViolationKind::InvalidInterfaceFlags { flags: invalid.bits() });
+        }
+    } else {
+        if flags.contains(ClassAccessFlags::ACC_ANNOTATION) {
+            violations.push(FormatViolationKind::AnnotationNotInterface);
+        }
+        if flags.contains(ClassAccessFlags::ACC_FINAL | ClassAccessFlags::ACC_ABSTRACT) {
+            violations.push(FormatViolationKind::FinalAndAbstract);
+        }
+    }
+    violations
+}
+
+/// Whether more than one of the visibility flags, which share their bits between fields and methods, is set
+fn conflicting_visibility(bits: u16) -> bool {
+    (bits & 0x0007).count_ones() > 1
+}
+
+fn check_field_flags(flags: FieldAccessFlags, field: String, is_interface: bool, major: u16) -> Vec<FormatViolationKind> {
+    let flags = flags & FieldAccessFlags::defined_in(major);
+    let mut violations = vec![];
+    if is_interface {
+        let required = FieldAccessFlags::ACC_PUBLIC | FieldAccessFlags::ACC_STATIC | FieldAccessFlags::ACC_FINAL;
+        if flags - FieldAccessFlags::ACC_SYNTHETIC != required {
+            violations.push(FormatViolationKind::InvalidInterfaceField { field, flags: flags.bits() });
+        }
+        return violations;
+    }
+    if conflicting_visibility(flags.bits()) {
+        violations.push(FormatViolationKind::ConflictingVisibility { member: format!("field {}", field) });
+    }
+    if flags.contains(FieldAccessFlags::ACC_FINAL | FieldAccessFlags::ACC_VOLATILE) {
+        violations.push(FormatViolationKind::FinalAndVolatile { field });
+    }
+    violations
+}
+
+fn check_method_flags(flags: MethodAccessFlags, name: &str, method: String, is_interface: bool, major: u16) -> Vec<FormatViolationKind> {
+    let flags = flags & MethodAccessFlags::defined_in(major);
+    let mut violations = vec![];
+    // Flags of class initialization methods other than ACC_STATIC are ignored, which they only need from version 51 on
+    if name == "<clinit>" {
+        if major >= 51 && !flags.contains(MethodAccessFlags::ACC_STATIC) {
+            violations.push(FormatViolationKind::ClassInitializerNotStatic { method });
+        }
+        return violations;
+    }
+    if conflicting_visibility(flags.bits()) {
+        violations.push(FormatViolationKind::ConflictingVisibility { member: format!("method {}", method) });
+    }
+    if is_interface {
+        let valid = if major < 52 {
+            // Only public abstract methods, without code
+            let allowed = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_BRIDGE |
+                MethodAccessFlags::ACC_VARARGS | MethodAccessFlags::ACC_SYNTHETIC;
+            flags.contains(MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT) && (flags - allowed).is_empty()
+        } else {
+            let forbidden = MethodAccessFlags::ACC_PROTECTED | MethodAccessFlags::ACC_FINAL | MethodAccessFlags::ACC_SYNCHRONIZED |
+                MethodAccessFlags::ACC_NATIVE;
+            flags.intersects(MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_PRIVATE) && !flags.intersects(forbidden)
+        };
+        if !valid {
+            violations.push(FormatViolationKind::InvalidInterfaceMethod { method: method.clone(), flags: flags.bits() });
+        }
+    }
+    if flags.contains(MethodAccessFlags::ACC_ABSTRACT) {
+        let invalid = flags & (MethodAccessFlags::ACC_PRIVATE | MethodAccessFlags::ACC_STATIC | MethodAccessFlags::ACC_FINAL |
+            MethodAccessFlags::ACC_SYNCHRONIZED | MethodAccessFlags::ACC_NATIVE | MethodAccessFlags::ACC_STRICT);
+        if !invalid.is_empty() {
+            violations.push(FormatViolationKind::InvalidAbstractMethod { method: method.clone(), flags: invalid.bits() });
+        }
+    }
+    if name == "<init>" {
+        let allowed = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_PRIVATE | MethodAccessFlags::ACC_PROTECTED |
+            MethodAccessFlags::ACC_VARARGS | MethodAccessFlags::ACC_STRICT | MethodAccessFlags::ACC_SYNTHETIC;
+        if !(flags - allowed).is_empty() {
+            violations.push(FormatViolationKind::InvalidConstructorFlags { method, flags: (flags - allowed).bits() });
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::access_flags::{ClassAccessFlags, MethodAccessFlags};
+
+    use super::{check_class_flags, check_method_flags, FormatViolationKind};
+
+    #[test]
+    pub fn flags_depend_on_the_version() {
+        let enum_interface = ClassAccessFlags::ACC_INTERFACE | ClassAccessFlags::ACC_ABSTRACT | ClassAccessFlags::ACC_ENUM;
+        assert_eq!(check_class_flags(enum_interface, 49), vec![FormatViolationKind::InvalidInterfaceFlags { flags: 0x4000 }]);
+        assert_eq!(check_class_flags(enum_interface, 48), vec![]);
+
+        let strict = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_STRICT;
+        assert_eq!(
+            check_method_flags(strict, "f", "f()V".to_string(), false, 60),
+            vec![FormatViolationKind::InvalidAbstractMethod { method: "f()V".to_string(), flags: 0x0800 }]
+        );
+        assert_eq!(check_method_flags(strict, "f", "f()V".to_string(), false, 61), vec![]);
+    }
+
+    #[test]
+    pub fn class_initializers_are_static_from_version_51() {
+        let flags = MethodAccessFlags::empty();
+        assert_eq!(check_method_flags(flags, "<clinit>", "<clinit>()V".to_string(), false, 50), vec![]);
+        assert_eq!(
+            check_method_flags(flags, "<clinit>", "<clinit>()V".to_string(), false, 51),
+            vec![FormatViolationKind::ClassInitializerNotStatic { method: "<clinit>()V".to_string() }]
+        );
+        assert_eq!(check_method_flags(MethodAccessFlags::ACC_STATIC, "<clinit>", "<clinit>()V".to_string(), false, 51), vec![]);
+    }
+
+    #[test]
+    pub fn interface_methods_depend_on_the_version() {
+        let private = MethodAccessFlags::ACC_PRIVATE;
+        assert_eq!(check_method_flags(private, "f", "f()V".to_string(), true, 52), vec![]);
+        assert_eq!(
+            check_method_flags(private, "f", "f()V".to_string(), true, 51),
+            vec![FormatViolationKind::InvalidInterfaceMethod { method: "f()V".to_string(), flags: 0x0002 }]
+        );
+    }
+
+    #[test]
+    pub fn old_interfaces_are_implicitly_abstract() {
+        let interface = ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_INTERFACE;
+        assert_eq!(check_class_flags(interface, 49), vec![]);
+        assert_eq!(check_class_flags(interface, 50), vec![FormatViolationKind::InterfaceNotAbstract]);
+    }
+}
@@ -0,0 +1,516 @@
+//! Computing the stack map frames of a method's code, for classes that need a `StackMapTable`
+//!
+//! The types of the locals and the operand stack are inferred for every instruction by following the control flow
+//! until nothing changes. Where paths with different types merge, the result is their common supertype, so this needs
+//! a [`ClassHierarchy`]. Frames are then kept for the instructions that need one: branch targets, exception handlers
+//! and instructions right after an unconditional jump.
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.10.1
+use thiserror::Error;
+
+use crate::class_hierarchy::{array_of, ClassHierarchy, ClassHierarchyError, THROWABLE};
+use crate::code::CodeAttribute;
+use crate::constant_pool::{Constant, ConstantPool, ConstantPoolError};
+use crate::constant_pool_builder::ConstantPoolBuilderError;
+use crate::descriptor::{DescriptorError, FieldType, MethodDescriptor};
+use crate::frame_size::member_name_and_type;
+use crate::instruction::{DecodeError, Instruction};
+use crate::stack_map::{dup_slots, Frame, push_slots, VerificationType};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FrameInferenceError {
+    #[error("couldn't decode the code: {0}")]
+    DecodeError(#[from] DecodeError),
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("invalid descriptor: {0}")]
+    DescriptorError(#[from] DescriptorError),
+    #[error("couldn't merge types: {0}")]
+    ClassHierarchyError(#[from] ClassHierarchyError),
+    #[error("instruction at pc {pc} pops more values than there are on the stack")]
+    StackUnderflow { pc: u32 },
+    #[error("stack has {expected} slots at pc {pc} when reached from one path and {actual} from another")]
+    InconsistentStackDepth { pc: u32, expected: usize, actual: usize },
+    #[error("instruction at pc {pc} jumps to {target}, which isn't the start of an instruction")]
+    InvalidBranchTarget { pc: u32, target: u32 },
+    #[error("execution falls off the end of the code after pc {pc}")]
+    FallsOffEnd { pc: u32 },
+    #[error("subroutine instruction at pc {pc} can't be described by stack map frames")]
+    Subroutine { pc: u32 },
+    #[error("code at pc {pc} needs a frame but is never reached")]
+    UnreachableCode { pc: u32 },
+    #[error("constant {index} loaded at pc {pc} isn't loadable")]
+    NotLoadable { pc: u32, index: u16 },
+    #[error("instruction at pc {pc} expects an array of references but found {found}")]
+    NotAnArray { pc: u32, found: VerificationType },
+    #[error("constructor called at pc {pc} on {found}, which isn't uninitialized")]
+    NotUninitialized { pc: u32, found: VerificationType },
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StackMapError {
+    #[error("couldn't compute the frames of {method}: {error}")]
+    InvalidMethod { method: String, error: FrameInferenceError },
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("couldn't add the frame types to the constant pool: {0}")]
+    ConstantPoolBuilderError(#[from] ConstantPoolBuilderError),
+}
+
+/// Computes the frames `code` needs in its `StackMapTable`, sorted by pc
+///
+/// `this_class` is the name of the class the method belongs to and `initial` the frame it starts with, see
+/// [`Frame::initial`]. The frames can be encoded with [`crate::stack_map::compress_frames`].
+pub fn infer_frames(
+    code: &CodeAttribute,
+    constant_pool: &ConstantPool,
+    this_class: &str,
+    initial: Frame,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<Vec<(u32, Frame)>, FrameInferenceError> {
+    let instructions: Vec<(u32, Instruction)> = code.instructions().collect::<Result<_, _>>()?;
+    let context = Context { code, constant_pool, this_class };
+    let mut flow = Flow {
+        index_at: vec![None; code.code.len()],
+        frames: vec![None; instructions.len()],
+        worklist: vec![],
+        hierarchy,
+    };
+    for (i, (pc, _)) in instructions.iter().enumerate() {
+        flow.index_at[*pc as usize] = Some(i);
+    }
+
+    // Instructions that need a frame, other than the first one which always has the initial frame
+    let mut needs_frame = vec![false; instructions.len()];
+    for handler in &code.exception_table {
+        let i = flow.index(handler.start_pc as u32, handler.handler_pc as u32)?;
+        needs_frame[i] = true;
+    }
+    for (i, (pc, instruction)) in instructions.iter().enumerate() {
+        for target in instruction.branch_targets(*pc) {
+            needs_frame[flow.index(*pc, target)?] = true;
+        }
+        if !instruction.falls_through() && i + 1 < instructions.len() {
+            needs_frame[i + 1] = true;
+        }
+    }
+
+    flow.merge(0, 0, initial)?;
+    while let Some(i) = flow.worklist.pop() {
+        let (pc, instruction) = &instructions[i];
+        let before = flow.frames[i].clone().unwrap();
+        let mut after = before.clone();
+        context.execute(&mut after, *pc, instruction)?;
+        for handler in code.exception_table.iter().filter(|entry| entry.covers(*pc as u16)) {
+            let catch_type = handler.catch_type_name(constant_pool)?.unwrap_or(THROWABLE);
+            // A store can throw before or after changing the local, so the handler has to accept both
+            let locals = merge_slots(&before.locals, &after.locals, hierarchy)?;
+            let frame = Frame { locals, stack: vec![VerificationType::Object(catch_type.to_string())] };
+            flow.merge(*pc, handler.handler_pc as u32, frame)?;
+        }
+        for target in instruction.branch_targets(*pc) {
+            flow.merge(*pc, target, after.clone())?;
+        }
+        if instruction.falls_through() {
+            match instructions.get(i + 1) {
+                Some((next, _)) => flow.merge(*pc, *next, after)?,
+                None => return Err(FrameInferenceError::FallsOffEnd { pc: *pc }),
+            }
+        }
+    }
+
+    let mut frames = vec![];
+    for (i, frame) in flow.frames.into_iter().enumerate() {
+        if needs_frame[i] {
+            let pc = instructions[i].0;
+            frames.push((pc, frame.ok_or(FrameInferenceError::UnreachableCode { pc })?));
+        }
+    }
+    Ok(frames)
+}
+
+/// Frames inferred so far and the instructions left to visit
+struct Flow<'a> {
+    /// Index of the instruction starting at each pc
+    index_at: Vec<Option<usize>>,
+    /// Frame before each instruction, `None` if it hasn't been reached yet
+    frames: Vec<Option<Frame>>,
+    worklist: Vec<usize>,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl Flow<'_> {
+    /// Index of the instruction at `target`, which the instruction at `pc` jumps to
+    fn index(&self, pc: u32, target: u32) -> Result<usize, FrameInferenceError> {
+        self.index_at.get(target as usize)
+            .copied()
+            .flatten()
+            .ok_or(FrameInferenceError::InvalidBranchTarget { pc, target })
+    }
+
+    /// Merges `frame` into the frame of the instruction at `target`, reached from `pc`, visiting it again if it changed
+    fn merge(&mut self, pc: u32, target: u32, frame: Frame) -> Result<(), FrameInferenceError> {
+        let i = self.index(pc, target)?;
+        let merged = match &self.frames[i] {
+            None => frame,
+            Some(existing) => {
+                if existing.stack.len() != frame.stack.len() {
+                    return Err(FrameInferenceError::InconsistentStackDepth {
+                        pc: target,
+                        expected: existing.stack.len(),
+                        actual: frame.stack.len(),
+                    });
+                }
+                let merged = Frame {
+                    locals: merge_slots(&existing.locals, &frame.locals, self.hierarchy)?,
+                    stack: merge_slots(&existing.stack, &frame.stack, self.hierarchy)?,
+                };
+                if &merged == existing {
+                    return Ok(());
+                }
+                merged
+            }
+        };
+        self.frames[i] = Some(merged);
+        self.worklist.push(i);
+        Ok(())
+    }
+}
+
+/// Merges slot by slot, missing slots at the end of the shorter list are unusable
+fn merge_slots(
+    a: &[VerificationType],
+    b: &[VerificationType],
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<Vec<VerificationType>, ClassHierarchyError> {
+    (0..a.len().max(b.len()))
+        .map(|i| match (a.get(i), b.get(i)) {
+            (Some(a), Some(b)) => merge_types(a, b, hierarchy),
+            _ => Ok(VerificationType::Top),
+        })
+        .collect()
+}
+
+/// The most specific type values of type `a` and `b` both are, [`VerificationType::Top`] if they have nothing in common
+fn merge_types(a: &VerificationType, b: &VerificationType, hierarchy: &dyn ClassHierarchy) -> Result<VerificationType, ClassHierarchyError> {
+    Ok(match (a, b) {
+        _ if a == b => a.clone(),
+        (VerificationType::Null, VerificationType::Object(_)) => b.clone(),
+        (VerificationType::Object(_), VerificationType::Null) => a.clone(),
+        (VerificationType::Object(a), VerificationType::Object(b)) => VerificationType::Object(hierarchy.common_super_class(a, b)?),
+        _ => VerificationType::Top,
+    })
+}
+
+/// What executing an instruction needs to know about the method
+struct Context<'a> {
+    code: &'a CodeAttribute,
+    constant_pool: &'a ConstantPool,
+    this_class: &'a str,
+}
+
+impl Context<'_> {
+    /// Updates `frame` with the effect of `instruction`, which is at `pc`
+    fn execute(&self, frame: &mut Frame, pc: u32, instruction: &Instruction) -> Result<(), FrameInferenceError> {
+        use Instruction::*;
+        let (pop, push) = match instruction {
+            Nop | Iinc { .. } | Goto(_) | GotoW(_) | Return => (0, None),
+            AconstNull => (0, Some(VerificationType::Null)),
+            IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush(_) | Sipush(_) |
+            Iload(_) | Iload0 | Iload1 | Iload2 | Iload3 => (0, Some(VerificationType::Integer)),
+            Lconst0 | Lconst1 | Lload(_) | Lload0 | Lload1 | Lload2 | Lload3 => (0, Some(VerificationType::Long)),
+            Fconst0 | Fconst1 | Fconst2 | Fload(_) | Fload0 | Fload1 | Fload2 | Fload3 => (0, Some(VerificationType::Float)),
+            Dconst0 | Dconst1 | Dload(_) | Dload0 | Dload1 | Dload2 | Dload3 => (0, Some(VerificationType::Double)),
+            Ldc(index) => (0, Some(self.constant_type(pc, *index as u16)?)),
+            LdcW(index) | Ldc2W(index) => (0, Some(self.constant_type(pc, *index)?)),
+            Aload(index) => (0, Some(local(frame, *index))),
+            Aload0 => (0, Some(local(frame, 0))),
+            Aload1 => (0, Some(local(frame, 1))),
+            Aload2 => (0, Some(local(frame, 2))),
+            Aload3 => (0, Some(local(frame, 3))),
+            Iaload | Baload | Caload | Saload => (2, Some(VerificationType::Integer)),
+            Laload => (2, Some(VerificationType::Long)),
+            Faload => (2, Some(VerificationType::Float)),
+            Daload => (2, Some(VerificationType::Double)),
+            Aaload => {
+                pop_slots(frame, pc, 1)?;
+                let array = pop_slots(frame, pc, 1)?.remove(0);
+                frame.stack.push(component_type(pc, array)?);
+                return Ok(());
+            }
+            Istore(index) | Fstore(index) | Astore(index) => return store(frame, pc, *index, 1),
+            Lstore(index) | Dstore(index) => return store(frame, pc, *index, 2),
+            Istore0 | Fstore0 | Astore0 => return store(frame, pc, 0, 1),
+            Istore1 | Fstore1 | Astore1 => return store(frame, pc, 1, 1),
+            Istore2 | Fstore2 | Astore2 => return store(frame, pc, 2, 1),
+            Istore3 | Fstore3 | Astore3 => return store(frame, pc, 3, 1),
+            Lstore0 | Dstore0 => return store(frame, pc, 0, 2),
+            Lstore1 | Dstore1 => return store(frame, pc, 1, 2),
+            Lstore2 | Dstore2 => return store(frame, pc, 2, 2),
+            Lstore3 | Dstore3 => return store(frame, pc, 3, 2),
+            Iastore | Fastore | Aastore | Bastore | Castore | Sastore => (3, None),
+            Lastore | Dastore => (4, None),
+            Pop => (1, None),
+            Pop2 => (2, None),
+            Dup => return dup(frame, pc, 1, 0),
+            DupX1 => return dup(frame, pc, 1, 1),
+            DupX2 => return dup(frame, pc, 1, 2),
+            Dup2 => return dup(frame, pc, 2, 0),
+            Dup2X1 => return dup(frame, pc, 2, 1),
+            Dup2X2 => return dup(frame, pc, 2, 2),
+            Swap => {
+                let mut values = pop_slots(frame, pc, 2)?;
+                values.reverse();
+                frame.stack.extend(values);
+                return Ok(());
+            }
+            Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor |
+            Fcmpl | Fcmpg => (2, Some(VerificationType::Integer)),
+            Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor => (4, Some(VerificationType::Long)),
+            Lshl | Lshr | Lushr => (3, Some(VerificationType::Long)),
+            Fadd | Fsub | Fmul | Fdiv | Frem => (2, Some(VerificationType::Float)),
+            Dadd | Dsub | Dmul | Ddiv | Drem => (4, Some(VerificationType::Double)),
+            Ineg | I2b | I2c | I2s | F2i | Arraylength | Instanceof(_) => (1, Some(VerificationType::Integer)),
+            Lneg | D2l => (2, Some(VerificationType::Long)),
+            Fneg | I2f => (1, Some(VerificationType::Float)),
+            Dneg | L2d => (2, Some(VerificationType::Double)),
+            I2l | F2l => (1, Some(VerificationType::Long)),
+            I2d | F2d => (1, Some(VerificationType::Double)),
+            L2i | D2i => (2, Some(VerificationType::Integer)),
+            L2f | D2f => (2, Some(VerificationType::Float)),
+            Lcmp | Dcmpl | Dcmpg => (4, Some(VerificationType::Integer)),
+            Ifeq(_) | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) | Ifnull(_) | Ifnonnull(_) |
+            Tableswitch { .. } | Lookupswitch { .. } | Ireturn | Freturn | Areturn | Athrow | Monitorenter | Monitorexit => (1, None),
+            IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_) | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) | IfAcmpeq(_) | IfAcmpne(_) |
+            Lreturn | Dreturn => (2, None),
+            Jsr(_) | JsrW(_) | Ret(_) => return Err(FrameInferenceError::Subroutine { pc }),
+            Getstatic(index) => (0, Some(self.field_type(*index)?)),
+            Putstatic(index) => (self.field_type(*index)?.is_wide() as usize + 1, None),
+            Getfield(index) => (1, Some(self.field_type(*index)?)),
+            Putfield(index) => (self.field_type(*index)?.is_wide() as usize + 2, None),
+            Invokevirtual(index) | Invokespecial(index) | Invokestatic(index) |
+            Invokeinterface { index, .. } | Invokedynamic { index } => return self.invoke(frame, pc, instruction, *index),
+            New(_) => (0, Some(VerificationType::Uninitialized(pc as u16))),
+            Newarray { atype } => (1, Some(VerificationType::Object(format!("[{}", atype.base_type().descriptor_char())))),
+            Anewarray(index) => (1, Some(VerificationType::Object(array_of(self.constant_pool.get_class_name(*index)?)))),
+            Checkcast(index) => (1, Some(VerificationType::Object(self.constant_pool.get_class_name(*index)?.to_string()))),
+            Multianewarray { index, dimensions } => {
+                (*dimensions as usize, Some(VerificationType::Object(self.constant_pool.get_class_name(*index)?.to_string())))
+            }
+        };
+        pop_slots(frame, pc, pop)?;
+        if let Some(push) = push {
+            push_slots(&mut frame.stack, push);
+        }
+        Ok(())
+    }
+
+    /// Type of the constant at `index`, as loaded by `ldc`
+    fn constant_type(&self, pc: u32, index: u16) -> Result<VerificationType, FrameInferenceError> {
+        Ok(match self.constant_pool.get(index)? {
+            Constant::Integer(_) => VerificationType::Integer,
+            Constant::Float(_) => VerificationType::Float,
+            Constant::Long(_) => VerificationType::Long,
+            Constant::Double(_) => VerificationType::Double,
+            Constant::String { .. } => VerificationType::Object("java/lang/String".to_string()),
+            Constant::Class { .. } => VerificationType::Object("java/lang/Class".to_string()),
+            Constant::MethodType { .. } => VerificationType::Object("java/lang/invoke/MethodType".to_string()),
+            Constant::MethodHandle { .. } => VerificationType::Object("java/lang/invoke/MethodHandle".to_string()),
+            Constant::Dynamic { .. } => self.field_type(index)?,
+            _ => return Err(FrameInferenceError::NotLoadable { pc, index }),
+        })
+    }
+
+    /// Type of the field, or dynamic constant, at `index`
+    fn field_type(&self, index: u16) -> Result<VerificationType, FrameInferenceError> {
+        let (_, descriptor) = member_name_and_type(self.constant_pool, index)?;
+        Ok(VerificationType::from_field_type(&FieldType::parse(descriptor)?))
+    }
+
+    fn invoke(&self, frame: &mut Frame, pc: u32, instruction: &Instruction, index: u16) -> Result<(), FrameInferenceError> {
+        let (name, descriptor) = member_name_and_type(self.constant_pool, index)?;
+        let descriptor = MethodDescriptor::parse(descriptor)?;
+        pop_slots(frame, pc, descriptor.param_slots() as usize)?;
+        if !matches!(instruction, Instruction::Invokestatic(_) | Instruction::Invokedynamic { .. }) {
+            let receiver = pop_slots(frame, pc, 1)?.remove(0);
+            if matches!(instruction, Instruction::Invokespecial(_)) && name == "<init>" {
+                self.initialize(frame, pc, receiver)?;
+            }
+        }
+        if let Some(return_type) = &descriptor.return_type {
+            push_slots(&mut frame.stack, VerificationType::from_field_type(return_type));
+        }
+        Ok(())
+    }
+
+    /// Replaces every copy of the uninitialized `receiver` with the initialized object, after calling its constructor
+    fn initialize(&self, frame: &mut Frame, pc: u32, receiver: VerificationType) -> Result<(), FrameInferenceError> {
+        let class = match &receiver {
+            VerificationType::UninitializedThis => self.this_class,
+            VerificationType::Uninitialized(offset) => match Instruction::decode(&self.code.code, *offset as u32)? {
+                (Instruction::New(index), _) => self.constant_pool.get_class_name(index)?,
+                _ => return Err(FrameInferenceError::NotUninitialized { pc, found: receiver }),
+            },
+            _ => return Err(FrameInferenceError::NotUninitialized { pc, found: receiver }),
+        };
+        let initialized = VerificationType::Object(class.to_string());
+        for slot in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+            if *slot == receiver {
+                *slot = initialized.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pops `count` slots off the stack, returning them bottom first
+fn pop_slots(frame: &mut Frame, pc: u32, count: usize) -> Result<Vec<VerificationType>, FrameInferenceError> {
+    if frame.stack.len() < count {
+        return Err(FrameInferenceError::StackUnderflow { pc });
+    }
+    Ok(frame.stack.split_off(frame.stack.len() - count))
+}
+
+/// Type of local `index`, as loaded by `aload`
+fn local(frame: &Frame, index: u16) -> VerificationType {
+    frame.locals.get(index as usize).cloned().unwrap_or(VerificationType::Top)
+}
+
+/// Pops a value taking `size` slots into local `index`
+fn store(frame: &mut Frame, pc: u32, index: u16, size: usize) -> Result<(), FrameInferenceError> {
+    let value = pop_slots(frame, pc, size)?.remove(0);
+    let index = index as usize;
+    if frame.locals.len() < index + size {
+        frame.locals.resize(index + size, VerificationType::Top);
+    }
+    // Overwriting the second slot of a long or double breaks it
+    if index > 0 && frame.locals[index - 1].is_wide() {
+        frame.locals[index - 1] = VerificationType::Top;
+    }
+    frame.locals[index] = value;
+    if size == 2 {
+        frame.locals[index + 1] = VerificationType::Top;
+    }
+    Ok(())
+}
+
+/// [`dup_slots`] on the stack of `frame`, failing if it's too short
+fn dup(frame: &mut Frame, pc: u32, count: usize, depth: usize) -> Result<(), FrameInferenceError> {
+    if frame.stack.len() < count + depth {
+        return Err(FrameInferenceError::StackUnderflow { pc });
+    }
+    dup_slots(&mut frame.stack, count, depth);
+    Ok(())
+}
+
+/// Type of the elements loaded by `aaload` from an array of type `array`
+fn component_type(pc: u32, array: VerificationType) -> Result<VerificationType, FrameInferenceError> {
+    if let VerificationType::Object(name) = &array {
+        if let Some(component) = name.strip_prefix('[') {
+            if component.starts_with('[') {
+                return Ok(VerificationType::Object(component.to_string()));
+            }
+            if let Some(class) = component.strip_prefix('L').and_then(|class| class.strip_suffix(';')) {
+                return Ok(VerificationType::Object(class.to_string()));
+            }
+        }
+    }
+    match array {
+        VerificationType::Null => Ok(VerificationType::Null),
+        found => Err(FrameInferenceError::NotAnArray { pc, found }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameInferenceError, infer_frames};
+    use crate::assembler::Assembler;
+    use crate::class_hierarchy::ClassHierarchyMap;
+    use crate::constant_pool::ConstantPool;
+    use crate::constant_pool_builder::ConstantPoolBuilder;
+    use crate::instruction::{Instruction, Opcode};
+    use crate::stack_map::{Frame, VerificationType};
+
+    fn object(name: &str) -> VerificationType {
+        VerificationType::Object(name.to_string())
+    }
+
+    #[test]
+    pub fn merges_to_common_super_class() {
+        let mut hierarchy = ClassHierarchyMap::new();
+        hierarchy.insert("A", Some("java/lang/Object"), false);
+        hierarchy.insert("B", Some("A"), false);
+        hierarchy.insert("C", Some("A"), false);
+        let mut pool = ConstantPoolBuilder::new();
+        let (b, c) = (pool.class("B").unwrap(), pool.class("C").unwrap());
+        let b_init = pool.method_ref("B", "<init>", "()V").unwrap();
+        let c_init = pool.method_ref("C", "<init>", "()V").unwrap();
+        let pool = pool.build();
+
+        let mut asm = Assembler::new();
+        let (other, end) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::Iload0);
+        asm.branch(Opcode::Ifeq, other).unwrap();
+        asm.emit(Instruction::New(b));
+        asm.emit(Instruction::Dup);
+        asm.emit(Instruction::Invokespecial(b_init));
+        asm.branch(Opcode::Goto, end).unwrap();
+        asm.bind(other).unwrap();
+        asm.emit(Instruction::New(c));
+        asm.emit(Instruction::Dup);
+        asm.emit(Instruction::Invokespecial(c_init));
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Astore1);
+        asm.emit(Instruction::Return);
+        let code = asm.finish(2, 2).unwrap();
+
+        let initial = Frame { locals: vec![VerificationType::Integer], stack: vec![] };
+        let frames = infer_frames(&code, &pool, "Test", initial, &hierarchy).unwrap();
+        assert_eq!(frames, vec![
+            (14, Frame { locals: vec![VerificationType::Integer], stack: vec![] }),
+            (21, Frame { locals: vec![VerificationType::Integer], stack: vec![object("A")] }),
+        ]);
+    }
+
+    #[test]
+    pub fn tracks_uninitialized_this() {
+        let mut pool = ConstantPoolBuilder::new();
+        let init = pool.method_ref("java/lang/Object", "<init>", "()V").unwrap();
+        let pool = pool.build();
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        asm.emit(Instruction::Aload0);
+        asm.emit(Instruction::Invokespecial(init));
+        asm.emit(Instruction::Aload0);
+        asm.branch(Opcode::Ifnonnull, end).unwrap();
+        asm.emit(Instruction::Nop);
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+        let code = asm.finish(1, 1).unwrap();
+
+        let initial = Frame { locals: vec![VerificationType::UninitializedThis], stack: vec![] };
+        let frames = infer_frames(&code, &pool, "Test", initial, &ClassHierarchyMap::new()).unwrap();
+        assert_eq!(frames, vec![(9, Frame { locals: vec![object("Test")], stack: vec![] })]);
+    }
+
+    #[test]
+    pub fn reports_code_without_frames() {
+        let pool = ConstantPool::new();
+        let hierarchy = ClassHierarchyMap::new();
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::Return);
+        asm.emit(Instruction::Return);
+        let code = asm.finish(0, 0).unwrap();
+        assert_eq!(infer_frames(&code, &pool, "Test", Frame::default(), &hierarchy), Err(FrameInferenceError::UnreachableCode { pc: 1 }));
+
+        let mut asm = Assembler::new();
+        let subroutine = asm.new_label();
+        asm.branch(Opcode::Jsr, subroutine).unwrap();
+        asm.emit(Instruction::Return);
+        asm.bind(subroutine).unwrap();
+        asm.emit(Instruction::Astore0);
+        asm.emit(Instruction::Ret(0));
+        let code = asm.finish(1, 1).unwrap();
+        assert_eq!(infer_frames(&code, &pool, "Test", Frame::default(), &hierarchy), Err(FrameInferenceError::Subroutine { pc: 0 }));
+    }
+}
@@ -0,0 +1,331 @@
+//! Computing `max_stack` and `max_locals` of a method's code
+//!
+//! The operand stack depth is tracked through every path of the control flow graph, including exception handlers,
+//! which start with just the exception on the stack.
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::code::CodeAttribute;
+use crate::constant_pool::{Constant, ConstantKind, ConstantPool, ConstantPoolError};
+use crate::descriptor::{DescriptorError, FieldType, MethodDescriptor};
+use crate::instruction::{DecodeError, Instruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSize {
+    pub max_stack: u16,
+    pub max_locals: u16,
+}
+
+impl FrameSize {
+    /// The sizes declared by a `Code` attribute
+    pub fn declared(code: &CodeAttribute) -> Self {
+        Self { max_stack: code.max_stack, max_locals: code.max_locals }
+    }
+}
+
+/// A `Code` attribute that declares a smaller `max_stack` or `max_locals` than its code needs
+///
+/// The JVM refuses to load a class like this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSizeWarning {
+    pub declared: FrameSize,
+    pub computed: FrameSize,
+}
+
+impl Display for FrameSizeWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut problems = vec![];
+        if self.declared.max_stack < self.computed.max_stack {
+            problems.push(format!("max_stack is {} but the code needs {}", self.declared.max_stack, self.computed.max_stack));
+        }
+        if self.declared.max_locals < self.computed.max_locals {
+            problems.push(format!("max_locals is {} but the code needs {}", self.declared.max_locals, self.computed.max_locals));
+        }
+        f.write_str(&problems.join(", "))
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FrameSizeError {
+    #[error("couldn't decode the code: {0}")]
+    DecodeError(#[from] DecodeError),
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("invalid descriptor: {0}")]
+    DescriptorError(#[from] DescriptorError),
+    #[error("instruction at pc {pc} pops more values than there are on the stack")]
+    StackUnderflow { pc: u32 },
+    #[error("stack has {expected} slots at pc {pc} when reached from one path and {actual} from another")]
+    InconsistentStackDepth { pc: u32, expected: u32, actual: u32 },
+    #[error("instruction at pc {pc} jumps to {target}, which isn't the start of an instruction")]
+    InvalidBranchTarget { pc: u32, target: u32 },
+    #[error("execution falls off the end of the code after pc {pc}")]
+    FallsOffEnd { pc: u32 },
+    #[error("the code needs {0} slots, more than a u16 can hold")]
+    TooLarge(u32),
+}
+
+/// Computes the frame size of `code`, which belongs to a method with the given descriptor
+///
+/// Instance methods get an extra local for `this`.
+pub fn compute_frame_size(code: &CodeAttribute, constant_pool: &ConstantPool, descriptor: &MethodDescriptor, is_static: bool) -> Result<FrameSize, FrameSizeError> {
+    let instructions: Vec<(u32, Instruction)> = code.instructions().collect::<Result<_, _>>()?;
+
+    let mut max_locals = descriptor.param_slots() as u32 + if is_static { 0 } else { 1 };
+    for (_, instruction) in &instructions {
+        if let Some((index, size)) = instruction.local_variable() {
+            max_locals = max_locals.max(index as u32 + size as u32);
+        }
+    }
+
+    let mut flow = Flow {
+        index_at: vec![None; code.code.len()],
+        depths: vec![None; instructions.len()],
+        worklist: vec![],
+    };
+    for (i, (pc, _)) in instructions.iter().enumerate() {
+        flow.index_at[*pc as usize] = Some(i);
+    }
+    flow.enqueue(0, 0, 0)?;
+
+    let mut max_stack = 0;
+    while let Some(i) = flow.worklist.pop() {
+        let depth = flow.depths[i].unwrap();
+        let (pc, instruction) = &instructions[i];
+        let (pop, push) = stack_effect(instruction, constant_pool)?;
+        if depth < pop {
+            return Err(FrameSizeError::StackUnderflow { pc: *pc });
+        }
+        let after = depth - pop + push;
+        max_stack = max_stack.max(after);
+        for handler in code.exception_table.iter().filter(|entry| entry.covers(*pc as u16)) {
+            max_stack = max_stack.max(1);
+            flow.enqueue(*pc, handler.handler_pc as u32, 1)?;
+        }
+        for target in instruction.branch_targets(*pc) {
+            flow.enqueue(*pc, target, after)?;
+        }
+        if instruction.falls_through() {
+            let next = match instructions.get(i + 1) {
+                Some((next, _)) => *next,
+                None => return Err(FrameSizeError::FallsOffEnd { pc: *pc }),
+            };
+            // The subroutine called by jsr pops its return address before coming back
+            let depth = if matches!(instruction, Instruction::Jsr(_) | Instruction::JsrW(_)) { depth } else { after };
+            flow.enqueue(*pc, next, depth)?;
+        }
+    }
+
+    Ok(FrameSize {
+        max_stack: u16::try_from(max_stack).map_err(|_| FrameSizeError::TooLarge(max_stack))?,
+        max_locals: u16::try_from(max_locals).map_err(|_| FrameSizeError::TooLarge(max_locals))?,
+    })
+}
+
+/// Stack depths found so far and the instructions left to visit
+struct Flow {
+    /// Index of the instruction starting at each pc
+    index_at: Vec<Option<usize>>,
+    /// Stack depth before each instruction, `None` if it hasn't been reached yet
+    depths: Vec<Option<u32>>,
+    worklist: Vec<usize>,
+}
+
+impl Flow {
+    /// Records that the instruction at `target` is reached from `pc` with `depth` slots on the stack
+    fn enqueue(&mut self, pc: u32, target: u32, depth: u32) -> Result<(), FrameSizeError> {
+        let i = self.index_at.get(target as usize)
+            .copied()
+            .flatten()
+            .ok_or(FrameSizeError::InvalidBranchTarget { pc, target })?;
+        match self.depths[i] {
+            None => {
+                self.depths[i] = Some(depth);
+                self.worklist.push(i);
+            }
+            Some(expected) if expected != depth => {
+                return Err(FrameSizeError::InconsistentStackDepth { pc: target, expected, actual: depth });
+            }
+            Some(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Compares the sizes declared by `code` to the computed ones, returning a warning if they're too small
+pub fn check_frame_size(code: &CodeAttribute, computed: FrameSize) -> Option<FrameSizeWarning> {
+    let declared = FrameSize::declared(code);
+    if declared.max_stack < computed.max_stack || declared.max_locals < computed.max_locals {
+        Some(FrameSizeWarning { declared, computed })
+    } else {
+        None
+    }
+}
+
+/// Name and descriptor of the field, method or call site referenced by the constant at `index`
+pub(crate) fn member_name_and_type(constant_pool: &ConstantPool, index: u16) -> Result<(&str, &str), ConstantPoolError> {
+    match constant_pool.get(index)? {
+        Constant::Field { name_and_type_index, .. } |
+        Constant::Method { name_and_type_index, .. } |
+        Constant::InterfaceMethod { name_and_type_index, .. } |
+        Constant::InvokeDynamic { name_and_type_index, .. } |
+        Constant::Dynamic { name_and_type_index, .. } => constant_pool.get_name_and_type(*name_and_type_index),
+        other => Err(ConstantPoolError::UnexpectedKind { index, expected: ConstantKind::Method, actual: other.kind() }),
+    }
+}
+
+fn field_slots(constant_pool: &ConstantPool, index: u16) -> Result<u32, FrameSizeError> {
+    Ok(FieldType::parse(member_name_and_type(constant_pool, index)?.1)?.slot_size() as u32)
+}
+
+/// Slots taken by the arguments and the return value of the method referenced at `index`
+fn method_slots(constant_pool: &ConstantPool, index: u16) -> Result<(u32, u32), FrameSizeError> {
+    let descriptor = MethodDescriptor::parse(member_name_and_type(constant_pool, index)?.1)?;
+    Ok((descriptor.param_slots() as u32, descriptor.return_slots() as u32))
+}
+
+/// How many stack slots `instruction` pops and pushes
+fn stack_effect(instruction: &Instruction, constant_pool: &ConstantPool) -> Result<(u32, u32), FrameSizeError> {
+    use Instruction::*;
+    Ok(match instruction {
+        Nop | Iinc { .. } | Goto(_) | GotoW(_) | Ret(_) | Return => (0, 0),
+        AconstNull | IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 |
+        Fconst0 | Fconst1 | Fconst2 | Bipush(_) | Sipush(_) | Ldc(_) | LdcW(_) |
+        Iload(_) | Fload(_) | Aload(_) | Iload0 | Iload1 | Iload2 | Iload3 | Fload0 | Fload1 | Fload2 | Fload3 |
+        Aload0 | Aload1 | Aload2 | Aload3 | Jsr(_) | JsrW(_) | New(_) => (0, 1),
+        Lconst0 | Lconst1 | Dconst0 | Dconst1 | Ldc2W(_) |
+        Lload(_) | Dload(_) | Lload0 | Lload1 | Lload2 | Lload3 | Dload0 | Dload1 | Dload2 | Dload3 => (0, 2),
+        Iaload | Faload | Aaload | Baload | Caload | Saload => (2, 1),
+        Laload | Daload => (2, 2),
+        Istore(_) | Fstore(_) | Astore(_) | Istore0 | Istore1 | Istore2 | Istore3 | Fstore0 | Fstore1 | Fstore2 | Fstore3 |
+        Astore0 | Astore1 | Astore2 | Astore3 | Pop |
+        Ifeq(_) | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) | Ifnull(_) | Ifnonnull(_) |
+        Tableswitch { .. } | Lookupswitch { .. } | Ireturn | Freturn | Areturn | Athrow | Monitorenter | Monitorexit => (1, 0),
+        Lstore(_) | Dstore(_) | Lstore0 | Lstore1 | Lstore2 | Lstore3 | Dstore0 | Dstore1 | Dstore2 | Dstore3 | Pop2 |
+        IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_) | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) | IfAcmpeq(_) | IfAcmpne(_) |
+        Lreturn | Dreturn => (2, 0),
+        Iastore | Fastore | Aastore | Bastore | Castore | Sastore => (3, 0),
+        Lastore | Dastore => (4, 0),
+        Dup => (1, 2),
+        DupX1 => (2, 3),
+        DupX2 => (3, 4),
+        Dup2 => (2, 4),
+        Dup2X1 => (3, 5),
+        Dup2X2 => (4, 6),
+        Swap => (2, 2),
+        Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor |
+        Fadd | Fsub | Fmul | Fdiv | Frem | Fcmpl | Fcmpg => (2, 1),
+        Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor | Dadd | Dsub | Dmul | Ddiv | Drem => (4, 2),
+        Lshl | Lshr | Lushr => (3, 2),
+        Ineg | Fneg | I2f | F2i | I2b | I2c | I2s | Newarray { .. } | Anewarray(_) | Arraylength |
+        Checkcast(_) | Instanceof(_) => (1, 1),
+        Lneg | Dneg | L2d | D2l => (2, 2),
+        I2l | I2d | F2l | F2d => (1, 2),
+        L2i | L2f | D2i | D2f => (2, 1),
+        Lcmp | Dcmpl | Dcmpg => (4, 1),
+        Getstatic(index) => (0, field_slots(constant_pool, *index)?),
+        Putstatic(index) => (field_slots(constant_pool, *index)?, 0),
+        Getfield(index) => (1, field_slots(constant_pool, *index)?),
+        Putfield(index) => (1 + field_slots(constant_pool, *index)?, 0),
+        Invokevirtual(index) | Invokespecial(index) | Invokeinterface { index, .. } => {
+            let (args, ret) = method_slots(constant_pool, *index)?;
+            (1 + args, ret)
+        }
+        Invokestatic(index) | Invokedynamic { index } => method_slots(constant_pool, *index)?,
+        Multianewarray { dimensions, .. } => (*dimensions as u32, 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_frame_size, compute_frame_size, FrameSize, FrameSizeError};
+    use crate::assembler::Assembler;
+    use crate::constant_pool::ConstantPool;
+    use crate::constant_pool_builder::ConstantPoolBuilder;
+    use crate::descriptor::MethodDescriptor;
+    use crate::instruction::{Instruction, Opcode};
+
+    fn descriptor(s: &str) -> MethodDescriptor {
+        MethodDescriptor::parse(s).unwrap()
+    }
+
+    #[test]
+    pub fn counts_parameter_slots() {
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::Return);
+        let code = asm.finish(0, 0).unwrap();
+        let pool = ConstantPool::new();
+        assert_eq!(compute_frame_size(&code, &pool, &descriptor("(IJD)V"), true).unwrap(), FrameSize { max_stack: 0, max_locals: 5 });
+        assert_eq!(compute_frame_size(&code, &pool, &descriptor("(IJD)V"), false).unwrap(), FrameSize { max_stack: 0, max_locals: 6 });
+    }
+
+    #[test]
+    pub fn follows_every_path() {
+        let mut pool = ConstantPoolBuilder::new();
+        let max = pool.method_ref("java/lang/Math", "max", "(JJ)J").unwrap();
+        let pool = pool.build();
+        let mut asm = Assembler::new();
+        let (other, end, handler, start) = (asm.new_label(), asm.new_label(), asm.new_label(), asm.new_label());
+        asm.bind(start).unwrap();
+        asm.emit(Instruction::Iload0);
+        asm.branch(Opcode::Ifeq, other).unwrap();
+        asm.emit(Instruction::Lconst1);
+        asm.emit(Instruction::Lstore(5));
+        asm.branch(Opcode::Goto, end).unwrap();
+        asm.bind(other).unwrap();
+        asm.emit(Instruction::Lconst0);
+        asm.emit(Instruction::Lconst1);
+        asm.emit(Instruction::Invokestatic(max));
+        asm.emit(Instruction::Pop2);
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+        asm.bind(handler).unwrap();
+        asm.emit(Instruction::Athrow);
+        asm.try_catch(start, end, handler, 0);
+        let code = asm.finish(0, 0).unwrap();
+        let computed = compute_frame_size(&code, &pool, &descriptor("(I)V"), true).unwrap();
+        assert_eq!(computed, FrameSize { max_stack: 4, max_locals: 7 });
+        let warning = check_frame_size(&code, computed).unwrap();
+        assert_eq!(warning.to_string(), "max_stack is 0 but the code needs 4, max_locals is 0 but the code needs 7");
+        let mut code = code;
+        code.max_stack = 4;
+        code.max_locals = 8;
+        assert_eq!(check_frame_size(&code, computed), None);
+    }
+
+    #[test]
+    pub fn reports_broken_code() {
+        let pool = ConstantPool::new();
+        let void = descriptor("()V");
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::Pop);
+        asm.emit(Instruction::Return);
+        assert_eq!(compute_frame_size(&asm.finish(0, 0).unwrap(), &pool, &void, true), Err(FrameSizeError::StackUnderflow { pc: 0 }));
+
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::Nop);
+        assert_eq!(compute_frame_size(&asm.finish(0, 0).unwrap(), &pool, &void, true), Err(FrameSizeError::FallsOffEnd { pc: 0 }));
+
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::Goto(1));
+        asm.emit(Instruction::Return);
+        assert_eq!(
+            compute_frame_size(&asm.finish(0, 0).unwrap(), &pool, &void, true),
+            Err(FrameSizeError::InvalidBranchTarget { pc: 0, target: 1 })
+        );
+
+        let mut asm = Assembler::new();
+        let join = asm.new_label();
+        asm.emit(Instruction::Iconst0);
+        asm.emit(Instruction::Iconst0);
+        asm.branch(Opcode::Ifeq, join).unwrap();
+        asm.emit(Instruction::Iconst0);
+        asm.bind(join).unwrap();
+        asm.emit(Instruction::Return);
+        assert_eq!(
+            compute_frame_size(&asm.finish(0, 0).unwrap(), &pool, &void, true),
+            Err(FrameSizeError::InconsistentStackDepth { pc: 6, expected: 1, actual: 2 })
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! A DEFLATE decoder, for the compressed entries of jars and jmods and the resources of jimages
+//!
+//! Follows [RFC 1951](https://www.rfc-editor.org/rfc/rfc1951) and decodes Huffman codes a bit at a time, which is
+//! slow next to zlib but plenty for reading classes.
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InflateError {
+    #[error("the compressed data ends too early")]
+    UnexpectedEnd,
+    #[error("invalid block type 3")]
+    InvalidBlockType,
+    #[error("the length of a stored block doesn't match its complement")]
+    InvalidStoredLength,
+    #[error("invalid huffman code lengths")]
+    InvalidCodeLengths,
+    #[error("invalid huffman code")]
+    InvalidCode,
+    #[error("a match reaches {distance} bytes back, before the start of the data")]
+    DistanceTooFar { distance: usize },
+    #[error("invalid zlib header")]
+    InvalidZlibHeader,
+    #[error("the adler-32 checksum doesn't match the data")]
+    ChecksumMismatch,
+}
+
+/// Lengths of the codes 257 to 285 of the literal/length alphabet, before the extra bits are added
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order the lengths of the code length alphabet are written in by dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const MAX_BITS: usize = 15;
+
+/// Decompresses raw DEFLATE data, without a zlib or gzip header
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+    inflate_blocks(data).map(|(out, _)| out)
+}
+
+/// Decompresses data with a zlib header and checksum, like what `java.util.zip.Deflater` writes
+pub fn inflate_zlib(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+    let header = data.get(..2).ok_or(InflateError::UnexpectedEnd)?;
+    // Only DEFLATE without a preset dictionary, and the header is a multiple of 31
+    if header[0] & 0x0f != 8 || header[1] & 0x20 != 0 || u16::from_be_bytes([header[0], header[1]]) % 31 != 0 {
+        return Err(InflateError::InvalidZlibHeader);
+    }
+    let (out, end) = inflate_blocks(&data[2..])?;
+    let checksum = data.get(2 + end..2 + end + 4).ok_or(InflateError::UnexpectedEnd)?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(InflateError::ChecksumMismatch);
+    }
+    Ok(out)
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums of this many bytes can't overflow before being reduced
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// The decompressed data and where the last block ends
+fn inflate_blocks(data: &[u8]) -> Result<(Vec<u8>, usize), InflateError> {
+    let mut input = Bits { data, pos: 0, bit: 0 };
+    let mut out = Vec::with_capacity(data.len() * 3);
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored(&mut input, &mut out)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                codes(&mut input, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut input)?;
+                codes(&mut input, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(InflateError::InvalidBlockType),
+        }
+        if last {
+            input.align();
+            return Ok((out, input.pos));
+        }
+    }
+}
+
+/// Reads bits starting from the least significant bit of each byte
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl<'a> Bits<'a> {
+    fn bits(&mut self, count: u8) -> Result<u32, InflateError> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::UnexpectedEnd)?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skips to the start of the next byte
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols sorted by code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// The code where symbol `i` has a code `lengths[i]` bits long, no code if it's 0
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        // Codes can be incomplete, like a distance code with a single distance, but not oversubscribed
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err(InflateError::InvalidCodeLengths);
+            }
+        }
+        let mut offsets = [0; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, input: &mut Bits) -> Result<u16, InflateError> {
+        // Codes of each length follow the last code of the length before, so the code read so far is compared with
+        // the first code of its length
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for length in 1..=MAX_BITS {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError::InvalidCode)
+    }
+}
+
+fn stored(input: &mut Bits, out: &mut Vec<u8>) -> Result<(), InflateError> {
+    input.align();
+    let header = input.data.get(input.pos..input.pos + 4).ok_or(InflateError::UnexpectedEnd)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    if length != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(InflateError::InvalidStoredLength);
+    }
+    let start = input.pos + 4;
+    let bytes = input.data.get(start..start + length as usize).ok_or(InflateError::UnexpectedEnd)?;
+    out.extend_from_slice(bytes);
+    input.pos = start + length as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    // Both codes are complete
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&[5; 30]).unwrap())
+}
+
+fn dynamic_codes(input: &mut Bits) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_length_count = input.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(InflateError::InvalidCodeLengths);
+    }
+    let mut code_lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = input.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(InflateError::InvalidCodeLengths)?, 3 + input.bits(2)?),
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err(InflateError::InvalidCodeLengths);
+        }
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    // Without a code for the end of the block the data could never end
+    if lengths[256] == 0 {
+        return Err(InflateError::InvalidCodeLengths);
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+/// Decodes the literals and matches of a compressed block, up to its end code
+fn codes(input: &mut Bits, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= LENGTH_BASE.len() {
+                    return Err(InflateError::InvalidCode);
+                }
+                let length = LENGTH_BASE[symbol] as usize + input.bits(LENGTH_EXTRA[symbol])? as usize;
+                let symbol = distances.decode(input)? as usize;
+                if symbol >= DISTANCE_BASE.len() {
+                    return Err(InflateError::InvalidCode);
+                }
+                let distance = DISTANCE_BASE[symbol] as usize + input.bits(DISTANCE_EXTRA[symbol])? as usize;
+                if distance > out.len() {
+                    return Err(InflateError::DistanceTooFar { distance });
+                }
+                // Matches can overlap what they copy, so bytes are copied one at a time
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InflateError, inflate, inflate_zlib};
+
+    #[test]
+    pub fn inflates_every_block_type() {
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c']).unwrap(), b"abc");
+        // The match of 6 bytes copies the 3 before it twice
+        assert_eq!(inflate(&[0x4b, 0x4c, 0x4a, 0x4e, 0x04, 0x03, 0x00]).unwrap(), b"abcaaaaaa");
+        let dynamic = [
+            0x05, 0xc1, 0xc1, 0x01, 0x00, 0x20, 0x08, 0x03, 0xb1, 0x85, 0xee, 0x21, 0xa5, 0x16, 0xd9, 0x7f, 0x31, 0x93,
+            0x43, 0x61, 0x96, 0x0a, 0xba, 0x74, 0xf0, 0x12, 0xf3, 0x8a, 0x46, 0xc6, 0xc3, 0x08, 0xf1, 0x01,
+        ];
+        assert_eq!(inflate(&dynamic).unwrap(), b"0,1,4,9,16,25,36,49,64,81,3,24,47,72,2,");
+    }
+
+    #[test]
+    pub fn checks_zlib_headers_and_checksums() {
+        let zlib = [0x78, 0xda, 0x4b, 0x4c, 0x4a, 0x4e, 0x04, 0x03, 0x00, 0x11, 0x2c, 0x03, 0x6d];
+        assert_eq!(inflate_zlib(&zlib).unwrap(), b"abcaaaaaa");
+        let mut wrong_checksum = zlib;
+        wrong_checksum[12] ^= 1;
+        assert_eq!(inflate_zlib(&wrong_checksum), Err(InflateError::ChecksumMismatch));
+        assert_eq!(inflate_zlib(&zlib[..2]), Err(InflateError::UnexpectedEnd));
+        assert_eq!(inflate_zlib(&[0x78, 0xdb, 0x03, 0x00]), Err(InflateError::InvalidZlibHeader));
+    }
+
+    #[test]
+    pub fn rejects_broken_data() {
+        assert_eq!(inflate(&[0x07]), Err(InflateError::InvalidBlockType));
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xfe]), Err(InflateError::InvalidStoredLength));
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a']), Err(InflateError::UnexpectedEnd));
+        assert_eq!(inflate(&[0x4b, 0x04, 0x42, 0x00]).unwrap_err(), InflateError::DistanceTooFar { distance: 2 });
+    }
+}
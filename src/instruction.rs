@@ -0,0 +1,1872 @@
+//! Decoding of the bytecode in a `Code` attribute
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-6.html
+use num_traits::FromPrimitive;
+use thiserror::Error;
+
+use crate::descriptor::BaseType;
+
+/// Every opcode of the JVM, including the reserved ones that can't appear in a class file
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Nop = 0x00,
+    AconstNull = 0x01,
+    IconstM1 = 0x02,
+    Iconst0 = 0x03,
+    Iconst1 = 0x04,
+    Iconst2 = 0x05,
+    Iconst3 = 0x06,
+    Iconst4 = 0x07,
+    Iconst5 = 0x08,
+    Lconst0 = 0x09,
+    Lconst1 = 0x0a,
+    Fconst0 = 0x0b,
+    Fconst1 = 0x0c,
+    Fconst2 = 0x0d,
+    Dconst0 = 0x0e,
+    Dconst1 = 0x0f,
+    Bipush = 0x10,
+    Sipush = 0x11,
+    Ldc = 0x12,
+    LdcW = 0x13,
+    Ldc2W = 0x14,
+    Iload = 0x15,
+    Lload = 0x16,
+    Fload = 0x17,
+    Dload = 0x18,
+    Aload = 0x19,
+    Iload0 = 0x1a,
+    Iload1 = 0x1b,
+    Iload2 = 0x1c,
+    Iload3 = 0x1d,
+    Lload0 = 0x1e,
+    Lload1 = 0x1f,
+    Lload2 = 0x20,
+    Lload3 = 0x21,
+    Fload0 = 0x22,
+    Fload1 = 0x23,
+    Fload2 = 0x24,
+    Fload3 = 0x25,
+    Dload0 = 0x26,
+    Dload1 = 0x27,
+    Dload2 = 0x28,
+    Dload3 = 0x29,
+    Aload0 = 0x2a,
+    Aload1 = 0x2b,
+    Aload2 = 0x2c,
+    Aload3 = 0x2d,
+    Iaload = 0x2e,
+    Laload = 0x2f,
+    Faload = 0x30,
+    Daload = 0x31,
+    Aaload = 0x32,
+    Baload = 0x33,
+    Caload = 0x34,
+    Saload = 0x35,
+    Istore = 0x36,
+    Lstore = 0x37,
+    Fstore = 0x38,
+    Dstore = 0x39,
+    Astore = 0x3a,
+    Istore0 = 0x3b,
+    Istore1 = 0x3c,
+    Istore2 = 0x3d,
+    Istore3 = 0x3e,
+    Lstore0 = 0x3f,
+    Lstore1 = 0x40,
+    Lstore2 = 0x41,
+    Lstore3 = 0x42,
+    Fstore0 = 0x43,
+    Fstore1 = 0x44,
+    Fstore2 = 0x45,
+    Fstore3 = 0x46,
+    Dstore0 = 0x47,
+    Dstore1 = 0x48,
+    Dstore2 = 0x49,
+    Dstore3 = 0x4a,
+    Astore0 = 0x4b,
+    Astore1 = 0x4c,
+    Astore2 = 0x4d,
+    Astore3 = 0x4e,
+    Iastore = 0x4f,
+    Lastore = 0x50,
+    Fastore = 0x51,
+    Dastore = 0x52,
+    Aastore = 0x53,
+    Bastore = 0x54,
+    Castore = 0x55,
+    Sastore = 0x56,
+    Pop = 0x57,
+    Pop2 = 0x58,
+    Dup = 0x59,
+    DupX1 = 0x5a,
+    DupX2 = 0x5b,
+    Dup2 = 0x5c,
+    Dup2X1 = 0x5d,
+    Dup2X2 = 0x5e,
+    Swap = 0x5f,
+    Iadd = 0x60,
+    Ladd = 0x61,
+    Fadd = 0x62,
+    Dadd = 0x63,
+    Isub = 0x64,
+    Lsub = 0x65,
+    Fsub = 0x66,
+    Dsub = 0x67,
+    Imul = 0x68,
+    Lmul = 0x69,
+    Fmul = 0x6a,
+    Dmul = 0x6b,
+    Idiv = 0x6c,
+    Ldiv = 0x6d,
+    Fdiv = 0x6e,
+    Ddiv = 0x6f,
+    Irem = 0x70,
+    Lrem = 0x71,
+    Frem = 0x72,
+    Drem = 0x73,
+    Ineg = 0x74,
+    Lneg = 0x75,
+    Fneg = 0x76,
+    Dneg = 0x77,
+    Ishl = 0x78,
+    Lshl = 0x79,
+    Ishr = 0x7a,
+    Lshr = 0x7b,
+    Iushr = 0x7c,
+    Lushr = 0x7d,
+    Iand = 0x7e,
+    Land = 0x7f,
+    Ior = 0x80,
+    Lor = 0x81,
+    Ixor = 0x82,
+    Lxor = 0x83,
+    Iinc = 0x84,
+    I2l = 0x85,
+    I2f = 0x86,
+    I2d = 0x87,
+    L2i = 0x88,
+    L2f = 0x89,
+    L2d = 0x8a,
+    F2i = 0x8b,
+    F2l = 0x8c,
+    F2d = 0x8d,
+    D2i = 0x8e,
+    D2l = 0x8f,
+    D2f = 0x90,
+    I2b = 0x91,
+    I2c = 0x92,
+    I2s = 0x93,
+    Lcmp = 0x94,
+    Fcmpl = 0x95,
+    Fcmpg = 0x96,
+    Dcmpl = 0x97,
+    Dcmpg = 0x98,
+    Ifeq = 0x99,
+    Ifne = 0x9a,
+    Iflt = 0x9b,
+    Ifge = 0x9c,
+    Ifgt = 0x9d,
+    Ifle = 0x9e,
+    IfIcmpeq = 0x9f,
+    IfIcmpne = 0xa0,
+    IfIcmplt = 0xa1,
+    IfIcmpge = 0xa2,
+    IfIcmpgt = 0xa3,
+    IfIcmple = 0xa4,
+    IfAcmpeq = 0xa5,
+    IfAcmpne = 0xa6,
+    Goto = 0xa7,
+    Jsr = 0xa8,
+    Ret = 0xa9,
+    Tableswitch = 0xaa,
+    Lookupswitch = 0xab,
+    Ireturn = 0xac,
+    Lreturn = 0xad,
+    Freturn = 0xae,
+    Dreturn = 0xaf,
+    Areturn = 0xb0,
+    Return = 0xb1,
+    Getstatic = 0xb2,
+    Putstatic = 0xb3,
+    Getfield = 0xb4,
+    Putfield = 0xb5,
+    Invokevirtual = 0xb6,
+    Invokespecial = 0xb7,
+    Invokestatic = 0xb8,
+    Invokeinterface = 0xb9,
+    Invokedynamic = 0xba,
+    New = 0xbb,
+    Newarray = 0xbc,
+    Anewarray = 0xbd,
+    Arraylength = 0xbe,
+    Athrow = 0xbf,
+    Checkcast = 0xc0,
+    Instanceof = 0xc1,
+    Monitorenter = 0xc2,
+    Monitorexit = 0xc3,
+    Wide = 0xc4,
+    Multianewarray = 0xc5,
+    Ifnull = 0xc6,
+    Ifnonnull = 0xc7,
+    GotoW = 0xc8,
+    JsrW = 0xc9,
+    Breakpoint = 0xca,
+    Impdep1 = 0xfe,
+    Impdep2 = 0xff,
+}
+
+impl Opcode {
+    /// Name of the opcode as written in the specification, like `invokevirtual`
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Nop => "nop",
+            Opcode::AconstNull => "aconst_null",
+            Opcode::IconstM1 => "iconst_m1",
+            Opcode::Iconst0 => "iconst_0",
+            Opcode::Iconst1 => "iconst_1",
+            Opcode::Iconst2 => "iconst_2",
+            Opcode::Iconst3 => "iconst_3",
+            Opcode::Iconst4 => "iconst_4",
+            Opcode::Iconst5 => "iconst_5",
+            Opcode::Lconst0 => "lconst_0",
+            Opcode::Lconst1 => "lconst_1",
+            Opcode::Fconst0 => "fconst_0",
+            Opcode::Fconst1 => "fconst_1",
+            Opcode::Fconst2 => "fconst_2",
+            Opcode::Dconst0 => "dconst_0",
+            Opcode::Dconst1 => "dconst_1",
+            Opcode::Bipush => "bipush",
+            Opcode::Sipush => "sipush",
+            Opcode::Ldc => "ldc",
+            Opcode::LdcW => "ldc_w",
+            Opcode::Ldc2W => "ldc2_w",
+            Opcode::Iload => "iload",
+            Opcode::Lload => "lload",
+            Opcode::Fload => "fload",
+            Opcode::Dload => "dload",
+            Opcode::Aload => "aload",
+            Opcode::Iload0 => "iload_0",
+            Opcode::Iload1 => "iload_1",
+            Opcode::Iload2 => "iload_2",
+            Opcode::Iload3 => "iload_3",
+            Opcode::Lload0 => "lload_0",
+            Opcode::Lload1 => "lload_1",
+            Opcode::Lload2 => "lload_2",
+            Opcode::Lload3 => "lload_3",
+            Opcode::Fload0 => "fload_0",
+            Opcode::Fload1 => "fload_1",
+            Opcode::Fload2 => "fload_2",
+            Opcode::Fload3 => "fload_3",
+            Opcode::Dload0 => "dload_0",
+            Opcode::Dload1 => "dload_1",
+            Opcode::Dload2 => "dload_2",
+            Opcode::Dload3 => "dload_3",
+            Opcode::Aload0 => "aload_0",
+            Opcode::Aload1 => "aload_1",
+            Opcode::Aload2 => "aload_2",
+            Opcode::Aload3 => "aload_3",
+            Opcode::Iaload => "iaload",
+            Opcode::Laload => "laload",
+            Opcode::Faload => "faload",
+            Opcode::Daload => "daload",
+            Opcode::Aaload => "aaload",
+            Opcode::Baload => "baload",
+            Opcode::Caload => "caload",
+            Opcode::Saload => "saload",
+            Opcode::Istore => "istore",
+            Opcode::Lstore => "lstore",
+            Opcode::Fstore => "fstore",
+            Opcode::Dstore => "dstore",
+            Opcode::Astore => "astore",
+            Opcode::Istore0 => "istore_0",
+            Opcode::Istore1 => "istore_1",
+            Opcode::Istore2 => "istore_2",
+            Opcode::Istore3 => "istore_3",
+            Opcode::Lstore0 => "lstore_0",
+            Opcode::Lstore1 => "lstore_1",
+            Opcode::Lstore2 => "lstore_2",
+            Opcode::Lstore3 => "lstore_3",
+            Opcode::Fstore0 => "fstore_0",
+            Opcode::Fstore1 => "fstore_1",
+            Opcode::Fstore2 => "fstore_2",
+            Opcode::Fstore3 => "fstore_3",
+            Opcode::Dstore0 => "dstore_0",
+            Opcode::Dstore1 => "dstore_1",
+            Opcode::Dstore2 => "dstore_2",
+            Opcode::Dstore3 => "dstore_3",
+            Opcode::Astore0 => "astore_0",
+            Opcode::Astore1 => "astore_1",
+            Opcode::Astore2 => "astore_2",
+            Opcode::Astore3 => "astore_3",
+            Opcode::Iastore => "iastore",
+            Opcode::Lastore => "lastore",
+            Opcode::Fastore => "fastore",
+            Opcode::Dastore => "dastore",
+            Opcode::Aastore => "aastore",
+            Opcode::Bastore => "bastore",
+            Opcode::Castore => "castore",
+            Opcode::Sastore => "sastore",
+            Opcode::Pop => "pop",
+            Opcode::Pop2 => "pop2",
+            Opcode::Dup => "dup",
+            Opcode::DupX1 => "dup_x1",
+            Opcode::DupX2 => "dup_x2",
+            Opcode::Dup2 => "dup2",
+            Opcode::Dup2X1 => "dup2_x1",
+            Opcode::Dup2X2 => "dup2_x2",
+            Opcode::Swap => "swap",
+            Opcode::Iadd => "iadd",
+            Opcode::Ladd => "ladd",
+            Opcode::Fadd => "fadd",
+            Opcode::Dadd => "dadd",
+            Opcode::Isub => "isub",
+            Opcode::Lsub => "lsub",
+            Opcode::Fsub => "fsub",
+            Opcode::Dsub => "dsub",
+            Opcode::Imul => "imul",
+            Opcode::Lmul => "lmul",
+            Opcode::Fmul => "fmul",
+            Opcode::Dmul => "dmul",
+            Opcode::Idiv => "idiv",
+            Opcode::Ldiv => "ldiv",
+            Opcode::Fdiv => "fdiv",
+            Opcode::Ddiv => "ddiv",
+            Opcode::Irem => "irem",
+            Opcode::Lrem => "lrem",
+            Opcode::Frem => "frem",
+            Opcode::Drem => "drem",
+            Opcode::Ineg => "ineg",
+            Opcode::Lneg => "lneg",
+            Opcode::Fneg => "fneg",
+            Opcode::Dneg => "dneg",
+            Opcode::Ishl => "ishl",
+            Opcode::Lshl => "lshl",
+            Opcode::Ishr => "ishr",
+            Opcode::Lshr => "lshr",
+            Opcode::Iushr => "iushr",
+            Opcode::Lushr => "lushr",
+            Opcode::Iand => "iand",
+            Opcode::Land => "land",
+            Opcode::Ior => "ior",
+            Opcode::Lor => "lor",
+            Opcode::Ixor => "ixor",
+            Opcode::Lxor => "lxor",
+            Opcode::Iinc => "iinc",
+            Opcode::I2l => "i2l",
+            Opcode::I2f => "i2f",
+            Opcode::I2d => "i2d",
+            Opcode::L2i => "l2i",
+            Opcode::L2f => "l2f",
+            Opcode::L2d => "l2d",
+            Opcode::F2i => "f2i",
+            Opcode::F2l => "f2l",
+            Opcode::F2d => "f2d",
+            Opcode::D2i => "d2i",
+            Opcode::D2l => "d2l",
+            Opcode::D2f => "d2f",
+            Opcode::I2b => "i2b",
+            Opcode::I2c => "i2c",
+            Opcode::I2s => "i2s",
+            Opcode::Lcmp => "lcmp",
+            Opcode::Fcmpl => "fcmpl",
+            Opcode::Fcmpg => "fcmpg",
+            Opcode::Dcmpl => "dcmpl",
+            Opcode::Dcmpg => "dcmpg",
+            Opcode::Ifeq => "ifeq",
+            Opcode::Ifne => "ifne",
+            Opcode::Iflt => "iflt",
+            Opcode::Ifge => "ifge",
+            Opcode::Ifgt => "ifgt",
+            Opcode::Ifle => "ifle",
+            Opcode::IfIcmpeq => "if_icmpeq",
+            Opcode::IfIcmpne => "if_icmpne",
+            Opcode::IfIcmplt => "if_icmplt",
+            Opcode::IfIcmpge => "if_icmpge",
+            Opcode::IfIcmpgt => "if_icmpgt",
+            Opcode::IfIcmple => "if_icmple",
+            Opcode::IfAcmpeq => "if_acmpeq",
+            Opcode::IfAcmpne => "if_acmpne",
+            Opcode::Goto => "goto",
+            Opcode::Jsr => "jsr",
+            Opcode::Ret => "ret",
+            Opcode::Tableswitch => "tableswitch",
+            Opcode::Lookupswitch => "lookupswitch",
+            Opcode::Ireturn => "ireturn",
+            Opcode::Lreturn => "lreturn",
+            Opcode::Freturn => "freturn",
+            Opcode::Dreturn => "dreturn",
+            Opcode::Areturn => "areturn",
+            Opcode::Return => "return",
+            Opcode::Getstatic => "getstatic",
+            Opcode::Putstatic => "putstatic",
+            Opcode::Getfield => "getfield",
+            Opcode::Putfield => "putfield",
+            Opcode::Invokevirtual => "invokevirtual",
+            Opcode::Invokespecial => "invokespecial",
+            Opcode::Invokestatic => "invokestatic",
+            Opcode::Invokeinterface => "invokeinterface",
+            Opcode::Invokedynamic => "invokedynamic",
+            Opcode::New => "new",
+            Opcode::Newarray => "newarray",
+            Opcode::Anewarray => "anewarray",
+            Opcode::Arraylength => "arraylength",
+            Opcode::Athrow => "athrow",
+            Opcode::Checkcast => "checkcast",
+            Opcode::Instanceof => "instanceof",
+            Opcode::Monitorenter => "monitorenter",
+            Opcode::Monitorexit => "monitorexit",
+            Opcode::Wide => "wide",
+            Opcode::Multianewarray => "multianewarray",
+            Opcode::Ifnull => "ifnull",
+            Opcode::Ifnonnull => "ifnonnull",
+            Opcode::GotoW => "goto_w",
+            Opcode::JsrW => "jsr_w",
+            Opcode::Breakpoint => "breakpoint",
+            Opcode::Impdep1 => "impdep1",
+            Opcode::Impdep2 => "impdep2",
+        }
+    }
+
+    /// The opcode called `mnemonic`, the reverse of [`Opcode::mnemonic`]
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        (0..=u8::MAX).filter_map(Opcode::from_u8).find(|opcode| opcode.mnemonic() == mnemonic)
+    }
+}
+
+/// Element type of an array created by `newarray`
+#[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArrayType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+}
+
+impl ArrayType {
+    /// Type of the elements of the array
+    pub fn base_type(&self) -> BaseType {
+        match self {
+            ArrayType::Boolean => BaseType::Boolean,
+            ArrayType::Char => BaseType::Char,
+            ArrayType::Float => BaseType::Float,
+            ArrayType::Double => BaseType::Double,
+            ArrayType::Byte => BaseType::Byte,
+            ArrayType::Short => BaseType::Short,
+            ArrayType::Int => BaseType::Int,
+            ArrayType::Long => BaseType::Long,
+        }
+    }
+
+    /// Name of the element type, like `int`
+    pub fn name(&self) -> &'static str {
+        self.base_type().java_name()
+    }
+
+    pub fn from_name(name: &str) -> Option<ArrayType> {
+        (4..=11).filter_map(ArrayType::from_u8).find(|array_type| array_type.name() == name)
+    }
+}
+
+/// A decoded instruction along with its operands
+///
+/// Local variable indices are always 16 bits wide, the `wide` prefix decodes into the same variants as the short forms.
+/// Branch and switch offsets are relative to the pc of the instruction, like in the class file.
+/// Constant pool indices aren't resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    Bipush(i8),
+    Sipush(i16),
+    Ldc(u8),
+    LdcW(u16),
+    Ldc2W(u16),
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc {
+        /// Local variable to increment
+        index: u16,
+        value: i16,
+    },
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    Ifeq(i16),
+    Ifne(i16),
+    Iflt(i16),
+    Ifge(i16),
+    Ifgt(i16),
+    Ifle(i16),
+    IfIcmpeq(i16),
+    IfIcmpne(i16),
+    IfIcmplt(i16),
+    IfIcmpge(i16),
+    IfIcmpgt(i16),
+    IfIcmple(i16),
+    IfAcmpeq(i16),
+    IfAcmpne(i16),
+    Goto(i16),
+    Jsr(i16),
+    Ret(u16),
+    Tableswitch {
+        default: i32,
+        low: i32,
+        high: i32,
+        /// Jump offsets for the values `low..=high`, in order
+        offsets: Vec<i32>,
+    },
+    Lookupswitch {
+        default: i32,
+        /// Pairs of match value and jump offset, sorted by the match value
+        pairs: Vec<(i32, i32)>,
+    },
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    Getstatic(u16),
+    Putstatic(u16),
+    Getfield(u16),
+    Putfield(u16),
+    Invokevirtual(u16),
+    Invokespecial(u16),
+    Invokestatic(u16),
+    Invokeinterface {
+        /// Points to an `InterfaceMethodref` in the constant pool
+        index: u16,
+        /// Number of stack slots taken by the arguments, including the receiver
+        count: u8,
+    },
+    Invokedynamic {
+        /// Points to an `InvokeDynamic` constant in the constant pool
+        index: u16,
+    },
+    New(u16),
+    Newarray {
+        atype: ArrayType,
+    },
+    Anewarray(u16),
+    Arraylength,
+    Athrow,
+    Checkcast(u16),
+    Instanceof(u16),
+    Monitorenter,
+    Monitorexit,
+    Multianewarray {
+        /// Points to the class of the array in the constant pool
+        index: u16,
+        dimensions: u8,
+    },
+    Ifnull(i16),
+    Ifnonnull(i16),
+    GotoW(i32),
+    JsrW(i32),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("unknown opcode {opcode:#04x} at pc {pc}")]
+    UnknownOpcode { pc: u32, opcode: u8 },
+    #[error("reserved opcode {} at pc {pc}", opcode.mnemonic())]
+    ReservedOpcode { pc: u32, opcode: Opcode },
+    #[error("instruction at pc {pc} is truncated")]
+    Truncated { pc: u32 },
+    #[error("opcode {opcode:#04x} can't follow wide at pc {pc}")]
+    InvalidWideOpcode { pc: u32, opcode: u8 },
+    #[error("invalid newarray type {atype} at pc {pc}")]
+    InvalidArrayType { pc: u32, atype: u8 },
+    #[error("tableswitch at pc {pc} has low {low} greater than high {high}")]
+    InvalidSwitchRange { pc: u32, low: i32, high: i32 },
+    #[error("lookupswitch at pc {pc} has a negative number of pairs ({npairs})")]
+    InvalidLookupswitchPairs { pc: u32, npairs: i32 },
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Nop => Opcode::Nop,
+            Instruction::AconstNull => Opcode::AconstNull,
+            Instruction::IconstM1 => Opcode::IconstM1,
+            Instruction::Iconst0 => Opcode::Iconst0,
+            Instruction::Iconst1 => Opcode::Iconst1,
+            Instruction::Iconst2 => Opcode::Iconst2,
+            Instruction::Iconst3 => Opcode::Iconst3,
+            Instruction::Iconst4 => Opcode::Iconst4,
+            Instruction::Iconst5 => Opcode::Iconst5,
+            Instruction::Lconst0 => Opcode::Lconst0,
+            Instruction::Lconst1 => Opcode::Lconst1,
+            Instruction::Fconst0 => Opcode::Fconst0,
+            Instruction::Fconst1 => Opcode::Fconst1,
+            Instruction::Fconst2 => Opcode::Fconst2,
+            Instruction::Dconst0 => Opcode::Dconst0,
+            Instruction::Dconst1 => Opcode::Dconst1,
+            Instruction::Bipush(_) => Opcode::Bipush,
+            Instruction::Sipush(_) => Opcode::Sipush,
+            Instruction::Ldc(_) => Opcode::Ldc,
+            Instruction::LdcW(_) => Opcode::LdcW,
+            Instruction::Ldc2W(_) => Opcode::Ldc2W,
+            Instruction::Iload(_) => Opcode::Iload,
+            Instruction::Lload(_) => Opcode::Lload,
+            Instruction::Fload(_) => Opcode::Fload,
+            Instruction::Dload(_) => Opcode::Dload,
+            Instruction::Aload(_) => Opcode::Aload,
+            Instruction::Iload0 => Opcode::Iload0,
+            Instruction::Iload1 => Opcode::Iload1,
+            Instruction::Iload2 => Opcode::Iload2,
+            Instruction::Iload3 => Opcode::Iload3,
+            Instruction::Lload0 => Opcode::Lload0,
+            Instruction::Lload1 => Opcode::Lload1,
+            Instruction::Lload2 => Opcode::Lload2,
+            Instruction::Lload3 => Opcode::Lload3,
+            Instruction::Fload0 => Opcode::Fload0,
+            Instruction::Fload1 => Opcode::Fload1,
+            Instruction::Fload2 => Opcode::Fload2,
+            Instruction::Fload3 => Opcode::Fload3,
+            Instruction::Dload0 => Opcode::Dload0,
+            Instruction::Dload1 => Opcode::Dload1,
+            Instruction::Dload2 => Opcode::Dload2,
+            Instruction::Dload3 => Opcode::Dload3,
+            Instruction::Aload0 => Opcode::Aload0,
+            Instruction::Aload1 => Opcode::Aload1,
+            Instruction::Aload2 => Opcode::Aload2,
+            Instruction::Aload3 => Opcode::Aload3,
+            Instruction::Iaload => Opcode::Iaload,
+            Instruction::Laload => Opcode::Laload,
+            Instruction::Faload => Opcode::Faload,
+            Instruction::Daload => Opcode::Daload,
+            Instruction::Aaload => Opcode::Aaload,
+            Instruction::Baload => Opcode::Baload,
+            Instruction::Caload => Opcode::Caload,
+            Instruction::Saload => Opcode::Saload,
+            Instruction::Istore(_) => Opcode::Istore,
+            Instruction::Lstore(_) => Opcode::Lstore,
+            Instruction::Fstore(_) => Opcode::Fstore,
+            Instruction::Dstore(_) => Opcode::Dstore,
+            Instruction::Astore(_) => Opcode::Astore,
+            Instruction::Istore0 => Opcode::Istore0,
+            Instruction::Istore1 => Opcode::Istore1,
+            Instruction::Istore2 => Opcode::Istore2,
+            Instruction::Istore3 => Opcode::Istore3,
+            Instruction::Lstore0 => Opcode::Lstore0,
+            Instruction::Lstore1 => Opcode::Lstore1,
+            Instruction::Lstore2 => Opcode::Lstore2,
+            Instruction::Lstore3 => Opcode::Lstore3,
+            Instruction::Fstore0 => Opcode::Fstore0,
+            Instruction::Fstore1 => Opcode::Fstore1,
+            Instruction::Fstore2 => Opcode::Fstore2,
+            Instruction::Fstore3 => Opcode::Fstore3,
+            Instruction::Dstore0 => Opcode::Dstore0,
+            Instruction::Dstore1 => Opcode::Dstore1,
+            Instruction::Dstore2 => Opcode::Dstore2,
+            Instruction::Dstore3 => Opcode::Dstore3,
+            Instruction::Astore0 => Opcode::Astore0,
+            Instruction::Astore1 => Opcode::Astore1,
+            Instruction::Astore2 => Opcode::Astore2,
+            Instruction::Astore3 => Opcode::Astore3,
+            Instruction::Iastore => Opcode::Iastore,
+            Instruction::Lastore => Opcode::Lastore,
+            Instruction::Fastore => Opcode::Fastore,
+            Instruction::Dastore => Opcode::Dastore,
+            Instruction::Aastore => Opcode::Aastore,
+            Instruction::Bastore => Opcode::Bastore,
+            Instruction::Castore => Opcode::Castore,
+            Instruction::Sastore => Opcode::Sastore,
+            Instruction::Pop => Opcode::Pop,
+            Instruction::Pop2 => Opcode::Pop2,
+            Instruction::Dup => Opcode::Dup,
+            Instruction::DupX1 => Opcode::DupX1,
+            Instruction::DupX2 => Opcode::DupX2,
+            Instruction::Dup2 => Opcode::Dup2,
+            Instruction::Dup2X1 => Opcode::Dup2X1,
+            Instruction::Dup2X2 => Opcode::Dup2X2,
+            Instruction::Swap => Opcode::Swap,
+            Instruction::Iadd => Opcode::Iadd,
+            Instruction::Ladd => Opcode::Ladd,
+            Instruction::Fadd => Opcode::Fadd,
+            Instruction::Dadd => Opcode::Dadd,
+            Instruction::Isub => Opcode::Isub,
+            Instruction::Lsub => Opcode::Lsub,
+            Instruction::Fsub => Opcode::Fsub,
+            Instruction::Dsub => Opcode::Dsub,
+            Instruction::Imul => Opcode::Imul,
+            Instruction::Lmul => Opcode::Lmul,
+            Instruction::Fmul => Opcode::Fmul,
+            Instruction::Dmul => Opcode::Dmul,
+            Instruction::Idiv => Opcode::Idiv,
+            Instruction::Ldiv => Opcode::Ldiv,
+            Instruction::Fdiv => Opcode::Fdiv,
+            Instruction::Ddiv => Opcode::Ddiv,
+            Instruction::Irem => Opcode::Irem,
+            Instruction::Lrem => Opcode::Lrem,
+            Instruction::Frem => Opcode::Frem,
+            Instruction::Drem => Opcode::Drem,
+            Instruction::Ineg => Opcode::Ineg,
+            Instruction::Lneg => Opcode::Lneg,
+            Instruction::Fneg => Opcode::Fneg,
+            Instruction::Dneg => Opcode::Dneg,
+            Instruction::Ishl => Opcode::Ishl,
+            Instruction::Lshl => Opcode::Lshl,
+            Instruction::Ishr => Opcode::Ishr,
+            Instruction::Lshr => Opcode::Lshr,
+            Instruction::Iushr => Opcode::Iushr,
+            Instruction::Lushr => Opcode::Lushr,
+            Instruction::Iand => Opcode::Iand,
+            Instruction::Land => Opcode::Land,
+            Instruction::Ior => Opcode::Ior,
+            Instruction::Lor => Opcode::Lor,
+            Instruction::Ixor => Opcode::Ixor,
+            Instruction::Lxor => Opcode::Lxor,
+            Instruction::Iinc { .. } => Opcode::Iinc,
+            Instruction::I2l => Opcode::I2l,
+            Instruction::I2f => Opcode::I2f,
+            Instruction::I2d => Opcode::I2d,
+            Instruction::L2i => Opcode::L2i,
+            Instruction::L2f => Opcode::L2f,
+            Instruction::L2d => Opcode::L2d,
+            Instruction::F2i => Opcode::F2i,
+            Instruction::F2l => Opcode::F2l,
+            Instruction::F2d => Opcode::F2d,
+            Instruction::D2i => Opcode::D2i,
+            Instruction::D2l => Opcode::D2l,
+            Instruction::D2f => Opcode::D2f,
+            Instruction::I2b => Opcode::I2b,
+            Instruction::I2c => Opcode::I2c,
+            Instruction::I2s => Opcode::I2s,
+            Instruction::Lcmp => Opcode::Lcmp,
+            Instruction::Fcmpl => Opcode::Fcmpl,
+            Instruction::Fcmpg => Opcode::Fcmpg,
+            Instruction::Dcmpl => Opcode::Dcmpl,
+            Instruction::Dcmpg => Opcode::Dcmpg,
+            Instruction::Ifeq(_) => Opcode::Ifeq,
+            Instruction::Ifne(_) => Opcode::Ifne,
+            Instruction::Iflt(_) => Opcode::Iflt,
+            Instruction::Ifge(_) => Opcode::Ifge,
+            Instruction::Ifgt(_) => Opcode::Ifgt,
+            Instruction::Ifle(_) => Opcode::Ifle,
+            Instruction::IfIcmpeq(_) => Opcode::IfIcmpeq,
+            Instruction::IfIcmpne(_) => Opcode::IfIcmpne,
+            Instruction::IfIcmplt(_) => Opcode::IfIcmplt,
+            Instruction::IfIcmpge(_) => Opcode::IfIcmpge,
+            Instruction::IfIcmpgt(_) => Opcode::IfIcmpgt,
+            Instruction::IfIcmple(_) => Opcode::IfIcmple,
+            Instruction::IfAcmpeq(_) => Opcode::IfAcmpeq,
+            Instruction::IfAcmpne(_) => Opcode::IfAcmpne,
+            Instruction::Goto(_) => Opcode::Goto,
+            Instruction::Jsr(_) => Opcode::Jsr,
+            Instruction::Ret(_) => Opcode::Ret,
+            Instruction::Tableswitch { .. } => Opcode::Tableswitch,
+            Instruction::Lookupswitch { .. } => Opcode::Lookupswitch,
+            Instruction::Ireturn => Opcode::Ireturn,
+            Instruction::Lreturn => Opcode::Lreturn,
+            Instruction::Freturn => Opcode::Freturn,
+            Instruction::Dreturn => Opcode::Dreturn,
+            Instruction::Areturn => Opcode::Areturn,
+            Instruction::Return => Opcode::Return,
+            Instruction::Getstatic(_) => Opcode::Getstatic,
+            Instruction::Putstatic(_) => Opcode::Putstatic,
+            Instruction::Getfield(_) => Opcode::Getfield,
+            Instruction::Putfield(_) => Opcode::Putfield,
+            Instruction::Invokevirtual(_) => Opcode::Invokevirtual,
+            Instruction::Invokespecial(_) => Opcode::Invokespecial,
+            Instruction::Invokestatic(_) => Opcode::Invokestatic,
+            Instruction::Invokeinterface { .. } => Opcode::Invokeinterface,
+            Instruction::Invokedynamic { .. } => Opcode::Invokedynamic,
+            Instruction::New(_) => Opcode::New,
+            Instruction::Newarray { .. } => Opcode::Newarray,
+            Instruction::Anewarray(_) => Opcode::Anewarray,
+            Instruction::Arraylength => Opcode::Arraylength,
+            Instruction::Athrow => Opcode::Athrow,
+            Instruction::Checkcast(_) => Opcode::Checkcast,
+            Instruction::Instanceof(_) => Opcode::Instanceof,
+            Instruction::Monitorenter => Opcode::Monitorenter,
+            Instruction::Monitorexit => Opcode::Monitorexit,
+            Instruction::Multianewarray { .. } => Opcode::Multianewarray,
+            Instruction::Ifnull(_) => Opcode::Ifnull,
+            Instruction::Ifnonnull(_) => Opcode::Ifnonnull,
+            Instruction::GotoW(_) => Opcode::GotoW,
+            Instruction::JsrW(_) => Opcode::JsrW,
+        }
+    }
+
+    /// The instruction that is just `opcode`, `None` if it has operands, is `wide` or is reserved and can't be used
+    pub fn from_opcode(opcode: Opcode) -> Option<Instruction> {
+        Instruction::decode(&[opcode as u8], 0).ok().map(|(instruction, _)| instruction)
+    }
+
+    /// Decodes the instruction at `pc`, returning it along with the pc of the next instruction
+    pub fn decode(code: &[u8], pc: u32) -> Result<(Instruction, u32), DecodeError> {
+        let mut r = Operands { code, pc, pos: pc as usize };
+        let instruction = decode(&mut r)?;
+        Ok((instruction, r.pos as u32))
+    }
+
+    /// Appends the instruction, as if it was at `pc`, to `out`
+    ///
+    /// Local variable indices over 255 and `iinc`s that don't fit in a byte get the `wide` prefix, everything else
+    /// uses the short form.
+    pub fn encode(&self, pc: u32, out: &mut Vec<u8>) {
+        let opcode = self.opcode() as u8;
+        match self {
+            Instruction::Nop |
+            Instruction::AconstNull |
+            Instruction::IconstM1 |
+            Instruction::Iconst0 |
+            Instruction::Iconst1 |
+            Instruction::Iconst2 |
+            Instruction::Iconst3 |
+            Instruction::Iconst4 |
+            Instruction::Iconst5 |
+            Instruction::Lconst0 |
+            Instruction::Lconst1 |
+            Instruction::Fconst0 |
+            Instruction::Fconst1 |
+            Instruction::Fconst2 |
+            Instruction::Dconst0 |
+            Instruction::Dconst1 |
+            Instruction::Iload0 |
+            Instruction::Iload1 |
+            Instruction::Iload2 |
+            Instruction::Iload3 |
+            Instruction::Lload0 |
+            Instruction::Lload1 |
+            Instruction::Lload2 |
+            Instruction::Lload3 |
+            Instruction::Fload0 |
+            Instruction::Fload1 |
+            Instruction::Fload2 |
+            Instruction::Fload3 |
+            Instruction::Dload0 |
+            Instruction::Dload1 |
+            Instruction::Dload2 |
+            Instruction::Dload3 |
+            Instruction::Aload0 |
+            Instruction::Aload1 |
+            Instruction::Aload2 |
+            Instruction::Aload3 |
+            Instruction::Iaload |
+            Instruction::Laload |
+            Instruction::Faload |
+            Instruction::Daload |
+            Instruction::Aaload |
+            Instruction::Baload |
+            Instruction::Caload |
+            Instruction::Saload |
+            Instruction::Istore0 |
+            Instruction::Istore1 |
+            Instruction::Istore2 |
+            Instruction::Istore3 |
+            Instruction::Lstore0 |
+            Instruction::Lstore1 |
+            Instruction::Lstore2 |
+            Instruction::Lstore3 |
+            Instruction::Fstore0 |
+            Instruction::Fstore1 |
+            Instruction::Fstore2 |
+            Instruction::Fstore3 |
+            Instruction::Dstore0 |
+            Instruction::Dstore1 |
+            Instruction::Dstore2 |
+            Instruction::Dstore3 |
+            Instruction::Astore0 |
+            Instruction::Astore1 |
+            Instruction::Astore2 |
+            Instruction::Astore3 |
+            Instruction::Iastore |
+            Instruction::Lastore |
+            Instruction::Fastore |
+            Instruction::Dastore |
+            Instruction::Aastore |
+            Instruction::Bastore |
+            Instruction::Castore |
+            Instruction::Sastore |
+            Instruction::Pop |
+            Instruction::Pop2 |
+            Instruction::Dup |
+            Instruction::DupX1 |
+            Instruction::DupX2 |
+            Instruction::Dup2 |
+            Instruction::Dup2X1 |
+            Instruction::Dup2X2 |
+            Instruction::Swap |
+            Instruction::Iadd |
+            Instruction::Ladd |
+            Instruction::Fadd |
+            Instruction::Dadd |
+            Instruction::Isub |
+            Instruction::Lsub |
+            Instruction::Fsub |
+            Instruction::Dsub |
+            Instruction::Imul |
+            Instruction::Lmul |
+            Instruction::Fmul |
+            Instruction::Dmul |
+            Instruction::Idiv |
+            Instruction::Ldiv |
+            Instruction::Fdiv |
+            Instruction::Ddiv |
+            Instruction::Irem |
+            Instruction::Lrem |
+            Instruction::Frem |
+            Instruction::Drem |
+            Instruction::Ineg |
+            Instruction::Lneg |
+            Instruction::Fneg |
+            Instruction::Dneg |
+            Instruction::Ishl |
+            Instruction::Lshl |
+            Instruction::Ishr |
+            Instruction::Lshr |
+            Instruction::Iushr |
+            Instruction::Lushr |
+            Instruction::Iand |
+            Instruction::Land |
+            Instruction::Ior |
+            Instruction::Lor |
+            Instruction::Ixor |
+            Instruction::Lxor |
+            Instruction::I2l |
+            Instruction::I2f |
+            Instruction::I2d |
+            Instruction::L2i |
+            Instruction::L2f |
+            Instruction::L2d |
+            Instruction::F2i |
+            Instruction::F2l |
+            Instruction::F2d |
+            Instruction::D2i |
+            Instruction::D2l |
+            Instruction::D2f |
+            Instruction::I2b |
+            Instruction::I2c |
+            Instruction::I2s |
+            Instruction::Lcmp |
+            Instruction::Fcmpl |
+            Instruction::Fcmpg |
+            Instruction::Dcmpl |
+            Instruction::Dcmpg |
+            Instruction::Ireturn |
+            Instruction::Lreturn |
+            Instruction::Freturn |
+            Instruction::Dreturn |
+            Instruction::Areturn |
+            Instruction::Return |
+            Instruction::Arraylength |
+            Instruction::Athrow |
+            Instruction::Monitorenter |
+            Instruction::Monitorexit => out.push(opcode),
+            Instruction::Bipush(value) => {
+                out.push(opcode);
+                out.push(*value as u8);
+            }
+            Instruction::Sipush(value) |
+            Instruction::Ifeq(value) |
+            Instruction::Ifne(value) |
+            Instruction::Iflt(value) |
+            Instruction::Ifge(value) |
+            Instruction::Ifgt(value) |
+            Instruction::Ifle(value) |
+            Instruction::IfIcmpeq(value) |
+            Instruction::IfIcmpne(value) |
+            Instruction::IfIcmplt(value) |
+            Instruction::IfIcmpge(value) |
+            Instruction::IfIcmpgt(value) |
+            Instruction::IfIcmple(value) |
+            Instruction::IfAcmpeq(value) |
+            Instruction::IfAcmpne(value) |
+            Instruction::Goto(value) |
+            Instruction::Jsr(value) |
+            Instruction::Ifnull(value) |
+            Instruction::Ifnonnull(value) => {
+                out.push(opcode);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            Instruction::Ldc(index) => {
+                out.push(opcode);
+                out.push(*index);
+            }
+            Instruction::LdcW(index) |
+            Instruction::Ldc2W(index) |
+            Instruction::Getstatic(index) |
+            Instruction::Putstatic(index) |
+            Instruction::Getfield(index) |
+            Instruction::Putfield(index) |
+            Instruction::Invokevirtual(index) |
+            Instruction::Invokespecial(index) |
+            Instruction::Invokestatic(index) |
+            Instruction::New(index) |
+            Instruction::Anewarray(index) |
+            Instruction::Checkcast(index) |
+            Instruction::Instanceof(index) => {
+                out.push(opcode);
+                out.extend_from_slice(&index.to_be_bytes());
+            }
+            Instruction::Iload(index) |
+            Instruction::Lload(index) |
+            Instruction::Fload(index) |
+            Instruction::Dload(index) |
+            Instruction::Aload(index) |
+            Instruction::Istore(index) |
+            Instruction::Lstore(index) |
+            Instruction::Fstore(index) |
+            Instruction::Dstore(index) |
+            Instruction::Astore(index) |
+            Instruction::Ret(index) => {
+                if *index > u8::MAX as u16 {
+                    out.push(Opcode::Wide as u8);
+                    out.push(opcode);
+                    out.extend_from_slice(&index.to_be_bytes());
+                } else {
+                    out.push(opcode);
+                    out.push(*index as u8);
+                }
+            }
+            Instruction::GotoW(offset) |
+            Instruction::JsrW(offset) => {
+                out.push(opcode);
+                out.extend_from_slice(&offset.to_be_bytes());
+            }
+            Instruction::Iinc { index, value } => {
+                if *index > u8::MAX as u16 || i8::try_from(*value).is_err() {
+                    out.push(Opcode::Wide as u8);
+                    out.push(opcode);
+                    out.extend_from_slice(&index.to_be_bytes());
+                    out.extend_from_slice(&value.to_be_bytes());
+                } else {
+                    out.push(opcode);
+                    out.push(*index as u8);
+                    out.push(*value as u8);
+                }
+            }
+            Instruction::Tableswitch { default, low, high, offsets } => {
+                out.push(opcode);
+                out.resize(out.len() + switch_padding(pc) as usize, 0);
+                out.extend_from_slice(&default.to_be_bytes());
+                out.extend_from_slice(&low.to_be_bytes());
+                out.extend_from_slice(&high.to_be_bytes());
+                for offset in offsets {
+                    out.extend_from_slice(&offset.to_be_bytes());
+                }
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                out.push(opcode);
+                out.resize(out.len() + switch_padding(pc) as usize, 0);
+                out.extend_from_slice(&default.to_be_bytes());
+                out.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                for (key, offset) in pairs {
+                    out.extend_from_slice(&key.to_be_bytes());
+                    out.extend_from_slice(&offset.to_be_bytes());
+                }
+            }
+            Instruction::Invokeinterface { index, count } => {
+                out.push(opcode);
+                out.extend_from_slice(&index.to_be_bytes());
+                out.push(*count);
+                out.push(0);
+            }
+            Instruction::Invokedynamic { index } => {
+                out.push(opcode);
+                out.extend_from_slice(&index.to_be_bytes());
+                out.extend_from_slice(&[0, 0]);
+            }
+            Instruction::Newarray { atype } => {
+                out.push(opcode);
+                out.push(*atype as u8);
+            }
+            Instruction::Multianewarray { index, dimensions } => {
+                out.push(opcode);
+                out.extend_from_slice(&index.to_be_bytes());
+                out.push(*dimensions);
+            }
+        }
+    }
+
+    /// Pcs this instruction may jump to when it's at `pc`, not counting the next instruction
+    ///
+    /// Targets that would be negative are left out, they can't be valid anyway.
+    pub fn branch_targets(&self, pc: u32) -> Vec<u32> {
+        let target = |offset: i32| u32::try_from(pc as i64 + offset as i64).ok();
+        match self {
+            Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) |
+            Instruction::Ifge(offset) | Instruction::Ifgt(offset) | Instruction::Ifle(offset) |
+            Instruction::IfIcmpeq(offset) | Instruction::IfIcmpne(offset) | Instruction::IfIcmplt(offset) |
+            Instruction::IfIcmpge(offset) | Instruction::IfIcmpgt(offset) | Instruction::IfIcmple(offset) |
+            Instruction::IfAcmpeq(offset) | Instruction::IfAcmpne(offset) | Instruction::Goto(offset) |
+            Instruction::Jsr(offset) | Instruction::Ifnull(offset) | Instruction::Ifnonnull(offset) => {
+                target(*offset as i32).into_iter().collect()
+            }
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => target(*offset).into_iter().collect(),
+            Instruction::Tableswitch { default, offsets, .. } => {
+                std::iter::once(default).chain(offsets).filter_map(|offset| target(*offset)).collect()
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                std::iter::once(default).chain(pairs.iter().map(|(_, offset)| offset)).filter_map(|offset| target(*offset)).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Whether execution can continue with the next instruction
+    ///
+    /// For `jsr` this is where the subroutine returns to.
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::Ret(_) | Instruction::Athrow |
+            Instruction::Tableswitch { .. } | Instruction::Lookupswitch { .. } |
+            Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn |
+            Instruction::Areturn | Instruction::Return
+        )
+    }
+
+    /// The local variable read or written by this instruction and how many slots it takes, including the
+    /// implicit index of forms like `iload_1`
+    pub fn local_variable(&self) -> Option<(u16, u16)> {
+        Some(match self {
+            Instruction::Iload(index) | Instruction::Fload(index) | Instruction::Aload(index) |
+            Instruction::Istore(index) | Instruction::Fstore(index) | Instruction::Astore(index) |
+            Instruction::Ret(index) | Instruction::Iinc { index, .. } => (*index, 1),
+            Instruction::Lload(index) | Instruction::Dload(index) | Instruction::Lstore(index) | Instruction::Dstore(index) => (*index, 2),
+            Instruction::Iload0 | Instruction::Fload0 | Instruction::Aload0 | Instruction::Istore0 | Instruction::Fstore0 | Instruction::Astore0 => (0, 1),
+            Instruction::Iload1 | Instruction::Fload1 | Instruction::Aload1 | Instruction::Istore1 | Instruction::Fstore1 | Instruction::Astore1 => (1, 1),
+            Instruction::Iload2 | Instruction::Fload2 | Instruction::Aload2 | Instruction::Istore2 | Instruction::Fstore2 | Instruction::Astore2 => (2, 1),
+            Instruction::Iload3 | Instruction::Fload3 | Instruction::Aload3 | Instruction::Istore3 | Instruction::Fstore3 | Instruction::Astore3 => (3, 1),
+            Instruction::Lload0 | Instruction::Dload0 | Instruction::Lstore0 | Instruction::Dstore0 => (0, 2),
+            Instruction::Lload1 | Instruction::Dload1 | Instruction::Lstore1 | Instruction::Dstore1 => (1, 2),
+            Instruction::Lload2 | Instruction::Dload2 | Instruction::Lstore2 | Instruction::Dstore2 => (2, 2),
+            Instruction::Lload3 | Instruction::Dload3 | Instruction::Lstore3 | Instruction::Dstore3 => (3, 2),
+            _ => return None,
+        })
+    }
+
+    /// Number of bytes the instruction takes when encoded at `pc`
+    pub fn encoded_len(&self, pc: u32) -> u32 {
+        match self {
+            Instruction::Bipush(_) | Instruction::Ldc(_) | Instruction::Newarray { .. } => 2,
+            Instruction::Sipush(_) | Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) |
+            Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) | Instruction::IfIcmpeq(_) |
+            Instruction::IfIcmpne(_) | Instruction::IfIcmplt(_) | Instruction::IfIcmpge(_) | Instruction::IfIcmpgt(_) |
+            Instruction::IfIcmple(_) | Instruction::IfAcmpeq(_) | Instruction::IfAcmpne(_) | Instruction::Goto(_) |
+            Instruction::Jsr(_) | Instruction::Ifnull(_) | Instruction::Ifnonnull(_) | Instruction::LdcW(_) |
+            Instruction::Ldc2W(_) | Instruction::Getstatic(_) | Instruction::Putstatic(_) | Instruction::Getfield(_) |
+            Instruction::Putfield(_) | Instruction::Invokevirtual(_) | Instruction::Invokespecial(_) |
+            Instruction::Invokestatic(_) | Instruction::New(_) | Instruction::Anewarray(_) | Instruction::Checkcast(_) |
+            Instruction::Instanceof(_) => 3,
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) | Instruction::Dload(index) |
+            Instruction::Aload(index) | Instruction::Istore(index) | Instruction::Lstore(index) | Instruction::Fstore(index) |
+            Instruction::Dstore(index) | Instruction::Astore(index) | Instruction::Ret(index) => {
+                if *index > u8::MAX as u16 { 4 } else { 2 }
+            }
+            Instruction::Iinc { index, value } => {
+                if *index > u8::MAX as u16 || i8::try_from(*value).is_err() { 6 } else { 3 }
+            }
+            Instruction::Multianewarray { .. } => 4,
+            Instruction::GotoW(_) | Instruction::JsrW(_) | Instruction::Invokeinterface { .. } | Instruction::Invokedynamic { .. } => 5,
+            Instruction::Tableswitch { offsets, .. } => 1 + switch_padding(pc) + 12 + 4 * offsets.len() as u32,
+            Instruction::Lookupswitch { pairs, .. } => 1 + switch_padding(pc) + 8 + 8 * pairs.len() as u32,
+            _ => 1,
+        }
+    }
+}
+
+/// Number of padding bytes after a `tableswitch` or `lookupswitch` at `pc`, so the operands start at a multiple of 4
+pub fn switch_padding(pc: u32) -> u32 {
+    (4 - (pc + 1) % 4) % 4
+}
+
+/// Encodes a local variable instruction with the `wide` prefix even if it doesn't need it, `None` for other instructions
+pub(crate) fn encode_wide(instruction: &Instruction, out: &mut Vec<u8>) -> Option<()> {
+    let (index, value) = match instruction {
+        Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) | Instruction::Dload(index) |
+        Instruction::Aload(index) | Instruction::Istore(index) | Instruction::Lstore(index) | Instruction::Fstore(index) |
+        Instruction::Dstore(index) | Instruction::Astore(index) | Instruction::Ret(index) => (*index, None),
+        Instruction::Iinc { index, value } => (*index, Some(*value)),
+        _ => return None,
+    };
+    out.push(Opcode::Wide as u8);
+    out.push(instruction.opcode() as u8);
+    out.extend_from_slice(&index.to_be_bytes());
+    if let Some(value) = value {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+    Some(())
+}
+
+/// Iterator over the instructions of some bytecode and their pcs
+///
+/// Stops after the first error.
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    pc: u32,
+    failed: bool,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Self { code, pc: 0, failed: false }
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(u32, Instruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pc as usize >= self.code.len() {
+            return None;
+        }
+        match Instruction::decode(self.code, self.pc) {
+            Ok((instruction, next)) => {
+                let pc = self.pc;
+                self.pc = next;
+                Some(Ok((pc, instruction)))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Reads the operands of the instruction at `pc`, errors always point at the start of the instruction
+struct Operands<'a> {
+    code: &'a [u8],
+    pc: u32,
+    pos: usize,
+}
+
+impl<'a> Operands<'a> {
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.code.get(self.pos).ok_or(DecodeError::Truncated { pc: self.pc })?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok((self.u8()? as u16) << 8 | self.u8()? as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok((self.u16()? as u32) << 16 | self.u16()? as u32)
+    }
+
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// Fails early if fewer than `n` bytes are left, so corrupted switch sizes don't cause huge allocations
+    fn ensure(&self, n: usize) -> Result<(), DecodeError> {
+        if self.code.len().saturating_sub(self.pos) < n {
+            return Err(DecodeError::Truncated { pc: self.pc });
+        }
+        Ok(())
+    }
+}
+
+fn decode(r: &mut Operands) -> Result<Instruction, DecodeError> {
+    let pc = r.pc;
+    let byte = r.u8()?;
+    let opcode: Opcode = FromPrimitive::from_u8(byte).ok_or(DecodeError::UnknownOpcode { pc, opcode: byte })?;
+    Ok(match opcode {
+        Opcode::Nop => Instruction::Nop,
+        Opcode::AconstNull => Instruction::AconstNull,
+        Opcode::IconstM1 => Instruction::IconstM1,
+        Opcode::Iconst0 => Instruction::Iconst0,
+        Opcode::Iconst1 => Instruction::Iconst1,
+        Opcode::Iconst2 => Instruction::Iconst2,
+        Opcode::Iconst3 => Instruction::Iconst3,
+        Opcode::Iconst4 => Instruction::Iconst4,
+        Opcode::Iconst5 => Instruction::Iconst5,
+        Opcode::Lconst0 => Instruction::Lconst0,
+        Opcode::Lconst1 => Instruction::Lconst1,
+        Opcode::Fconst0 => Instruction::Fconst0,
+        Opcode::Fconst1 => Instruction::Fconst1,
+        Opcode::Fconst2 => Instruction::Fconst2,
+        Opcode::Dconst0 => Instruction::Dconst0,
+        Opcode::Dconst1 => Instruction::Dconst1,
+        Opcode::Iload0 => Instruction::Iload0,
+        Opcode::Iload1 => Instruction::Iload1,
+        Opcode::Iload2 => Instruction::Iload2,
+        Opcode::Iload3 => Instruction::Iload3,
+        Opcode::Lload0 => Instruction::Lload0,
+        Opcode::Lload1 => Instruction::Lload1,
+        Opcode::Lload2 => Instruction::Lload2,
+        Opcode::Lload3 => Instruction::Lload3,
+        Opcode::Fload0 => Instruction::Fload0,
+        Opcode::Fload1 => Instruction::Fload1,
+        Opcode::Fload2 => Instruction::Fload2,
+        Opcode::Fload3 => Instruction::Fload3,
+        Opcode::Dload0 => Instruction::Dload0,
+        Opcode::Dload1 => Instruction::Dload1,
+        Opcode::Dload2 => Instruction::Dload2,
+        Opcode::Dload3 => Instruction::Dload3,
+        Opcode::Aload0 => Instruction::Aload0,
+        Opcode::Aload1 => Instruction::Aload1,
+        Opcode::Aload2 => Instruction::Aload2,
+        Opcode::Aload3 => Instruction::Aload3,
+        Opcode::Iaload => Instruction::Iaload,
+        Opcode::Laload => Instruction::Laload,
+        Opcode::Faload => Instruction::Faload,
+        Opcode::Daload => Instruction::Daload,
+        Opcode::Aaload => Instruction::Aaload,
+        Opcode::Baload => Instruction::Baload,
+        Opcode::Caload => Instruction::Caload,
+        Opcode::Saload => Instruction::Saload,
+        Opcode::Istore0 => Instruction::Istore0,
+        Opcode::Istore1 => Instruction::Istore1,
+        Opcode::Istore2 => Instruction::Istore2,
+        Opcode::Istore3 => Instruction::Istore3,
+        Opcode::Lstore0 => Instruction::Lstore0,
+        Opcode::Lstore1 => Instruction::Lstore1,
+        Opcode::Lstore2 => Instruction::Lstore2,
+        Opcode::Lstore3 => Instruction::Lstore3,
+        Opcode::Fstore0 => Instruction::Fstore0,
+        Opcode::Fstore1 => Instruction::Fstore1,
+        Opcode::Fstore2 => Instruction::Fstore2,
+        Opcode::Fstore3 => Instruction::Fstore3,
+        Opcode::Dstore0 => Instruction::Dstore0,
+        Opcode::Dstore1 => Instruction::Dstore1,
+        Opcode::Dstore2 => Instruction::Dstore2,
+        Opcode::Dstore3 => Instruction::Dstore3,
+        Opcode::Astore0 => Instruction::Astore0,
+        Opcode::Astore1 => Instruction::Astore1,
+        Opcode::Astore2 => Instruction::Astore2,
+        Opcode::Astore3 => Instruction::Astore3,
+        Opcode::Iastore => Instruction::Iastore,
+        Opcode::Lastore => Instruction::Lastore,
+        Opcode::Fastore => Instruction::Fastore,
+        Opcode::Dastore => Instruction::Dastore,
+        Opcode::Aastore => Instruction::Aastore,
+        Opcode::Bastore => Instruction::Bastore,
+        Opcode::Castore => Instruction::Castore,
+        Opcode::Sastore => Instruction::Sastore,
+        Opcode::Pop => Instruction::Pop,
+        Opcode::Pop2 => Instruction::Pop2,
+        Opcode::Dup => Instruction::Dup,
+        Opcode::DupX1 => Instruction::DupX1,
+        Opcode::DupX2 => Instruction::DupX2,
+        Opcode::Dup2 => Instruction::Dup2,
+        Opcode::Dup2X1 => Instruction::Dup2X1,
+        Opcode::Dup2X2 => Instruction::Dup2X2,
+        Opcode::Swap => Instruction::Swap,
+        Opcode::Iadd => Instruction::Iadd,
+        Opcode::Ladd => Instruction::Ladd,
+        Opcode::Fadd => Instruction::Fadd,
+        Opcode::Dadd => Instruction::Dadd,
+        Opcode::Isub => Instruction::Isub,
+        Opcode::Lsub => Instruction::Lsub,
+        Opcode::Fsub => Instruction::Fsub,
+        Opcode::Dsub => Instruction::Dsub,
+        Opcode::Imul => Instruction::Imul,
+        Opcode::Lmul => Instruction::Lmul,
+        Opcode::Fmul => Instruction::Fmul,
+        Opcode::Dmul => Instruction::Dmul,
+        Opcode::Idiv => Instruction::Idiv,
+        Opcode::Ldiv => Instruction::Ldiv,
+        Opcode::Fdiv => Instruction::Fdiv,
+        Opcode::Ddiv => Instruction::Ddiv,
+        Opcode::Irem => Instruction::Irem,
+        Opcode::Lrem => Instruction::Lrem,
+        Opcode::Frem => Instruction::Frem,
+        Opcode::Drem => Instruction::Drem,
+        Opcode::Ineg => Instruction::Ineg,
+        Opcode::Lneg => Instruction::Lneg,
+        Opcode::Fneg => Instruction::Fneg,
+        Opcode::Dneg => Instruction::Dneg,
+        Opcode::Ishl => Instruction::Ishl,
+        Opcode::Lshl => Instruction::Lshl,
+        Opcode::Ishr => Instruction::Ishr,
+        Opcode::Lshr => Instruction::Lshr,
+        Opcode::Iushr => Instruction::Iushr,
+        Opcode::Lushr => Instruction::Lushr,
+        Opcode::Iand => Instruction::Iand,
+        Opcode::Land => Instruction::Land,
+        Opcode::Ior => Instruction::Ior,
+        Opcode::Lor => Instruction::Lor,
+        Opcode::Ixor => Instruction::Ixor,
+        Opcode::Lxor => Instruction::Lxor,
+        Opcode::I2l => Instruction::I2l,
+        Opcode::I2f => Instruction::I2f,
+        Opcode::I2d => Instruction::I2d,
+        Opcode::L2i => Instruction::L2i,
+        Opcode::L2f => Instruction::L2f,
+        Opcode::L2d => Instruction::L2d,
+        Opcode::F2i => Instruction::F2i,
+        Opcode::F2l => Instruction::F2l,
+        Opcode::F2d => Instruction::F2d,
+        Opcode::D2i => Instruction::D2i,
+        Opcode::D2l => Instruction::D2l,
+        Opcode::D2f => Instruction::D2f,
+        Opcode::I2b => Instruction::I2b,
+        Opcode::I2c => Instruction::I2c,
+        Opcode::I2s => Instruction::I2s,
+        Opcode::Lcmp => Instruction::Lcmp,
+        Opcode::Fcmpl => Instruction::Fcmpl,
+        Opcode::Fcmpg => Instruction::Fcmpg,
+        Opcode::Dcmpl => Instruction::Dcmpl,
+        Opcode::Dcmpg => Instruction::Dcmpg,
+        Opcode::Ireturn => Instruction::Ireturn,
+        Opcode::Lreturn => Instruction::Lreturn,
+        Opcode::Freturn => Instruction::Freturn,
+        Opcode::Dreturn => Instruction::Dreturn,
+        Opcode::Areturn => Instruction::Areturn,
+        Opcode::Return => Instruction::Return,
+        Opcode::Arraylength => Instruction::Arraylength,
+        Opcode::Athrow => Instruction::Athrow,
+        Opcode::Monitorenter => Instruction::Monitorenter,
+        Opcode::Monitorexit => Instruction::Monitorexit,
+        Opcode::Bipush => Instruction::Bipush(r.u8()? as i8),
+        Opcode::Sipush => Instruction::Sipush(r.u16()? as i16),
+        Opcode::Ldc => Instruction::Ldc(r.u8()?),
+        Opcode::LdcW => Instruction::LdcW(r.u16()?),
+        Opcode::Ldc2W => Instruction::Ldc2W(r.u16()?),
+        Opcode::Iload => Instruction::Iload(r.u8()? as u16),
+        Opcode::Lload => Instruction::Lload(r.u8()? as u16),
+        Opcode::Fload => Instruction::Fload(r.u8()? as u16),
+        Opcode::Dload => Instruction::Dload(r.u8()? as u16),
+        Opcode::Aload => Instruction::Aload(r.u8()? as u16),
+        Opcode::Istore => Instruction::Istore(r.u8()? as u16),
+        Opcode::Lstore => Instruction::Lstore(r.u8()? as u16),
+        Opcode::Fstore => Instruction::Fstore(r.u8()? as u16),
+        Opcode::Dstore => Instruction::Dstore(r.u8()? as u16),
+        Opcode::Astore => Instruction::Astore(r.u8()? as u16),
+        Opcode::Ifeq => Instruction::Ifeq(r.u16()? as i16),
+        Opcode::Ifne => Instruction::Ifne(r.u16()? as i16),
+        Opcode::Iflt => Instruction::Iflt(r.u16()? as i16),
+        Opcode::Ifge => Instruction::Ifge(r.u16()? as i16),
+        Opcode::Ifgt => Instruction::Ifgt(r.u16()? as i16),
+        Opcode::Ifle => Instruction::Ifle(r.u16()? as i16),
+        Opcode::IfIcmpeq => Instruction::IfIcmpeq(r.u16()? as i16),
+        Opcode::IfIcmpne => Instruction::IfIcmpne(r.u16()? as i16),
+        Opcode::IfIcmplt => Instruction::IfIcmplt(r.u16()? as i16),
+        Opcode::IfIcmpge => Instruction::IfIcmpge(r.u16()? as i16),
+        Opcode::IfIcmpgt => Instruction::IfIcmpgt(r.u16()? as i16),
+        Opcode::IfIcmple => Instruction::IfIcmple(r.u16()? as i16),
+        Opcode::IfAcmpeq => Instruction::IfAcmpeq(r.u16()? as i16),
+        Opcode::IfAcmpne => Instruction::IfAcmpne(r.u16()? as i16),
+        Opcode::Goto => Instruction::Goto(r.u16()? as i16),
+        Opcode::Jsr => Instruction::Jsr(r.u16()? as i16),
+        Opcode::Ret => Instruction::Ret(r.u8()? as u16),
+        Opcode::Getstatic => Instruction::Getstatic(r.u16()?),
+        Opcode::Putstatic => Instruction::Putstatic(r.u16()?),
+        Opcode::Getfield => Instruction::Getfield(r.u16()?),
+        Opcode::Putfield => Instruction::Putfield(r.u16()?),
+        Opcode::Invokevirtual => Instruction::Invokevirtual(r.u16()?),
+        Opcode::Invokespecial => Instruction::Invokespecial(r.u16()?),
+        Opcode::Invokestatic => Instruction::Invokestatic(r.u16()?),
+        Opcode::New => Instruction::New(r.u16()?),
+        Opcode::Anewarray => Instruction::Anewarray(r.u16()?),
+        Opcode::Checkcast => Instruction::Checkcast(r.u16()?),
+        Opcode::Instanceof => Instruction::Instanceof(r.u16()?),
+        Opcode::Ifnull => Instruction::Ifnull(r.u16()? as i16),
+        Opcode::Ifnonnull => Instruction::Ifnonnull(r.u16()? as i16),
+        Opcode::GotoW => Instruction::GotoW(r.u32()? as i32),
+        Opcode::JsrW => Instruction::JsrW(r.u32()? as i32),
+        Opcode::Iinc => Instruction::Iinc {
+            index: r.u8()? as u16,
+            value: r.u8()? as i8 as i16,
+        },
+        Opcode::Tableswitch => {
+            r.pos += switch_padding(pc) as usize;
+            let default = r.i32()?;
+            let low = r.i32()?;
+            let high = r.i32()?;
+            if low > high {
+                return Err(DecodeError::InvalidSwitchRange { pc, low, high });
+            }
+            let len = (high as i64 - low as i64 + 1) as usize;
+            r.ensure(len * 4)?;
+            let mut offsets = Vec::with_capacity(len);
+            for _ in 0..len {
+                offsets.push(r.i32()?);
+            }
+            Instruction::Tableswitch { default, low, high, offsets }
+        }
+        Opcode::Lookupswitch => {
+            r.pos += switch_padding(pc) as usize;
+            let default = r.i32()?;
+            let npairs = r.i32()?;
+            if npairs < 0 {
+                return Err(DecodeError::InvalidLookupswitchPairs { pc, npairs });
+            }
+            r.ensure(npairs as usize * 8)?;
+            let mut pairs = Vec::with_capacity(npairs as usize);
+            for _ in 0..npairs {
+                pairs.push((r.i32()?, r.i32()?));
+            }
+            Instruction::Lookupswitch { default, pairs }
+        }
+        Opcode::Invokeinterface => {
+            let index = r.u16()?;
+            let count = r.u8()?;
+            // The last operand is always 0
+            r.u8()?;
+            Instruction::Invokeinterface { index, count }
+        }
+        Opcode::Invokedynamic => {
+            let index = r.u16()?;
+            // Two zero bytes
+            r.u16()?;
+            Instruction::Invokedynamic { index }
+        }
+        Opcode::Newarray => {
+            let atype = r.u8()?;
+            Instruction::Newarray {
+                atype: FromPrimitive::from_u8(atype).ok_or(DecodeError::InvalidArrayType { pc, atype })?,
+            }
+        }
+        Opcode::Multianewarray => Instruction::Multianewarray {
+            index: r.u16()?,
+            dimensions: r.u8()?,
+        },
+        Opcode::Wide => decode_wide(r)?,
+        Opcode::Breakpoint | Opcode::Impdep1 | Opcode::Impdep2 => return Err(DecodeError::ReservedOpcode { pc, opcode }),
+    })
+}
+
+fn decode_wide(r: &mut Operands) -> Result<Instruction, DecodeError> {
+    let pc = r.pc;
+    let byte = r.u8()?;
+    Ok(match FromPrimitive::from_u8(byte) {
+        Some(Opcode::Iload) => Instruction::Iload(r.u16()?),
+        Some(Opcode::Lload) => Instruction::Lload(r.u16()?),
+        Some(Opcode::Fload) => Instruction::Fload(r.u16()?),
+        Some(Opcode::Dload) => Instruction::Dload(r.u16()?),
+        Some(Opcode::Aload) => Instruction::Aload(r.u16()?),
+        Some(Opcode::Istore) => Instruction::Istore(r.u16()?),
+        Some(Opcode::Lstore) => Instruction::Lstore(r.u16()?),
+        Some(Opcode::Fstore) => Instruction::Fstore(r.u16()?),
+        Some(Opcode::Dstore) => Instruction::Dstore(r.u16()?),
+        Some(Opcode::Astore) => Instruction::Astore(r.u16()?),
+        Some(Opcode::Ret) => Instruction::Ret(r.u16()?),
+        Some(Opcode::Iinc) => Instruction::Iinc {
+            index: r.u16()?,
+            value: r.u16()? as i16,
+        },
+        _ => return Err(DecodeError::InvalidWideOpcode { pc, opcode: byte }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArrayType, DecodeError, Instruction, Instructions, Opcode};
+
+    fn decode_all(code: &[u8]) -> Result<Vec<(u32, Instruction)>, DecodeError> {
+        Instructions::new(code).collect()
+    }
+
+    #[test]
+    pub fn decodes_simple_instructions() {
+        let code = [0x2a, 0xb7, 0x00, 0x01, 0x10, 0xff, 0x11, 0x80, 0x00, 0xb1];
+        assert_eq!(decode_all(&code).unwrap(), vec![
+            (0, Instruction::Aload0),
+            (1, Instruction::Invokespecial(1)),
+            (4, Instruction::Bipush(-1)),
+            (6, Instruction::Sipush(-32768)),
+            (9, Instruction::Return),
+        ]);
+    }
+
+    #[test]
+    pub fn decodes_branches() {
+        let code = [0xa7, 0xff, 0xfd, 0xc8, 0x00, 0x00, 0x01, 0x00];
+        assert_eq!(decode_all(&code).unwrap(), vec![
+            (0, Instruction::Goto(-3)),
+            (3, Instruction::GotoW(256)),
+        ]);
+    }
+
+    #[test]
+    pub fn decodes_tableswitch_with_padding() {
+        // nop, then a tableswitch at pc 1 which needs 2 bytes of padding
+        let code = [
+            0x00, 0xaa, 0, 0,
+            0, 0, 0, 20,
+            0, 0, 0, 1,
+            0, 0, 0, 2,
+            0, 0, 0, 30,
+            0, 0, 0, 40,
+        ];
+        assert_eq!(decode_all(&code).unwrap(), vec![
+            (0, Instruction::Nop),
+            (1, Instruction::Tableswitch { default: 20, low: 1, high: 2, offsets: vec![30, 40] }),
+        ]);
+    }
+
+    #[test]
+    pub fn decodes_lookupswitch_without_padding() {
+        let code = [
+            0x00, 0x00, 0x00, 0xab,
+            0, 0, 0, 8,
+            0, 0, 0, 1,
+            0xff, 0xff, 0xff, 0xff, 0, 0, 0, 12,
+        ];
+        assert_eq!(decode_all(&code).unwrap()[3], (3, Instruction::Lookupswitch { default: 8, pairs: vec![(-1, 12)] }));
+    }
+
+    #[test]
+    pub fn decodes_wide() {
+        let code = [0xc4, 0x15, 0x01, 0x00, 0xc4, 0x84, 0x01, 0x00, 0xff, 0x9c];
+        assert_eq!(decode_all(&code).unwrap(), vec![
+            (0, Instruction::Iload(256)),
+            (4, Instruction::Iinc { index: 256, value: -100 }),
+        ]);
+    }
+
+    #[test]
+    pub fn decodes_multi_operand_instructions() {
+        let code = [0xb9, 0x00, 0x05, 0x02, 0x00, 0xc5, 0x00, 0x07, 0x03, 0xba, 0x00, 0x09, 0x00, 0x00, 0xbc, 0x0a, 0x84, 0x01, 0xff];
+        assert_eq!(decode_all(&code).unwrap(), vec![
+            (0, Instruction::Invokeinterface { index: 5, count: 2 }),
+            (5, Instruction::Multianewarray { index: 7, dimensions: 3 }),
+            (9, Instruction::Invokedynamic { index: 9 }),
+            (14, Instruction::Newarray { atype: ArrayType::Int }),
+            (16, Instruction::Iinc { index: 1, value: -1 }),
+        ]);
+    }
+
+    #[test]
+    pub fn reports_truncated_instructions() {
+        assert_eq!(decode_all(&[0x00, 0x11, 0x01]), Err(DecodeError::Truncated { pc: 1 }));
+        assert_eq!(decode_all(&[0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff]), Err(DecodeError::Truncated { pc: 0 }));
+    }
+
+    #[test]
+    pub fn reports_invalid_opcodes() {
+        assert_eq!(decode_all(&[0x00, 0xcb]), Err(DecodeError::UnknownOpcode { pc: 1, opcode: 0xcb }));
+        assert_eq!(decode_all(&[0xca]), Err(DecodeError::ReservedOpcode { pc: 0, opcode: Opcode::Breakpoint }));
+        assert_eq!(decode_all(&[0xc4, 0x10, 0x00]), Err(DecodeError::InvalidWideOpcode { pc: 0, opcode: 0x10 }));
+        assert_eq!(decode_all(&[0xbc, 0x03]), Err(DecodeError::InvalidArrayType { pc: 0, atype: 3 }));
+        assert_eq!(
+            decode_all(&[0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 1]),
+            Err(DecodeError::InvalidSwitchRange { pc: 0, low: 2, high: 1 })
+        );
+    }
+
+    #[test]
+    pub fn encoding_round_trips() {
+        let code = [
+            0x2a, 0xb7, 0x00, 0x01, 0x10, 0xff, 0x15, 0x04, 0xc4, 0x15, 0x01, 0x00, 0x84, 0x01, 0xff,
+            0xc4, 0x84, 0x00, 0x01, 0x01, 0x00, 0xaa, 0, 0, 0, 0, 0, 20, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 30,
+            0xab, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 12,
+            0xb9, 0x00, 0x05, 0x02, 0x00, 0xba, 0x00, 0x09, 0x00, 0x00, 0xbc, 0x0a, 0xc5, 0x00, 0x07, 0x03,
+            0xc8, 0x00, 0x00, 0x01, 0x00, 0xa7, 0xff, 0xfd, 0xb1,
+        ];
+        let mut out = vec![];
+        for instruction in Instructions::new(&code) {
+            let (pc, instruction) = instruction.unwrap();
+            assert_eq!(instruction.encoded_len(pc), Instruction::decode(&code, pc).unwrap().1 - pc);
+            instruction.encode(pc, &mut out);
+        }
+        assert_eq!(out, code);
+    }
+
+    #[test]
+    pub fn widens_when_needed() {
+        let mut out = vec![];
+        Instruction::Aload(300).encode(0, &mut out);
+        Instruction::Iinc { index: 1, value: 200 }.encode(4, &mut out);
+        assert_eq!(out, vec![0xc4, 0x19, 0x01, 0x2c, 0xc4, 0x84, 0x00, 0x01, 0x00, 0xc8]);
+    }
+
+    #[test]
+    pub fn encoded_len_matches_encoding() {
+        let mut instructions = vec![Instruction::Aload(300), Instruction::Iinc { index: 1, value: 200 }];
+        for opcode in 0..=u8::MAX {
+            let mut code = vec![opcode];
+            code.extend([0; 32]);
+            instructions.extend(Instruction::decode(&code, 0).map(|(instruction, _)| instruction));
+        }
+        for instruction in instructions {
+            for pc in 0..4 {
+                let mut out = vec![];
+                instruction.encode(pc, &mut out);
+                assert_eq!(instruction.encoded_len(pc), out.len() as u32, "{:?} at {}", instruction, pc);
+            }
+        }
+    }
+
+    #[test]
+    pub fn instructions_from_names() {
+        assert_eq!(ArrayType::from_name("boolean"), Some(ArrayType::Boolean));
+        assert_eq!(ArrayType::Long.name(), "long");
+        assert_eq!(ArrayType::from_name("Int"), None);
+        assert_eq!(Instruction::from_opcode(Opcode::Iadd), Some(Instruction::Iadd));
+        assert_eq!(Instruction::from_opcode(Opcode::Bipush), None);
+        assert_eq!(Instruction::from_opcode(Opcode::Wide), None);
+    }
+
+    #[test]
+    pub fn control_flow() {
+        assert_eq!(Instruction::Goto(-3).branch_targets(10), vec![7]);
+        assert_eq!(Instruction::Goto(-30).branch_targets(10), Vec::<u32>::new());
+        assert_eq!(Instruction::Lookupswitch { default: 20, pairs: vec![(1, 8)] }.branch_targets(4), vec![24, 12]);
+        assert!(Instruction::Ifeq(5).falls_through());
+        assert!(!Instruction::Athrow.falls_through());
+        assert_eq!(Instruction::Dstore2.local_variable(), Some((2, 2)));
+        assert_eq!(Instruction::Iinc { index: 7, value: 1 }.local_variable(), Some((7, 1)));
+        assert_eq!(Instruction::Iadd.local_variable(), None);
+    }
+
+    #[test]
+    pub fn stops_after_first_error() {
+        let mut instructions = Instructions::new(&[0xcb, 0x00]);
+        assert!(instructions.next().unwrap().is_err());
+        assert!(instructions.next().is_none());
+    }
+
+    #[test]
+    pub fn instruction_opcodes_match() {
+        assert_eq!(Instruction::Iload(3).opcode().mnemonic(), "iload");
+        assert_eq!(Instruction::Tableswitch { default: 0, low: 0, high: 0, offsets: vec![0] }.opcode(), Opcode::Tableswitch);
+        assert_eq!(Opcode::Ldc2W.mnemonic(), "ldc2_w");
+        assert_eq!(Opcode::from_mnemonic("ldc2_w"), Some(Opcode::Ldc2W));
+        assert_eq!(Opcode::from_mnemonic("iload_w"), None);
+    }
+}
@@ -0,0 +1,1139 @@
+//! Printing classes the way the JDK's `javap` tool does
+//!
+//! See: https://docs.oracle.com/en/java/javase/21/docs/specs/man/javap.html
+use std::fmt::{Debug, Display, LowerExp};
+
+use thiserror::Error;
+
+use crate::access_flags::{ClassAccessFlags, FieldAccessFlags, InnerClassAccessFlags, MethodAccessFlags, MethodParameterAccessFlags, ModuleFlags, RequiresFlags};
+use crate::attribute::{self, Attribute, AttributeInfo, InnerClass, LocalVariable, ModuleAttribute, ModuleExports};
+use crate::class::Class;
+use crate::class_hierarchy::OBJECT;
+use crate::code::CodeAttribute;
+use crate::constant_pool::{Constant, ConstantKind, ConstantPoolError, ConstantPool, MethodReferenceKind};
+use crate::descriptor::{DescriptorError, FieldType, java_class_name, MethodDescriptor};
+use crate::field::Field;
+use crate::instruction::{DecodeError, Instruction, Opcode};
+use crate::method::Method;
+use crate::mutf8;
+use crate::signature::{ClassSignature, FieldSignature, MethodSignature, SignatureError, TypeParameter};
+use crate::stack_map::{StackMapFrame, VerificationTypeInfo};
+
+/// What to print besides the declarations, named after the options of `javap`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JavapOptions {
+    /// `-c`, disassembles the code of every method
+    pub code: bool,
+    /// `-v`, prints the constant pool and every attribute, implies `code` and `lines`
+    pub verbose: bool,
+    /// `-p`, shows private members too
+    pub private: bool,
+    /// `-l`, prints the line number and local variable tables
+    pub lines: bool,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum JavapError {
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("invalid descriptor: {0}")]
+    DescriptorError(#[from] DescriptorError),
+    #[error("invalid signature: {0}")]
+    SignatureError(#[from] SignatureError),
+    #[error("couldn't decode the code: {0}")]
+    DecodeError(#[from] DecodeError),
+}
+
+/// Prints `class` like `javap` would with the given options
+///
+/// The `Classfile`, `Last modified` and checksum lines of verbose output are left out, they're about the file and not
+/// the class.
+pub fn javap(class: &Class, options: &JavapOptions) -> Result<String, JavapError> {
+    let options = JavapOptions {
+        code: options.code || options.verbose,
+        lines: options.lines || options.verbose,
+        ..*options
+    };
+    let mut printer = Printer { class, pool: &class.constant_pool, options, this_class: class.name()?, out: String::new() };
+    printer.class()?;
+    Ok(printer.out)
+}
+
+/// Column comments start at, counted from the indentation of their line
+const COMMENT_COLUMN: usize = 40;
+
+struct Printer<'a> {
+    class: &'a Class,
+    pool: &'a ConstantPool,
+    options: JavapOptions,
+    this_class: &'a str,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    /// Prints a line, without trailing spaces like `javap`
+    fn line(&mut self, indent: usize, text: &str) {
+        self.out.push_str(&" ".repeat(indent));
+        self.out.push_str(text.trim_end_matches(' '));
+        self.out.push('\n');
+    }
+
+    /// Prints `text` followed by `// comment`, lined up with the other comments at the same indentation
+    fn commented(&mut self, indent: usize, text: &str, comment: &str) {
+        let padding = (indent + COMMENT_COLUMN).saturating_sub(indent + text.chars().count()).max(1);
+        self.line(indent, &format!("{}{}// {}", text, " ".repeat(padding), comment));
+    }
+
+    fn class(&mut self) -> Result<(), JavapError> {
+        let class = self.class;
+        let verbose = self.options.verbose;
+        if let Some(source_file) = self.source_file()? {
+            self.line(if verbose { 2 } else { 0 }, &format!("Compiled from \"{}\"", source_file));
+        }
+        let declaration = self.declaration()?;
+        if verbose {
+            self.line(0, &declaration);
+            self.header()?;
+            self.line(0, "{");
+        } else {
+            self.line(0, &format!("{} {{", declaration));
+            if let Some(module) = self.module() {
+                self.module_directives(module)?;
+            }
+        }
+        // When there's more than declarations to print, fields are followed by an empty line and methods are
+        // separated by one
+        let detailed = self.options.code || self.options.lines;
+        for field in &class.fields {
+            if self.shows(field.access_flags.contains(FieldAccessFlags::ACC_PRIVATE)) {
+                self.field(field)?;
+                if detailed {
+                    self.out.push('\n');
+                }
+            }
+        }
+        let mut first = true;
+        for method in &class.methods {
+            if self.shows(method.access_flags.contains(MethodAccessFlags::ACC_PRIVATE)) {
+                if detailed && !first {
+                    self.out.push('\n');
+                }
+                first = false;
+                self.method(method)?;
+            }
+        }
+        self.line(0, "}");
+        if verbose {
+            for attribute in &class.attributes {
+                self.attribute(0, attribute)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn shows(&self, private: bool) -> bool {
+        !private || self.options.private
+    }
+
+    fn source_file(&self) -> Result<Option<&'a str>, JavapError> {
+        for attribute in &self.class.attributes {
+            if let AttributeInfo::SourceFile { sourcefile_index } = attribute.info {
+                return Ok(Some(self.pool.get_utf8(sourcefile_index)?));
+            }
+        }
+        Ok(None)
+    }
+
+    fn module(&self) -> Option<&'a ModuleAttribute> {
+        self.class.attributes.iter().find_map(|attribute| match &attribute.info {
+            AttributeInfo::Module(module) => Some(module),
+            _ => None,
+        })
+    }
+
+    /// The class declaration, like `public class Foo<T> extends Bar implements java.lang.Runnable`
+    fn declaration(&self) -> Result<String, JavapError> {
+        let flags = self.class.access_flags;
+        if let (true, Some(module)) = (flags.contains(ClassAccessFlags::ACC_MODULE), self.module()) {
+            let open = if module.module_flags.contains(ModuleFlags::ACC_OPEN) { "open " } else { "" };
+            let mut declaration = format!("{}module {}", open, self.module_name(module.module_name_index)?);
+            if module.module_version_index != 0 {
+                declaration.push('@');
+                declaration.push_str(self.pool.get_utf8(module.module_version_index)?);
+            }
+            return Ok(declaration);
+        }
+        let is_interface = flags.contains(ClassAccessFlags::ACC_INTERFACE);
+        let mut s = String::new();
+        if flags.contains(ClassAccessFlags::ACC_PUBLIC) {
+            s.push_str("public ");
+        }
+        if flags.contains(ClassAccessFlags::ACC_ABSTRACT) && !is_interface {
+            s.push_str("abstract ");
+        }
+        if flags.contains(ClassAccessFlags::ACC_FINAL) {
+            s.push_str("final ");
+        }
+        s.push_str(if is_interface { "interface " } else { "class " });
+        s.push_str(&java_class_name(self.this_class, true));
+        let signature = self.class.signature()?;
+        let (superclass, interfaces) = match signature {
+            Some(signature) => {
+                let signature = ClassSignature::parse(signature)?;
+                s.push_str(&self.type_parameters(&signature.type_parameters));
+                let superclass = (signature.superclass.class_name(), signature.superclass.java_name(true));
+                let interfaces: Vec<String> = signature.interfaces.iter().map(|i| i.java_name(true)).collect();
+                (Some(superclass), interfaces.join(", "))
+            }
+            None => {
+                let superclass = self.class.super_class_name()?.map(|name| (name.to_string(), java_class_name(name, true)));
+                let interfaces: Vec<String> = self.class.interfaces()?.into_iter().map(|name| java_class_name(name, true)).collect();
+                // javap doesn't put spaces between interfaces that don't come from a signature
+                (superclass, interfaces.join(","))
+            }
+        };
+        if is_interface {
+            if !interfaces.is_empty() {
+                s.push_str(&format!(" extends {}", interfaces));
+            }
+            return Ok(s);
+        }
+        if let Some((internal_name, java_name)) = superclass {
+            // Verbose output spells out the implicit superclass of generic classes, like it does with type parameters
+            if (self.options.verbose && signature.is_some()) || internal_name != OBJECT {
+                s.push_str(&format!(" extends {}", java_name));
+            }
+        }
+        if !interfaces.is_empty() {
+            s.push_str(&format!(" implements {}", interfaces));
+        }
+        Ok(s)
+    }
+
+    /// Type parameters like `<K extends java.lang.Comparable<K>, V>`, verbose output spells out `Object` bounds too
+    fn type_parameters(&self, parameters: &[TypeParameter]) -> String {
+        if parameters.is_empty() {
+            return String::new();
+        }
+        let parameters: Vec<String> = parameters.iter()
+            .map(|parameter| {
+                if !self.options.verbose {
+                    return parameter.java_declaration(true);
+                }
+                let bounds: Vec<String> = parameter.class_bound.iter()
+                    .chain(&parameter.interface_bounds)
+                    .map(|bound| bound.java_name(true))
+                    .collect();
+                format!("{} extends {}", parameter.name, bounds.join(" & "))
+            })
+            .collect();
+        format!("<{}>", parameters.join(", "))
+    }
+
+    /// Versions, flags and constant pool of verbose output
+    fn header(&mut self) -> Result<(), JavapError> {
+        let class = self.class;
+        self.line(2, &format!("minor version: {}", class.java_version.minor));
+        self.line(2, &format!("major version: {}", class.java_version.major));
+        self.line(2, &format!("flags: {}", access_flags(class.access_flags.bits(), class.access_flags)));
+        self.commented(2, &format!("this_class: #{}", class.this_class), &check_name(self.this_class));
+        match class.super_class {
+            0 => self.line(2, "super_class: #0"),
+            index => self.commented(2, &format!("super_class: #{}", index), &check_name(self.pool.get_class_name(index)?)),
+        }
+        self.line(2, &format!(
+            "interfaces: {}, fields: {}, methods: {}, attributes: {}",
+            class.interface_indices.len(), class.fields.len(), class.methods.len(), class.attributes.len()
+        ));
+        self.line(0, "Constant pool:");
+        // Wide enough for constant_pool_count, which is one more than the last index
+        let width = format!("#{}", self.pool.len() + 1).len() + 2;
+        for (index, constant) in self.pool.iter() {
+            let text = format!("{:>width$} = {:<18} {}", format!("#{}", index), kind_name(constant.kind()), self.constant_operands(constant), width = width);
+            match constant {
+                Constant::UTF8String(_) | Constant::UTF8Bytes { .. } | Constant::Integer(_) | Constant::Float(_) | Constant::Long(_) | Constant::Double(_) => {
+                    self.line(0, &text)
+                }
+                // javap puts an extra space in front of method types
+                Constant::MethodType { .. } => self.commented(2, &text[2..], &format!(" {}", self.constant_value(index, false)?)),
+                _ => self.commented(2, &text[2..], &self.constant_value(index, false)?),
+            }
+        }
+        Ok(())
+    }
+
+    /// What a constant pool entry holds, like `#2.#3` for a reference
+    fn constant_operands(&self, constant: &Constant) -> String {
+        match constant {
+            Constant::Class { name_index } |
+            Constant::Module { name_index } |
+            Constant::Package { name_index } => format!("#{}", name_index),
+            Constant::String { string_index } => format!("#{}", string_index),
+            Constant::MethodType { descriptor_index } => format!("#{}", descriptor_index),
+            Constant::Field { class_index, name_and_type_index } |
+            Constant::Method { class_index, name_and_type_index } |
+            Constant::InterfaceMethod { class_index, name_and_type_index } => format!("#{}.#{}", class_index, name_and_type_index),
+            Constant::NameAndType { name_index, descriptor_index } => format!("#{}:#{}", name_index, descriptor_index),
+            Constant::MethodHandle { reference_kind, reference_index } => format!("{}:#{}", *reference_kind as u8, reference_index),
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+            Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("#{}:#{}", bootstrap_method_attr_index, name_and_type_index)
+            }
+            Constant::UTF8String(string) => escape(string),
+            Constant::UTF8Bytes { string, bytes } => escape_surrogates(string, bytes),
+            Constant::Integer(value) => value.to_string(),
+            Constant::Float(value) => format!("{}f", java_float(*value)),
+            Constant::Long(value) => format!("{}l", value),
+            Constant::Double(value) => format!("{}d", java_float(*value)),
+            Constant::Unusable => String::new(),
+        }
+    }
+
+    /// A constant written out, like `java/lang/Object."<init>":()V`
+    ///
+    /// `in_code` leaves out the class of members of this class, like the comments of disassembled code do.
+    fn constant_value(&self, index: u16, in_code: bool) -> Result<String, JavapError> {
+        let constant = self.pool.get(index)?;
+        Ok(match constant {
+            Constant::Class { .. } => check_name(self.pool.get_class_name(index)?),
+            Constant::Field { class_index, name_and_type_index } |
+            Constant::Method { class_index, name_and_type_index } |
+            Constant::InterfaceMethod { class_index, name_and_type_index } => {
+                let class = self.pool.get_class_name(*class_index)?;
+                let member = self.name_and_type(*name_and_type_index)?;
+                if in_code && class == self.this_class {
+                    member
+                } else {
+                    format!("{}.{}", check_name(class), member)
+                }
+            }
+            Constant::NameAndType { .. } => self.name_and_type(index)?,
+            Constant::MethodHandle { reference_kind, reference_index } => {
+                format!("{} {}", reference_kind_name(*reference_kind), self.constant_value(*reference_index, false)?)
+            }
+            Constant::MethodType { descriptor_index } => self.pool.get_utf8(*descriptor_index)?.to_string(),
+            Constant::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } |
+            Constant::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+                format!("#{}:{}", bootstrap_method_attr_index, self.name_and_type(*name_and_type_index)?)
+            }
+            Constant::Module { name_index } |
+            Constant::Package { name_index } => check_name(self.pool.get_utf8(*name_index)?),
+            Constant::String { string_index } => match self.pool.get(*string_index)? {
+                Constant::UTF8Bytes { string, bytes } => escape_surrogates(string, bytes),
+                _ => escape(self.pool.get_utf8(*string_index)?),
+            },
+            other => self.constant_operands(other),
+        })
+    }
+
+    /// A constant along with its kind, like `Method java/io/Reader.read:()I` or `int 42`
+    fn constant_comment(&self, index: u16) -> Result<String, JavapError> {
+        let kind = match self.pool.get(index)? {
+            Constant::Class { .. } => "class",
+            Constant::Field { .. } => "Field",
+            Constant::Method { .. } => "Method",
+            Constant::InterfaceMethod { .. } => "InterfaceMethod",
+            Constant::String { .. } => "String",
+            Constant::Integer(_) => "int",
+            Constant::Float(_) => "float",
+            Constant::Long(_) => "long",
+            Constant::Double(_) => "double",
+            Constant::MethodHandle { .. } => "MethodHandle",
+            Constant::MethodType { .. } => "MethodType",
+            Constant::InvokeDynamic { .. } => "InvokeDynamic",
+            Constant::Dynamic { .. } => "Dynamic",
+            other => return Ok(kind_name(other.kind()).to_string()),
+        };
+        Ok(format!("{} {}", kind, self.constant_value(index, true)?))
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<String, JavapError> {
+        let (name, descriptor) = self.pool.get_name_and_type(index)?;
+        Ok(format!("{}:{}", check_name(name), descriptor))
+    }
+
+    fn module_name(&self, index: u16) -> Result<&'a str, JavapError> {
+        match self.pool.get(index)? {
+            Constant::Module { name_index } => Ok(self.pool.get_utf8(*name_index)?),
+            other => Err(ConstantPoolError::UnexpectedKind { index, expected: ConstantKind::Module, actual: other.kind() }.into()),
+        }
+    }
+
+    fn package_name(&self, index: u16) -> Result<&'a str, JavapError> {
+        match self.pool.get(index)? {
+            Constant::Package { name_index } => Ok(self.pool.get_utf8(*name_index)?),
+            other => Err(ConstantPoolError::UnexpectedKind { index, expected: ConstantKind::Package, actual: other.kind() }.into()),
+        }
+    }
+
+    /// The directives of a module declaration, like `requires java.base;`
+    fn module_directives(&mut self, module: &ModuleAttribute) -> Result<(), JavapError> {
+        for requires in &module.requires {
+            let mut s = "requires ".to_string();
+            if requires.requires_flags.contains(RequiresFlags::ACC_TRANSITIVE) {
+                s.push_str("transitive ");
+            }
+            if requires.requires_flags.contains(RequiresFlags::ACC_STATIC_PHASE) {
+                s.push_str("static ");
+            }
+            s.push_str(self.module_name(requires.requires_index)?);
+            self.line(2, &format!("{};", s));
+        }
+        for (keyword, packages) in [("exports", &module.exports), ("opens", &module.opens)] {
+            for package in packages {
+                let name = java_class_name(self.package_name(package.package_index)?, true);
+                let to = package.to_index.iter().map(|&index| self.module_name(index).map(str::to_string)).collect::<Result<Vec<_>, _>>()?;
+                self.list_directive(&format!("{} {}", keyword, name), "to", &to);
+            }
+        }
+        for &uses in &module.uses_index {
+            self.line(2, &format!("uses {};", java_class_name(self.pool.get_class_name(uses)?, true)));
+        }
+        for provides in &module.provides {
+            let service = java_class_name(self.pool.get_class_name(provides.provides_index)?, true);
+            let with = provides.provides_with_index.iter()
+                .map(|&index| self.pool.get_class_name(index).map(|name| java_class_name(name, true)))
+                .collect::<Result<Vec<_>, _>>()?;
+            // javap puts two spaces after `provides`
+            self.list_directive(&format!("provides  {}", service), "with", &with);
+        }
+        Ok(())
+    }
+
+    /// A directive followed by a list with one entry per line, like `opens foo to` and the modules it's opened to
+    fn list_directive(&mut self, directive: &str, keyword: &str, list: &[String]) {
+        if list.is_empty() {
+            self.line(2, &format!("{};", directive));
+            return;
+        }
+        self.line(2, &format!("{} {}", directive, keyword));
+        for (i, entry) in list.iter().enumerate() {
+            self.line(4, &format!("{}{}", entry, if i + 1 == list.len() { ";" } else { "," }));
+        }
+    }
+
+    fn field(&mut self, field: &Field) -> Result<(), JavapError> {
+        let flags = field.access_flags;
+        let mut s = String::new();
+        for (flag, modifier) in [
+            (FieldAccessFlags::ACC_PUBLIC, "public"),
+            (FieldAccessFlags::ACC_PROTECTED, "protected"),
+            (FieldAccessFlags::ACC_PRIVATE, "private"),
+            (FieldAccessFlags::ACC_STATIC, "static"),
+            (FieldAccessFlags::ACC_FINAL, "final"),
+            (FieldAccessFlags::ACC_VOLATILE, "volatile"),
+            (FieldAccessFlags::ACC_TRANSIENT, "transient"),
+        ] {
+            if flags.contains(flag) {
+                s.push_str(modifier);
+                s.push(' ');
+            }
+        }
+        let descriptor = field.descriptor(self.pool)?;
+        let field_type = match field.signature(self.pool)? {
+            Some(signature) => FieldSignature::parse(signature)?.java_name(true),
+            None => FieldType::parse(descriptor)?.java_name(true),
+        };
+        self.line(2, &format!("{}{} {};", s, field_type, field.name(self.pool)?));
+        if self.options.verbose {
+            self.line(4, &format!("descriptor: {}", descriptor));
+            self.line(4, &format!("flags: {}", access_flags(flags.bits(), flags)));
+            for attribute in &field.attributes {
+                self.attribute(4, attribute)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn method(&mut self, method: &Method) -> Result<(), JavapError> {
+        let declaration = self.method_declaration(method)?;
+        self.line(2, &format!("{};", declaration));
+        let descriptor = method.descriptor(self.pool)?;
+        if self.options.verbose {
+            self.line(4, &format!("descriptor: {}", descriptor));
+            self.line(4, &format!("flags: {}", access_flags(method.access_flags.bits(), method.access_flags)));
+            for attribute in &method.attributes {
+                match &attribute.info {
+                    AttributeInfo::Code(code) => {
+                        let this = if method.access_flags.contains(MethodAccessFlags::ACC_STATIC) { 0 } else { 1 };
+                        let args_size = MethodDescriptor::parse(descriptor)?.params.len() as u16 + this;
+                        self.code_attribute(4, code, Some(args_size))?;
+                    }
+                    _ => self.attribute(4, attribute)?,
+                }
+            }
+        } else if let Some(code) = method.code() {
+            if self.options.code {
+                self.line(4, "Code:");
+                self.code(4, code)?;
+            }
+            if self.options.lines {
+                for attribute in &code.attributes {
+                    if matches!(attribute.info, AttributeInfo::LineNumberTable(_) | AttributeInfo::LocalVariableTable(_)) {
+                        self.attribute(4, attribute)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The method declaration, like `public static void main(java.lang.String...) throws java.io.IOException`
+    fn method_declaration(&self, method: &Method) -> Result<String, JavapError> {
+        let flags = method.access_flags;
+        let name = method.name(self.pool)?;
+        if name == "<clinit>" {
+            return Ok("static {}".to_string());
+        }
+        let mut s = String::new();
+        for (flag, modifier) in [
+            (MethodAccessFlags::ACC_PUBLIC, "public"),
+            (MethodAccessFlags::ACC_PROTECTED, "protected"),
+            (MethodAccessFlags::ACC_PRIVATE, "private"),
+            (MethodAccessFlags::ACC_ABSTRACT, "abstract"),
+        ] {
+            if flags.contains(flag) {
+                s.push_str(modifier);
+                s.push(' ');
+            }
+        }
+        let is_interface = self.class.access_flags.contains(ClassAccessFlags::ACC_INTERFACE);
+        if is_interface && !flags.intersects(MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_STATIC | MethodAccessFlags::ACC_PRIVATE) {
+            s.push_str("default ");
+        }
+        for (flag, modifier) in [
+            (MethodAccessFlags::ACC_STATIC, "static"),
+            (MethodAccessFlags::ACC_FINAL, "final"),
+            (MethodAccessFlags::ACC_SYNCHRONIZED, "synchronized"),
+            (MethodAccessFlags::ACC_NATIVE, "native"),
+            (MethodAccessFlags::ACC_STRICT, "strictfp"),
+        ] {
+            if flags.contains(flag) {
+                s.push_str(modifier);
+                s.push(' ');
+            }
+        }
+        let (type_parameters, return_type, mut params, mut throws) = match method.signature(self.pool)? {
+            Some(signature) => {
+                let signature = MethodSignature::parse(signature)?;
+                (
+                    self.type_parameters(&signature.type_parameters),
+                    signature.return_type.as_ref().map_or("void".to_string(), |t| t.java_name(true)),
+                    signature.params.iter().map(|param| param.java_name(true)).collect::<Vec<_>>(),
+                    signature.throws.iter().map(|throw| throw.java_name(true)).collect::<Vec<_>>(),
+                )
+            }
+            None => {
+                let descriptor = MethodDescriptor::parse(method.descriptor(self.pool)?)?;
+                (
+                    String::new(),
+                    descriptor.return_type.as_ref().map_or("void".to_string(), |t| t.java_name(true)),
+                    descriptor.params.iter().map(|param| param.java_name(true)).collect(),
+                    vec![],
+                )
+            }
+        };
+        if throws.is_empty() {
+            for attribute in &method.attributes {
+                if let AttributeInfo::Exceptions(exceptions) = &attribute.info {
+                    for &exception in exceptions {
+                        throws.push(java_class_name(self.pool.get_class_name(exception)?, true));
+                    }
+                }
+            }
+        }
+        if flags.contains(MethodAccessFlags::ACC_VARARGS) {
+            if let Some(last) = params.last_mut().and_then(|last| last.strip_suffix("[]").map(str::to_string)) {
+                *params.last_mut().unwrap() = format!("{}...", last);
+            }
+        }
+        if !type_parameters.is_empty() {
+            s.push_str(&type_parameters);
+            s.push(' ');
+        }
+        if name == "<init>" {
+            s.push_str(&java_class_name(self.this_class, true));
+        } else {
+            s.push_str(&format!("{} {}", return_type, name));
+        }
+        s.push_str(&format!("({})", params.join(", ")));
+        if !throws.is_empty() {
+            s.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        Ok(s)
+    }
+
+    /// The whole `Code` attribute of verbose output, `args_size` is only known for the code of a method
+    fn code_attribute(&mut self, indent: usize, code: &CodeAttribute, args_size: Option<u16>) -> Result<(), JavapError> {
+        self.line(indent, "Code:");
+        let mut sizes = format!("stack={}, locals={}", code.max_stack, code.max_locals);
+        if let Some(args_size) = args_size {
+            sizes.push_str(&format!(", args_size={}", args_size));
+        }
+        self.line(indent + 2, &sizes);
+        self.code(indent + 2, code)?;
+        for attribute in &code.attributes {
+            self.attribute(indent + 2, attribute)?;
+        }
+        Ok(())
+    }
+
+    /// Disassembled instructions followed by the exception table
+    fn code(&mut self, indent: usize, code: &CodeAttribute) -> Result<(), JavapError> {
+        for instruction in code.instructions() {
+            let (pc, instruction) = instruction?;
+            let wide = code.code[pc as usize] == Opcode::Wide as u8;
+            self.instruction(indent, pc, &instruction, wide)?;
+        }
+        if !code.exception_table.is_empty() {
+            self.line(indent, "Exception table:");
+            self.line(indent + 2, " from    to  target type");
+            for entry in &code.exception_table {
+                let catch_type = match entry.catch_type_name(self.pool)? {
+                    Some(name) => format!("Class {}", name),
+                    None => "any".to_string(),
+                };
+                self.line(indent + 2, &format!(" {:5} {:5} {:5}   {}", entry.start_pc, entry.end_pc, entry.handler_pc, catch_type));
+            }
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, indent: usize, pc: u32, instruction: &Instruction, wide: bool) -> Result<(), JavapError> {
+        let mnemonic = instruction.opcode().mnemonic();
+        let mnemonic = if wide { format!("{}_w", mnemonic) } else { mnemonic.to_string() };
+        let target = |offset: i64| pc as i64 + offset;
+        let (operands, comment) = match instruction {
+            Instruction::Tableswitch { default, low, high, offsets } => {
+                self.line(indent, &format!("{:4}: {:<13} {{ // {} to {}", pc, mnemonic, low, high));
+                for (value, offset) in (*low..=*high).zip(offsets) {
+                    self.line(indent, &format!("{:>18}: {}", value, target(*offset as i64)));
+                }
+                self.switch_end(indent, target(*default as i64));
+                return Ok(());
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                self.line(indent, &format!("{:4}: {:<13} {{ // {}", pc, mnemonic, pairs.len()));
+                for (value, offset) in pairs {
+                    self.line(indent, &format!("{:>18}: {}", value, target(*offset as i64)));
+                }
+                self.switch_end(indent, target(*default as i64));
+                return Ok(());
+            }
+            Instruction::Bipush(value) => (value.to_string(), None),
+            Instruction::Sipush(value) => (value.to_string(), None),
+            Instruction::Ldc(index) => (format!("#{}", index), Some(*index as u16)),
+            Instruction::LdcW(index) | Instruction::Ldc2W(index) |
+            Instruction::Getstatic(index) | Instruction::Putstatic(index) |
+            Instruction::Getfield(index) | Instruction::Putfield(index) |
+            Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) | Instruction::Invokestatic(index) |
+            Instruction::New(index) | Instruction::Anewarray(index) |
+            Instruction::Checkcast(index) | Instruction::Instanceof(index) => (format!("#{}", index), Some(*index)),
+            Instruction::Invokeinterface { index, count } => (format!("#{},  {}", index, count), Some(*index)),
+            Instruction::Invokedynamic { index } => (format!("#{},  0", index), Some(*index)),
+            Instruction::Multianewarray { index, dimensions } => (format!("#{},  {}", index, dimensions), Some(*index)),
+            Instruction::Newarray { atype } => (format!(" {}", atype.base_type().java_name()), None),
+            Instruction::Iinc { index, value } => (format!("{}, {}", index, value), None),
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) |
+            Instruction::Dload(index) | Instruction::Aload(index) | Instruction::Istore(index) |
+            Instruction::Lstore(index) | Instruction::Fstore(index) | Instruction::Dstore(index) |
+            Instruction::Astore(index) | Instruction::Ret(index) => (index.to_string(), None),
+            _ => match instruction.branch_targets(pc).first() {
+                Some(target) => (target.to_string(), None),
+                None => {
+                    self.line(indent, &format!("{:4}: {}", pc, mnemonic));
+                    return Ok(());
+                }
+            },
+        };
+        let text = format!("{:4}: {:<13} {}", pc, mnemonic, operands);
+        match comment {
+            Some(index) => {
+                let comment = self.constant_comment(index)?;
+                self.commented(indent, &text, &comment);
+            }
+            None => self.line(indent, &text),
+        }
+        Ok(())
+    }
+
+    fn switch_end(&mut self, indent: usize, default: i64) {
+        self.line(indent, &format!("{:>18}: {}", "default", default));
+        self.line(indent + 6, "}");
+    }
+
+    fn attribute(&mut self, indent: usize, attribute: &Attribute) -> Result<(), JavapError> {
+        let name = attribute.name(self.pool)?;
+        match &attribute.info {
+            AttributeInfo::ConstantValue { constantvalue_index } => {
+                let value = self.constant_comment(*constantvalue_index)?;
+                self.line(indent, &format!("ConstantValue: {}", value));
+            }
+            AttributeInfo::StackMapTable(frames) => {
+                self.line(indent, &format!("StackMapTable: number_of_entries = {}", frames.len()));
+                for frame in frames {
+                    self.stack_map_frame(indent + 2, frame)?;
+                }
+            }
+            AttributeInfo::Exceptions(exceptions) => {
+                let exceptions = exceptions.iter()
+                    .map(|&exception| self.pool.get_class_name(exception).map(|name| java_class_name(name, true)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.line(indent, "Exceptions:");
+                self.line(indent + 2, &format!("throws {}", exceptions.join(", ")));
+            }
+            AttributeInfo::InnerClasses(classes) => {
+                self.line(indent, "InnerClasses:");
+                for class in classes {
+                    self.inner_class(indent + 2, class)?;
+                }
+            }
+            AttributeInfo::EnclosingMethod { class_index, method_index } => {
+                let mut comment = java_class_name(self.pool.get_class_name(*class_index)?, true);
+                if *method_index != 0 {
+                    comment.push('.');
+                    comment.push_str(self.pool.get_name_and_type(*method_index)?.0);
+                }
+                self.commented(indent, &format!("EnclosingMethod: #{}.#{}", class_index, method_index), &comment);
+            }
+            AttributeInfo::Synthetic => self.line(indent, "Synthetic: true"),
+            AttributeInfo::Deprecated => self.line(indent, "Deprecated: true"),
+            AttributeInfo::Signature { signature_index } => {
+                let signature = self.pool.get_utf8(*signature_index)?;
+                self.commented(indent, &format!("Signature: #{}", signature_index), signature);
+            }
+            AttributeInfo::SourceFile { sourcefile_index } => {
+                let source_file = self.pool.get_utf8(*sourcefile_index)?;
+                self.line(indent, &format!("SourceFile: \"{}\"", source_file));
+            }
+            AttributeInfo::SourceDebugExtension(extension) => {
+                self.line(indent, "SourceDebugExtension:");
+                for line in String::from_utf8_lossy(extension).lines() {
+                    self.line(indent + 2, line);
+                }
+            }
+            AttributeInfo::LineNumberTable(lines) => {
+                self.line(indent, "LineNumberTable:");
+                for line in lines {
+                    self.line(indent + 2, &format!("line {}: {}", line.line_number, line.start_pc));
+                }
+            }
+            AttributeInfo::LocalVariableTable(variables) => {
+                self.line(indent, "LocalVariableTable:");
+                self.local_variables(indent + 2, variables)?;
+            }
+            AttributeInfo::LocalVariableTypeTable(variables) => {
+                self.line(indent, "LocalVariableTypeTable:");
+                // Same columns as the LocalVariableTable, with signatures instead of descriptors
+                let variables: Vec<LocalVariable> = variables.iter()
+                    .map(|v| LocalVariable { start_pc: v.start_pc, length: v.length, name_index: v.name_index, descriptor_index: v.signature_index, index: v.index })
+                    .collect();
+                self.local_variables(indent + 2, &variables)?;
+            }
+            AttributeInfo::BootstrapMethods(methods) => {
+                self.line(indent, "BootstrapMethods:");
+                for (i, method) in methods.iter().enumerate() {
+                    let handle = self.constant_value(method.bootstrap_method_ref, false)?;
+                    self.line(indent + 2, &format!("{}: #{} {}", i, method.bootstrap_method_ref, handle));
+                    self.line(indent + 4, "Method arguments:");
+                    for &argument in &method.bootstrap_arguments {
+                        let value = self.constant_value(argument, false)?;
+                        self.line(indent + 6, &format!("#{} {}", argument, value));
+                    }
+                }
+            }
+            AttributeInfo::MethodParameters(parameters) => {
+                self.line(indent, "MethodParameters:");
+                self.line(indent + 2, &format!("{:<31}{}", "Name", "Flags"));
+                for parameter in parameters {
+                    let name = match parameter.name_index {
+                        0 => "<no name>",
+                        index => self.pool.get_utf8(index)?,
+                    };
+                    let flags: Vec<&str> = [
+                        (MethodParameterAccessFlags::ACC_FINAL, "final"),
+                        (MethodParameterAccessFlags::ACC_SYNTHETIC, "synthetic"),
+                        (MethodParameterAccessFlags::ACC_MANDATED, "mandated"),
+                    ].into_iter().filter(|(flag, _)| parameter.access_flags.contains(*flag)).map(|(_, flag)| flag).collect();
+                    match flags.is_empty() {
+                        true => self.line(indent + 2, name),
+                        false => self.line(indent + 2, &format!("{:<31}{}", name, flags.join(" "))),
+                    }
+                }
+            }
+            AttributeInfo::Module(module) => self.module_attribute(indent, module)?,
+            AttributeInfo::ModulePackages(packages) => {
+                self.line(indent, "ModulePackages:");
+                for &package in packages {
+                    let name = java_class_name(self.package_name(package)?, true);
+                    self.commented(indent + 2, &format!("#{}", package), &name);
+                }
+            }
+            AttributeInfo::ModuleMainClass { main_class_index } => {
+                let main_class = check_name(self.pool.get_class_name(*main_class_index)?);
+                self.commented(indent, &format!("ModuleMainClass: #{}", main_class_index), &main_class);
+            }
+            AttributeInfo::NestHost { host_class_index } => {
+                let host = self.pool.get_class_name(*host_class_index)?;
+                self.line(indent, &format!("NestHost: class {}", host));
+            }
+            AttributeInfo::NestMembers(classes) | AttributeInfo::PermittedSubclasses(classes) => {
+                self.line(indent, &format!("{}:", name));
+                for &class in classes {
+                    let class = self.pool.get_class_name(class)?;
+                    self.line(indent + 2, class);
+                }
+            }
+            AttributeInfo::Record(components) => {
+                self.line(indent, "Record:");
+                for component in components {
+                    let descriptor = component.descriptor(self.pool)?;
+                    let component_type = match attribute::signature(&component.attributes, self.pool)? {
+                        Some(signature) => FieldSignature::parse(signature)?.java_name(true),
+                        None => FieldType::parse(descriptor)?.java_name(true),
+                    };
+                    self.line(indent + 2, &format!("{} {};", component_type, component.name(self.pool)?));
+                    self.line(indent + 4, &format!("descriptor: {}", descriptor));
+                    for attribute in &component.attributes {
+                        self.attribute(indent + 4, attribute)?;
+                    }
+                    self.out.push('\n');
+                }
+            }
+            AttributeInfo::Code(code) => self.code_attribute(indent, code, None)?,
+            AttributeInfo::Unknown(bytes) => {
+                self.line(indent, &format!("{}: length = {:#x} (unknown attribute)", name, bytes.len()));
+                for chunk in bytes.chunks(16) {
+                    let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    self.line(indent + 3, &hex.join(" "));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn local_variables(&mut self, indent: usize, variables: &[LocalVariable]) -> Result<(), JavapError> {
+        self.line(indent, "Start  Length  Slot  Name   Signature");
+        for variable in variables {
+            let name = self.pool.get_utf8(variable.name_index)?;
+            let descriptor = self.pool.get_utf8(variable.descriptor_index)?;
+            self.line(indent, &format!("{:5} {:7} {:5} {:>5}   {}", variable.start_pc, variable.length, variable.index, name, descriptor));
+        }
+        Ok(())
+    }
+
+    /// An entry of `InnerClasses`, like `public static #12= #10 of #8;` with the names of the classes as a comment
+    fn inner_class(&mut self, indent: usize, class: &InnerClass) -> Result<(), JavapError> {
+        let flags = class.inner_class_access_flags;
+        let mut s = String::new();
+        for (flag, modifier) in [
+            (InnerClassAccessFlags::ACC_PUBLIC, "public"),
+            (InnerClassAccessFlags::ACC_PROTECTED, "protected"),
+            (InnerClassAccessFlags::ACC_PRIVATE, "private"),
+            (InnerClassAccessFlags::ACC_STATIC, "static"),
+            (InnerClassAccessFlags::ACC_FINAL, "final"),
+        ] {
+            if flags.contains(flag) {
+                s.push_str(modifier);
+                s.push(' ');
+            }
+        }
+        if flags.contains(InnerClassAccessFlags::ACC_ABSTRACT) && !flags.contains(InnerClassAccessFlags::ACC_INTERFACE) {
+            s.push_str("abstract ");
+        }
+        let mut comment = String::new();
+        if class.inner_name_index != 0 {
+            s.push_str(&format!("#{}= ", class.inner_name_index));
+            comment.push_str(&format!("{}=", check_name(self.pool.get_utf8(class.inner_name_index)?)));
+        }
+        s.push_str(&format!("#{}", class.inner_class_info_index));
+        comment.push_str(&format!("class {}", check_name(self.pool.get_class_name(class.inner_class_info_index)?)));
+        if class.outer_class_info_index != 0 {
+            s.push_str(&format!(" of #{}", class.outer_class_info_index));
+            comment.push_str(&format!(" of class {}", check_name(self.pool.get_class_name(class.outer_class_info_index)?)));
+        }
+        s.push(';');
+        self.commented(indent, &s, &comment);
+        Ok(())
+    }
+
+    fn stack_map_frame(&mut self, indent: usize, frame: &StackMapFrame) -> Result<(), JavapError> {
+        let (tag, kind) = match frame {
+            StackMapFrame::SameFrame { offset_delta } => (*offset_delta, "same"),
+            StackMapFrame::SameLocals1StackItemFrame { offset_delta, .. } => (64 + offset_delta, "same_locals_1_stack_item"),
+            StackMapFrame::SameLocals1StackItemFrameExtended { .. } => (247, "same_locals_1_stack_item_frame_extended"),
+            StackMapFrame::ChopFrame { k, .. } => (251 - k, "chop"),
+            StackMapFrame::SameFrameExtended { .. } => (251, "same_frame_extended"),
+            StackMapFrame::AppendFrame { locals, .. } => (251 + locals.len() as u8, "append"),
+            StackMapFrame::FullFrame { .. } => (255, "full_frame"),
+        };
+        self.line(indent, &format!("frame_type = {} /* {} */", tag, kind));
+        if !matches!(frame, StackMapFrame::SameFrame { .. } | StackMapFrame::SameLocals1StackItemFrame { .. }) {
+            self.line(indent + 2, &format!("offset_delta = {}", frame.offset_delta()));
+        }
+        match frame {
+            StackMapFrame::SameLocals1StackItemFrame { stack, .. } |
+            StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+                let stack = self.verification_types(std::slice::from_ref(stack))?;
+                self.line(indent + 2, &format!("stack = {}", stack));
+            }
+            StackMapFrame::AppendFrame { locals, .. } => {
+                let locals = self.verification_types(locals)?;
+                self.line(indent + 2, &format!("locals = {}", locals));
+            }
+            StackMapFrame::FullFrame { locals, stack, .. } => {
+                let locals = self.verification_types(locals)?;
+                let stack = self.verification_types(stack)?;
+                self.line(indent + 2, &format!("locals = {}", locals));
+                self.line(indent + 2, &format!("stack = {}", stack));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A list of verification types like `[ int, class java/lang/String ]`
+    fn verification_types(&self, types: &[VerificationTypeInfo]) -> Result<String, JavapError> {
+        if types.is_empty() {
+            return Ok("[]".to_string());
+        }
+        let types = types.iter()
+            .map(|info| -> Result<String, JavapError> {
+                Ok(match info {
+                    VerificationTypeInfo::Top => "top".to_string(),
+                    VerificationTypeInfo::Integer => "int".to_string(),
+                    VerificationTypeInfo::Float => "float".to_string(),
+                    VerificationTypeInfo::Double => "double".to_string(),
+                    VerificationTypeInfo::Long => "long".to_string(),
+                    VerificationTypeInfo::Null => "null".to_string(),
+                    VerificationTypeInfo::UninitializedThis => "this".to_string(),
+                    VerificationTypeInfo::Object { cpool_index } => format!("class {}", check_name(self.pool.get_class_name(*cpool_index)?)),
+                    VerificationTypeInfo::Uninitialized { offset } => format!("uninitialized {}", offset),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("[ {} ]", types.join(", ")))
+    }
+
+    /// The `Module` attribute entry by entry, with the count of each table
+    fn module_attribute(&mut self, indent: usize, module: &ModuleAttribute) -> Result<(), JavapError> {
+        self.line(indent, "Module:");
+        let name = check_name(self.module_name(module.module_name_index)?);
+        let comment = with_flag_names(name, module.module_flags);
+        self.commented(indent + 2, &format!("#{},{:x}", module.module_name_index, module.module_flags.bits()), &comment);
+        self.module_version(indent + 2, module.module_version_index)?;
+        self.commented(indent + 2, &module.requires.len().to_string(), "requires");
+        for requires in &module.requires {
+            let name = check_name(self.module_name(requires.requires_index)?);
+            let comment = with_flag_names(name, requires.requires_flags);
+            self.commented(indent + 4, &format!("#{},{:x}", requires.requires_index, requires.requires_flags.bits()), &comment);
+            self.module_version(indent + 4, requires.requires_version_index)?;
+        }
+        self.module_packages(indent, "exports", &module.exports)?;
+        self.module_packages(indent, "opens", &module.opens)?;
+        self.commented(indent + 2, &module.uses_index.len().to_string(), "uses");
+        for &uses in &module.uses_index {
+            let class = check_name(self.pool.get_class_name(uses)?);
+            self.commented(indent + 4, &format!("#{}", uses), &class);
+        }
+        self.commented(indent + 2, &module.provides.len().to_string(), "provides");
+        for provides in &module.provides {
+            let service = check_name(self.pool.get_class_name(provides.provides_index)?);
+            let comment = format!("{} with ... {}", service, provides.provides_with_index.len());
+            self.commented(indent + 4, &format!("#{}", provides.provides_index), &comment);
+            for &with in &provides.provides_with_index {
+                let class = check_name(self.pool.get_class_name(with)?);
+                self.commented(indent + 6, &format!("#{}", with), &format!("... with {}", class));
+            }
+        }
+        Ok(())
+    }
+
+    fn module_version(&mut self, indent: usize, version_index: u16) -> Result<(), JavapError> {
+        match version_index {
+            0 => self.line(indent, "#0"),
+            index => {
+                let version = self.pool.get_utf8(index)?;
+                self.commented(indent, &format!("#{}", index), version);
+            }
+        }
+        Ok(())
+    }
+
+    fn module_packages(&mut self, indent: usize, table: &str, packages: &[ModuleExports]) -> Result<(), JavapError> {
+        self.commented(indent + 2, &packages.len().to_string(), table);
+        for package in packages {
+            let mut comment = with_flag_names(check_name(self.package_name(package.package_index)?), package.flags);
+            if !package.to_index.is_empty() {
+                comment.push_str(&format!(" to ... {}", package.to_index.len()));
+            }
+            self.commented(indent + 4, &format!("#{},{:x}", package.package_index, package.flags.bits()), &comment);
+            for &to in &package.to_index {
+                let module = check_name(self.module_name(to)?);
+                self.commented(indent + 6, &format!("#{}", to), &format!("... to {}", module));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flags like `(0x0021) ACC_PUBLIC, ACC_SUPER`, named after the constants of their bitflags type
+fn access_flags(bits: u16, flags: impl Debug) -> String {
+    let names = flag_names(flags);
+    if names.is_empty() {
+        format!("({:#06x})", bits)
+    } else {
+        format!("({:#06x}) {}", bits, names.join(", "))
+    }
+}
+
+fn flag_names(flags: impl Debug) -> Vec<String> {
+    format!("{:?}", flags).split(" | ").filter(|name| name.starts_with("ACC_")).map(str::to_string).collect()
+}
+
+fn with_flag_names(text: String, flags: impl Debug) -> String {
+    let mut text = text;
+    for name in flag_names(flags) {
+        text.push(' ');
+        text.push_str(&name);
+    }
+    text
+}
+
+fn kind_name(kind: ConstantKind) -> &'static str {
+    match kind {
+        ConstantKind::Class => "Class",
+        ConstantKind::Field => "Fieldref",
+        ConstantKind::Method => "Methodref",
+        ConstantKind::InterfaceMethod => "InterfaceMethodref",
+        ConstantKind::String => "String",
+        ConstantKind::Integer => "Integer",
+        ConstantKind::Float => "Float",
+        ConstantKind::Long => "Long",
+        ConstantKind::Double => "Double",
+        ConstantKind::NameAndType => "NameAndType",
+        ConstantKind::UTF8String => "Utf8",
+        ConstantKind::MethodHandle => "MethodHandle",
+        ConstantKind::MethodType => "MethodType",
+        ConstantKind::InvokeDynamic => "InvokeDynamic",
+        ConstantKind::Dynamic => "Dynamic",
+        ConstantKind::Module => "Module",
+        ConstantKind::Package => "Package",
+        ConstantKind::Unusable => "Unusable",
+    }
+}
+
+fn reference_kind_name(kind: MethodReferenceKind) -> &'static str {
+    match kind {
+        MethodReferenceKind::GetField => "REF_getField",
+        MethodReferenceKind::GetStatic => "REF_getStatic",
+        MethodReferenceKind::PutField => "REF_putField",
+        MethodReferenceKind::PutStatic => "REF_putStatic",
+        MethodReferenceKind::InvokeVirtual => "REF_invokeVirtual",
+        MethodReferenceKind::InvokeStatic => "REF_invokeStatic",
+        MethodReferenceKind::InvokeSpecial => "REF_invokeSpecial",
+        MethodReferenceKind::NewInvokeSpecial => "REF_newInvokeSpecial",
+        MethodReferenceKind::InvokeInterface => "REF_invokeInterface",
+    }
+}
+
+/// Quotes names that aren't made of java identifiers separated by `/`, like `"<init>"` or `"[I"`
+fn check_name(name: &str) -> String {
+    let valid = !name.is_empty() && name.split('/').all(|part| {
+        let mut chars = part.chars();
+        matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    });
+    if valid {
+        name.to_string()
+    } else {
+        format!("\"{}\"", escape(name))
+    }
+}
+
+/// Escapes a string the way java source code would
+fn escape(string: &str) -> String {
+    let mut s = String::with_capacity(string.len());
+    for c in string.chars() {
+        escape_char(&mut s, c);
+    }
+    s
+}
+
+/// Like [`escape`] for a utf8 constant with unpaired surrogates, which come out as `?` like javap can't print them either
+fn escape_surrogates(string: &str, bytes: &[u8]) -> String {
+    let units = match mutf8::decode_utf16(bytes) {
+        Ok(units) => units,
+        Err(_) => return escape(string),
+    };
+    let mut s = String::with_capacity(units.len());
+    for c in char::decode_utf16(units) {
+        escape_char(&mut s, c.unwrap_or('?'));
+    }
+    s
+}
+
+fn escape_char(s: &mut String, c: char) {
+    match c {
+        '\t' => s.push_str("\\t"),
+        '\n' => s.push_str("\\n"),
+        '\r' => s.push_str("\\r"),
+        '\u{8}' => s.push_str("\\b"),
+        '\u{c}' => s.push_str("\\f"),
+        '"' => s.push_str("\\\""),
+        '\'' => s.push_str("\\'"),
+        '\\' => s.push_str("\\\\"),
+        c if c.is_control() => s.push_str(&format!("\\u{:04x}", c as u32)),
+        c => s.push(c),
+    }
+}
+
+/// Formats a floating point number like java's `Double.toString`, like `1.5`, `3.0` or `1.0E10`
+fn java_float<T: Copy + Display + LowerExp + Into<f64>>(value: T) -> String {
+    let float: f64 = value.into();
+    if float.is_nan() {
+        return "NaN".to_string();
+    }
+    if float.is_infinite() {
+        return if float > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if float == 0.0 || (1e-3..1e7).contains(&float.abs()) {
+        let s = value.to_string();
+        return if s.contains('.') { s } else { format!("{}.0", s) };
+    }
+    let s = format!("{:e}", value);
+    let (mantissa, exponent) = s.split_once('e').unwrap();
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, exponent)
+    } else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_name, escape, java_float};
+
+    #[test]
+    pub fn formats_floats_like_java() {
+        assert_eq!(java_float(1.5f32), "1.5");
+        assert_eq!(java_float(3.0f32), "3.0");
+        assert_eq!(java_float(-0.0f64), "-0.0");
+        assert_eq!(java_float(1e10f64), "1.0E10");
+        assert_eq!(java_float(1.25e-5f64), "1.25E-5");
+        assert_eq!(java_float(0.001f64), "0.001");
+        assert_eq!(java_float(f64::INFINITY), "Infinity");
+        assert_eq!(java_float(f32::NAN), "NaN");
+    }
+
+    #[test]
+    pub fn quotes_names_that_arent_identifiers() {
+        assert_eq!(check_name("java/lang/Object"), "java/lang/Object");
+        assert_eq!(check_name("Ops$Color"), "Ops$Color");
+        assert_eq!(check_name("<init>"), "\"<init>\"");
+        assert_eq!(check_name("[[I"), "\"[[I\"");
+        assert_eq!(check_name("module-info"), "\"module-info\"");
+        assert_eq!(check_name("java.base"), "\"java.base\"");
+        assert_eq!(escape("a\0b\n\"c\""), "a\\u0000b\\n\\\"c\\\"");
+    }
+}
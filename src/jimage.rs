@@ -0,0 +1,425 @@
+//! Reading jimages, the container a JDK keeps its runtime classes in at `lib/modules`
+//!
+//! The format isn't specified anywhere, this follows `jdk.internal.jimage` in OpenJDK. An image starts with a header
+//! and an index, made of a perfect hash table from names to locations, the attributes of each location and a table
+//! of strings the attributes point to. Resources follow the index, each one named like
+//! `/java.base/java/lang/String.class` and possibly compressed by the plugins of `jlink --compress`.
+//!
+//! The index is read into memory when the image is opened, resources are read from the file as they're asked for.
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+use thiserror::Error;
+
+use crate::inflate::{InflateError, inflate_zlib};
+use crate::mutf8;
+
+#[derive(Error, Debug)]
+pub enum JImageError {
+    #[error("couldn't read the image: {0}")]
+    IoError(#[from] io::Error),
+    #[error("expected magic number to be 0xcafedada")]
+    InvalidMagicNumber,
+    #[error("jimage version {major}.{minor} isn't supported")]
+    UnsupportedVersion { major: u16, minor: u16 },
+    #[error("the index of the image is corrupt")]
+    InvalidIndex,
+    #[error("{0}: the compressed resource is corrupt")]
+    InvalidCompressedResource(String),
+    #[error("{name}: decompressor {decompressor} isn't supported")]
+    UnsupportedDecompressor { name: String, decompressor: String },
+    #[error("{name}: {error}")]
+    InflateError { name: String, error: InflateError },
+}
+
+pub const MAGIC: u32 = 0xcafedada;
+const COMPRESSED_MAGIC: u32 = 0xcafefafa;
+const HEADER_SIZE: usize = 28;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+const HASH_MULTIPLIER: u32 = 0x01000193;
+
+const ATTRIBUTE_MODULE: usize = 1;
+const ATTRIBUTE_PARENT: usize = 2;
+const ATTRIBUTE_BASE: usize = 3;
+const ATTRIBUTE_EXTENSION: usize = 4;
+const ATTRIBUTE_OFFSET: usize = 5;
+const ATTRIBUTE_COMPRESSED: usize = 6;
+const ATTRIBUTE_UNCOMPRESSED: usize = 7;
+const ATTRIBUTE_COUNT: usize = 8;
+
+/// Tags the `compact-cp` plugin replaces `CONSTANT_Utf8` with, for strings it moved to the strings of the image
+const EXTERNALIZED_STRING: u8 = 23;
+const EXTERNALIZED_STRING_DESCRIPTOR: u8 = 25;
+
+/// The hash of the index, over the modified utf8 bytes of a name
+fn hash(name: &str, seed: u32) -> u32 {
+    mutf8::encode(name).iter().fold(seed, |hash, &byte| hash.wrapping_mul(HASH_MULTIPLIER) ^ byte as u32) & 0x7fffffff
+}
+
+/// Where a resource is in an image, and the parts of its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageLocation {
+    pub module: String,
+    /// Directories of the resource, like `java/lang`
+    pub parent: String,
+    pub base: String,
+    pub extension: String,
+    /// Where the resource is from the end of the index
+    pub offset: u64,
+    /// 0 for resources that aren't compressed
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+impl ImageLocation {
+    /// The full name of the resource, like `/java.base/java/lang/String.class`
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        if !self.module.is_empty() {
+            name.push('/');
+            name.push_str(&self.module);
+            name.push('/');
+        }
+        if !self.parent.is_empty() {
+            name.push_str(&self.parent);
+            name.push('/');
+        }
+        name.push_str(&self.base);
+        if !self.extension.is_empty() {
+            name.push('.');
+            name.push_str(&self.extension);
+        }
+        name
+    }
+}
+
+/// An open jimage
+#[derive(Debug)]
+pub struct JImage {
+    file: Mutex<File>,
+    /// Images are written in the byte order of the platform they're for
+    big_endian: bool,
+    redirect: Vec<i32>,
+    offsets: Vec<u32>,
+    locations: Vec<u8>,
+    strings: Vec<u8>,
+    /// Where resources start in the file
+    index_size: u64,
+}
+
+impl JImage {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JImageError> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Reads the header and index of the image in `file`
+    pub fn new(mut file: File) -> Result<Self, JImageError> {
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        let big_endian = match u32::from_le_bytes([header[0], header[1], header[2], header[3]]) {
+            MAGIC => false,
+            magic if magic.swap_bytes() == MAGIC => true,
+            _ => return Err(JImageError::InvalidMagicNumber),
+        };
+        let read_u32 = |at: usize| {
+            let bytes = [header[at], header[at + 1], header[at + 2], header[at + 3]];
+            if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+        };
+        let version = read_u32(4);
+        let (major, minor) = ((version >> 16) as u16, version as u16);
+        if (major, minor) != (1, 0) {
+            return Err(JImageError::UnsupportedVersion { major, minor });
+        }
+        let table_length = read_u32(16) as usize;
+        let locations_size = read_u32(20) as usize;
+        let strings_size = read_u32(24) as usize;
+
+        let index_size = HEADER_SIZE + table_length * 8 + locations_size + strings_size;
+        let mut index = vec![];
+        // Reading through take doesn't trust the sizes with a huge allocation up front
+        file.by_ref().take((index_size - HEADER_SIZE) as u64).read_to_end(&mut index)?;
+        if index.len() != index_size - HEADER_SIZE {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let (tables, rest) = index.split_at(table_length * 8);
+        let (locations, strings) = rest.split_at(locations_size);
+        let mut image = JImage {
+            file: Mutex::new(file),
+            big_endian,
+            redirect: vec![],
+            offsets: vec![],
+            locations: locations.to_vec(),
+            strings: strings.to_vec(),
+            index_size: index_size as u64,
+        };
+        let (redirect, offsets) = tables.split_at(table_length * 4);
+        image.redirect = (0..table_length).map(|i| image.u32_at(redirect, i * 4) as i32).collect();
+        image.offsets = (0..table_length).map(|i| image.u32_at(offsets, i * 4)).collect();
+        Ok(image)
+    }
+
+    fn u32_at(&self, bytes: &[u8], at: usize) -> u32 {
+        let bytes = [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+
+    fn u64_at(&self, bytes: &[u8], at: usize) -> u64 {
+        let (first, second) = (self.u32_at(bytes, at) as u64, self.u32_at(bytes, at + 4) as u64);
+        if self.big_endian { first << 32 | second } else { second << 32 | first }
+    }
+
+    /// The modified utf8 bytes of the string at `offset` in the strings of the image
+    fn string_bytes(&self, offset: usize) -> Result<&[u8], JImageError> {
+        let bytes = self.strings.get(offset..).ok_or(JImageError::InvalidIndex)?;
+        let end = bytes.iter().position(|&byte| byte == 0).ok_or(JImageError::InvalidIndex)?;
+        Ok(&bytes[..end])
+    }
+
+    fn string(&self, offset: usize) -> Result<String, JImageError> {
+        mutf8::decode(self.string_bytes(offset)?).map_err(|_| JImageError::InvalidIndex)
+    }
+
+    /// The location whose attributes are at `offset` in the locations of the image
+    fn location(&self, offset: usize) -> Result<ImageLocation, JImageError> {
+        let mut attributes = [0u64; ATTRIBUTE_COUNT];
+        let mut pos = offset;
+        loop {
+            let byte = *self.locations.get(pos).ok_or(JImageError::InvalidIndex)?;
+            // Each attribute is a byte with its kind and length, followed by its value in big endian
+            let kind = (byte >> 3) as usize;
+            if kind == 0 {
+                break;
+            }
+            let length = (byte & 7) as usize + 1;
+            let value = self.locations.get(pos + 1..pos + 1 + length).ok_or(JImageError::InvalidIndex)?;
+            *attributes.get_mut(kind).ok_or(JImageError::InvalidIndex)? = value.iter().fold(0, |value, &byte| value << 8 | byte as u64);
+            pos += 1 + length;
+        }
+        let string = |kind: usize| self.string(usize::try_from(attributes[kind]).map_err(|_| JImageError::InvalidIndex)?);
+        Ok(ImageLocation {
+            module: string(ATTRIBUTE_MODULE)?,
+            parent: string(ATTRIBUTE_PARENT)?,
+            base: string(ATTRIBUTE_BASE)?,
+            extension: string(ATTRIBUTE_EXTENSION)?,
+            offset: attributes[ATTRIBUTE_OFFSET],
+            compressed_size: attributes[ATTRIBUTE_COMPRESSED],
+            uncompressed_size: attributes[ATTRIBUTE_UNCOMPRESSED],
+        })
+    }
+
+    /// The location of the resource called `name`, like `/java.base/java/lang/String.class`
+    pub fn find(&self, name: &str) -> Result<Option<ImageLocation>, JImageError> {
+        let count = self.redirect.len() as u32;
+        if count == 0 {
+            return Ok(None);
+        }
+        // Names whose hash is alone in its bucket are redirected to their location, the others to a seed for a
+        // second hash that tells them apart
+        let index = match self.redirect[(hash(name, HASH_MULTIPLIER) % count) as usize] {
+            0 => return Ok(None),
+            redirect if redirect < 0 => (-(redirect + 1)) as u32,
+            seed => hash(name, seed as u32) % count,
+        };
+        let offset = *self.offsets.get(index as usize).ok_or(JImageError::InvalidIndex)?;
+        let location = self.location(offset as usize)?;
+        // Names that aren't in the image still land on some location
+        Ok(Some(location).filter(|location| location.name() == name))
+    }
+
+    /// Locations of every resource in the image, in the order of the index
+    pub fn locations(&self) -> Result<Vec<ImageLocation>, JImageError> {
+        self.offsets.iter().map(|&offset| self.location(offset as usize)).collect()
+    }
+
+    /// The contents of the resource at `location`, decompressed
+    pub fn read(&self, location: &ImageLocation) -> Result<Vec<u8>, JImageError> {
+        let size = if location.compressed_size != 0 { location.compressed_size } else { location.uncompressed_size };
+        let mut content = vec![];
+        {
+            // A poisoned lock only means another read panicked, the file itself is fine
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.seek(SeekFrom::Start(self.index_size + location.offset))?;
+            file.by_ref().take(size).read_to_end(&mut content)?;
+        }
+        if content.len() as u64 != size {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        if location.compressed_size == 0 {
+            return Ok(content);
+        }
+        self.decompress(&location.name(), content)
+    }
+
+    /// The contents of the resource called `name`, `None` if there's no such resource
+    pub fn read_resource(&self, name: &str) -> Result<Option<Vec<u8>>, JImageError> {
+        self.find(name)?.map(|location| self.read(&location)).transpose()
+    }
+
+    /// Modules with classes in `package`, like `java/lang`
+    pub fn package_modules(&self, package: &str) -> Result<Vec<String>, JImageError> {
+        // Each module is a pair of a flag, set if the module only has the package as a directory, and its name
+        let content = match self.read_resource(&format!("/packages/{}", package.replace('/', ".")))? {
+            Some(content) => content,
+            None => return Ok(vec![]),
+        };
+        content.chunks_exact(8)
+            .filter(|pair| self.u32_at(pair, 0) == 0)
+            .map(|pair| self.string(self.u32_at(pair, 4) as usize))
+            .collect()
+    }
+
+    /// The class file of the class called `class` in internal form, like `java/lang/String`
+    pub fn find_class(&self, class: &str) -> Result<Option<Vec<u8>>, JImageError> {
+        let package = class.rsplit_once('/').map_or("", |(package, _)| package);
+        for module in self.package_modules(package)? {
+            if let Some(bytes) = self.read_resource(&format!("/{}/{}.class", module, class))? {
+                return Ok(Some(bytes));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Undoes the plugins that compressed a resource, which wrap their output in a header naming the plugin
+    fn decompress(&self, name: &str, mut content: Vec<u8>) -> Result<Vec<u8>, JImageError> {
+        let corrupt = || JImageError::InvalidCompressedResource(name.to_string());
+        while content.len() >= COMPRESSED_HEADER_SIZE && self.u32_at(&content, 0) == COMPRESSED_MAGIC {
+            let compressed_size = self.u64_at(&content, 4);
+            let uncompressed_size = self.u64_at(&content, 12);
+            let decompressor = self.string(self.u32_at(&content, 20) as usize)?;
+            let end = usize::try_from(compressed_size).ok().and_then(|size| size.checked_add(COMPRESSED_HEADER_SIZE)).ok_or_else(corrupt)?;
+            let compressed = content.get(COMPRESSED_HEADER_SIZE..end).ok_or_else(corrupt)?;
+            content = match decompressor.as_str() {
+                "zip" => inflate_zlib(compressed).map_err(|error| JImageError::InflateError { name: name.to_string(), error })?,
+                "compact-cp" => self.expand_shared_strings(compressed).ok_or_else(corrupt)?,
+                _ => return Err(JImageError::UnsupportedDecompressor { name: name.to_string(), decompressor }),
+            };
+            if content.len() as u64 != uncompressed_size {
+                return Err(corrupt());
+            }
+        }
+        Ok(content)
+    }
+
+    /// Puts back the strings the `compact-cp` plugin moved from the constant pool of a class to the strings of the
+    /// image
+    fn expand_shared_strings(&self, class: &[u8]) -> Option<Vec<u8>> {
+        let mut reader = SharedStrings { bytes: class, pos: 0 };
+        let mut out = Vec::with_capacity(class.len() * 2);
+        // Magic, version and constant pool count
+        out.extend_from_slice(reader.take(10)?);
+        let count = u16::from_be_bytes([out[8], out[9]]);
+        let mut index = 1;
+        while index < count {
+            let tag = reader.take(1)?[0];
+            let utf8 = |out: &mut Vec<u8>, bytes: &[u8]| {
+                out.push(1);
+                out.extend_from_slice(&u16::try_from(bytes.len()).ok()?.to_be_bytes());
+                out.extend_from_slice(bytes);
+                Some(())
+            };
+            match tag {
+                1 => {
+                    let length = u16::from_be_bytes(reader.take(2)?.try_into().ok()?);
+                    let bytes = reader.take(length as usize)?;
+                    utf8(&mut out, bytes)?;
+                }
+                EXTERNALIZED_STRING => {
+                    let string = self.string_bytes(reader.int()? as usize).ok()?;
+                    utf8(&mut out, string)?;
+                }
+                EXTERNALIZED_STRING_DESCRIPTOR => {
+                    let descriptor = self.expand_descriptor(&mut reader)?;
+                    utf8(&mut out, &descriptor)?;
+                }
+                _ => {
+                    let size = match tag {
+                        3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+                        5 | 6 => 8,
+                        7 | 8 | 16 | 19 | 20 => 2,
+                        15 => 3,
+                        _ => return None,
+                    };
+                    out.push(tag);
+                    out.extend_from_slice(reader.take(size)?);
+                    // Longs and doubles take two entries
+                    if size == 8 {
+                        index += 1;
+                    }
+                }
+            }
+            index += 1;
+        }
+        out.extend_from_slice(&class[reader.pos..]);
+        Some(out)
+    }
+
+    /// A descriptor whose class names were split into a package and a simple name, both moved to the strings of the
+    /// image, like `(Ljava/lang/String;)V` written as `(L;)V` and the strings `java/lang` and `String`
+    fn expand_descriptor(&self, reader: &mut SharedStrings) -> Option<Vec<u8>> {
+        let descriptor = self.string_bytes(reader.int()? as usize).ok()?;
+        let length = reader.int()? as usize;
+        let mut names = SharedStrings { bytes: reader.take(length)?, pos: 0 };
+        let mut out = Vec::with_capacity(descriptor.len() * 4);
+        for &byte in descriptor {
+            out.push(byte);
+            if byte == b'L' {
+                let package = self.string_bytes(names.int()? as usize).ok()?;
+                if !package.is_empty() {
+                    out.extend_from_slice(package);
+                    out.push(b'/');
+                }
+                out.extend_from_slice(self.string_bytes(names.int()? as usize).ok()?);
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Reads a class rewritten by the `compact-cp` plugin
+struct SharedStrings<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SharedStrings<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(length)?)?;
+        self.pos += length;
+        Some(bytes)
+    }
+
+    /// An int written in 1 to 4 bytes: if the top bit of the first byte is set, the next 2 bits are the number of
+    /// bytes and the low 5 bits start the value, otherwise the int is those 4 bytes
+    fn int(&mut self) -> Option<u32> {
+        let first = self.take(1)?[0];
+        let (length, value) = match first & 0x80 {
+            0 => (4, first as u32),
+            _ => (((first >> 5) & 3) as usize, (first & 0x1f) as u32),
+        };
+        let rest = self.take(length.saturating_sub(1))?;
+        Some(rest.iter().fold(value, |value, &byte| value << 8 | byte as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HASH_MULTIPLIER, SharedStrings, hash};
+
+    #[test]
+    pub fn hashes_like_the_jdk() {
+        // From jdk.internal.jimage.ImageStringsReader.hashCode
+        assert_eq!(hash("", HASH_MULTIPLIER), 0x01000193);
+        assert_eq!(hash("a", 1), 0x01000193 ^ 0x61);
+    }
+
+    #[test]
+    pub fn reads_compressed_ints() {
+        let mut reader = SharedStrings { bytes: &[0x85, 0xc1, 0x02, 0x00, 0x00, 0x01, 0x00], pos: 0 };
+        assert_eq!(reader.int(), Some(0x5));
+        assert_eq!(reader.int(), Some(0x102));
+        assert_eq!(reader.int(), Some(0x100));
+        assert_eq!(reader.int(), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! A small JSON reader and writer, just enough for [`crate::class_json`]
+//!
+//! Numbers keep the text they were written with, so 64 bit integers don't lose precision by going through an `f64`.
+use std::fmt::{self, Display, Formatter, Write};
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// A number as written in the document, like `-12` or `1.5e3`
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they were written, names aren't deduplicated
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message} at byte {offset}")]
+pub struct JsonError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl Json {
+    pub fn int(value: impl Into<i64>) -> Self {
+        Json::Number(value.into().to_string())
+    }
+
+    pub fn string(value: impl Into<String>) -> Self {
+        Json::String(value.into())
+    }
+
+    pub fn object<'a>(members: impl IntoIterator<Item=(&'a str, Json)>) -> Self {
+        Json::Object(members.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    /// The value of the first member called `name`, `None` if there's no such member or this isn't an object
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(member, _)| member == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos != text.len() {
+            return Err(parser.error("unexpected text after the document"));
+        }
+        Ok(value)
+    }
+
+    fn is_scalar(&self) -> bool {
+        !matches!(self, Json::Array(_) | Json::Object(_))
+    }
+
+    /// Writes the value indented by two spaces per level, arrays and objects that only hold scalars go on one line
+    fn write(&self, f: &mut Formatter<'_>, indent: usize) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => f.write_str(value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                let one_line = values.iter().all(Json::is_scalar);
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    separator(f, i, one_line, indent + 2)?;
+                    value.write(f, indent + 2)?;
+                }
+                end(f, values.is_empty(), one_line, indent)?;
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                let one_line = members.iter().all(|(_, value)| value.is_scalar());
+                f.write_char('{')?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    separator(f, i, one_line, indent + 2)?;
+                    write_string(f, name)?;
+                    f.write_str(": ")?;
+                    value.write(f, indent + 2)?;
+                }
+                end(f, members.is_empty(), one_line, indent)?;
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn separator(f: &mut Formatter<'_>, i: usize, one_line: bool, indent: usize) -> fmt::Result {
+    match (i, one_line) {
+        (0, true) => Ok(()),
+        (_, true) => f.write_str(", "),
+        (0, false) => write!(f, "\n{:indent$}", ""),
+        (_, false) => write!(f, ",\n{:indent$}", ""),
+    }
+}
+
+fn end(f: &mut Formatter<'_>, empty: bool, one_line: bool, indent: usize) -> fmt::Result {
+    if empty || one_line {
+        Ok(())
+    } else {
+        write!(f, "\n{:indent$}", "")
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 || c == '\u{7f}' => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Deepest nesting of arrays and objects accepted, so malicious documents can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError { offset: self.pos, message }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.text.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> Result<(), JsonError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> Result<Json, JsonError> {
+        if !self.text[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(self.error("invalid literal"));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        match self.peek() {
+            None => Err(self.error("unexpected end of document")),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut values = vec![];
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value(depth + 1)?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(values));
+                        }
+                        _ => return Err(self.error("expected , or ]")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = vec![];
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("expected a member name"));
+                    }
+                    let name = self.string()?;
+                    self.expect(b':', "expected :")?;
+                    members.push((name, self.value(depth + 1)?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("expected , or }")),
+                    }
+                }
+            }
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while matches!(self.text.get(self.pos), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        if self.text[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        let leading_zero = self.text.get(self.pos) == Some(&b'0');
+        match self.digits() {
+            0 => return Err(self.error("expected a digit")),
+            n if n > 1 && leading_zero => return Err(JsonError { offset: start, message: "numbers can't have leading zeros" }),
+            _ => {}
+        }
+        if self.text.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        if matches!(self.text.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.text.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit"));
+            }
+        }
+        // Only ASCII was consumed, so this can't split a character
+        Ok(Json::Number(String::from_utf8_lossy(&self.text[start..self.pos]).into_owned()))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or_else(|| self.error("truncated escape"))?;
+        let digits = std::str::from_utf8(digits).map_err(|_| self.error("invalid escape"))?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        // The opening quote
+        self.pos += 1;
+        let mut string = vec![];
+        loop {
+            let byte = *self.text.get(self.pos).ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.text.get(self.pos).ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) {
+                                if !self.text[self.pos..].starts_with(b"\\u") {
+                                    return Err(self.error("unpaired surrogate"));
+                                }
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("unpaired surrogate"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    string.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                0..=0x1f => return Err(self.error("control character in string")),
+                _ => string.push(byte),
+            }
+        }
+        // The input is a str and escapes are encoded as utf8, so this can't fail
+        String::from_utf8(string).map_err(|_| self.error("invalid utf8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, JsonError};
+
+    #[test]
+    pub fn round_trips() {
+        let text = r#"{"a": [1, -2.5e3, true, null], "b": {"c": "x\"\n\u00e9\ud83d\ude00"}, "d": []}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.get("b").unwrap().get("c"), Some(&Json::string("x\"\né😀")));
+        assert_eq!(json.to_string(), "{\n  \"a\": [1, -2.5e3, true, null],\n  \"b\": {\"c\": \"x\\\"\\né😀\"},\n  \"d\": []\n}");
+        assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+    }
+
+    #[test]
+    pub fn reports_errors() {
+        assert_eq!(Json::parse("[1, 2"), Err(JsonError { offset: 5, message: "expected , or ]" }));
+        assert_eq!(Json::parse("01").unwrap_err().message, "numbers can't have leading zeros");
+        assert_eq!(Json::parse("\"\\ud800\"").unwrap_err().message, "unpaired surrogate");
+        assert_eq!(Json::parse("{} x").unwrap_err().message, "unexpected text after the document");
+        assert_eq!(Json::parse(&"[".repeat(1000)).unwrap_err().message, "too deeply nested");
+    }
+}
@@ -7,9 +7,32 @@
 extern crate num_derive;
 
 pub mod big_endian;
+pub mod reader;
+pub mod writer;
 pub mod class;
 pub mod constant_pool;
+pub mod constant_pool_builder;
 pub mod access_flags;
 pub mod field;
 pub mod method;
-pub mod attribute;
\ No newline at end of file
+pub mod attribute;
+pub mod code;
+pub mod stack_map;
+pub mod instruction;
+pub mod assembler;
+pub mod frame_size;
+pub mod frame_inference;
+pub mod class_hierarchy;
+pub mod verifier;
+pub mod format_check;
+pub mod javap;
+pub mod json;
+pub mod class_json;
+pub mod class_text;
+pub mod descriptor;
+pub mod signature;
+pub mod mutf8;
+pub mod inflate;
+pub mod zip;
+pub mod jimage;
+pub mod class_path;
\ No newline at end of file
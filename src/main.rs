@@ -1,10 +1,200 @@
+use std::path::Path;
+use std::process::ExitCode;
+
 use jerris::class::Class;
+use jerris::class_path::ClassPath;
+use jerris::javap::{javap, JavapOptions};
+
+const USAGE: &str = "usage: jerris javap [-c] [-v] [-p] [-l] [-cp <class path>] [--system <jdk>] <class file or name>...
+       jerris dump [--format json] <class file>
+       jerris import [--format json] <json file> <class file>
+       jerris disasm <class file>
+       jerris asm <assembly file> -o <class file>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("javap") => javap_command(&args[1..]),
+        Some("dump") => dump_command(&args[1..]),
+        Some("import") => import_command(&args[1..]),
+        Some("disasm") => disasm_command(&args[1..]),
+        Some("asm") => asm_command(&args[1..]),
+        _ => usage(),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{}", USAGE);
+    ExitCode::from(2)
+}
+
+/// Prints every class file, or class on the class path given with `-cp` and the runtime classes of the JDK given with
+/// `--system`, like `javap`, failing if any of them can't be read
+fn javap_command(args: &[String]) -> ExitCode {
+    let mut options = JavapOptions::default();
+    let mut class_path = None;
+    let mut system = None;
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-cp" | "-classpath" | "--class-path" => match args.next() {
+                Some(path) => class_path = Some(ClassPath::parse(path)),
+                None => return usage(),
+            },
+            "--system" => match args.next() {
+                Some(jdk) => system = Some(jdk),
+                None => return usage(),
+            },
+            "-c" => options.code = true,
+            "-v" | "-verbose" => options.verbose = true,
+            "-p" | "-private" => options.private = true,
+            "-l" => options.lines = true,
+            option if option.starts_with('-') => {
+                eprintln!("unknown option {}", option);
+                return usage();
+            }
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        return usage();
+    }
+    // The runtime classes come first, like the boot class path of the JVM
+    if let Some(jdk) = system {
+        let mut runtime = ClassPath::new();
+        runtime.push_jdk(jdk);
+        runtime.append(class_path.unwrap_or_default());
+        class_path = Some(runtime);
+    }
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        // With a class path, names like java.lang.String are looked up in it instead of being files
+        let class = match &class_path {
+            Some(class_path) if !file.ends_with(".class") => class_path.load(&file.replace('.', "/")).map_err(|e| e.to_string()),
+            _ => Class::from_file(file).map_err(|e| e.to_string()),
+        };
+        let output = class.and_then(|class| javap(&class, &options).map_err(|e| e.to_string()));
+        match output {
+            Ok(output) => {
+                if options.verbose && Path::new(file).is_file() {
+                    let path = Path::new(file).canonicalize().unwrap_or_else(|_| file.into());
+                    println!("Classfile {}", path.display());
+                }
+                print!("{}", output);
+            }
+            Err(e) => {
+                eprintln!("error: {}: {}", file, e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// Reads the `--format` option out of `args`, only JSON is supported for now
+fn files_in_format(args: &[String]) -> Result<Vec<&str>, ExitCode> {
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("json") => {}
+                Some(format) => {
+                    eprintln!("unknown format {}", format);
+                    return Err(usage());
+                }
+                None => return Err(usage()),
+            },
+            option if option.starts_with('-') => {
+                eprintln!("unknown option {}", option);
+                return Err(usage());
+            }
+            file => files.push(file),
+        }
+    }
+    Ok(files)
+}
 
-fn main() {
-    match Class::from_file(std::env::args().next().unwrap()) {
+/// Prints a class file as JSON
+fn dump_command(args: &[String]) -> ExitCode {
+    let file = match files_in_format(args).as_deref() {
+        Ok([file]) => *file,
+        Ok(_) => return usage(),
+        Err(status) => return *status,
+    };
+    match Class::from_file(file) {
         Ok(class) => {
-            println!("{:#?}", class);
+            println!("{}", class.to_json());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}: {}", file, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes a class file from JSON, refusing to write it if it isn't a valid class
+fn import_command(args: &[String]) -> ExitCode {
+    let (input, output) = match files_in_format(args).as_deref() {
+        Ok([input, output]) => (*input, *output),
+        Ok(_) => return usage(),
+        Err(status) => return *status,
+    };
+    let class = std::fs::read_to_string(input)
+        .map_err(|e| e.to_string())
+        .and_then(|json| Class::from_json(&json).map_err(|e| e.to_string()));
+    write_class(input, class, output)
+}
+
+/// Prints a class file in the assembly language `asm` reads
+fn disasm_command(args: &[String]) -> ExitCode {
+    let file = match args {
+        [file] if !file.starts_with('-') => file,
+        _ => return usage(),
+    };
+    match Class::from_file(file).map_err(|e| e.to_string()).and_then(|class| class.to_text().map_err(|e| e.to_string())) {
+        Ok(text) => {
+            print!("{}", text);
+            ExitCode::SUCCESS
         }
-        Err(e) => println!("{e}")
+        Err(e) => {
+            eprintln!("error: {}: {}", file, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Assembles a class file, refusing to write it if it isn't a valid class
+fn asm_command(args: &[String]) -> ExitCode {
+    let (input, output) = match args {
+        [input, option, output] if option == "-o" => (input, output),
+        [option, output, input] if option == "-o" => (input, output),
+        _ => return usage(),
+    };
+    let class = std::fs::read_to_string(input)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Class::from_text(&text).map_err(|e| e.to_string()));
+    write_class(input, class, output)
+}
+
+/// Writes the class read from `input` to `output` if it's valid, reporting errors against `input`
+fn write_class(input: &str, class: Result<Class, String>, output: &str) -> ExitCode {
+    let bytes = match class.and_then(|class| class.to_bytes().map_err(|e| e.to_string())) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: {}: {}", input, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = Class::from_bytes(&bytes) {
+        eprintln!("error: {}: the class isn't valid: {}", input, e);
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = std::fs::write(output, bytes) {
+        eprintln!("error: {}: {}", output, e);
+        return ExitCode::FAILURE;
     }
-}
\ No newline at end of file
+    ExitCode::SUCCESS
+}
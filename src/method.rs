@@ -1,10 +1,15 @@
-use std::fs::File;
-
-use thiserror::Error;
-
 use crate::access_flags::MethodAccessFlags;
-use crate::attribute::{Attribute, parse_attributes};
+use crate::attribute::{self, Attribute, AttributeInfo, parse_attributes, write_attributes};
 use crate::class::{ParseClassError, read_u16};
+use crate::class_hierarchy::ClassHierarchy;
+use crate::code::CodeAttribute;
+use crate::constant_pool::{ConstantPool, ConstantPoolError};
+use crate::descriptor::MethodDescriptor;
+use crate::frame_inference::{FrameInferenceError, infer_frames};
+use crate::frame_size::{check_frame_size, compute_frame_size, FrameSize, FrameSizeError, FrameSizeWarning};
+use crate::reader::ClassReader;
+use crate::stack_map::Frame;
+use crate::writer::{ClassWriter, WriteClassError, write_table};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Method {
@@ -20,21 +25,94 @@ pub struct Method {
     pub attributes: Vec<Attribute>,
 }
 
-#[derive(Error, Debug)]
-pub enum MethodParseError {
-    #[error("method has invalid access flags")]
-    InvalidAccessFlags,
+impl Method {
+    pub fn name<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, constant_pool: &'a ConstantPool) -> Result<&'a str, ConstantPoolError> {
+        constant_pool.get_utf8(self.descriptor_index)
+    }
+
+    /// The generic signature from the `Signature` attribute, see [`crate::signature`]
+    pub fn signature<'a>(&self, constant_pool: &'a ConstantPool) -> Result<Option<&'a str>, ConstantPoolError> {
+        attribute::signature(&self.attributes, constant_pool)
+    }
+
+    /// The body of this method, `None` for abstract and native methods
+    pub fn code(&self) -> Option<&CodeAttribute> {
+        self.attributes.iter().find_map(|attribute| match &attribute.info {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None
+        })
+    }
+
+    pub fn code_mut(&mut self) -> Option<&mut CodeAttribute> {
+        self.attributes.iter_mut().find_map(|attribute| match &mut attribute.info {
+            AttributeInfo::Code(code) => Some(code),
+            _ => None
+        })
+    }
+
+    /// Computes `max_stack` and `max_locals` from the method's code, `None` for abstract and native methods
+    pub fn compute_frame_size(&self, constant_pool: &ConstantPool) -> Result<Option<FrameSize>, FrameSizeError> {
+        let code = match self.code() {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+        let descriptor = MethodDescriptor::parse(self.descriptor(constant_pool)?)?;
+        let is_static = self.access_flags.contains(MethodAccessFlags::ACC_STATIC);
+        compute_frame_size(code, constant_pool, &descriptor, is_static).map(Some)
+    }
+
+    /// Warns if the method's code declares a smaller `max_stack` or `max_locals` than it needs
+    pub fn check_frame_size(&self, constant_pool: &ConstantPool) -> Result<Option<FrameSizeWarning>, FrameSizeError> {
+        Ok(match (self.code(), self.compute_frame_size(constant_pool)?) {
+            (Some(code), Some(computed)) => check_frame_size(code, computed),
+            _ => None,
+        })
+    }
+
+    /// Replaces `max_stack` and `max_locals` of the method's code with computed values, after editing it
+    pub fn update_frame_size(&mut self, constant_pool: &ConstantPool) -> Result<(), FrameSizeError> {
+        if let Some(computed) = self.compute_frame_size(constant_pool)? {
+            let code = self.code_mut().unwrap();
+            code.max_stack = computed.max_stack;
+            code.max_locals = computed.max_locals;
+        }
+        Ok(())
+    }
+
+    /// The frame the method starts with, holding its parameters, see [`Frame::initial`]
+    pub fn initial_frame(&self, this_class: &str, constant_pool: &ConstantPool) -> Result<Frame, FrameInferenceError> {
+        let descriptor = MethodDescriptor::parse(self.descriptor(constant_pool)?)?;
+        let is_static = self.access_flags.contains(MethodAccessFlags::ACC_STATIC);
+        Ok(Frame::initial(this_class, self.name(constant_pool)?, &descriptor, is_static))
+    }
+
+    /// Computes the frames the method's code needs in its `StackMapTable`, `None` for abstract and native methods
+    ///
+    /// `this_class` is the name of the class the method belongs to, see [`infer_frames`].
+    pub fn infer_frames(
+        &self,
+        this_class: &str,
+        constant_pool: &ConstantPool,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<Option<Vec<(u32, Frame)>>, FrameInferenceError> {
+        let code = match self.code() {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+        let initial = self.initial_frame(this_class, constant_pool)?;
+        infer_frames(code, constant_pool, this_class, initial, hierarchy).map(Some)
+    }
 }
 
-pub(crate) fn parse_method(f: &mut File) -> Result<Method, ParseClassError> {
-    let access_flags = MethodAccessFlags::from_bits(read_u16(f)?);
-    let access_flags = match access_flags {
-        Some(mf) => Ok(mf),
-        None => Err(ParseClassError::MethodParseError(MethodParseError::InvalidAccessFlags))
-    }?;
+pub(crate) fn parse_method<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Method, ParseClassError> {
+    let access_flags = MethodAccessFlags::from_bits_retain(read_u16(f)?);
     let name_index = read_u16(f)?;
     let descriptor_index = read_u16(f)?;
-    let attributes = parse_attributes(f)?;
+    let attributes = parse_attributes(f, constant_pool, major)?;
     Ok(Method {
         access_flags,
         name_index,
@@ -43,12 +121,22 @@ pub(crate) fn parse_method(f: &mut File) -> Result<Method, ParseClassError> {
     })
 }
 
-pub(crate) fn parse_methods(f: &mut File) -> Result<Vec<Method>, ParseClassError> {
+pub(crate) fn parse_methods<R: ClassReader>(f: &mut R, constant_pool: &ConstantPool, major: u16) -> Result<Vec<Method>, ParseClassError> {
     let len = read_u16(f)?;
-    let mut result = vec![];
-    result.reserve(len as usize);
+    let mut result = Vec::with_capacity(len as usize);
     for _ in 0..len {
-        result.push(parse_method(f)?);
+        result.push(parse_method(f, constant_pool, major)?);
     }
     Ok(result)
-}
\ No newline at end of file
+}
+
+fn write_method(out: &mut Vec<u8>, method: &Method) -> Result<(), WriteClassError> {
+    out.write_u16(method.access_flags.bits());
+    out.write_u16(method.name_index);
+    out.write_u16(method.descriptor_index);
+    write_attributes(out, &method.attributes)
+}
+
+pub(crate) fn write_methods(out: &mut Vec<u8>, methods: &[Method]) -> Result<(), WriteClassError> {
+    write_table(out, "methods", methods, write_method)
+}
@@ -0,0 +1,227 @@
+//! Java's "modified UTF-8", the encoding of `CONSTANT_Utf8` entries
+//!
+//! It differs from standard UTF-8 in two ways: `\u0000` is encoded as the two bytes `0xC0 0x80`, so an encoded
+//! string never contains a zero byte, and supplementary characters are encoded as a surrogate pair where each half
+//! takes three bytes, instead of a single four byte sequence. Surrogates don't have to be paired either, javac writes
+//! a lone one for a literal like `"\uD800"`, but a Rust `String` can't hold those, so [`decode`] rejects them and
+//! [`decode_utf16`] keeps them.
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.7
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ModifiedUtf8Error {
+    #[error("invalid byte at index {0}")]
+    InvalidByte(usize),
+    #[error("truncated character at index {0}")]
+    Truncated(usize),
+    #[error("overlong encoding at index {0}")]
+    Overlong(usize),
+    #[error("unpaired surrogate at index {0}")]
+    UnpairedSurrogate(usize),
+}
+
+impl ModifiedUtf8Error {
+    /// Index of the first byte of the offending character
+    pub fn index(&self) -> usize {
+        match self {
+            ModifiedUtf8Error::InvalidByte(index) |
+            ModifiedUtf8Error::Truncated(index) |
+            ModifiedUtf8Error::Overlong(index) |
+            ModifiedUtf8Error::UnpairedSurrogate(index) => *index
+        }
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+/// Decodes a single 1, 2 or 3 byte unit, returning the UTF-16 code unit and how many bytes it took
+fn decode_unit(bytes: &[u8], index: usize) -> Result<(u16, usize), ModifiedUtf8Error> {
+    let first = bytes[index];
+    match first {
+        0x01..=0x7f => Ok((first as u16, 1)),
+        0xc0..=0xdf => {
+            let second = *bytes.get(index + 1).ok_or(ModifiedUtf8Error::Truncated(index))?;
+            if !is_continuation(second) {
+                return Err(ModifiedUtf8Error::InvalidByte(index + 1));
+            }
+            let unit = ((first as u16 & 0x1f) << 6) | (second as u16 & 0x3f);
+            // 0xC0 0x80 is the only overlong form allowed, it's how NUL is encoded
+            if unit != 0 && unit < 0x80 {
+                return Err(ModifiedUtf8Error::Overlong(index));
+            }
+            Ok((unit, 2))
+        }
+        0xe0..=0xef => {
+            if bytes.len() < index + 3 {
+                return Err(ModifiedUtf8Error::Truncated(index));
+            }
+            let (second, third) = (bytes[index + 1], bytes[index + 2]);
+            if !is_continuation(second) {
+                return Err(ModifiedUtf8Error::InvalidByte(index + 1));
+            }
+            if !is_continuation(third) {
+                return Err(ModifiedUtf8Error::InvalidByte(index + 2));
+            }
+            let unit = ((first as u16 & 0x0f) << 12) | ((second as u16 & 0x3f) << 6) | (third as u16 & 0x3f);
+            if unit < 0x800 {
+                return Err(ModifiedUtf8Error::Overlong(index));
+            }
+            Ok((unit, 3))
+        }
+        _ => Err(ModifiedUtf8Error::InvalidByte(index))
+    }
+}
+
+/// Decodes a modified UTF-8 string
+///
+/// Only the canonical encoding of each character is accepted, so `encode(&decode(bytes)?) == bytes` always holds.
+pub fn decode(bytes: &[u8]) -> Result<String, ModifiedUtf8Error> {
+    // Plain ASCII is by far the most common case, and it's encoded the same way in both encodings
+    if bytes.iter().all(|b| (0x01..=0x7f).contains(b)) {
+        return Ok(String::from_utf8(bytes.to_vec()).expect("ascii is valid utf8"));
+    }
+    let mut string = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let (unit, len) = decode_unit(bytes, i)?;
+        let c = match unit {
+            0xd800..=0xdbff => {
+                let low_start = i + len;
+                if low_start >= bytes.len() {
+                    return Err(ModifiedUtf8Error::UnpairedSurrogate(i));
+                }
+                let (low, low_len) = decode_unit(bytes, low_start)?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(ModifiedUtf8Error::UnpairedSurrogate(i));
+                }
+                let code_point = 0x10000 + (((unit as u32 - 0xd800) << 10) | (low as u32 - 0xdc00));
+                let c = char::from_u32(code_point).ok_or(ModifiedUtf8Error::UnpairedSurrogate(i))?;
+                string.push(c);
+                i = low_start + low_len;
+                continue;
+            }
+            0xdc00..=0xdfff => return Err(ModifiedUtf8Error::UnpairedSurrogate(i)),
+            _ => char::from_u32(unit as u32).ok_or(ModifiedUtf8Error::InvalidByte(i))?
+        };
+        string.push(c);
+        i += len;
+    }
+    Ok(string)
+}
+
+/// Decodes a modified UTF-8 string into the UTF-16 code units of a Java string, which may be unpaired surrogates
+pub fn decode_utf16(bytes: &[u8]) -> Result<Vec<u16>, ModifiedUtf8Error> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let (unit, len) = decode_unit(bytes, i)?;
+        units.push(unit);
+        i += len;
+    }
+    Ok(units)
+}
+
+fn encode_unit(unit: u16, out: &mut Vec<u8>) {
+    match unit {
+        0x01..=0x7f => out.push(unit as u8),
+        0x00 | 0x80..=0x7ff => {
+            out.push(0xc0 | (unit >> 6) as u8);
+            out.push(0x80 | (unit & 0x3f) as u8);
+        }
+        _ => {
+            out.push(0xe0 | (unit >> 12) as u8);
+            out.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+            out.push(0x80 | (unit & 0x3f) as u8);
+        }
+    }
+}
+
+/// Encodes a string as modified UTF-8
+pub fn encode(string: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(string.len());
+    for unit in string.encode_utf16() {
+        encode_unit(unit, &mut out);
+    }
+    out
+}
+
+/// Length of the modified UTF-8 encoding of `string`, without encoding it
+pub fn encoded_len(string: &str) -> usize {
+    string.encode_utf16().map(|unit| match unit {
+        0x01..=0x7f => 1,
+        0x00 | 0x80..=0x7ff => 2,
+        _ => 3,
+    }).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_utf16, encode, encoded_len, ModifiedUtf8Error};
+
+    fn round_trip(string: &str, expected: &[u8]) {
+        assert_eq!(encode(string), expected);
+        assert_eq!(encoded_len(string), expected.len());
+        assert_eq!(decode(expected).unwrap(), string);
+    }
+
+    #[test]
+    pub fn ascii() {
+        round_trip("Hello World!", b"Hello World!");
+    }
+
+    #[test]
+    pub fn nul_is_two_bytes() {
+        round_trip("a\0b", &[b'a', 0xc0, 0x80, b'b']);
+    }
+
+    #[test]
+    pub fn bmp_characters() {
+        round_trip("é€", &[0xc3, 0xa9, 0xe2, 0x82, 0xac]);
+    }
+
+    #[test]
+    pub fn supplementary_characters_use_surrogate_pairs() {
+        // U+1F600 is the surrogate pair D83D DE00
+        round_trip("😀", &[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]);
+    }
+
+    #[test]
+    pub fn rejects_raw_zero_byte() {
+        assert_eq!(decode(&[b'a', 0]), Err(ModifiedUtf8Error::InvalidByte(1)));
+    }
+
+    #[test]
+    pub fn rejects_four_byte_form() {
+        assert_eq!(decode("😀".as_bytes()), Err(ModifiedUtf8Error::InvalidByte(0)));
+    }
+
+    #[test]
+    pub fn rejects_overlong_forms() {
+        assert_eq!(decode(&[0xc1, 0x81]), Err(ModifiedUtf8Error::Overlong(0)));
+        assert_eq!(decode(&[b'x', 0xe0, 0x81, 0x81]), Err(ModifiedUtf8Error::Overlong(1)));
+    }
+
+    #[test]
+    pub fn rejects_truncated_characters() {
+        assert_eq!(decode(&[b'x', 0xe2, 0x82]), Err(ModifiedUtf8Error::Truncated(1)));
+        assert_eq!(decode(&[0xc3]), Err(ModifiedUtf8Error::Truncated(0)));
+        assert_eq!(decode(&[0xc3, b'a']), Err(ModifiedUtf8Error::InvalidByte(1)));
+    }
+
+    #[test]
+    pub fn rejects_unpaired_surrogates() {
+        assert_eq!(decode(&[0xed, 0xa0, 0xbd]), Err(ModifiedUtf8Error::UnpairedSurrogate(0)));
+        assert_eq!(decode(&[b'a', 0xed, 0xb8, 0x80]), Err(ModifiedUtf8Error::UnpairedSurrogate(1)));
+        assert_eq!(decode(&[0xed, 0xa0, 0xbd, b'a']), Err(ModifiedUtf8Error::UnpairedSurrogate(0)));
+    }
+
+    #[test]
+    pub fn keeps_unpaired_surrogates_as_code_units() {
+        assert_eq!(decode_utf16(&[b'a', 0xed, 0xa0, 0xbd]), Ok(vec![0x61, 0xd83d]));
+        assert_eq!(decode_utf16(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]), Ok(vec![0xd83d, 0xde00]));
+        assert_eq!(decode_utf16(&[b'a', 0]), Err(ModifiedUtf8Error::InvalidByte(1)));
+    }
+}
@@ -0,0 +1,152 @@
+//! Sources a class file can be parsed from
+//!
+//! Every parser in this crate is generic over [`ClassReader`], so a class can be read from a file,
+//! any [`Read`] implementation or straight out of a byte slice.
+use std::borrow::Cow;
+use std::io::{ErrorKind, Read};
+
+use crate::class::ParseClassError;
+
+/// A source of class file bytes that knows how far into the class file it is
+pub trait ClassReader {
+    /// Number of bytes consumed from the start of the class file
+    fn offset(&self) -> usize;
+    /// Fills the whole buffer, failing with [`ParseClassError::UnexpectedEof`] if the input ends first
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseClassError>;
+    /// Reads the next `n` bytes, borrowing them from the input when it's already in memory
+    fn read_bytes(&mut self, n: usize) -> Result<Cow<'_, [u8]>, ParseClassError>;
+}
+
+/// Adapts any [`Read`] into a [`ClassReader`]
+pub struct IoReader<R> {
+    inner: R,
+    offset: usize,
+}
+
+impl<R: Read> IoReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, offset: 0 }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> ClassReader for IoReader<R> {
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseClassError> {
+        match self.inner.read_exact(buf) {
+            Ok(()) => {
+                self.offset += buf.len();
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(ParseClassError::UnexpectedEof { offset: self.offset }),
+            Err(e) => Err(ParseClassError::IoError(e)),
+        }
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<Cow<'_, [u8]>, ParseClassError> {
+        // Don't trust `n` for the allocation, a corrupted length shouldn't make us reserve gigabytes
+        let mut bytes = vec![];
+        (&mut self.inner).take(n as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != n {
+            return Err(ParseClassError::UnexpectedEof { offset: self.offset });
+        }
+        self.offset += n;
+        Ok(Cow::Owned(bytes))
+    }
+}
+
+/// Zero-copy cursor over a class file that's already in memory
+///
+/// If parsing fails, [`SliceCursor::offset`] tells where in the class file it stopped.
+pub struct SliceCursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    /// Where `bytes` starts in the class file
+    base: usize,
+}
+
+impl<'a> SliceCursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::at(bytes, 0)
+    }
+
+    /// Cursor over a part of a class file that starts `base` bytes into it, so offsets stay relative to the whole file
+    pub fn at(bytes: &'a [u8], base: usize) -> Self {
+        Self { bytes, offset: 0, base }
+    }
+
+    /// Bytes that haven't been consumed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.offset..]
+    }
+
+    /// Reads the next `n` bytes without copying them
+    pub fn read_slice(&mut self, n: usize) -> Result<&'a [u8], ParseClassError> {
+        if self.remaining().len() < n {
+            return Err(ParseClassError::UnexpectedEof { offset: self.offset() });
+        }
+        let slice = &self.bytes[self.offset..self.offset + n];
+        self.offset += n;
+        Ok(slice)
+    }
+}
+
+impl<'a> ClassReader for SliceCursor<'a> {
+    fn offset(&self) -> usize {
+        self.base + self.offset
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseClassError> {
+        buf.copy_from_slice(self.read_slice(buf.len())?);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<Cow<'_, [u8]>, ParseClassError> {
+        self.read_slice(n).map(Cow::Borrowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassReader, IoReader, SliceCursor};
+    use crate::class::ParseClassError;
+
+    #[test]
+    pub fn slice_cursor_reads_in_order() {
+        let mut cursor = SliceCursor::new(&[1, 2, 3, 4, 5]);
+        assert_eq!(cursor.read_slice(2).unwrap(), &[1, 2]);
+        let mut buf = [0u8; 2];
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [3, 4]);
+        assert_eq!(cursor.offset(), 4);
+        assert_eq!(cursor.remaining(), &[5]);
+    }
+
+    #[test]
+    pub fn slice_cursor_reports_offset_of_truncated_read() {
+        let mut cursor = SliceCursor::new(&[1, 2, 3]);
+        cursor.read_slice(1).unwrap();
+        assert!(matches!(cursor.read_bytes(4), Err(ParseClassError::UnexpectedEof { offset: 1 })));
+    }
+
+    #[test]
+    pub fn slice_cursor_offsets_include_base() {
+        let mut cursor = SliceCursor::at(&[1, 2, 3], 10);
+        cursor.read_slice(2).unwrap();
+        assert_eq!(cursor.offset(), 12);
+        assert!(matches!(cursor.read_slice(2), Err(ParseClassError::UnexpectedEof { offset: 12 })));
+    }
+
+    #[test]
+    pub fn io_reader_reports_offset_of_truncated_read() {
+        let mut reader = IoReader::new(&[1u8, 2, 3][..]);
+        assert_eq!(&*reader.read_bytes(2).unwrap(), &[1, 2]);
+        assert!(matches!(reader.read_bytes(u32::MAX as usize), Err(ParseClassError::UnexpectedEof { offset: 2 })));
+    }
+}
@@ -0,0 +1,643 @@
+//! Generic signatures stored in the `Signature` attribute, like `<T:Ljava/lang/Object;>(Ljava/util/List<TT;>;)TT;`
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.9.1
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::descriptor::{BaseType, java_class_name, MAX_ARRAY_DIMENSIONS};
+
+/// A type that can appear in a generic signature
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JavaTypeSignature {
+    Base(BaseType),
+    Reference(ReferenceTypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReferenceTypeSignature {
+    Class(ClassTypeSignature),
+    /// A type variable like `T`
+    TypeVariable(String),
+    Array(Box<JavaTypeSignature>),
+}
+
+/// A possibly parameterized class type, like `java/util/Map<TK;TV;>.Entry`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    /// Package of the class in internal form, like `java/util`, empty for the default package
+    pub package: String,
+    /// The outermost class followed by every inner class separated with `.` in the signature
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    /// A type without a wildcard
+    Exact(ReferenceTypeSignature),
+    /// `? extends T`
+    Extends(ReferenceTypeSignature),
+    /// `? super T`
+    Super(ReferenceTypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: String,
+    /// `None` when the parameter is only bound by interfaces
+    pub class_bound: Option<ReferenceTypeSignature>,
+    pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+/// Signature of a generic class, its superclass and its interfaces
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub params: Vec<JavaTypeSignature>,
+    /// `None` if the method returns `void`
+    pub return_type: Option<JavaTypeSignature>,
+    /// Either class types or type variables
+    pub throws: Vec<ReferenceTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldSignature {
+    pub field_type: ReferenceTypeSignature,
+}
+
+/// Errors point at the character where parsing failed, counted in bytes from the start of the signature
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("signature ends unexpectedly at {position}")]
+    UnexpectedEnd { position: usize },
+    #[error("unexpected character {found:?} at {position}")]
+    UnexpectedCharacter { position: usize, found: char },
+    #[error("expected an identifier at {position}")]
+    ExpectedIdentifier { position: usize },
+    #[error("array at {position} has more than 255 dimensions")]
+    TooManyDimensions { position: usize },
+    #[error("unexpected characters after the end of the signature at {position}")]
+    TrailingCharacters { position: usize },
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Result<Self, SignatureError> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type()?;
+        let mut interfaces = vec![];
+        while parser.peek().is_some() {
+            interfaces.push(parser.class_type()?);
+        }
+        Ok(Self { type_parameters, superclass, interfaces })
+    }
+
+    /// How the class is declared in java source code after its name, like `<T> extends Object implements List<T>`
+    pub fn java_declaration(&self, qualified: bool) -> String {
+        let mut s = type_parameters_java(&self.type_parameters, qualified);
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        s.push_str("extends ");
+        s.push_str(&self.superclass.java_name(qualified));
+        if !self.interfaces.is_empty() {
+            let interfaces: Vec<String> = self.interfaces.iter().map(|i| i.java_name(qualified)).collect();
+            s.push_str(" implements ");
+            s.push_str(&interfaces.join(", "));
+        }
+        s
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Result<Self, SignatureError> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        parser.expect('(')?;
+        let mut params = vec![];
+        while parser.peek() != Some(')') {
+            params.push(parser.java_type()?);
+        }
+        parser.expect(')')?;
+        let return_type = if parser.peek() == Some('V') {
+            parser.next()?;
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws = vec![];
+        while parser.peek().is_some() {
+            parser.expect('^')?;
+            let position = parser.position;
+            match parser.reference_type()? {
+                ReferenceTypeSignature::Array(_) => return Err(SignatureError::UnexpectedCharacter { position, found: '[' }),
+                throw => throws.push(throw),
+            }
+        }
+        Ok(Self { type_parameters, params, return_type, throws })
+    }
+
+    /// How a method with this signature is declared in java source code, like `<T> void sort(List<T>)`
+    pub fn java_signature(&self, name: &str, qualified: bool) -> String {
+        let mut s = type_parameters_java(&self.type_parameters, qualified);
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        match &self.return_type {
+            Some(return_type) => s.push_str(&return_type.java_name(qualified)),
+            None => s.push_str("void"),
+        }
+        let params: Vec<String> = self.params.iter().map(|param| param.java_name(qualified)).collect();
+        s.push_str(&format!(" {}({})", name, params.join(", ")));
+        if !self.throws.is_empty() {
+            let throws: Vec<String> = self.throws.iter().map(|throw| throw.java_name(qualified)).collect();
+            s.push_str(" throws ");
+            s.push_str(&throws.join(", "));
+        }
+        s
+    }
+}
+
+impl FieldSignature {
+    pub fn parse(signature: &str) -> Result<Self, SignatureError> {
+        let mut parser = Parser::new(signature);
+        let field_type = parser.reference_type()?;
+        parser.end()?;
+        Ok(Self { field_type })
+    }
+
+    pub fn java_name(&self, qualified: bool) -> String {
+        self.field_type.java_name(qualified)
+    }
+}
+
+impl JavaTypeSignature {
+    /// How this type is written in java source code, like `List<? extends T>[]`
+    pub fn java_name(&self, qualified: bool) -> String {
+        match self {
+            JavaTypeSignature::Base(base) => base.java_name().to_string(),
+            JavaTypeSignature::Reference(reference) => reference.java_name(qualified),
+        }
+    }
+}
+
+impl ReferenceTypeSignature {
+    /// How this type is written in java source code, like `Map<K, List<? extends V>>`
+    pub fn java_name(&self, qualified: bool) -> String {
+        match self {
+            ReferenceTypeSignature::Class(class) => class.java_name(qualified),
+            ReferenceTypeSignature::TypeVariable(name) => name.clone(),
+            ReferenceTypeSignature::Array(component) => format!("{}[]", component.java_name(qualified)),
+        }
+    }
+}
+
+impl ClassTypeSignature {
+    /// Name of the outermost class in internal form, like `java/util/Map`
+    pub fn outer_class_name(&self) -> String {
+        if self.package.is_empty() {
+            self.classes[0].name.clone()
+        } else {
+            format!("{}/{}", self.package, self.classes[0].name)
+        }
+    }
+
+    /// Name of the class in internal form, with inner classes separated by `$` like `java/util/Map$Entry`
+    pub fn class_name(&self) -> String {
+        let mut name = self.outer_class_name();
+        for class in &self.classes[1..] {
+            name.push('$');
+            name.push_str(&class.name);
+        }
+        name
+    }
+
+    /// How this type is written in java source code, like `Map<K, V>` or `Outer<T>.Inner`
+    pub fn java_name(&self, qualified: bool) -> String {
+        let mut s = java_class_name(&self.outer_class_name(), qualified);
+        for (i, class) in self.classes.iter().enumerate() {
+            if i > 0 {
+                s.push('.');
+                s.push_str(&class.name);
+            }
+            if !class.type_arguments.is_empty() {
+                let arguments: Vec<String> = class.type_arguments.iter().map(|a| a.java_name(qualified)).collect();
+                s.push('<');
+                s.push_str(&arguments.join(", "));
+                s.push('>');
+            }
+        }
+        s
+    }
+}
+
+impl TypeArgument {
+    pub fn java_name(&self, qualified: bool) -> String {
+        match self {
+            TypeArgument::Any => "?".to_string(),
+            TypeArgument::Exact(reference) => reference.java_name(qualified),
+            TypeArgument::Extends(reference) => format!("? extends {}", reference.java_name(qualified)),
+            TypeArgument::Super(reference) => format!("? super {}", reference.java_name(qualified)),
+        }
+    }
+}
+
+impl TypeParameter {
+    /// How this parameter is declared in java source code, like `T extends Number & Comparable<T>`
+    ///
+    /// A bound of `Object` is left out, since that's what an unbounded parameter compiles to.
+    pub fn java_declaration(&self, qualified: bool) -> String {
+        let bounds: Vec<String> = self.class_bound.iter()
+            .filter(|bound| !is_object(bound))
+            .chain(self.interface_bounds.iter())
+            .map(|bound| bound.java_name(qualified))
+            .collect();
+        if bounds.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} extends {}", self.name, bounds.join(" & "))
+        }
+    }
+}
+
+fn is_object(reference: &ReferenceTypeSignature) -> bool {
+    match reference {
+        ReferenceTypeSignature::Class(class) => class.package == "java/lang"
+            && class.classes.len() == 1
+            && class.classes[0].name == "Object"
+            && class.classes[0].type_arguments.is_empty(),
+        _ => false,
+    }
+}
+
+fn type_parameters_java(type_parameters: &[TypeParameter], qualified: bool) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    let parameters: Vec<String> = type_parameters.iter().map(|p| p.java_declaration(qualified)).collect();
+    format!("<{}>", parameters.join(", "))
+}
+
+fn write_type_parameters(f: &mut Formatter<'_>, type_parameters: &[TypeParameter]) -> fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+    write!(f, "<")?;
+    for parameter in type_parameters {
+        write!(f, "{}", parameter)?;
+    }
+    write!(f, ">")
+}
+
+impl Display for ClassSignature {
+    /// Writes the signature back in the class file form
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.superclass)?;
+        for interface in &self.interfaces {
+            write!(f, "{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MethodSignature {
+    /// Writes the signature back in the class file form
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for param in &self.params {
+            write!(f, "{}", param)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type)?,
+            None => write!(f, "V")?,
+        }
+        for throw in &self.throws {
+            write!(f, "^{}", throw)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for FieldSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.field_type)
+    }
+}
+
+impl Display for JavaTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JavaTypeSignature::Base(base) => write!(f, "{}", base.descriptor_char()),
+            JavaTypeSignature::Reference(reference) => write!(f, "{}", reference),
+        }
+    }
+}
+
+impl Display for ReferenceTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceTypeSignature::Class(class) => write!(f, "{}", class),
+            ReferenceTypeSignature::TypeVariable(name) => write!(f, "T{};", name),
+            ReferenceTypeSignature::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+impl Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "L")?;
+        if !self.package.is_empty() {
+            write!(f, "{}/", self.package)?;
+        }
+        for (i, class) in self.classes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class.name)?;
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                for argument in &class.type_arguments {
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ">")?;
+            }
+        }
+        write!(f, ";")
+    }
+}
+
+impl Display for TypeArgument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "*"),
+            TypeArgument::Exact(reference) => write!(f, "{}", reference),
+            TypeArgument::Extends(reference) => write!(f, "+{}", reference),
+            TypeArgument::Super(reference) => write!(f, "-{}", reference),
+        }
+    }
+}
+
+impl Display for TypeParameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        if let Some(class_bound) = &self.class_bound {
+            write!(f, "{}", class_bound)?;
+        }
+        for bound in &self.interface_bounds {
+            write!(f, ":{}", bound)?;
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { s, position: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, SignatureError> {
+        let c = self.peek().ok_or(SignatureError::UnexpectedEnd { position: self.position })?;
+        self.position += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SignatureError> {
+        let position = self.position;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            found => Err(SignatureError::UnexpectedCharacter { position, found }),
+        }
+    }
+
+    fn end(&self) -> Result<(), SignatureError> {
+        if self.position != self.s.len() {
+            return Err(SignatureError::TrailingCharacters { position: self.position });
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<&'a str, SignatureError> {
+        let start = self.position;
+        let rest = &self.s[start..];
+        let len = rest.find(['.', ';', '[', '/', '<', '>', ':']).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(SignatureError::ExpectedIdentifier { position: start });
+        }
+        self.position += len;
+        Ok(&rest[..len])
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut type_parameters = vec![];
+        if self.peek() != Some('<') {
+            return Ok(type_parameters);
+        }
+        self.next()?;
+        loop {
+            let name = self.identifier()?.to_string();
+            self.expect(':')?;
+            let class_bound = match self.peek() {
+                Some(':') => None,
+                _ => Some(self.reference_type()?),
+            };
+            let mut interface_bounds = vec![];
+            while self.peek() == Some(':') {
+                self.next()?;
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter { name, class_bound, interface_bounds });
+            if self.peek() == Some('>') {
+                self.next()?;
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<JavaTypeSignature, SignatureError> {
+        let position = self.position;
+        match self.peek() {
+            Some(c) => match BaseType::from_descriptor_char(c) {
+                Some(base) => {
+                    self.next()?;
+                    Ok(JavaTypeSignature::Base(base))
+                }
+                None => self.reference_type().map(JavaTypeSignature::Reference),
+            },
+            None => Err(SignatureError::UnexpectedEnd { position }),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<ReferenceTypeSignature, SignatureError> {
+        let position = self.position;
+        match self.peek() {
+            Some('L') => Ok(ReferenceTypeSignature::Class(self.class_type()?)),
+            Some('T') => {
+                self.next()?;
+                let name = self.identifier()?.to_string();
+                self.expect(';')?;
+                Ok(ReferenceTypeSignature::TypeVariable(name))
+            }
+            Some('[') => {
+                let mut dimensions = 0;
+                while self.peek() == Some('[') {
+                    self.next()?;
+                    dimensions += 1;
+                }
+                if dimensions > MAX_ARRAY_DIMENSIONS {
+                    return Err(SignatureError::TooManyDimensions { position });
+                }
+                let mut reference = ReferenceTypeSignature::Array(Box::new(self.java_type()?));
+                for _ in 1..dimensions {
+                    reference = ReferenceTypeSignature::Array(Box::new(JavaTypeSignature::Reference(reference)));
+                }
+                Ok(reference)
+            }
+            Some(found) => Err(SignatureError::UnexpectedCharacter { position, found }),
+            None => Err(SignatureError::UnexpectedEnd { position }),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect('L')?;
+        let mut package = String::new();
+        let mut name = self.identifier()?;
+        while self.peek() == Some('/') {
+            self.next()?;
+            if !package.is_empty() {
+                package.push('/');
+            }
+            package.push_str(name);
+            name = self.identifier()?;
+        }
+        let mut classes = vec![SimpleClassTypeSignature { name: name.to_string(), type_arguments: self.type_arguments()? }];
+        while self.peek() == Some('.') {
+            self.next()?;
+            let name = self.identifier()?.to_string();
+            classes.push(SimpleClassTypeSignature { name, type_arguments: self.type_arguments()? });
+        }
+        self.expect(';')?;
+        Ok(ClassTypeSignature { package, classes })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>, SignatureError> {
+        let mut type_arguments = vec![];
+        if self.peek() != Some('<') {
+            return Ok(type_arguments);
+        }
+        self.next()?;
+        loop {
+            let argument = match self.peek() {
+                Some('*') => {
+                    self.next()?;
+                    TypeArgument::Any
+                }
+                Some('+') => {
+                    self.next()?;
+                    TypeArgument::Extends(self.reference_type()?)
+                }
+                Some('-') => {
+                    self.next()?;
+                    TypeArgument::Super(self.reference_type()?)
+                }
+                _ => TypeArgument::Exact(self.reference_type()?),
+            };
+            type_arguments.push(argument);
+            if self.peek() == Some('>') {
+                self.next()?;
+                return Ok(type_arguments);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassSignature, FieldSignature, MethodSignature, ReferenceTypeSignature, SignatureError, TypeArgument};
+
+    #[test]
+    pub fn parses_field_signature() {
+        let signature = FieldSignature::parse("Ljava/util/Map<TK;Ljava/util/List<+TV;>;>;").unwrap();
+        assert_eq!(signature.java_name(false), "Map<K, List<? extends V>>");
+        assert_eq!(signature.java_name(true), "java.util.Map<K, java.util.List<? extends V>>");
+        match &signature.field_type {
+            ReferenceTypeSignature::Class(class) => {
+                assert_eq!(class.package, "java/util");
+                assert_eq!(class.classes[0].type_arguments[0], TypeArgument::Exact(ReferenceTypeSignature::TypeVariable("K".to_string())));
+            }
+            other => panic!("expected a class type, got {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn parses_class_signature() {
+        let s = "<T::Ljava/lang/Comparable<-TT;>;U:Ljava/lang/Number;>Ljava/lang/Object;Ljava/util/List<[TT;>;";
+        let signature = ClassSignature::parse(s).unwrap();
+        assert_eq!(signature.type_parameters.len(), 2);
+        assert!(signature.type_parameters[0].class_bound.is_none());
+        assert_eq!(signature.to_string(), s);
+        assert_eq!(
+            signature.java_declaration(false),
+            "<T extends Comparable<? super T>, U extends Number> extends Object implements List<T[]>"
+        );
+    }
+
+    #[test]
+    pub fn parses_method_signature() {
+        let s = "<T:Ljava/lang/Object;>(Ljava/util/List<*>;[[ITT;)TT;^Ljava/io/IOException;^TE;";
+        let signature = MethodSignature::parse(s).unwrap();
+        assert_eq!(signature.params.len(), 3);
+        assert_eq!(signature.throws.len(), 2);
+        assert_eq!(signature.to_string(), s);
+        assert_eq!(signature.java_signature("get", false), "<T> T get(List<?>, int[][], T) throws IOException, E");
+        assert_eq!(MethodSignature::parse("()V").unwrap().java_signature("run", false), "void run()");
+    }
+
+    #[test]
+    pub fn parses_inner_classes() {
+        let s = "Lcom/example/Outer<Ljava/lang/String;>.Inner<TT;>.Deeper;";
+        let signature = FieldSignature::parse(s).unwrap();
+        assert_eq!(signature.to_string(), s);
+        assert_eq!(signature.java_name(false), "Outer<String>.Inner<T>.Deeper");
+        match &signature.field_type {
+            ReferenceTypeSignature::Class(class) => assert_eq!(class.class_name(), "com/example/Outer$Inner$Deeper"),
+            other => panic!("expected a class type, got {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn reports_error_positions() {
+        assert_eq!(FieldSignature::parse("Ljava/util/List<TT;"), Err(SignatureError::UnexpectedEnd { position: 19 }));
+        assert_eq!(FieldSignature::parse("Ljava//List;"), Err(SignatureError::ExpectedIdentifier { position: 6 }));
+        assert_eq!(FieldSignature::parse("I"), Err(SignatureError::UnexpectedCharacter { position: 0, found: 'I' }));
+        assert_eq!(FieldSignature::parse("TT;X"), Err(SignatureError::TrailingCharacters { position: 3 }));
+        assert_eq!(MethodSignature::parse("(I)V^[I"), Err(SignatureError::UnexpectedCharacter { position: 5, found: '[' }));
+        assert_eq!(ClassSignature::parse("<>Ljava/lang/Object;"), Err(SignatureError::ExpectedIdentifier { position: 1 }));
+    }
+}
@@ -0,0 +1,578 @@
+//! Entries of the `StackMapTable` attribute
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.7.4
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::attribute::AttributeParseError;
+use crate::class::{ParseClassError, read_u16, read_u8};
+use crate::class_hierarchy::OBJECT;
+use crate::constant_pool::{ConstantPool, ConstantPoolError};
+use crate::constant_pool_builder::{ConstantPoolBuilder, ConstantPoolBuilderError};
+use crate::descriptor::{BaseType, FieldType, MethodDescriptor};
+use crate::reader::ClassReader;
+use crate::writer::{ClassWriter, WriteClassError, write_table};
+
+/// Type of a local variable or operand stack entry in a stack map frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    Object {
+        /// Points to the class of the object in the constant pool
+        cpool_index: u16,
+    },
+    Uninitialized {
+        /// Offset of the `new` instruction that created the object
+        offset: u16,
+    },
+}
+
+/// A stack map frame as stored in the class file, relative to the previous one
+///
+/// The pc a frame applies to is the previous frame's pc plus `offset_delta + 1`, except for the first frame,
+/// which applies to pc `offset_delta`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackMapFrame {
+    /// Same locals as the previous frame and an empty stack, tags 0 to 63
+    SameFrame {
+        offset_delta: u8,
+    },
+    /// Same locals as the previous frame and a single stack entry, tags 64 to 127
+    SameLocals1StackItemFrame {
+        offset_delta: u8,
+        stack: VerificationTypeInfo,
+    },
+    /// Like [`StackMapFrame::SameLocals1StackItemFrame`] with a wider offset, tag 247
+    SameLocals1StackItemFrameExtended {
+        offset_delta: u16,
+        stack: VerificationTypeInfo,
+    },
+    /// The last `k` locals of the previous frame are gone and the stack is empty, tags 248 to 250
+    ChopFrame {
+        offset_delta: u16,
+        k: u8,
+    },
+    /// Like [`StackMapFrame::SameFrame`] with a wider offset, tag 251
+    SameFrameExtended {
+        offset_delta: u16,
+    },
+    /// Up to three more locals than the previous frame and an empty stack, tags 252 to 254
+    AppendFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+    },
+    /// Every local and stack entry spelled out, tag 255
+    FullFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl StackMapFrame {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::SameFrame { offset_delta } |
+            StackMapFrame::SameLocals1StackItemFrame { offset_delta, .. } => *offset_delta as u16,
+            StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. } |
+            StackMapFrame::ChopFrame { offset_delta, .. } |
+            StackMapFrame::SameFrameExtended { offset_delta } |
+            StackMapFrame::AppendFrame { offset_delta, .. } |
+            StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
+/// Like [`VerificationTypeInfo`], with classes named instead of pointed to in the constant pool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    /// An object of a class in internal form, or an array with its descriptor like `[I`
+    Object(String),
+    /// An object created by the `new` instruction at this offset whose constructor hasn't been called yet
+    Uninitialized(u16),
+}
+
+impl VerificationType {
+    /// Type of a value of `field_type` on the operand stack, where `boolean`, `byte`, `char` and `short` are all ints
+    pub fn from_field_type(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Base(BaseType::Float) => VerificationType::Float,
+            FieldType::Base(BaseType::Double) => VerificationType::Double,
+            FieldType::Base(BaseType::Long) => VerificationType::Long,
+            FieldType::Base(_) => VerificationType::Integer,
+            FieldType::Object(name) => VerificationType::Object(name.clone()),
+            FieldType::Array(_) => VerificationType::Object(field_type.to_string()),
+        }
+    }
+
+    /// Whether values of this type take two slots, which are written as this type followed by [`VerificationType::Top`]
+    pub fn is_wide(&self) -> bool {
+        matches!(self, VerificationType::Long | VerificationType::Double)
+    }
+
+    /// Resolves the class of a [`VerificationTypeInfo::Object`] in `constant_pool`
+    pub fn from_info(info: &VerificationTypeInfo, constant_pool: &ConstantPool) -> Result<Self, ConstantPoolError> {
+        Ok(match info {
+            VerificationTypeInfo::Top => VerificationType::Top,
+            VerificationTypeInfo::Integer => VerificationType::Integer,
+            VerificationTypeInfo::Float => VerificationType::Float,
+            VerificationTypeInfo::Double => VerificationType::Double,
+            VerificationTypeInfo::Long => VerificationType::Long,
+            VerificationTypeInfo::Null => VerificationType::Null,
+            VerificationTypeInfo::UninitializedThis => VerificationType::UninitializedThis,
+            VerificationTypeInfo::Object { cpool_index } => VerificationType::Object(constant_pool.get_class_name(*cpool_index)?.to_string()),
+            VerificationTypeInfo::Uninitialized { offset } => VerificationType::Uninitialized(*offset),
+        })
+    }
+
+    /// Converts the type back into its class file form, adding the classes it names to `constant_pool`
+    pub fn to_info(&self, constant_pool: &mut ConstantPoolBuilder) -> Result<VerificationTypeInfo, ConstantPoolBuilderError> {
+        Ok(match self {
+            VerificationType::Top => VerificationTypeInfo::Top,
+            VerificationType::Integer => VerificationTypeInfo::Integer,
+            VerificationType::Float => VerificationTypeInfo::Float,
+            VerificationType::Double => VerificationTypeInfo::Double,
+            VerificationType::Long => VerificationTypeInfo::Long,
+            VerificationType::Null => VerificationTypeInfo::Null,
+            VerificationType::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            VerificationType::Object(name) => VerificationTypeInfo::Object { cpool_index: constant_pool.class(name)? },
+            VerificationType::Uninitialized(offset) => VerificationTypeInfo::Uninitialized { offset: *offset },
+        })
+    }
+}
+
+impl Display for VerificationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationType::Top => f.write_str("top"),
+            VerificationType::Integer => f.write_str("int"),
+            VerificationType::Float => f.write_str("float"),
+            VerificationType::Double => f.write_str("double"),
+            VerificationType::Long => f.write_str("long"),
+            VerificationType::Null => f.write_str("null"),
+            VerificationType::UninitializedThis => f.write_str("uninitializedThis"),
+            VerificationType::Object(name) => f.write_str(name),
+            VerificationType::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+        }
+    }
+}
+
+/// The types of the local variables and operand stack at some point of a method
+///
+/// Both are listed slot by slot, so a long or double is followed by [`VerificationType::Top`] for its second slot,
+/// unlike in the class file where it's a single entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    pub locals: Vec<VerificationType>,
+    pub stack: Vec<VerificationType>,
+}
+
+impl Frame {
+    /// The implicit frame a method starts with, holding its parameters
+    ///
+    /// `this` is uninitialized in constructors until they call another constructor, except in `java.lang.Object`.
+    pub fn initial(this_class: &str, method_name: &str, descriptor: &MethodDescriptor, is_static: bool) -> Self {
+        let mut frame = Frame::default();
+        if !is_static {
+            if method_name == "<init>" && this_class != OBJECT {
+                frame.locals.push(VerificationType::UninitializedThis);
+            } else {
+                frame.locals.push(VerificationType::Object(this_class.to_string()));
+            }
+        }
+        for param in &descriptor.params {
+            push_slots(&mut frame.locals, VerificationType::from_field_type(param));
+        }
+        frame
+    }
+
+    /// Locals as they're listed in the class file, with one entry per long or double and without the unused slots at the end
+    pub fn locals_entries(&self) -> Vec<VerificationType> {
+        let mut entries = to_entries(&self.locals);
+        while entries.last() == Some(&VerificationType::Top) {
+            entries.pop();
+        }
+        entries
+    }
+
+    /// The operand stack as it's listed in the class file, with one entry per long or double
+    pub fn stack_entries(&self) -> Vec<VerificationType> {
+        to_entries(&self.stack)
+    }
+}
+
+/// Pushes a value of type `verification_type`, along with the [`VerificationType::Top`] for its second slot if it takes two
+pub(crate) fn push_slots(slots: &mut Vec<VerificationType>, verification_type: VerificationType) {
+    let is_wide = verification_type.is_wide();
+    slots.push(verification_type);
+    if is_wide {
+        slots.push(VerificationType::Top);
+    }
+}
+
+/// Copies the top `count` slots of `stack` below the `depth` slots under them, like the `dup` instructions
+///
+/// `stack` must hold at least `count + depth` slots.
+pub(crate) fn dup_slots(stack: &mut Vec<VerificationType>, count: usize, depth: usize) {
+    let top = stack[stack.len() - count..].to_vec();
+    let at = stack.len() - count - depth;
+    stack.splice(at..at, top);
+}
+
+fn to_entries(slots: &[VerificationType]) -> Vec<VerificationType> {
+    let mut entries = vec![];
+    let mut slots = slots.iter();
+    while let Some(slot) = slots.next() {
+        if slot.is_wide() {
+            slots.next();
+        }
+        entries.push(slot.clone());
+    }
+    entries
+}
+
+fn to_slots(infos: &[VerificationTypeInfo], constant_pool: &ConstantPool) -> Result<Vec<VerificationType>, ConstantPoolError> {
+    let mut slots = vec![];
+    for info in infos {
+        push_slots(&mut slots, VerificationType::from_info(info, constant_pool)?);
+    }
+    Ok(slots)
+}
+
+fn to_infos(entries: &[VerificationType], constant_pool: &mut ConstantPoolBuilder) -> Result<Vec<VerificationTypeInfo>, ConstantPoolBuilderError> {
+    entries.iter().map(|entry| entry.to_info(constant_pool)).collect()
+}
+
+/// Encodes frames at the given pcs, sorted by pc, as a `StackMapTable` using the smallest form for each frame
+///
+/// `initial` is the frame the method starts with, from [`Frame::initial`], which the first frame is encoded relative to.
+pub fn compress_frames(
+    initial: &Frame,
+    frames: &[(u32, Frame)],
+    constant_pool: &mut ConstantPoolBuilder,
+) -> Result<Vec<StackMapFrame>, ConstantPoolBuilderError> {
+    let mut compressed = Vec::with_capacity(frames.len());
+    let mut previous_locals = initial.locals_entries();
+    let mut previous_pc = None;
+    for (pc, frame) in frames {
+        let offset_delta = match previous_pc {
+            Some(previous_pc) => (pc - previous_pc - 1) as u16,
+            None => *pc as u16,
+        };
+        let locals = frame.locals_entries();
+        let stack = frame.stack_entries();
+        compressed.push(if locals == previous_locals && stack.is_empty() {
+            if offset_delta < 64 {
+                StackMapFrame::SameFrame { offset_delta: offset_delta as u8 }
+            } else {
+                StackMapFrame::SameFrameExtended { offset_delta }
+            }
+        } else if locals == previous_locals && stack.len() == 1 {
+            let stack = stack[0].to_info(constant_pool)?;
+            if offset_delta < 64 {
+                StackMapFrame::SameLocals1StackItemFrame { offset_delta: offset_delta as u8, stack }
+            } else {
+                StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack }
+            }
+        } else if stack.is_empty() && locals.len() < previous_locals.len() && previous_locals.len() - locals.len() <= 3
+            && previous_locals.starts_with(&locals) {
+            StackMapFrame::ChopFrame { offset_delta, k: (previous_locals.len() - locals.len()) as u8 }
+        } else if stack.is_empty() && locals.len() > previous_locals.len() && locals.len() - previous_locals.len() <= 3
+            && locals.starts_with(&previous_locals) {
+            StackMapFrame::AppendFrame { offset_delta, locals: to_infos(&locals[previous_locals.len()..], constant_pool)? }
+        } else {
+            StackMapFrame::FullFrame {
+                offset_delta,
+                locals: to_infos(&locals, constant_pool)?,
+                stack: to_infos(&stack, constant_pool)?,
+            }
+        });
+        previous_locals = locals;
+        previous_pc = Some(*pc);
+    }
+    Ok(compressed)
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DecodeFrameError {
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("frame at pc {pc} removes {k} locals but there are only {locals}")]
+    ChopTooMany { pc: u32, k: u8, locals: usize },
+    #[error("frame at pc {pc} has {locals} locals, more than the 3 an append frame can add")]
+    AppendTooMany { pc: u32, locals: usize },
+}
+
+/// Expands the frames of a `StackMapTable` into the full frame at each pc, the reverse of [`compress_frames`]
+///
+/// `initial` is the frame the method starts with, from [`Frame::initial`].
+pub fn decode_frames(
+    initial: &Frame,
+    frames: &[StackMapFrame],
+    constant_pool: &ConstantPool,
+) -> Result<Vec<(u32, Frame)>, DecodeFrameError> {
+    let mut decoded = Vec::with_capacity(frames.len());
+    // Locals with one entry per long or double, like in the class file, since chop and append frames count them that way
+    let mut locals = initial.locals_entries();
+    let mut previous_pc = None;
+    for frame in frames {
+        let pc = match previous_pc {
+            Some(previous_pc) => previous_pc + frame.offset_delta() as u32 + 1,
+            None => frame.offset_delta() as u32,
+        };
+        let stack = match frame {
+            StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => vec![],
+            StackMapFrame::SameLocals1StackItemFrame { stack, .. } |
+            StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => to_slots(std::slice::from_ref(stack), constant_pool)?,
+            StackMapFrame::ChopFrame { k, .. } => {
+                if *k as usize > locals.len() {
+                    return Err(DecodeFrameError::ChopTooMany { pc, k: *k, locals: locals.len() });
+                }
+                locals.truncate(locals.len() - *k as usize);
+                vec![]
+            }
+            StackMapFrame::AppendFrame { locals: appended, .. } => {
+                if appended.len() > 3 {
+                    return Err(DecodeFrameError::AppendTooMany { pc, locals: appended.len() });
+                }
+                for info in appended {
+                    locals.push(VerificationType::from_info(info, constant_pool)?);
+                }
+                vec![]
+            }
+            StackMapFrame::FullFrame { locals: full, stack, .. } => {
+                locals = full.iter().map(|info| VerificationType::from_info(info, constant_pool)).collect::<Result<_, _>>()?;
+                to_slots(stack, constant_pool)?
+            }
+        };
+        let mut local_slots = vec![];
+        for local in &locals {
+            push_slots(&mut local_slots, local.clone());
+        }
+        decoded.push((pc, Frame { locals: local_slots, stack }));
+        previous_pc = Some(pc);
+    }
+    Ok(decoded)
+}
+
+fn parse_verification_type<R: ClassReader>(f: &mut R) -> Result<VerificationTypeInfo, ParseClassError> {
+    let tag = read_u8(f)?;
+    Ok(match tag {
+        0 => VerificationTypeInfo::Top,
+        1 => VerificationTypeInfo::Integer,
+        2 => VerificationTypeInfo::Float,
+        3 => VerificationTypeInfo::Double,
+        4 => VerificationTypeInfo::Long,
+        5 => VerificationTypeInfo::Null,
+        6 => VerificationTypeInfo::UninitializedThis,
+        7 => VerificationTypeInfo::Object { cpool_index: read_u16(f)? },
+        8 => VerificationTypeInfo::Uninitialized { offset: read_u16(f)? },
+        _ => return Err(AttributeParseError::InvalidVerificationType(tag).into()),
+    })
+}
+
+fn parse_verification_types<R: ClassReader>(f: &mut R, len: usize) -> Result<Vec<VerificationTypeInfo>, ParseClassError> {
+    let mut types = Vec::with_capacity(len);
+    for _ in 0..len {
+        types.push(parse_verification_type(f)?);
+    }
+    Ok(types)
+}
+
+fn parse_frame<R: ClassReader>(f: &mut R) -> Result<StackMapFrame, ParseClassError> {
+    let frame_type = read_u8(f)?;
+    Ok(match frame_type {
+        0..=63 => StackMapFrame::SameFrame { offset_delta: frame_type },
+        64..=127 => StackMapFrame::SameLocals1StackItemFrame {
+            offset_delta: frame_type - 64,
+            stack: parse_verification_type(f)?,
+        },
+        247 => StackMapFrame::SameLocals1StackItemFrameExtended {
+            offset_delta: read_u16(f)?,
+            stack: parse_verification_type(f)?,
+        },
+        248..=250 => StackMapFrame::ChopFrame {
+            offset_delta: read_u16(f)?,
+            k: 251 - frame_type,
+        },
+        251 => StackMapFrame::SameFrameExtended { offset_delta: read_u16(f)? },
+        252..=254 => {
+            let offset_delta = read_u16(f)?;
+            StackMapFrame::AppendFrame {
+                offset_delta,
+                locals: parse_verification_types(f, frame_type as usize - 251)?,
+            }
+        }
+        255 => {
+            let offset_delta = read_u16(f)?;
+            let locals_len = read_u16(f)?;
+            let locals = parse_verification_types(f, locals_len as usize)?;
+            let stack_len = read_u16(f)?;
+            let stack = parse_verification_types(f, stack_len as usize)?;
+            StackMapFrame::FullFrame { offset_delta, locals, stack }
+        }
+        _ => return Err(AttributeParseError::InvalidStackMapFrameType(frame_type).into()),
+    })
+}
+
+pub(crate) fn parse_stack_map_table<R: ClassReader>(f: &mut R) -> Result<Vec<StackMapFrame>, ParseClassError> {
+    let len = read_u16(f)?;
+    let mut frames = Vec::with_capacity(len as usize);
+    for _ in 0..len {
+        frames.push(parse_frame(f)?);
+    }
+    Ok(frames)
+}
+
+fn write_verification_type(out: &mut Vec<u8>, verification_type: &VerificationTypeInfo) {
+    match verification_type {
+        VerificationTypeInfo::Top => out.write_u8(0),
+        VerificationTypeInfo::Integer => out.write_u8(1),
+        VerificationTypeInfo::Float => out.write_u8(2),
+        VerificationTypeInfo::Double => out.write_u8(3),
+        VerificationTypeInfo::Long => out.write_u8(4),
+        VerificationTypeInfo::Null => out.write_u8(5),
+        VerificationTypeInfo::UninitializedThis => out.write_u8(6),
+        VerificationTypeInfo::Object { cpool_index } => {
+            out.write_u8(7);
+            out.write_u16(*cpool_index);
+        }
+        VerificationTypeInfo::Uninitialized { offset } => {
+            out.write_u8(8);
+            out.write_u16(*offset);
+        }
+    }
+}
+
+fn write_verification_types(out: &mut Vec<u8>, what: &'static str, types: &[VerificationTypeInfo]) -> Result<(), WriteClassError> {
+    write_table(out, what, types, |out, verification_type| {
+        write_verification_type(out, verification_type);
+        Ok(())
+    })
+}
+
+fn write_frame(out: &mut Vec<u8>, frame: &StackMapFrame) -> Result<(), WriteClassError> {
+    match frame {
+        StackMapFrame::SameFrame { offset_delta } => out.write_u8(*offset_delta),
+        StackMapFrame::SameLocals1StackItemFrame { offset_delta, stack } => {
+            out.write_u8(64 + offset_delta);
+            write_verification_type(out, stack);
+        }
+        StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, stack } => {
+            out.write_u8(247);
+            out.write_u16(*offset_delta);
+            write_verification_type(out, stack);
+        }
+        StackMapFrame::ChopFrame { offset_delta, k } => {
+            out.write_u8(251 - k);
+            out.write_u16(*offset_delta);
+        }
+        StackMapFrame::SameFrameExtended { offset_delta } => {
+            out.write_u8(251);
+            out.write_u16(*offset_delta);
+        }
+        StackMapFrame::AppendFrame { offset_delta, locals } => {
+            out.write_u8(251 + locals.len() as u8);
+            out.write_u16(*offset_delta);
+            for local in locals {
+                write_verification_type(out, local);
+            }
+        }
+        StackMapFrame::FullFrame { offset_delta, locals, stack } => {
+            out.write_u8(255);
+            out.write_u16(*offset_delta);
+            write_verification_types(out, "stack map frame locals", locals)?;
+            write_verification_types(out, "stack map frame stack", stack)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn write_stack_map_table(out: &mut Vec<u8>, frames: &[StackMapFrame]) -> Result<(), WriteClassError> {
+    write_table(out, "StackMapTable", frames, write_frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_frames, decode_frames, DecodeFrameError, Frame, StackMapFrame, VerificationType, VerificationTypeInfo};
+    use crate::constant_pool::ConstantPool;
+    use crate::constant_pool_builder::ConstantPoolBuilder;
+    use crate::descriptor::MethodDescriptor;
+
+    fn frame(locals: &[VerificationType], stack: &[VerificationType]) -> Frame {
+        Frame { locals: locals.to_vec(), stack: stack.to_vec() }
+    }
+
+    #[test]
+    pub fn initial_frame_holds_parameters() {
+        let descriptor = MethodDescriptor::parse("(JLjava/lang/String;[I)V").unwrap();
+        assert_eq!(Frame::initial("Foo", "<init>", &descriptor, false).locals, vec![
+            VerificationType::UninitializedThis,
+            VerificationType::Long,
+            VerificationType::Top,
+            VerificationType::Object("java/lang/String".to_string()),
+            VerificationType::Object("[I".to_string()),
+        ]);
+        assert_eq!(Frame::initial("Foo", "bar", &descriptor, false).locals[0], VerificationType::Object("Foo".to_string()));
+        assert_eq!(Frame::initial("Foo", "bar", &descriptor, true).locals.len(), 4);
+    }
+
+    #[test]
+    pub fn picks_smallest_frame_types() {
+        use VerificationType::*;
+        let mut pool = ConstantPoolBuilder::new();
+        let initial = frame(&[Integer], &[]);
+        let frames = [
+            (3, frame(&[Integer, Long, Top, Top], &[])),
+            (4, frame(&[Integer, Long, Top], &[Float])),
+            (100, frame(&[Integer, Long, Top], &[])),
+            (102, frame(&[Integer], &[])),
+            (103, frame(&[], &[Object("Foo".to_string())])),
+            (104, frame(&[Integer, Integer, Integer, Integer], &[])),
+        ];
+        assert_eq!(compress_frames(&initial, &frames, &mut pool).unwrap(), vec![
+            StackMapFrame::AppendFrame { offset_delta: 3, locals: vec![VerificationTypeInfo::Long] },
+            StackMapFrame::SameLocals1StackItemFrame { offset_delta: 0, stack: VerificationTypeInfo::Float },
+            StackMapFrame::SameFrameExtended { offset_delta: 95 },
+            StackMapFrame::ChopFrame { offset_delta: 1, k: 1 },
+            StackMapFrame::FullFrame { offset_delta: 0, locals: vec![], stack: vec![VerificationTypeInfo::Object { cpool_index: 2 }] },
+            StackMapFrame::FullFrame { offset_delta: 0, locals: vec![VerificationTypeInfo::Integer; 4], stack: vec![] },
+        ]);
+        assert_eq!(pool.pool().get_class_name(2), Ok("Foo"));
+    }
+
+    #[test]
+    pub fn expands_relative_frames() {
+        use VerificationType::*;
+        let pool = ConstantPool::new();
+        let initial = frame(&[Integer], &[]);
+        let frames = [
+            StackMapFrame::AppendFrame { offset_delta: 2, locals: vec![VerificationTypeInfo::Double, VerificationTypeInfo::Null] },
+            StackMapFrame::SameLocals1StackItemFrame { offset_delta: 0, stack: VerificationTypeInfo::Long },
+            StackMapFrame::ChopFrame { offset_delta: 9, k: 2 },
+        ];
+        assert_eq!(decode_frames(&initial, &frames, &pool).unwrap(), vec![
+            (2, frame(&[Integer, Double, Top, Null], &[])),
+            (3, frame(&[Integer, Double, Top, Null], &[Long, Top])),
+            (13, frame(&[Integer], &[])),
+        ]);
+        let frames = [StackMapFrame::ChopFrame { offset_delta: 4, k: 2 }];
+        assert_eq!(decode_frames(&initial, &frames, &pool), Err(DecodeFrameError::ChopTooMany { pc: 4, k: 2, locals: 1 }));
+        let frames = [StackMapFrame::FullFrame { offset_delta: 0, locals: vec![VerificationTypeInfo::Object { cpool_index: 1 }], stack: vec![] }];
+        assert!(matches!(decode_frames(&initial, &frames, &pool), Err(DecodeFrameError::ConstantPoolError(_))));
+    }
+}
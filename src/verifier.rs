@@ -0,0 +1,927 @@
+//! Type checking the bytecode of methods against their `StackMapTable`
+//!
+//! Each instruction is checked in order, starting from the frame before it, which is either the frame the previous
+//! instruction left or the one from the `StackMapTable`. Whenever control can move somewhere else, like a branch or
+//! an exception handler, the frame at that point has to be assignable to the target's frame in the table.
+//!
+//! Classes older than version 50 don't have a `StackMapTable`, so their frames are inferred first, see
+//! [`crate::frame_inference`], which can't follow subroutines, so old code using `jsr` and `ret` is reported as
+//! [`VerifyErrorKind::UnsupportedSubroutine`]. Access checks for protected members aren't done.
+//!
+//! See: https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.10.1
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::access_flags::MethodAccessFlags;
+use crate::class::Class;
+use crate::class_hierarchy::{array_component, array_of, ClassHierarchy, ClassHierarchyError, OBJECT, THROWABLE};
+use crate::code::CodeAttribute;
+use crate::constant_pool::{Constant, ConstantPool, ConstantPoolError};
+use crate::descriptor::{DescriptorError, FieldType, MethodDescriptor};
+use crate::frame_inference::{FrameInferenceError, infer_frames};
+use crate::frame_size::member_name_and_type;
+use crate::instruction::{DecodeError, Instruction};
+use crate::method::Method;
+use crate::stack_map::{DecodeFrameError, dup_slots, Frame, push_slots, VerificationType};
+
+/// A method that doesn't pass verification
+#[derive(Error, Debug, PartialEq, Eq)]
+pub struct VerifyError {
+    /// Name of the class, in internal form
+    pub class: String,
+    /// Name and descriptor of the method, like `main([Ljava/lang/String;)V`
+    pub method: String,
+    /// The instruction that failed, `None` for problems with the method as a whole
+    pub pc: Option<u32>,
+    pub kind: VerifyErrorKind,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.class, self.method)?;
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {}", pc)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+/// What an instruction needed to find on the stack or in a local
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// A value assignable to this type
+    Type(VerificationType),
+    /// Any reference, initialized or not
+    Reference,
+    /// Any array, or null
+    Array,
+    /// An array of references, or null
+    ArrayOfReferences,
+    /// A value taking a single slot
+    Category1,
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Type(verification_type) => write!(f, "{}", verification_type),
+            Expected::Reference => f.write_str("a reference"),
+            Expected::Array => f.write_str("an array"),
+            Expected::ArrayOfReferences => f.write_str("an array of references"),
+            Expected::Category1 => f.write_str("a value taking one slot"),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+    #[error("couldn't decode the code: {0}")]
+    DecodeError(#[from] DecodeError),
+    #[error("invalid constant pool reference: {0}")]
+    ConstantPoolError(#[from] ConstantPoolError),
+    #[error("invalid descriptor: {0}")]
+    DescriptorError(#[from] DescriptorError),
+    #[error("invalid StackMapTable: {0}")]
+    DecodeFrameError(#[from] DecodeFrameError),
+    #[error("couldn't infer frames: {0}")]
+    FrameInferenceError(#[from] FrameInferenceError),
+    #[error("couldn't check assignability: {0}")]
+    ClassHierarchyError(#[from] ClassHierarchyError),
+    #[error("method has no code but isn't abstract or native")]
+    MissingCode,
+    #[error("expected {expected} but found {actual}")]
+    TypeMismatch { expected: Expected, actual: VerificationType },
+    #[error("expected {expected} but the stack is empty")]
+    StackUnderflow { expected: Expected },
+    #[error("operand stack grows past max_stack {max_stack}")]
+    StackOverflow { max_stack: u16 },
+    #[error("local {index} is out of range, max_locals is {max_locals}")]
+    LocalOutOfRange { index: u16, max_locals: u16 },
+    #[error("instruction splits a long or double on the stack")]
+    SplitsWideValue,
+    #[error("stack map frame at pc {pc} isn't at the start of an instruction")]
+    FrameNotAtInstruction { pc: u32 },
+    #[error("no stack map frame at pc {target}, which isn't reached by falling through")]
+    MissingFrame { target: u32 },
+    #[error("jumps to {target}, which isn't the start of an instruction")]
+    InvalidBranchTarget { target: u32 },
+    #[error("stack map frame at pc {target} has {expected} stack slots but there are {actual}")]
+    StackDepthMismatch { target: u32, expected: usize, actual: usize },
+    #[error("stack map frame at pc {target} expects {expected} in local {index} but found {actual}")]
+    IncompatibleLocal { target: u32, index: usize, expected: VerificationType, actual: VerificationType },
+    #[error("stack map frame at pc {target} expects {expected} in stack slot {index} but found {actual}")]
+    IncompatibleStack { target: u32, index: usize, expected: VerificationType, actual: VerificationType },
+    #[error("execution falls off the end of the code")]
+    FallsOffEnd,
+    #[error("subroutines aren't allowed in class files from version 50 on")]
+    Subroutine,
+    /// Older classes may use subroutines, but they can't be verified yet
+    #[error("unsupported: subroutine at pc {pc} in a class file older than version 50")]
+    UnsupportedSubroutine { pc: u32 },
+    #[error("constant {index} can't be loaded by this instruction")]
+    NotLoadable { index: u16 },
+    #[error("return instruction doesn't match the return type {return_type}")]
+    WrongReturn { return_type: String },
+    #[error("constructor returns before calling another constructor on this")]
+    UninitializedReturn,
+    #[error("{name} can only be called by invokespecial")]
+    InvalidMethodName { name: String },
+    #[error("expected a constructor of {expected} but found one of {actual}")]
+    WrongConstructor { expected: String, actual: String },
+    #[error("{class} has fewer than {dimensions} dimensions")]
+    TooManyDimensions { class: String, dimensions: u8 },
+}
+
+/// Verifies every method of `class`, returning an error for each method that fails
+pub fn verify_class(class: &Class, hierarchy: &dyn ClassHierarchy) -> Vec<VerifyError> {
+    class.methods.iter()
+        .filter_map(|method| verify_method(class, method, hierarchy).err())
+        .collect()
+}
+
+/// Verifies the code of `method`, which belongs to `class`
+pub fn verify_method(class: &Class, method: &Method, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+    let constant_pool = &class.constant_pool;
+    let error = |pc, kind| VerifyError {
+        class: class.name().unwrap_or("?").to_string(),
+        method: format!("{}{}", method.name(constant_pool).unwrap_or("?"), method.descriptor(constant_pool).unwrap_or("")),
+        pc,
+        kind,
+    };
+    let code = match method.code() {
+        Some(code) => code,
+        None if method.access_flags.intersects(MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_NATIVE) => return Ok(()),
+        None => return Err(error(None, VerifyErrorKind::MissingCode)),
+    };
+    let setup = || -> Result<Verifier, VerifyErrorKind> {
+        let this_class = class.name()?;
+        let initial = method.initial_frame(this_class, constant_pool).map_err(|error| match error {
+            FrameInferenceError::ConstantPoolError(error) => VerifyErrorKind::ConstantPoolError(error),
+            FrameInferenceError::DescriptorError(error) => VerifyErrorKind::DescriptorError(error),
+            error => VerifyErrorKind::FrameInferenceError(error),
+        })?;
+        let frames = if class.java_version.major >= 50 {
+            code.frames(&initial, constant_pool)?
+        } else {
+            let inferred = infer_frames(code, constant_pool, this_class, initial.clone(), hierarchy).map_err(|error| match error {
+                FrameInferenceError::Subroutine { pc } => VerifyErrorKind::UnsupportedSubroutine { pc },
+                error => VerifyErrorKind::FrameInferenceError(error),
+            })?;
+            inferred.into_iter().collect()
+        };
+        Ok(Verifier {
+            major: class.java_version.major,
+            code,
+            instructions: code.instructions().collect::<Result<_, _>>()?,
+            constant_pool,
+            hierarchy,
+            this_class,
+            super_class: class.super_class_name()?,
+            is_constructor: method.name(constant_pool)? == "<init>",
+            descriptor: MethodDescriptor::parse(method.descriptor(constant_pool)?)?,
+            initial,
+            frames,
+        })
+    };
+    let verifier = setup().map_err(|kind| error(None, kind))?;
+    verifier.verify().map_err(|(pc, kind)| error(pc, kind))
+}
+
+/// Everything known about the method being verified
+struct Verifier<'a> {
+    /// Major version of the class, which decides whether subroutines are allowed
+    major: u16,
+    code: &'a CodeAttribute,
+    /// Decoded instructions with their pcs, in order
+    instructions: Vec<(u32, Instruction)>,
+    constant_pool: &'a ConstantPool,
+    hierarchy: &'a dyn ClassHierarchy,
+    this_class: &'a str,
+    super_class: Option<&'a str>,
+    is_constructor: bool,
+    descriptor: MethodDescriptor,
+    initial: Frame,
+    /// Frames of the `StackMapTable`, by pc
+    frames: BTreeMap<u32, Frame>,
+}
+
+/// Where an instruction can go after it runs
+enum Successors {
+    /// The next instruction, and maybe some branch targets
+    FallThrough(Vec<u32>),
+    /// Only the branch targets, like `goto` or the switches
+    Jump(Vec<u32>),
+    /// Nowhere in this method, like `return` or `athrow`
+    Exit,
+}
+
+impl Verifier<'_> {
+    fn verify(&self) -> Result<(), (Option<u32>, VerifyErrorKind)> {
+        for pc in self.frames.keys() {
+            if self.instructions.binary_search_by_key(pc, |(pc, _)| *pc).is_err() {
+                return Err((None, VerifyErrorKind::FrameNotAtInstruction { pc: *pc }));
+            }
+        }
+        let mut current = Some(self.initial.clone());
+        for (pc, instruction) in &self.instructions {
+            let pc = *pc;
+            self.verify_instruction(pc, instruction, &mut current).map_err(|kind| (Some(pc), kind))?;
+        }
+        match current {
+            Some(_) => Err((self.instructions.last().map(|(pc, _)| *pc), VerifyErrorKind::FallsOffEnd)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the instruction at `pc`, replacing `current` with the frame it leaves for the next instruction
+    fn verify_instruction(&self, pc: u32, instruction: &Instruction, current: &mut Option<Frame>) -> Result<(), VerifyErrorKind> {
+        let before = match (current.take(), self.frames.get(&pc)) {
+            (Some(frame), Some(declared)) => {
+                self.check_assignable_frame(&frame, declared, pc)?;
+                declared.clone()
+            }
+            (None, Some(declared)) => declared.clone(),
+            (Some(frame), None) => frame,
+            (None, None) => return Err(VerifyErrorKind::MissingFrame { target: pc }),
+        };
+        for handler in self.code.handlers_at(pc as u16) {
+            let catch_type = handler.catch_type_name(self.constant_pool)?.unwrap_or(THROWABLE);
+            let exception = VerificationType::Object(catch_type.to_string());
+            let throwable = VerificationType::Object(THROWABLE.to_string());
+            if !self.is_assignable(&exception, &throwable)? {
+                return Err(VerifyErrorKind::TypeMismatch { expected: Expected::Type(throwable), actual: exception });
+            }
+            let frame = Frame { locals: before.locals.clone(), stack: vec![exception] };
+            self.check_target(&frame, handler.handler_pc as u32)?;
+        }
+
+        let mut after = before;
+        let targets = match self.execute(&mut after, pc, instruction)? {
+            Successors::FallThrough(targets) => {
+                *current = Some(after.clone());
+                targets
+            }
+            Successors::Jump(targets) => targets,
+            Successors::Exit => vec![],
+        };
+        for target in targets {
+            self.check_target(&after, target)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `frame` can jump to `target`
+    fn check_target(&self, frame: &Frame, target: u32) -> Result<(), VerifyErrorKind> {
+        if self.instructions.binary_search_by_key(&target, |(pc, _)| *pc).is_err() {
+            return Err(VerifyErrorKind::InvalidBranchTarget { target });
+        }
+        match self.frames.get(&target) {
+            Some(declared) => self.check_assignable_frame(frame, declared, target),
+            None => Err(VerifyErrorKind::MissingFrame { target }),
+        }
+    }
+
+    /// Checks that every local and stack slot of `frame` is assignable to the one in `declared`, the frame at `target`
+    fn check_assignable_frame(&self, frame: &Frame, declared: &Frame, target: u32) -> Result<(), VerifyErrorKind> {
+        if frame.stack.len() != declared.stack.len() {
+            return Err(VerifyErrorKind::StackDepthMismatch { target, expected: declared.stack.len(), actual: frame.stack.len() });
+        }
+        for (index, expected) in declared.locals.iter().enumerate() {
+            let actual = frame.locals.get(index).unwrap_or(&VerificationType::Top);
+            if !self.is_assignable(actual, expected)? {
+                return Err(VerifyErrorKind::IncompatibleLocal { target, index, expected: expected.clone(), actual: actual.clone() });
+            }
+        }
+        for (index, (actual, expected)) in frame.stack.iter().zip(&declared.stack).enumerate() {
+            if !self.is_assignable(actual, expected)? {
+                return Err(VerifyErrorKind::IncompatibleStack { target, index, expected: expected.clone(), actual: actual.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a value of type `from` can be used where a `to` is expected
+    fn is_assignable(&self, from: &VerificationType, to: &VerificationType) -> Result<bool, ClassHierarchyError> {
+        Ok(match (from, to) {
+            _ if from == to => true,
+            (_, VerificationType::Top) => true,
+            (VerificationType::Null, VerificationType::Object(_)) => true,
+            (VerificationType::Object(from), VerificationType::Object(to)) => self.is_class_assignable(from, to)?,
+            _ => false,
+        })
+    }
+
+    /// Whether an object of class `from` can be used where a `to` is expected, both can be array descriptors
+    ///
+    /// Like the JVM, any class can be used as an interface, this is only checked when the interface is used.
+    fn is_class_assignable(&self, from: &str, to: &str) -> Result<bool, ClassHierarchyError> {
+        if from == to || to == OBJECT {
+            return Ok(true);
+        }
+        if to.starts_with('[') {
+            return match (array_component(from), array_component(to)) {
+                (Some(from), Some(to)) => self.is_class_assignable(from, to),
+                _ => Ok(false),
+            };
+        }
+        if from.starts_with('[') {
+            return Ok(to == "java/lang/Cloneable" || to == "java/io/Serializable");
+        }
+        if self.hierarchy.is_interface(to)? {
+            return Ok(true);
+        }
+        Ok(self.hierarchy.super_classes(from)?.iter().any(|class| class == to))
+    }
+
+    /// Pops a value of type `expected` off the stack, both slots of it if it's a long or double
+    fn pop(&self, frame: &mut Frame, expected: VerificationType) -> Result<VerificationType, VerifyErrorKind> {
+        let mismatch = |actual| VerifyErrorKind::TypeMismatch { expected: Expected::Type(expected.clone()), actual };
+        if expected.is_wide() {
+            match frame.stack.pop() {
+                Some(VerificationType::Top) => {}
+                Some(actual) => return Err(mismatch(actual)),
+                None => return Err(VerifyErrorKind::StackUnderflow { expected: Expected::Type(expected) }),
+            }
+        }
+        let actual = match frame.stack.pop() {
+            // The second slot of a long or double, which can't be used on its own
+            Some(VerificationType::Top) => return Err(mismatch(frame.stack.last().cloned().unwrap_or(VerificationType::Top))),
+            Some(actual) => actual,
+            None => return Err(VerifyErrorKind::StackUnderflow { expected: Expected::Type(expected) }),
+        };
+        if self.is_assignable(&actual, &expected)? {
+            Ok(actual)
+        } else {
+            Err(mismatch(actual))
+        }
+    }
+
+    /// Pops values of the given types, which are listed in the order they were pushed
+    fn pop_all(&self, frame: &mut Frame, expected: &[VerificationType]) -> Result<(), VerifyErrorKind> {
+        for expected in expected.iter().rev() {
+            self.pop(frame, expected.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Pops a value matching `expected`, which isn't a single type
+    fn pop_matching(&self, frame: &mut Frame, expected: Expected) -> Result<VerificationType, VerifyErrorKind> {
+        let actual = match frame.stack.pop() {
+            Some(VerificationType::Top) => frame.stack.last().cloned().unwrap_or(VerificationType::Top),
+            Some(actual) => actual,
+            None => return Err(VerifyErrorKind::StackUnderflow { expected }),
+        };
+        let matches = match &expected {
+            Expected::Type(expected) => self.is_assignable(&actual, expected)?,
+            Expected::Reference => is_reference(&actual),
+            Expected::Array => match &actual {
+                VerificationType::Object(name) => name.starts_with('['),
+                other => *other == VerificationType::Null,
+            },
+            Expected::ArrayOfReferences => match &actual {
+                VerificationType::Object(name) => array_component(name).is_some(),
+                other => *other == VerificationType::Null,
+            },
+            Expected::Category1 => actual != VerificationType::Top && !actual.is_wide(),
+        };
+        if matches {
+            Ok(actual)
+        } else {
+            Err(VerifyErrorKind::TypeMismatch { expected, actual })
+        }
+    }
+
+    fn push(&self, frame: &mut Frame, verification_type: VerificationType) -> Result<(), VerifyErrorKind> {
+        push_slots(&mut frame.stack, verification_type);
+        if frame.stack.len() > self.code.max_stack as usize {
+            return Err(VerifyErrorKind::StackOverflow { max_stack: self.code.max_stack });
+        }
+        Ok(())
+    }
+
+    fn check_local(&self, index: u16, size: usize) -> Result<(), VerifyErrorKind> {
+        if index as usize + size > self.code.max_locals as usize {
+            return Err(VerifyErrorKind::LocalOutOfRange { index, max_locals: self.code.max_locals });
+        }
+        Ok(())
+    }
+
+    /// Pushes local `index`, which has to be of type `expected`
+    fn load(&self, frame: &mut Frame, index: u16, expected: VerificationType) -> Result<(), VerifyErrorKind> {
+        let size = if expected.is_wide() { 2 } else { 1 };
+        self.check_local(index, size)?;
+        let actual = frame.locals.get(index as usize).cloned().unwrap_or(VerificationType::Top);
+        let second = frame.locals.get(index as usize + 1).unwrap_or(&VerificationType::Top);
+        if actual != expected || (size == 2 && *second != VerificationType::Top) {
+            return Err(VerifyErrorKind::TypeMismatch { expected: Expected::Type(expected), actual });
+        }
+        self.push(frame, actual)
+    }
+
+    /// Pushes the reference in local `index`
+    fn load_reference(&self, frame: &mut Frame, index: u16) -> Result<(), VerifyErrorKind> {
+        self.check_local(index, 1)?;
+        let actual = frame.locals.get(index as usize).cloned().unwrap_or(VerificationType::Top);
+        if !is_reference(&actual) {
+            return Err(VerifyErrorKind::TypeMismatch { expected: Expected::Reference, actual });
+        }
+        self.push(frame, actual)
+    }
+
+    /// Pops a value into local `index`, `expected` is `None` for any reference
+    fn store(&self, frame: &mut Frame, index: u16, expected: Option<VerificationType>) -> Result<(), VerifyErrorKind> {
+        let value = match expected {
+            Some(expected) => self.pop(frame, expected)?,
+            None => self.pop_matching(frame, Expected::Reference)?,
+        };
+        let size = if value.is_wide() { 2 } else { 1 };
+        self.check_local(index, size)?;
+        let index = index as usize;
+        if frame.locals.len() < index + size {
+            frame.locals.resize(index + size, VerificationType::Top);
+        }
+        if index > 0 && frame.locals[index - 1].is_wide() {
+            frame.locals[index - 1] = VerificationType::Top;
+        }
+        frame.locals[index] = value;
+        if size == 2 {
+            frame.locals[index + 1] = VerificationType::Top;
+        }
+        Ok(())
+    }
+
+    /// Checks that taking the top `count` slots of the stack doesn't split a long or double
+    fn check_split(&self, frame: &Frame, count: usize) -> Result<(), VerifyErrorKind> {
+        if frame.stack.len() < count {
+            return Err(VerifyErrorKind::StackUnderflow { expected: Expected::Category1 });
+        }
+        let at = frame.stack.len() - count;
+        if at > 0 && frame.stack[at - 1].is_wide() {
+            return Err(VerifyErrorKind::SplitsWideValue);
+        }
+        Ok(())
+    }
+
+    /// [`dup_slots`] on the stack of `frame`, which mustn't split a long or double or grow past `max_stack`
+    fn dup(&self, frame: &mut Frame, count: usize, depth: usize) -> Result<(), VerifyErrorKind> {
+        self.check_split(frame, count)?;
+        self.check_split(frame, count + depth)?;
+        dup_slots(&mut frame.stack, count, depth);
+        if frame.stack.len() > self.code.max_stack as usize {
+            return Err(VerifyErrorKind::StackOverflow { max_stack: self.code.max_stack });
+        }
+        Ok(())
+    }
+
+    /// Pops an array of `element` and an index, pushing the element
+    fn array_load(&self, frame: &mut Frame, element: VerificationType, arrays: &[&str]) -> Result<(), VerifyErrorKind> {
+        self.pop(frame, VerificationType::Integer)?;
+        self.pop_array(frame, arrays)?;
+        self.push(frame, element)
+    }
+
+    /// Pops an element, an index and an array of one of the `arrays` types
+    fn array_store(&self, frame: &mut Frame, element: VerificationType, arrays: &[&str]) -> Result<(), VerifyErrorKind> {
+        self.pop(frame, element)?;
+        self.pop(frame, VerificationType::Integer)?;
+        self.pop_array(frame, arrays)
+    }
+
+    fn pop_array(&self, frame: &mut Frame, arrays: &[&str]) -> Result<(), VerifyErrorKind> {
+        let actual = self.pop_matching(frame, Expected::Array)?;
+        match &actual {
+            VerificationType::Object(name) if !arrays.contains(&name.as_str()) => Err(VerifyErrorKind::TypeMismatch {
+                expected: Expected::Type(VerificationType::Object(arrays[0].to_string())),
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pops the value returned by a return instruction, checking it matches the method's return type
+    fn check_return(&self, frame: &mut Frame, returned: Option<VerificationType>) -> Result<Successors, VerifyErrorKind> {
+        let return_type = self.descriptor.return_type.as_ref().map(VerificationType::from_field_type);
+        let wrong_return = || VerifyErrorKind::WrongReturn {
+            return_type: self.descriptor.return_type.as_ref().map_or("void".to_string(), |return_type| return_type.to_string()),
+        };
+        match (returned, return_type) {
+            (None, None) => {
+                if self.is_constructor && frame.locals.contains(&VerificationType::UninitializedThis) {
+                    return Err(VerifyErrorKind::UninitializedReturn);
+                }
+            }
+            (Some(VerificationType::Object(_)), Some(return_type @ VerificationType::Object(_))) => {
+                self.pop(frame, return_type)?;
+            }
+            (Some(returned), Some(return_type)) if returned == return_type => {
+                self.pop(frame, return_type)?;
+            }
+            _ => return Err(wrong_return()),
+        }
+        Ok(Successors::Exit)
+    }
+
+    /// Type of a field or dynamic constant, from the constant at `index`
+    fn field_type(&self, index: u16) -> Result<VerificationType, VerifyErrorKind> {
+        let (_, descriptor) = member_name_and_type(self.constant_pool, index)?;
+        Ok(VerificationType::from_field_type(&FieldType::parse(descriptor)?))
+    }
+
+    /// Class that declares the field or method referenced at `index`
+    fn member_class(&self, index: u16) -> Result<&str, VerifyErrorKind> {
+        Ok(match self.constant_pool.get(index)? {
+            Constant::Field { class_index, .. } |
+            Constant::Method { class_index, .. } |
+            Constant::InterfaceMethod { class_index, .. } => self.constant_pool.get_class_name(*class_index)?,
+            _ => OBJECT,
+        })
+    }
+
+    fn ldc(&self, frame: &mut Frame, index: u16, wide: bool) -> Result<(), VerifyErrorKind> {
+        let loaded = match self.constant_pool.get(index)? {
+            Constant::Integer(_) => VerificationType::Integer,
+            Constant::Float(_) => VerificationType::Float,
+            Constant::Long(_) => VerificationType::Long,
+            Constant::Double(_) => VerificationType::Double,
+            Constant::String { .. } => VerificationType::Object("java/lang/String".to_string()),
+            Constant::Class { .. } => VerificationType::Object("java/lang/Class".to_string()),
+            Constant::MethodType { .. } => VerificationType::Object("java/lang/invoke/MethodType".to_string()),
+            Constant::MethodHandle { .. } => VerificationType::Object("java/lang/invoke/MethodHandle".to_string()),
+            Constant::Dynamic { .. } => self.field_type(index)?,
+            _ => return Err(VerifyErrorKind::NotLoadable { index }),
+        };
+        if loaded.is_wide() != wide {
+            return Err(VerifyErrorKind::NotLoadable { index });
+        }
+        self.push(frame, loaded)
+    }
+
+    fn invoke(&self, frame: &mut Frame, instruction: &Instruction, index: u16) -> Result<(), VerifyErrorKind> {
+        let (name, descriptor) = member_name_and_type(self.constant_pool, index)?;
+        let descriptor = MethodDescriptor::parse(descriptor)?;
+        let is_init = name == "<init>";
+        if (is_init && !matches!(instruction, Instruction::Invokespecial(_))) || name == "<clinit>" {
+            return Err(VerifyErrorKind::InvalidMethodName { name: name.to_string() });
+        }
+        for param in descriptor.params.iter().rev() {
+            self.pop(frame, VerificationType::from_field_type(param))?;
+        }
+        match instruction {
+            Instruction::Invokestatic(_) | Instruction::Invokedynamic { .. } => {}
+            Instruction::Invokespecial(_) if is_init => {
+                let receiver = self.pop_matching(frame, Expected::Reference)?;
+                self.initialize(frame, index, receiver)?;
+            }
+            Instruction::Invokespecial(_) => {
+                self.pop(frame, VerificationType::Object(self.this_class.to_string()))?;
+            }
+            Instruction::Invokeinterface { .. } => {
+                self.pop(frame, VerificationType::Object(OBJECT.to_string()))?;
+            }
+            _ => {
+                self.pop(frame, VerificationType::Object(self.member_class(index)?.to_string()))?;
+            }
+        }
+        if let Some(return_type) = &descriptor.return_type {
+            if is_init {
+                return Err(VerifyErrorKind::WrongReturn { return_type: return_type.to_string() });
+            }
+            self.push(frame, VerificationType::from_field_type(return_type))?;
+        }
+        Ok(())
+    }
+
+    /// Checks a constructor call on `receiver` and marks it initialized
+    fn initialize(&self, frame: &mut Frame, index: u16, receiver: VerificationType) -> Result<(), VerifyErrorKind> {
+        let called = self.member_class(index)?;
+        let initialized = match &receiver {
+            VerificationType::UninitializedThis => {
+                // A constructor has to call one of its own class or of the superclass
+                if called != self.this_class && Some(called) != self.super_class {
+                    return Err(VerifyErrorKind::WrongConstructor {
+                        expected: self.super_class.unwrap_or(self.this_class).to_string(),
+                        actual: called.to_string(),
+                    });
+                }
+                self.this_class
+            }
+            VerificationType::Uninitialized(offset) => {
+                let created = match Instruction::decode(&self.code.code, *offset as u32)? {
+                    (Instruction::New(class_index), _) => self.constant_pool.get_class_name(class_index)?,
+                    _ => return Err(VerifyErrorKind::TypeMismatch { expected: Expected::Reference, actual: receiver }),
+                };
+                if created != called {
+                    return Err(VerifyErrorKind::WrongConstructor { expected: created.to_string(), actual: called.to_string() });
+                }
+                created
+            }
+            _ => {
+                return Err(VerifyErrorKind::TypeMismatch {
+                    expected: Expected::Type(VerificationType::UninitializedThis),
+                    actual: receiver,
+                });
+            }
+        };
+        let initialized = VerificationType::Object(initialized.to_string());
+        for slot in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+            if *slot == receiver {
+                *slot = initialized.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Pops the object whose field is accessed by `getfield` or `putfield`
+    fn pop_field_owner(&self, frame: &mut Frame, index: u16, is_put: bool) -> Result<(), VerifyErrorKind> {
+        let class = self.member_class(index)?;
+        // Constructors can set their own fields before calling another constructor
+        if is_put && self.is_constructor && class == self.this_class && frame.stack.last() == Some(&VerificationType::UninitializedThis) {
+            frame.stack.pop();
+            return Ok(());
+        }
+        self.pop(frame, VerificationType::Object(class.to_string()))?;
+        Ok(())
+    }
+
+    /// Checks `instruction` and updates `frame` with its effect
+    fn execute(&self, frame: &mut Frame, pc: u32, instruction: &Instruction) -> Result<Successors, VerifyErrorKind> {
+        use Instruction::*;
+        use VerificationType::{Double as D, Float as F, Integer as I, Long as J};
+        let object = |name: &str| VerificationType::Object(name.to_string());
+        match instruction {
+            Nop => {}
+            AconstNull => self.push(frame, VerificationType::Null)?,
+            IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush(_) | Sipush(_) => self.push(frame, I)?,
+            Lconst0 | Lconst1 => self.push(frame, J)?,
+            Fconst0 | Fconst1 | Fconst2 => self.push(frame, F)?,
+            Dconst0 | Dconst1 => self.push(frame, D)?,
+            Ldc(index) => self.ldc(frame, *index as u16, false)?,
+            LdcW(index) => self.ldc(frame, *index, false)?,
+            Ldc2W(index) => self.ldc(frame, *index, true)?,
+            Iload(index) => self.load(frame, *index, I)?,
+            Lload(index) => self.load(frame, *index, J)?,
+            Fload(index) => self.load(frame, *index, F)?,
+            Dload(index) => self.load(frame, *index, D)?,
+            Aload(index) => self.load_reference(frame, *index)?,
+            Iload0 => self.load(frame, 0, I)?,
+            Iload1 => self.load(frame, 1, I)?,
+            Iload2 => self.load(frame, 2, I)?,
+            Iload3 => self.load(frame, 3, I)?,
+            Lload0 => self.load(frame, 0, J)?,
+            Lload1 => self.load(frame, 1, J)?,
+            Lload2 => self.load(frame, 2, J)?,
+            Lload3 => self.load(frame, 3, J)?,
+            Fload0 => self.load(frame, 0, F)?,
+            Fload1 => self.load(frame, 1, F)?,
+            Fload2 => self.load(frame, 2, F)?,
+            Fload3 => self.load(frame, 3, F)?,
+            Dload0 => self.load(frame, 0, D)?,
+            Dload1 => self.load(frame, 1, D)?,
+            Dload2 => self.load(frame, 2, D)?,
+            Dload3 => self.load(frame, 3, D)?,
+            Aload0 => self.load_reference(frame, 0)?,
+            Aload1 => self.load_reference(frame, 1)?,
+            Aload2 => self.load_reference(frame, 2)?,
+            Aload3 => self.load_reference(frame, 3)?,
+            Iaload => self.array_load(frame, I, &["[I"])?,
+            Laload => self.array_load(frame, J, &["[J"])?,
+            Faload => self.array_load(frame, F, &["[F"])?,
+            Daload => self.array_load(frame, D, &["[D"])?,
+            Baload => self.array_load(frame, I, &["[B", "[Z"])?,
+            Caload => self.array_load(frame, I, &["[C"])?,
+            Saload => self.array_load(frame, I, &["[S"])?,
+            Aaload => {
+                self.pop(frame, I)?;
+                let array = self.pop_matching(frame, Expected::ArrayOfReferences)?;
+                let element = match &array {
+                    VerificationType::Object(name) => object(array_component(name).unwrap()),
+                    _ => VerificationType::Null,
+                };
+                self.push(frame, element)?;
+            }
+            Istore(index) => self.store(frame, *index, Some(I))?,
+            Lstore(index) => self.store(frame, *index, Some(J))?,
+            Fstore(index) => self.store(frame, *index, Some(F))?,
+            Dstore(index) => self.store(frame, *index, Some(D))?,
+            Astore(index) => self.store(frame, *index, None)?,
+            Istore0 => self.store(frame, 0, Some(I))?,
+            Istore1 => self.store(frame, 1, Some(I))?,
+            Istore2 => self.store(frame, 2, Some(I))?,
+            Istore3 => self.store(frame, 3, Some(I))?,
+            Lstore0 => self.store(frame, 0, Some(J))?,
+            Lstore1 => self.store(frame, 1, Some(J))?,
+            Lstore2 => self.store(frame, 2, Some(J))?,
+            Lstore3 => self.store(frame, 3, Some(J))?,
+            Fstore0 => self.store(frame, 0, Some(F))?,
+            Fstore1 => self.store(frame, 1, Some(F))?,
+            Fstore2 => self.store(frame, 2, Some(F))?,
+            Fstore3 => self.store(frame, 3, Some(F))?,
+            Dstore0 => self.store(frame, 0, Some(D))?,
+            Dstore1 => self.store(frame, 1, Some(D))?,
+            Dstore2 => self.store(frame, 2, Some(D))?,
+            Dstore3 => self.store(frame, 3, Some(D))?,
+            Astore0 => self.store(frame, 0, None)?,
+            Astore1 => self.store(frame, 1, None)?,
+            Astore2 => self.store(frame, 2, None)?,
+            Astore3 => self.store(frame, 3, None)?,
+            Iastore => self.array_store(frame, I, &["[I"])?,
+            Lastore => self.array_store(frame, J, &["[J"])?,
+            Fastore => self.array_store(frame, F, &["[F"])?,
+            Dastore => self.array_store(frame, D, &["[D"])?,
+            Bastore => self.array_store(frame, I, &["[B", "[Z"])?,
+            Castore => self.array_store(frame, I, &["[C"])?,
+            Sastore => self.array_store(frame, I, &["[S"])?,
+            Aastore => {
+                // Whether the element fits the array is only known at runtime
+                self.pop_matching(frame, Expected::Reference)?;
+                self.pop(frame, I)?;
+                self.pop_matching(frame, Expected::ArrayOfReferences)?;
+            }
+            Pop => {
+                self.check_split(frame, 1)?;
+                self.pop_matching(frame, Expected::Category1)?;
+            }
+            Pop2 => {
+                self.check_split(frame, 2)?;
+                frame.stack.truncate(frame.stack.len() - 2);
+            }
+            Dup => self.dup(frame, 1, 0)?,
+            DupX1 => self.dup(frame, 1, 1)?,
+            DupX2 => self.dup(frame, 1, 2)?,
+            Dup2 => self.dup(frame, 2, 0)?,
+            Dup2X1 => self.dup(frame, 2, 1)?,
+            Dup2X2 => self.dup(frame, 2, 2)?,
+            Swap => {
+                let first = self.pop_matching(frame, Expected::Category1)?;
+                let second = self.pop_matching(frame, Expected::Category1)?;
+                frame.stack.push(first);
+                frame.stack.push(second);
+            }
+            Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor => {
+                self.pop_all(frame, &[I, I])?;
+                self.push(frame, I)?;
+            }
+            Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor => {
+                self.pop_all(frame, &[J, J])?;
+                self.push(frame, J)?;
+            }
+            Lshl | Lshr | Lushr => {
+                self.pop_all(frame, &[J, I])?;
+                self.push(frame, J)?;
+            }
+            Fadd | Fsub | Fmul | Fdiv | Frem => {
+                self.pop_all(frame, &[F, F])?;
+                self.push(frame, F)?;
+            }
+            Dadd | Dsub | Dmul | Ddiv | Drem => {
+                self.pop_all(frame, &[D, D])?;
+                self.push(frame, D)?;
+            }
+            Ineg | I2b | I2c | I2s => {
+                self.pop(frame, I)?;
+                self.push(frame, I)?;
+            }
+            Lneg => {
+                self.pop(frame, J)?;
+                self.push(frame, J)?;
+            }
+            Fneg => {
+                self.pop(frame, F)?;
+                self.push(frame, F)?;
+            }
+            Dneg => {
+                self.pop(frame, D)?;
+                self.push(frame, D)?;
+            }
+            Iinc { index, .. } => {
+                self.check_local(*index, 1)?;
+                let actual = frame.locals.get(*index as usize).cloned().unwrap_or(VerificationType::Top);
+                if actual != I {
+                    return Err(VerifyErrorKind::TypeMismatch { expected: Expected::Type(I), actual });
+                }
+            }
+            I2l | I2f | I2d | L2i | L2f | L2d | F2i | F2l | F2d | D2i | D2l | D2f => {
+                let (from, to) = match instruction {
+                    I2l => (I, J),
+                    I2f => (I, F),
+                    I2d => (I, D),
+                    L2i => (J, I),
+                    L2f => (J, F),
+                    L2d => (J, D),
+                    F2i => (F, I),
+                    F2l => (F, J),
+                    F2d => (F, D),
+                    D2i => (D, I),
+                    D2l => (D, J),
+                    _ => (D, F),
+                };
+                self.pop(frame, from)?;
+                self.push(frame, to)?;
+            }
+            Lcmp => {
+                self.pop_all(frame, &[J, J])?;
+                self.push(frame, I)?;
+            }
+            Fcmpl | Fcmpg => {
+                self.pop_all(frame, &[F, F])?;
+                self.push(frame, I)?;
+            }
+            Dcmpl | Dcmpg => {
+                self.pop_all(frame, &[D, D])?;
+                self.push(frame, I)?;
+            }
+            Ifeq(_) | Ifne(_) | Iflt(_) | Ifge(_) | Ifgt(_) | Ifle(_) => {
+                self.pop(frame, I)?;
+                return Ok(Successors::FallThrough(instruction.branch_targets(pc)));
+            }
+            IfIcmpeq(_) | IfIcmpne(_) | IfIcmplt(_) | IfIcmpge(_) | IfIcmpgt(_) | IfIcmple(_) => {
+                self.pop_all(frame, &[I, I])?;
+                return Ok(Successors::FallThrough(instruction.branch_targets(pc)));
+            }
+            IfAcmpeq(_) | IfAcmpne(_) => {
+                self.pop_matching(frame, Expected::Reference)?;
+                self.pop_matching(frame, Expected::Reference)?;
+                return Ok(Successors::FallThrough(instruction.branch_targets(pc)));
+            }
+            Ifnull(_) | Ifnonnull(_) => {
+                self.pop_matching(frame, Expected::Reference)?;
+                return Ok(Successors::FallThrough(instruction.branch_targets(pc)));
+            }
+            Goto(_) | GotoW(_) => return Ok(Successors::Jump(instruction.branch_targets(pc))),
+            Tableswitch { .. } | Lookupswitch { .. } => {
+                self.pop(frame, I)?;
+                return Ok(Successors::Jump(instruction.branch_targets(pc)));
+            }
+            Jsr(_) | JsrW(_) | Ret(_) if self.major < 50 => return Err(VerifyErrorKind::UnsupportedSubroutine { pc }),
+            Jsr(_) | JsrW(_) | Ret(_) => return Err(VerifyErrorKind::Subroutine),
+            Ireturn => return self.check_return(frame, Some(I)),
+            Lreturn => return self.check_return(frame, Some(J)),
+            Freturn => return self.check_return(frame, Some(F)),
+            Dreturn => return self.check_return(frame, Some(D)),
+            Areturn => return self.check_return(frame, Some(object(OBJECT))),
+            Return => return self.check_return(frame, None),
+            Getstatic(index) => self.push(frame, self.field_type(*index)?)?,
+            Putstatic(index) => {
+                self.pop(frame, self.field_type(*index)?)?;
+            }
+            Getfield(index) => {
+                self.pop_field_owner(frame, *index, false)?;
+                self.push(frame, self.field_type(*index)?)?;
+            }
+            Putfield(index) => {
+                self.pop(frame, self.field_type(*index)?)?;
+                self.pop_field_owner(frame, *index, true)?;
+            }
+            Invokevirtual(index) | Invokespecial(index) | Invokestatic(index) |
+            Invokeinterface { index, .. } | Invokedynamic { index } => self.invoke(frame, instruction, *index)?,
+            New(_) => self.push(frame, VerificationType::Uninitialized(pc as u16))?,
+            Newarray { atype } => {
+                self.pop(frame, I)?;
+                self.push(frame, object(&format!("[{}", atype.base_type().descriptor_char())))?;
+            }
+            Anewarray(index) => {
+                self.pop(frame, I)?;
+                self.push(frame, object(&array_of(self.constant_pool.get_class_name(*index)?)))?;
+            }
+            Arraylength => {
+                self.pop_matching(frame, Expected::Array)?;
+                self.push(frame, I)?;
+            }
+            Athrow => {
+                self.pop(frame, object(THROWABLE))?;
+                return Ok(Successors::Exit);
+            }
+            Checkcast(index) => {
+                self.pop_matching(frame, Expected::Reference)?;
+                self.push(frame, object(self.constant_pool.get_class_name(*index)?))?;
+            }
+            Instanceof(_) => {
+                self.pop_matching(frame, Expected::Reference)?;
+                self.push(frame, I)?;
+            }
+            Monitorenter | Monitorexit => {
+                self.pop_matching(frame, Expected::Reference)?;
+            }
+            Multianewarray { index, dimensions } => {
+                let class = self.constant_pool.get_class_name(*index)?;
+                if class.chars().take_while(|c| *c == '[').count() < *dimensions as usize || *dimensions == 0 {
+                    return Err(VerifyErrorKind::TooManyDimensions { class: class.to_string(), dimensions: *dimensions });
+                }
+                for _ in 0..*dimensions {
+                    self.pop(frame, I)?;
+                }
+                self.push(frame, object(class))?;
+            }
+        }
+        Ok(Successors::FallThrough(vec![]))
+    }
+}
+
+fn is_reference(verification_type: &VerificationType) -> bool {
+    matches!(
+        verification_type,
+        VerificationType::Null | VerificationType::Object(_) | VerificationType::Uninitialized(_) | VerificationType::UninitializedThis
+    )
+}
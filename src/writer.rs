@@ -0,0 +1,94 @@
+//! Serializing classes back into the class file format
+//!
+//! Every structure is written into an in-memory buffer first, since attributes have to be prefixed with their length.
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WriteClassError {
+    #[error("couldn't write the class file: {0}")]
+    IoError(#[from] io::Error),
+    #[error("{what} has {len} entries, more than the class file format allows")]
+    TooManyEntries { what: &'static str, len: usize },
+    #[error("{what} is {len} bytes long, more than the class file format allows")]
+    TooLong { what: &'static str, len: usize },
+    #[error("constant pool index {0} is an unusable slot that doesn't follow a long or double")]
+    StrayUnusableSlot(u16),
+}
+
+/// Big endian writes, mirroring the reading helpers in [`crate::class`]
+pub(crate) trait ClassWriter {
+    fn write_u8(&mut self, value: u8);
+    fn write_u16(&mut self, value: u16);
+    fn write_u32(&mut self, value: u32);
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    /// Writes the length of a table with a 16 bit length, failing if it doesn't fit
+    fn write_len(&mut self, what: &'static str, len: usize) -> Result<(), WriteClassError> {
+        let len = u16::try_from(len).map_err(|_| WriteClassError::TooManyEntries { what, len })?;
+        self.write_u16(len);
+        Ok(())
+    }
+}
+
+impl ClassWriter for Vec<u8> {
+    fn write_u8(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+pub(crate) fn write_u16_table(out: &mut Vec<u8>, what: &'static str, table: &[u16]) -> Result<(), WriteClassError> {
+    out.write_len(what, table.len())?;
+    for &value in table {
+        out.write_u16(value);
+    }
+    Ok(())
+}
+
+pub(crate) fn write_table<T>(
+    out: &mut Vec<u8>,
+    what: &'static str,
+    table: &[T],
+    mut write: impl FnMut(&mut Vec<u8>, &T) -> Result<(), WriteClassError>,
+) -> Result<(), WriteClassError> {
+    out.write_len(what, table.len())?;
+    for item in table {
+        write(out, item)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassWriter, WriteClassError};
+
+    #[test]
+    pub fn writes_big_endian() {
+        let mut out = vec![];
+        out.write_u8(1);
+        out.write_u16(0x0203);
+        out.write_u32(0x04050607);
+        assert_eq!(out, vec![1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    pub fn rejects_long_tables() {
+        let mut out = vec![];
+        assert!(matches!(out.write_len("test", 65536), Err(WriteClassError::TooManyEntries { what: "test", len: 65536 })));
+        out.write_len("test", 65535).unwrap();
+        assert_eq!(out, vec![0xff, 0xff]);
+    }
+}
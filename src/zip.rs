@@ -0,0 +1,245 @@
+//! Reading entries of zip archives, like jars and jmods
+//!
+//! Only what class paths need is supported: entries that are stored or deflated, and zip64 archives. Data before the
+//! archive is skipped, like the header of jmods or the stub of self-extracting archives, since offsets are checked
+//! against where the central directory actually is.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+use thiserror::Error;
+
+use crate::inflate::{InflateError, inflate};
+
+#[derive(Error, Debug)]
+pub enum ZipError {
+    #[error("couldn't read the archive: {0}")]
+    IoError(#[from] io::Error),
+    #[error("the end of central directory record is missing, this isn't a zip archive")]
+    NotAZip,
+    #[error("the central directory is corrupt")]
+    InvalidCentralDirectory,
+    #[error("{0}: the local file header is corrupt")]
+    InvalidLocalHeader(String),
+    #[error("{0}: encrypted entries aren't supported")]
+    Encrypted(String),
+    #[error("{name}: compression method {method} isn't supported")]
+    UnsupportedMethod { name: String, method: u16 },
+    #[error("{name}: {error}")]
+    InflateError { name: String, error: InflateError },
+    #[error("{0}: the data doesn't match its size or checksum")]
+    Corrupt(String),
+}
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x06064b50;
+const ZIP64_LOCATOR: u32 = 0x07064b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+pub(crate) fn le_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+pub(crate) fn le_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+pub(crate) fn le_u64(bytes: &[u8], at: usize) -> u64 {
+    le_u32(bytes, at) as u64 | (le_u32(bytes, at + 4) as u64) << 32
+}
+
+/// The CRC-32 of zip, for each value of a byte
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    size: u64,
+    /// Where the local file header is from the start of the file
+    header_offset: u64,
+}
+
+/// An open zip archive, which reads its entries from the file as they're asked for
+#[derive(Debug)]
+pub struct ZipArchive {
+    file: Mutex<File>,
+    entries: HashMap<String, Entry>,
+}
+
+impl ZipArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ZipError> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Reads the central directory of the archive in `file`
+    pub fn new(mut file: File) -> Result<Self, ZipError> {
+        let length = file.seek(SeekFrom::End(0))?;
+        // The record is 22 bytes, followed by a comment of up to 65535 bytes
+        let tail_length = length.min(22 + 0xffff);
+        let tail = read_at(&mut file, length - tail_length, tail_length as usize)?;
+        let end = (0..tail.len().saturating_sub(21)).rev()
+            .find(|&i| le_u32(&tail, i) == END_OF_CENTRAL_DIRECTORY)
+            .ok_or(ZipError::NotAZip)?;
+        let end_offset = length - tail_length + end as u64;
+        let mut count = le_u16(&tail, end + 10) as u64;
+        let mut directory_size = le_u32(&tail, end + 12) as u64;
+        let mut directory_offset = le_u32(&tail, end + 16) as u64;
+        // Where the central directory ends, from the start of the file
+        let mut directory_end = end_offset;
+        if count == 0xffff || directory_size == 0xffffffff || directory_offset == 0xffffffff {
+            if end < 20 || le_u32(&tail, end - 20) != ZIP64_LOCATOR {
+                return Err(ZipError::InvalidCentralDirectory);
+            }
+            // The zip64 record is right before its locator, its own offset doesn't account for data before the archive
+            directory_end = end_offset.checked_sub(20 + 56).ok_or(ZipError::InvalidCentralDirectory)?;
+            let record = read_at(&mut file, directory_end, 56)?;
+            if le_u32(&record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY {
+                return Err(ZipError::InvalidCentralDirectory);
+            }
+            count = le_u64(&record, 32);
+            directory_size = le_u64(&record, 40);
+            directory_offset = le_u64(&record, 48);
+        }
+        let directory_start = directory_end.checked_sub(directory_size).ok_or(ZipError::InvalidCentralDirectory)?;
+        // How much data comes before the archive
+        let base = directory_start.checked_sub(directory_offset).ok_or(ZipError::InvalidCentralDirectory)?;
+        let directory = read_at(&mut file, directory_start, directory_size as usize)?;
+
+        let mut entries = HashMap::with_capacity(count.min(0x10000) as usize);
+        let mut pos = 0;
+        for _ in 0..count {
+            let header = directory.get(pos..pos + 46).ok_or(ZipError::InvalidCentralDirectory)?;
+            if le_u32(header, 0) != CENTRAL_HEADER {
+                return Err(ZipError::InvalidCentralDirectory);
+            }
+            let name_length = le_u16(header, 28) as usize;
+            let extra_length = le_u16(header, 30) as usize;
+            let comment_length = le_u16(header, 32) as usize;
+            let name = directory.get(pos + 46..pos + 46 + name_length).ok_or(ZipError::InvalidCentralDirectory)?;
+            let extra = directory.get(pos + 46 + name_length..pos + 46 + name_length + extra_length).ok_or(ZipError::InvalidCentralDirectory)?;
+            let mut entry = Entry {
+                flags: le_u16(header, 8),
+                method: le_u16(header, 10),
+                crc32: le_u32(header, 16),
+                compressed_size: le_u32(header, 20) as u64,
+                size: le_u32(header, 24) as u64,
+                header_offset: le_u32(header, 42) as u64,
+            };
+            zip64_extra(&mut entry, extra)?;
+            entry.header_offset += base;
+            entries.insert(String::from_utf8_lossy(name).into_owned(), entry);
+            pos += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(ZipArchive { file: Mutex::new(file), entries })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Names of the entries, in no particular order
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The uncompressed contents of the entry called `name`, `None` if there's no such entry
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, ZipError> {
+        let entry = match self.entries.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if entry.flags & 1 != 0 {
+            return Err(ZipError::Encrypted(name.to_string()));
+        }
+        let data = {
+            // A poisoned lock only means another read panicked, the file itself is fine
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            let header = read_at(&mut file, entry.header_offset, 30)?;
+            if le_u32(&header, 0) != LOCAL_HEADER {
+                return Err(ZipError::InvalidLocalHeader(name.to_string()));
+            }
+            // The lengths here can differ from the ones in the central directory
+            let data_offset = entry.header_offset + 30 + le_u16(&header, 26) as u64 + le_u16(&header, 28) as u64;
+            read_at(&mut file, data_offset, entry.compressed_size as usize)?
+        };
+        let contents = match entry.method {
+            STORED => data,
+            DEFLATED => inflate(&data).map_err(|error| ZipError::InflateError { name: name.to_string(), error })?,
+            method => return Err(ZipError::UnsupportedMethod { name: name.to_string(), method }),
+        };
+        if contents.len() as u64 != entry.size || crc32(&contents) != entry.crc32 {
+            return Err(ZipError::Corrupt(name.to_string()));
+        }
+        Ok(Some(contents))
+    }
+}
+
+/// Replaces the sizes and offset that don't fit in 32 bits with the ones in the zip64 extra field
+fn zip64_extra(entry: &mut Entry, mut extra: &[u8]) -> Result<(), ZipError> {
+    while extra.len() >= 4 {
+        let id = le_u16(extra, 0);
+        let length = le_u16(extra, 2) as usize;
+        let data = extra.get(4..4 + length).ok_or(ZipError::InvalidCentralDirectory)?;
+        if id == ZIP64_EXTRA {
+            // Only the values that overflowed are there, in this order
+            let mut values = data.chunks_exact(8).map(|value| le_u64(value, 0));
+            for value in [&mut entry.size, &mut entry.compressed_size, &mut entry.header_offset] {
+                if *value == 0xffffffff {
+                    *value = values.next().ok_or(ZipError::InvalidCentralDirectory)?;
+                }
+            }
+            return Ok(());
+        }
+        extra = &extra[4 + length..];
+    }
+    Ok(())
+}
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = vec![];
+    // Reading through take doesn't trust the length with a huge allocation up front
+    file.by_ref().take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::crc32;
+
+    #[test]
+    pub fn computes_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+}
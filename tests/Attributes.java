@@ -0,0 +1,30 @@
+import java.io.IOException;
+import java.util.ArrayList;
+import java.util.List;
+
+public sealed class Attributes permits Attributes.Leaf {
+    public static final int ANSWER = 42;
+
+    public record Point(int x, int y) {
+    }
+
+    public static final class Leaf extends Attributes {
+    }
+
+    @Deprecated
+    public <T extends Comparable<T>> List<T> sorted(List<T> items, final int limit) throws IOException {
+        List<T> result = new ArrayList<>();
+        for (T item : items) {
+            if (result.size() >= limit) {
+                break;
+            }
+            result.add(item);
+        }
+        Runnable local = new Runnable() {
+            public void run() {
+            }
+        };
+        local.run();
+        return result;
+    }
+}
@@ -0,0 +1,11 @@
+import java.io.Serializable;
+import java.util.List;
+import java.util.Map;
+
+public class Generics<K extends Comparable<K>, V> implements Serializable {
+    public Map<K, List<? extends V>> entries;
+
+    public <E extends Exception> V lookup(K key, Class<? super E> type) throws E {
+        return null;
+    }
+}
@@ -0,0 +1,7 @@
+package jerris.test;
+
+public class Greeter {
+    public static String greet(String name) {
+        return "Hello " + name + "!";
+    }
+}
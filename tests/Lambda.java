@@ -0,0 +1,8 @@
+import java.util.function.Supplier;
+
+public class Lambda {
+    public static String greet(String name) {
+        Supplier<String> greeting = () -> "Hello " + name + "!";
+        return greeting.get();
+    }
+}
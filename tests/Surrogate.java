@@ -0,0 +1,4 @@
+public class Surrogate {
+    public static final String LONE = "\uD800";
+    public static final String REVERSED = "a\uDE00\uD83Db";
+}
@@ -0,0 +1,16 @@
+import java.io.IOException;
+import java.io.Reader;
+
+public class TryCatch {
+    public static int read(Reader reader) {
+        try {
+            return reader.read();
+        } catch (IOException e) {
+            return -1;
+        } finally {
+            System.out.println("done");
+        }
+    }
+
+    public native void nothing();
+}
@@ -0,0 +1,5 @@
+public class Unicode {
+    public static final String NUL = "a\0b";
+    public static final String EMOJI = "smile 😀";
+    public static final String ACCENTS = "café";
+}
@@ -0,0 +1,13 @@
+public class Wide implements Runnable, Comparable<Wide> {
+    public static final long BIG = 1234567890123L;
+    public static final double PI = 3.141592653589793;
+    public long counter = 9876543210L;
+
+    public void run() {
+        counter += 31415926535L;
+    }
+
+    public int compareTo(Wide other) {
+        return Long.compare(counter, other.counter);
+    }
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use jerris::attribute::AttributeInfo;
+use jerris::class::Class;
+use jerris::class_hierarchy::ClassHierarchyMap;
+use jerris::instruction::Instruction;
+use jerris::stack_map::{StackMapFrame, VerificationTypeInfo};
+
+use common::countdown_class;
+
+#[test]
+fn assembled_code_survives_the_writer() {
+    let class = countdown_class();
+    let reparsed = Class::from_bytes(&class.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed, class);
+    let main = &reparsed.methods[1];
+    let instructions: Vec<Instruction> = main.code().unwrap().instructions().map(|i| i.unwrap().1).collect();
+    assert_eq!(instructions[4], Instruction::Iflt(16));
+    assert_eq!(instructions[9], Instruction::Goto(-14));
+}
+
+#[test]
+fn assembled_code_gets_frames() {
+    let mut class = countdown_class();
+    class.compute_stack_maps(&ClassHierarchyMap::new()).unwrap();
+    let main = class.methods.iter().find(|m| m.name(&class.constant_pool).unwrap() == "main").unwrap();
+    let frames = main.code().unwrap().attributes.iter().find_map(|attribute| match &attribute.info {
+        AttributeInfo::StackMapTable(frames) => Some(frames.clone()),
+        _ => None,
+    });
+    assert_eq!(frames, Some(vec![
+        StackMapFrame::AppendFrame { offset_delta: 3, locals: vec![VerificationTypeInfo::Integer] },
+        StackMapFrame::SameFrame { offset_delta: 16 },
+    ]));
+}
@@ -0,0 +1,189 @@
+use jerris::access_flags::{InnerClassAccessFlags, MethodParameterAccessFlags, RequiresFlags};
+use jerris::attribute::{Attribute, AttributeInfo};
+use jerris::class::Class;
+use jerris::constant_pool::ConstantPool;
+
+fn find<'a>(attributes: &'a [Attribute], pool: &ConstantPool, name: &str) -> &'a AttributeInfo {
+    &attributes.iter()
+        .find(|attribute| attribute.name(pool).unwrap() == name)
+        .unwrap_or_else(|| panic!("missing {name} attribute"))
+        .info
+}
+
+#[test]
+fn class_attributes() {
+    let class = Class::from_file("tests/Attributes.class").unwrap();
+    let pool = &class.constant_pool;
+    match find(&class.attributes, pool, "SourceFile") {
+        AttributeInfo::SourceFile { sourcefile_index } => assert_eq!(pool.get_utf8(*sourcefile_index).unwrap(), "Attributes.java"),
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&class.attributes, pool, "PermittedSubclasses") {
+        AttributeInfo::PermittedSubclasses(classes) => {
+            let names: Vec<&str> = classes.iter().map(|c| pool.get_class_name(*c).unwrap()).collect();
+            assert_eq!(names, vec!["Attributes$Leaf"]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&class.attributes, pool, "NestMembers") {
+        AttributeInfo::NestMembers(classes) => assert_eq!(classes.len(), 3),
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&class.attributes, pool, "InnerClasses") {
+        AttributeInfo::InnerClasses(classes) => {
+            let point = classes.iter()
+                .find(|c| pool.get_class_name(c.inner_class_info_index).unwrap() == "Attributes$Point")
+                .unwrap();
+            assert_eq!(pool.get_utf8(point.inner_name_index).unwrap(), "Point");
+            assert!(point.inner_class_access_flags.contains(InnerClassAccessFlags::ACC_STATIC | InnerClassAccessFlags::ACC_FINAL));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn field_attributes() {
+    let class = Class::from_file("tests/Attributes.class").unwrap();
+    let pool = &class.constant_pool;
+    match find(&class.fields[0].attributes, pool, "ConstantValue") {
+        AttributeInfo::ConstantValue { constantvalue_index } => {
+            assert_eq!(pool.get(*constantvalue_index).unwrap(), &jerris::constant_pool::Constant::Integer(42));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn method_attributes() {
+    let class = Class::from_file("tests/Attributes.class").unwrap();
+    let pool = &class.constant_pool;
+    let sorted = class.methods.iter().find(|m| m.name(pool).unwrap() == "sorted").unwrap();
+    assert_eq!(find(&sorted.attributes, pool, "Deprecated"), &AttributeInfo::Deprecated);
+    assert!(matches!(find(&sorted.attributes, pool, "RuntimeVisibleAnnotations"), AttributeInfo::Unknown(_)));
+    match find(&sorted.attributes, pool, "Signature") {
+        AttributeInfo::Signature { signature_index } => {
+            assert_eq!(
+                pool.get_utf8(*signature_index).unwrap(),
+                "<T::Ljava/lang/Comparable<TT;>;>(Ljava/util/List<TT;>;I)Ljava/util/List<TT;>;"
+            );
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&sorted.attributes, pool, "Exceptions") {
+        AttributeInfo::Exceptions(classes) => assert_eq!(pool.get_class_name(classes[0]).unwrap(), "java/io/IOException"),
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&sorted.attributes, pool, "MethodParameters") {
+        AttributeInfo::MethodParameters(parameters) => {
+            assert_eq!(pool.get_utf8(parameters[0].name_index).unwrap(), "items");
+            assert_eq!(parameters[1].access_flags, MethodParameterAccessFlags::ACC_FINAL);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let code = match find(&sorted.attributes, pool, "Code") {
+        AttributeInfo::Code(code) => code,
+        other => panic!("unexpected {other:?}"),
+    };
+    assert!(matches!(find(&code.attributes, pool, "LineNumberTable"), AttributeInfo::LineNumberTable(lines) if !lines.is_empty()));
+    assert!(matches!(find(&code.attributes, pool, "StackMapTable"), AttributeInfo::StackMapTable(frames) if !frames.is_empty()));
+    match find(&code.attributes, pool, "LocalVariableTable") {
+        AttributeInfo::LocalVariableTable(variables) => {
+            let names: Vec<&str> = variables.iter().map(|v| pool.get_utf8(v.name_index).unwrap()).collect();
+            assert!(names.contains(&"this") && names.contains(&"items") && names.contains(&"result"));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&code.attributes, pool, "LocalVariableTypeTable") {
+        AttributeInfo::LocalVariableTypeTable(variables) => {
+            let result = variables.iter().find(|v| pool.get_utf8(v.name_index).unwrap() == "result").unwrap();
+            assert_eq!(pool.get_utf8(result.signature_index).unwrap(), "Ljava/util/List<TT;>;");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn record_and_nest_host() {
+    let class = Class::from_file("tests/Attributes$Point.class").unwrap();
+    let pool = &class.constant_pool;
+    match find(&class.attributes, pool, "Record") {
+        AttributeInfo::Record(components) => {
+            let names: Vec<&str> = components.iter().map(|c| c.name(pool).unwrap()).collect();
+            assert_eq!(names, vec!["x", "y"]);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match find(&class.attributes, pool, "NestHost") {
+        AttributeInfo::NestHost { host_class_index } => assert_eq!(pool.get_class_name(*host_class_index).unwrap(), "Attributes"),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(!class.bootstrap_methods().is_empty());
+}
+
+#[test]
+fn enclosing_method() {
+    let class = Class::from_file("tests/Attributes$1.class").unwrap();
+    let pool = &class.constant_pool;
+    match find(&class.attributes, pool, "EnclosingMethod") {
+        AttributeInfo::EnclosingMethod { class_index, method_index } => {
+            assert_eq!(pool.get_class_name(*class_index).unwrap(), "Attributes");
+            assert_eq!(pool.get_name_and_type(*method_index).unwrap().0, "sorted");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn module_attribute() {
+    let class = Class::from_file("tests/module-info.class").unwrap();
+    let pool = &class.constant_pool;
+    let module = match find(&class.attributes, pool, "Module") {
+        AttributeInfo::Module(module) => module,
+        other => panic!("unexpected {other:?}"),
+    };
+    let module_name = |index| match pool.get(index).unwrap() {
+        jerris::constant_pool::Constant::Module { name_index } => pool.get_utf8(*name_index).unwrap(),
+        other => panic!("unexpected {other:?}"),
+    };
+    assert_eq!(module_name(module.module_name_index), "jerris.test");
+    let logging = module.requires.iter().find(|r| module_name(r.requires_index) == "java.logging").unwrap();
+    assert_eq!(logging.requires_flags, RequiresFlags::empty());
+    let base = module.requires.iter().find(|r| module_name(r.requires_index) == "java.base").unwrap();
+    assert!(base.requires_flags.contains(RequiresFlags::ACC_MANDATED));
+    assert_eq!(module.exports.len(), 1);
+    assert_eq!(module.opens[0].to_index.len(), 1);
+}
+
+#[test]
+fn attribute_length_must_match_contents() {
+    let mut bytes = std::fs::read("tests/Main.class").unwrap();
+    // The SourceFile attribute is the last one: name index, length 2 and the file name index
+    let len = bytes.len();
+    bytes[len - 3] = 3;
+    bytes.push(0);
+    assert!(matches!(
+        Class::from_bytes(&bytes),
+        Err(jerris::class::ParseClassError::AttributeParseError(jerris::attribute::AttributeParseError::InvalidLength(name))) if name == "SourceFile"
+    ));
+}
+
+#[test]
+fn undefined_flag_bits_are_kept() {
+    let mut class = Class::from_file("tests/Attributes.class").unwrap();
+    for attribute in &mut class.attributes {
+        if let AttributeInfo::InnerClasses(classes) = &mut attribute.info {
+            for class in classes {
+                class.inner_class_access_flags = InnerClassAccessFlags::from_bits_retain(class.inner_class_access_flags.bits() | 0x0100);
+            }
+        }
+    }
+    let bytes = class.to_bytes().unwrap();
+    let parsed = Class::from_bytes(&bytes).unwrap();
+    match find(&parsed.attributes, &parsed.constant_pool, "InnerClasses") {
+        AttributeInfo::InnerClasses(classes) => assert!(classes.iter().all(|c| c.inner_class_access_flags.bits() & 0x0100 != 0)),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(parsed, class);
+    assert_eq!(Class::from_json(&parsed.to_json()).unwrap(), class);
+    assert_eq!(Class::from_text(&parsed.to_text().unwrap()).unwrap(), class);
+}
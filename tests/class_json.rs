@@ -0,0 +1,99 @@
+mod common;
+
+use std::process::Command;
+
+use jerris::class::Class;
+use jerris::class_json::ClassJsonError;
+use jerris::json::Json;
+
+const FIXTURES: [&str; 12] = [
+    "tests/Main.class",
+    "tests/Wide.class",
+    "tests/Unicode.class",
+    "tests/Surrogate.class",
+    "tests/module-info.class",
+    "tests/Lambda.class",
+    "tests/Attributes.class",
+    "tests/Attributes$Point.class",
+    "tests/Attributes$1.class",
+    "tests/TryCatch.class",
+    "tests/Switch.class",
+    "tests/Generics.class",
+];
+
+#[test]
+fn round_trips_through_json() {
+    for file in FIXTURES {
+        let bytes = std::fs::read(file).unwrap();
+        let class = Class::from_bytes(&bytes).unwrap();
+        let imported = Class::from_json(&class.to_json()).unwrap();
+        assert_eq!(imported, class, "{}", file);
+        assert_eq!(imported.to_bytes().unwrap(), bytes, "{}", file);
+    }
+}
+
+#[test]
+fn imports_hand_edited_json() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    let json = class.to_json().replace("\"value\": \"Hello World!\"", "\"value\": \"Goodbye!\"");
+    let edited = Class::from_bytes(&Class::from_json(&json).unwrap().to_bytes().unwrap()).unwrap();
+    assert!(edited.constant_pool.iter().any(|(_, constant)| format!("{:?}", constant) == "UTF8String(\"Goodbye!\")"));
+}
+
+#[test]
+fn writes_decoded_instructions() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    let json = Json::parse(&class.to_json()).unwrap();
+    let main = match json.get("methods") {
+        Some(Json::Array(methods)) => methods.iter().find(|method| method.get("name") == Some(&Json::string("main"))).unwrap(),
+        _ => panic!("no methods"),
+    };
+    let code = match main.get("attributes") {
+        Some(Json::Array(attributes)) => &attributes[0],
+        _ => panic!("no attributes"),
+    };
+    assert_eq!(code.get("kind"), Some(&Json::string("Code")));
+    let first = match code.get("instructions") {
+        Some(Json::Array(instructions)) => &instructions[0],
+        _ => panic!("no instructions"),
+    };
+    assert_eq!(first.to_string(), r#"{"pc": 0, "opcode": "getstatic", "index": 13, "constant": "java/lang/System.out:Ljava/io/PrintStream;"}"#);
+}
+
+#[test]
+fn reports_where_the_json_is_wrong() {
+    let json = Class::from_file("tests/Main.class").unwrap().to_json();
+    let error = |from: &str, to: &str| Class::from_json(&json.replacen(from, to, 1)).unwrap_err();
+    assert_eq!(error("\"schema_version\": 1", "\"schema_version\": 2"), ClassJsonError::UnsupportedSchemaVersion(2));
+    assert_eq!(error("\"opcode\": \"getstatic\"", "\"opcode\": \"getstatik\"").to_string(),
+        "$.methods[1].attributes[0].instructions[0]: unknown opcode getstatik");
+    assert_eq!(error("\"max_stack\": 2", "\"max_stack\": -2").to_string(), "$.methods[0].attributes[0].max_stack: expected a u16");
+    assert_eq!(error("\"index\": 2,", "\"index\": 3,").to_string(), "$.constant_pool[1].index: expected index 2");
+    assert_eq!(error("\"ACC_SUPER\"", "\"ACC_SUPPER\"").to_string(), "$.access_flags[1]: unknown flag ACC_SUPPER");
+    assert_eq!(error("\"this_class\"", "\"that_class\""), ClassJsonError::MissingMember { path: "$".to_string(), name: "this_class" });
+    assert!(matches!(error("{", "["), ClassJsonError::Json(_)));
+}
+
+#[test]
+fn dumps_and_imports_from_the_command_line() {
+    let jerris = env!("CARGO_BIN_EXE_jerris");
+    let directory = common::temp_dir("class_json_cli");
+    let json = directory.join("TryCatch.json");
+    let class = directory.join("TryCatch.class");
+    let output = Command::new(jerris).args(["dump", "--format", "json", "tests/TryCatch.class"]).output().unwrap();
+    assert!(output.status.success());
+    std::fs::write(&json, output.stdout).unwrap();
+    let status = Command::new(jerris).arg("import").arg(&json).arg(&class).status().unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&class).unwrap(), std::fs::read("tests/TryCatch.class").unwrap());
+
+    // Classes that don't parse aren't written
+    let broken = std::fs::read_to_string(&json).unwrap().replacen("\"class_index\": 2,", "\"class_index\": 3,", 1);
+    std::fs::write(&json, broken).unwrap();
+    let output = Command::new(jerris).arg("import").arg(&json).arg(&class).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the class isn't valid"));
+
+    let output = Command::new(jerris).args(["dump", "--format", "xml", "tests/TryCatch.class"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use jerris::class_hierarchy::{ClassHierarchy, ClassHierarchyError};
+use jerris::class_path::{ClassPath, ClassPathError};
+use jerris::zip::ZipArchive;
+
+#[test]
+fn reads_deflated_and_stored_entries() {
+    let deflated = ZipArchive::open("tests/classes.jar").unwrap();
+    assert_eq!(deflated.read("Switch.class").unwrap().unwrap(), std::fs::read("tests/Switch.class").unwrap());
+    assert!(deflated.contains("META-INF/MANIFEST.MF"));
+    assert!(deflated.read("Missing.class").unwrap().is_none());
+    let stored = ZipArchive::open("tests/stored.jar").unwrap();
+    assert_eq!(stored.read("Generics.class").unwrap().unwrap(), std::fs::read("tests/Generics.class").unwrap());
+    let mut names: Vec<_> = stored.names().collect();
+    names.sort();
+    assert_eq!(names, ["Generics.class", "META-INF/", "META-INF/MANIFEST.MF"]);
+}
+
+#[test]
+fn finds_classes_in_directories_jars_and_jmods() {
+    let class_path = ClassPath::parse("tests/missing:tests/stored.jar:tests/greeter.jmod:tests/classes.jar:tests");
+    assert_eq!(class_path.load("jerris/test/Greeter").unwrap().name().unwrap(), "jerris/test/Greeter");
+    assert_eq!(class_path.find("Generics").unwrap().unwrap(), std::fs::read("tests/Generics.class").unwrap());
+    assert_eq!(class_path.find("TryCatch").unwrap().unwrap(), std::fs::read("tests/TryCatch.class").unwrap());
+    assert_eq!(class_path.load("Attributes$Point").unwrap().name().unwrap(), "Attributes$Point");
+    assert!(class_path.find("java/lang/Missing").unwrap().is_none());
+    assert!(class_path.find("../tests/Main").unwrap().is_none());
+    assert!(matches!(class_path.load("Missing"), Err(ClassPathError::ClassNotFound(class)) if class == "Missing"));
+
+    assert_eq!(class_path.super_class("jerris/test/Greeter"), Ok(Some("java/lang/Object".to_string())));
+    assert_eq!(class_path.is_interface("Switch"), Ok(false));
+    assert_eq!(class_path.super_class("Missing"), Err(ClassHierarchyError::UnknownClass("Missing".to_string())));
+}
+
+#[test]
+fn searches_entries_in_order() {
+    let first = common::temp_dir("class_path_first");
+    let second = common::temp_dir("class_path_second");
+    std::fs::copy("tests/Main.class", first.join("Main.class")).unwrap();
+    std::fs::copy("tests/Switch.class", second.join("Main.class")).unwrap();
+    let class_path = |paths: [&Path; 2]| ClassPath::parse(std::env::join_paths(paths).unwrap().to_str().unwrap());
+    assert_eq!(class_path([&first, &second]).find("Main").unwrap().unwrap(), std::fs::read("tests/Main.class").unwrap());
+    assert_eq!(class_path([&second, &first]).find("Main").unwrap().unwrap(), std::fs::read("tests/Switch.class").unwrap());
+}
+
+#[test]
+fn expands_wildcards_to_jars() {
+    let jars = common::temp_dir("class_path_jars");
+    std::fs::copy("tests/stored.jar", jars.join("b.jar")).unwrap();
+    std::fs::copy("tests/classes.jar", jars.join("a.JAR")).unwrap();
+    std::fs::copy("tests/greeter.jmod", jars.join("c.jmod")).unwrap();
+    std::fs::copy("tests/Main.class", jars.join("Main.class")).unwrap();
+    let class_path = ClassPath::parse(jars.join("*").to_str().unwrap());
+    assert_eq!(class_path.paths().collect::<Vec<_>>(), [jars.join("a.JAR"), jars.join("b.jar")]);
+    assert!(class_path.find("Main").unwrap().is_some());
+    assert!(class_path.find("jerris/test/Greeter").unwrap().is_none());
+}
+
+#[test]
+fn reports_broken_archives() {
+    let broken = common::temp_dir("class_path_broken");
+    std::fs::copy("tests/Main.class", broken.join("broken.jar")).unwrap();
+    let class_path = ClassPath::parse(broken.join("broken.jar").to_str().unwrap());
+    let error = class_path.find("Main").unwrap_err();
+    assert_eq!(error.to_string(), format!("{}: the end of central directory record is missing, this isn't a zip archive", broken.join("broken.jar").display()));
+}
+
+#[test]
+fn prints_classes_from_the_class_path() {
+    let jerris = env!("CARGO_BIN_EXE_jerris");
+    let output = Command::new(jerris).args(["javap", "-cp", "tests/greeter.jmod", "jerris.test.Greeter"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("public class jerris.test.Greeter {"));
+
+    let output = Command::new(jerris).args(["javap", "-cp", "tests/classes.jar", "Missing"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "error: Missing: class Missing isn't on the class path\n");
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::process::Command;
+
+use jerris::class::Class;
+use jerris::class_text::ClassTextError;
+use jerris::constant_pool::Constant;
+
+const FIXTURES: [&str; 12] = [
+    "tests/Main.class",
+    "tests/Wide.class",
+    "tests/Unicode.class",
+    "tests/Surrogate.class",
+    "tests/module-info.class",
+    "tests/Lambda.class",
+    "tests/Attributes.class",
+    "tests/Attributes$Point.class",
+    "tests/Attributes$1.class",
+    "tests/TryCatch.class",
+    "tests/Switch.class",
+    "tests/Generics.class",
+];
+
+#[test]
+fn round_trips_through_text() {
+    for file in FIXTURES {
+        let bytes = std::fs::read(file).unwrap();
+        let class = Class::from_bytes(&bytes).unwrap();
+        let text = class.to_text().unwrap();
+        let assembled = Class::from_text(&text).unwrap_or_else(|e| panic!("{}: {}\n{}", file, e, text));
+        assert_eq!(assembled, class, "{}", file);
+        assert_eq!(assembled.to_bytes().unwrap(), bytes, "{}", file);
+        assert_eq!(assembled.to_text().unwrap(), text, "{}", file);
+    }
+}
+
+#[test]
+fn writes_labels_and_symbolic_constants() {
+    let text = Class::from_file("tests/Main.class").unwrap().to_text().unwrap();
+    assert!(text.contains("        getstatic Fieldref java/lang/System out Ljava/io/PrintStream;\n        ldc String \"Hello World!\"\n"), "{}", text);
+    assert!(text.contains("    L0:\n"), "{}", text);
+    assert!(text.contains("\n.end class\n"), "{}", text);
+}
+
+#[test]
+fn assembles_hand_edited_text() {
+    let text = Class::from_file("tests/Main.class").unwrap().to_text().unwrap();
+    // A constant that isn't listed is added to the end of the pool, and a new label can be jumped to
+    let edited = text.replace("ldc String \"Hello World!\"", "goto L100\n    L100:\n        ldc String \"Goodbye!\" ; was Hello World!");
+    let class = Class::from_bytes(&Class::from_text(&edited).unwrap().to_bytes().unwrap()).unwrap();
+    let (last, _) = class.constant_pool.iter().last().unwrap();
+    assert_eq!(class.constant_pool.get(last).unwrap(), &Constant::String { string_index: last - 1 });
+    assert_eq!(class.constant_pool.get_utf8(last - 1).unwrap(), "Goodbye!");
+}
+
+#[test]
+fn reports_where_the_text_is_wrong() {
+    let text = Class::from_file("tests/Main.class").unwrap().to_text().unwrap();
+    let line_of = |needle: &str| text.lines().position(|line| line.contains(needle)).unwrap() + 1;
+    let error = |from: &str, to: &str| Class::from_text(&text.replacen(from, to, 1)).unwrap_err();
+    assert_eq!(error("getstatic", "getstatik"), ClassTextError::Syntax { line: line_of("getstatic"), message: "unknown instruction getstatik".to_string() });
+    assert_eq!(error(".const #2 =", ".const #3 =").to_string(), format!("line {}: expected constant #2", line_of(".const #2 =")));
+    assert_eq!(error(".class public super", ".class public supper").to_string(), format!("line {}: unknown flag supper", line_of(".class")));
+    assert_eq!(error("    L0:\n", "").to_string(), format!("line {}: undefined label L0", line_of("        L0 ") - 1));
+    assert_eq!(error("ldc String \"Hello World!\"", "ldc String \"Hello World!").to_string(), format!("line {}: unterminated string", line_of("ldc")));
+    assert_eq!(error(".end class", "").to_string(), format!("line {}: unexpected end of file", text.lines().count()));
+}
+
+#[test]
+fn disassembles_and_assembles_from_the_command_line() {
+    let jerris = env!("CARGO_BIN_EXE_jerris");
+    let directory = common::temp_dir("class_text_cli");
+    let text = directory.join("Switch.j");
+    let class = directory.join("Switch.class");
+    let output = Command::new(jerris).args(["disasm", "tests/Switch.class"]).output().unwrap();
+    assert!(output.status.success());
+    std::fs::write(&text, output.stdout).unwrap();
+    let status = Command::new(jerris).arg("asm").arg(&text).arg("-o").arg(&class).status().unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&class).unwrap(), std::fs::read("tests/Switch.class").unwrap());
+
+    // Classes that don't parse aren't written
+    let broken = std::fs::read_to_string(&text).unwrap().replacen("Methodref java/lang/Object <init> ()V", "Methodref #4 <init> ()V", 1);
+    std::fs::write(&text, broken).unwrap();
+    let output = Command::new(jerris).arg("asm").arg(&text).arg("-o").arg(&class).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("the class isn't valid"));
+
+    let output = Command::new(jerris).args(["asm", "tests/Switch.class"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
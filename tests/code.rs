@@ -0,0 +1,92 @@
+mod common;
+
+use jerris::attribute::AttributeParseError;
+use jerris::class::{Class, ParseClassError};
+use jerris::code::ExceptionTableEntry;
+use jerris::frame_size::FrameSize;
+
+use common::CODE_FIXTURES;
+
+#[test]
+fn method_code() {
+    let class = Class::from_file("tests/TryCatch.class").unwrap();
+    let pool = &class.constant_pool;
+    let read = class.methods.iter().find(|m| m.name(pool).unwrap() == "read").unwrap();
+    let code = read.code().unwrap();
+    assert_eq!(code.max_stack, 2);
+    assert_eq!(code.max_locals, 4);
+    assert_eq!(code.code.len(), 39);
+    assert_eq!(code.code[0], 0x2a);
+    assert_eq!(code.exception_table, vec![
+        ExceptionTableEntry { start_pc: 0, end_pc: 5, handler_pc: 15, catch_type: code.exception_table[0].catch_type },
+        ExceptionTableEntry { start_pc: 0, end_pc: 5, handler_pc: 28, catch_type: 0 },
+        ExceptionTableEntry { start_pc: 15, end_pc: 18, handler_pc: 28, catch_type: 0 },
+    ]);
+    let catch_types: Vec<Option<&str>> = code.exception_table.iter()
+        .map(|entry| entry.catch_type_name(pool).unwrap())
+        .collect();
+    assert_eq!(catch_types, vec![Some("java/io/IOException"), None, None]);
+    let handlers: Vec<u16> = code.handlers_at(4).map(|entry| entry.handler_pc).collect();
+    assert_eq!(handlers, vec![15, 28]);
+    assert_eq!(code.handlers_at(20).count(), 0);
+    assert_eq!(code.line_number(0), Some(7));
+    assert_eq!(code.line_number(16), Some(9));
+}
+
+#[test]
+fn native_method_has_no_code() {
+    let class = Class::from_file("tests/TryCatch.class").unwrap();
+    let pool = &class.constant_pool;
+    let nothing = class.methods.iter().find(|m| m.name(pool).unwrap() == "nothing").unwrap();
+    assert!(nothing.code().is_none());
+}
+
+#[test]
+fn code_mut_edits_the_method() {
+    let mut class = Class::from_file("tests/TryCatch.class").unwrap();
+    let method = &mut class.methods[0];
+    method.code_mut().unwrap().max_stack = 10;
+    assert_eq!(method.code().unwrap().max_stack, 10);
+}
+
+#[test]
+fn exception_table_must_be_inside_code() {
+    let mut bytes = std::fs::read("tests/TryCatch.class").unwrap();
+    // The first entry of the exception table of `read`: 0 to 5, handler at 15
+    let entry = [0, 0, 0, 5, 0, 15];
+    let offset = bytes.windows(entry.len()).position(|w| w == entry).unwrap();
+    bytes[offset + 5] = 200;
+    assert!(matches!(
+        Class::from_bytes(&bytes),
+        Err(ParseClassError::AttributeParseError(AttributeParseError::InvalidExceptionTableEntry(entry))) if entry.handler_pc == 200
+    ));
+}
+
+#[test]
+fn computed_frame_sizes_match_javac() {
+    for file in CODE_FIXTURES {
+        let class = Class::from_file(file).unwrap();
+        for method in &class.methods {
+            let computed = match method.compute_frame_size(&class.constant_pool).unwrap() {
+                Some(computed) => computed,
+                None => continue,
+            };
+            assert_eq!(computed, FrameSize::declared(method.code().unwrap()), "{file} {}", method.name(&class.constant_pool).unwrap());
+            assert_eq!(method.check_frame_size(&class.constant_pool), Ok(None));
+        }
+    }
+}
+
+#[test]
+fn updates_frame_size_after_editing() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let main = class.methods.iter().position(|m| m.name(&class.constant_pool).unwrap() == "main").unwrap();
+    let code = class.methods[main].code_mut().unwrap();
+    let declared = FrameSize::declared(code);
+    code.max_stack = 0;
+    code.max_locals = 0;
+    let warning = class.methods[main].check_frame_size(&class.constant_pool).unwrap().unwrap();
+    assert_eq!(warning.computed, declared);
+    class.methods[main].update_frame_size(&class.constant_pool).unwrap();
+    assert_eq!(FrameSize::declared(class.methods[main].code().unwrap()), declared);
+}
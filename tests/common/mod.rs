@@ -0,0 +1,69 @@
+//! Fixtures and helpers shared by the integration tests, every test crate uses only some of them
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use jerris::assembler::Assembler;
+use jerris::class::Class;
+use jerris::class_hierarchy::ClassHierarchyMap;
+use jerris::constant_pool_builder::ConstantPoolBuilder;
+use jerris::instruction::{Instruction, Opcode};
+
+/// Classes compiled by javac whose frames and frame sizes jerris computes exactly like javac did
+pub const CODE_FIXTURES: [&str; 6] = [
+    "tests/Main.class",
+    "tests/TryCatch.class",
+    "tests/Switch.class",
+    "tests/Wide.class",
+    "tests/Generics.class",
+    "tests/Lambda.class",
+];
+
+/// A new, empty directory for a test to write to
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// The classes the fixtures use besides `java/lang/Object`
+pub fn hierarchy() -> ClassHierarchyMap {
+    let mut hierarchy = ClassHierarchyMap::with_exceptions();
+    hierarchy.insert("java/util/List", Some("java/lang/Object"), true);
+    hierarchy
+}
+
+/// Replaces the code of `Main.main` with the one `assemble` emits
+pub fn main_with(assemble: impl FnOnce(&mut Assembler, &mut ConstantPoolBuilder), max_stack: u16, max_locals: u16) -> Class {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let mut pool = ConstantPoolBuilder::from_pool(class.constant_pool.clone());
+    let mut asm = Assembler::new();
+    assemble(&mut asm, &mut pool);
+    class.constant_pool = pool.build();
+    let main = class.methods.iter().position(|m| m.name(&class.constant_pool).unwrap() == "main").unwrap();
+    *class.methods[main].code_mut().unwrap() = asm.finish(max_stack, max_locals).unwrap();
+    class
+}
+
+/// Replaces the body of `Main.main` with a loop printing its argument count down to zero
+pub fn countdown_class() -> Class {
+    main_with(|asm, pool| {
+        let out = pool.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;").unwrap();
+        let println = pool.method_ref("java/io/PrintStream", "println", "(I)V").unwrap();
+        let (top, end) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::Aload0);
+        asm.emit(Instruction::Arraylength);
+        asm.emit(Instruction::Istore1);
+        asm.bind(top).unwrap();
+        asm.emit(Instruction::Iload1);
+        asm.branch(Opcode::Iflt, end).unwrap();
+        asm.emit(Instruction::Getstatic(out));
+        asm.emit(Instruction::Iload1);
+        asm.emit(Instruction::Invokevirtual(println));
+        asm.emit(Instruction::Iinc { index: 1, value: -1 });
+        asm.branch(Opcode::Goto, top).unwrap();
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+    }, 2, 2)
+}
@@ -0,0 +1,28 @@
+use jerris::class::Class;
+use jerris::descriptor::{FieldType, MethodDescriptor};
+
+#[test]
+fn every_fixture_descriptor_parses() {
+    for path in ["tests/Main.class", "tests/Wide.class", "tests/Lambda.class", "tests/Attributes.class", "tests/Switch.class"] {
+        let class = Class::from_file(path).unwrap();
+        let pool = &class.constant_pool;
+        for field in &class.fields {
+            let descriptor = field.descriptor(pool).unwrap();
+            assert_eq!(FieldType::parse(descriptor).unwrap().to_string(), descriptor);
+        }
+        for method in &class.methods {
+            let descriptor = method.descriptor(pool).unwrap();
+            assert_eq!(MethodDescriptor::parse(descriptor).unwrap().to_string(), descriptor);
+        }
+    }
+}
+
+#[test]
+fn main_signature() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    let pool = &class.constant_pool;
+    let main = class.methods.iter().find(|m| m.name(pool).unwrap() == "main").unwrap();
+    let descriptor = MethodDescriptor::parse(main.descriptor(pool).unwrap()).unwrap();
+    assert_eq!(descriptor.java_signature("main", false), "void main(String[])");
+    assert_eq!(descriptor.param_slots(), 1);
+}
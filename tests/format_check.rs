@@ -0,0 +1,66 @@
+use jerris::access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
+use jerris::class::Class;
+use jerris::format_check::{format_check, format_check_bytes, FormatViolation, FormatViolationKind};
+
+const FIXTURES: [&str; 11] = [
+    "tests/Main.class",
+    "tests/Wide.class",
+    "tests/Unicode.class",
+    "tests/Surrogate.class",
+    "tests/module-info.class",
+    "tests/Lambda.class",
+    "tests/Attributes.class",
+    "tests/Attributes$Point.class",
+    "tests/Attributes$1.class",
+    "tests/TryCatch.class",
+    "tests/Generics.class",
+];
+
+#[test]
+fn javac_output_passes() {
+    for fixture in FIXTURES {
+        assert_eq!(format_check_bytes(&std::fs::read(fixture).unwrap()).unwrap(), vec![], "{}", fixture);
+    }
+}
+
+#[test]
+fn reports_trailing_bytes() {
+    let mut bytes = std::fs::read("tests/Main.class").unwrap();
+    bytes.extend([0xca, 0xfe]);
+    let violations = format_check_bytes(&bytes).unwrap();
+    assert_eq!(violations, vec![FormatViolation { section: "4.8", kind: FormatViolationKind::TrailingBytes { count: 2 } }]);
+    assert_eq!(violations[0].to_string(), "2 bytes after the last attribute (JVMS §4.8)");
+}
+
+#[test]
+fn reports_every_violation() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    class.access_flags = ClassAccessFlags::from_bits_retain(0x0002) | ClassAccessFlags::ACC_FINAL | ClassAccessFlags::ACC_ABSTRACT;
+    class.fields[0].access_flags = FieldAccessFlags::ACC_PUBLIC | FieldAccessFlags::ACC_PRIVATE | FieldAccessFlags::ACC_FINAL |
+        FieldAccessFlags::ACC_VOLATILE;
+    class.methods[1].access_flags = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_STATIC;
+    class.methods[1].name_index = class.methods[0].name_index;
+    class.methods[1].descriptor_index = class.methods[0].descriptor_index;
+
+    let violations = class.format_check();
+    assert_eq!(violations, vec![
+        FormatViolation { section: "4.1", kind: FormatViolationKind::FinalAndAbstract },
+        FormatViolation { section: "4.5", kind: FormatViolationKind::ConflictingVisibility { member: "field a I".to_string() } },
+        FormatViolation { section: "4.5", kind: FormatViolationKind::FinalAndVolatile { field: "a I".to_string() } },
+        FormatViolation { section: "4.6", kind: FormatViolationKind::InvalidAbstractMethod { method: "<init>()V".to_string(), flags: 0x0008 } },
+        FormatViolation { section: "4.6", kind: FormatViolationKind::InvalidConstructorFlags { method: "<init>()V".to_string(), flags: 0x0408 } },
+        FormatViolation { section: "4.6", kind: FormatViolationKind::DuplicateMethod { name: "<init>".to_string(), descriptor: "()V".to_string() } },
+    ]);
+    assert_eq!(violations[0].to_string(), "class is both final and abstract (JVMS §4.1)");
+}
+
+#[test]
+fn checks_as_another_version() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    class.methods[1].access_flags = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT | MethodAccessFlags::ACC_STRICT;
+    assert_eq!(format_check(&class, 60), vec![FormatViolation {
+        section: "4.6",
+        kind: FormatViolationKind::InvalidAbstractMethod { method: "main([Ljava/lang/String;)V".to_string(), flags: 0x0800 },
+    }]);
+    assert_eq!(format_check(&class, 61), vec![]);
+}
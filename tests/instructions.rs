@@ -0,0 +1,44 @@
+use jerris::class::Class;
+use jerris::instruction::{ArrayType, Instruction};
+
+fn decode(class: &Class, name: &str) -> Vec<(u32, Instruction)> {
+    let pool = &class.constant_pool;
+    let method = class.methods.iter().find(|m| m.name(pool).unwrap() == name).unwrap();
+    method.code().unwrap().instructions().collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn decode_main() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    let main = decode(&class, "main");
+    let opcodes: Vec<&str> = main.iter().map(|(_, i)| i.opcode().mnemonic()).collect();
+    assert_eq!(opcodes, vec!["getstatic", "ldc", "invokevirtual", "return"]);
+    assert_eq!(main.iter().map(|(pc, _)| *pc).collect::<Vec<_>>(), vec![0, 3, 5, 8]);
+}
+
+#[test]
+fn decode_switches() {
+    let class = Class::from_file("tests/Switch.class").unwrap();
+    let table = decode(&class, "table");
+    assert_eq!(table[1], (1, Instruction::Tableswitch { default: 36, low: 1, high: 3, offsets: vec![27, 30, 33] }));
+    assert_eq!(table[2].0, 28);
+    let lookup = decode(&class, "lookup");
+    match &lookup[1] {
+        (1, Instruction::Lookupswitch { pairs, .. }) => {
+            assert_eq!(pairs.iter().map(|(key, _)| *key).collect::<Vec<_>>(), vec![-1000, 7, 100000]);
+        }
+        other => panic!("expected a lookupswitch, got {:?}", other),
+    }
+}
+
+#[test]
+fn decode_operands() {
+    let class = Class::from_file("tests/Switch.class").unwrap();
+    let size = decode(&class, "size");
+    assert!(matches!(size[2], (2, Instruction::Multianewarray { dimensions: 2, .. })));
+    assert_eq!(size[5], (8, Instruction::Newarray { atype: ArrayType::Long }));
+    assert!(matches!(size[8], (12, Instruction::Invokeinterface { count: 1, .. })));
+    let wide = decode(&class, "wide");
+    assert!(wide.contains(&(587, Instruction::Iinc { index: 250, value: 1000 })));
+    assert!(wide.contains(&(593, Instruction::Iload(250))));
+}
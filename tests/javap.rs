@@ -0,0 +1,47 @@
+//! Compares `jerris javap` with the output of the JDK's javap, saved next to the fixtures as `*.javap`
+use std::process::Command;
+
+use jerris::class::Class;
+use jerris::javap::{javap, JavapOptions};
+
+fn assert_javap(file: &str, options: JavapOptions, expected: &str) {
+    let class = Class::from_file(format!("tests/{}.class", file)).unwrap();
+    let expected = std::fs::read_to_string(format!("tests/{}", expected)).unwrap();
+    assert_eq!(javap(&class, &options).unwrap(), expected, "{}", file);
+}
+
+#[test]
+fn prints_code_and_line_numbers_like_javap() {
+    let options = JavapOptions { code: true, lines: true, private: true, ..Default::default() };
+    for file in ["TryCatch", "Generics", "Switch", "Lambda", "Attributes$Point", "module-info"] {
+        assert_javap(file, options, &format!("{}.javap", file));
+    }
+}
+
+#[test]
+fn prints_only_public_declarations_by_default() {
+    assert_javap("Lambda", JavapOptions::default(), "Lambda.public.javap");
+}
+
+#[test]
+fn prints_everything_when_verbose() {
+    assert_javap("Switch", JavapOptions { verbose: true, ..Default::default() }, "Switch.verbose.javap");
+    assert_javap("Surrogate", JavapOptions { verbose: true, ..Default::default() }, "Surrogate.verbose.javap");
+}
+
+#[test]
+fn exits_with_failure_on_bad_files() {
+    let jerris = env!("CARGO_BIN_EXE_jerris");
+    let output = Command::new(jerris).args(["javap", "tests/Switch.java"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: tests/Switch.java: "));
+
+    let output = Command::new(jerris).args(["javap", "tests/Switch.class", "tests/missing.class"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Compiled from \"Switch.java\""));
+
+    let output = Command::new(jerris).args(["javap", "-x", "tests/Switch.class"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let output = Command::new(jerris).args(["javap"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::process::Command;
+
+use jerris::class_path::ClassPath;
+use jerris::jimage::{ImageLocation, JImage, JImageError};
+use jerris::zip::ZipArchive;
+
+// greeter.jimage has the classes of greeter.jmod, Greeter stored as is and module-info compressed by the zip plugin,
+// along with Main compressed by the compact-cp plugin and Switch by both
+
+fn jmod_class(name: &str) -> Vec<u8> {
+    ZipArchive::open("tests/greeter.jmod").unwrap().read(&format!("classes/{}", name)).unwrap().unwrap()
+}
+
+#[test]
+fn finds_locations() {
+    let image = JImage::open("tests/greeter.jimage").unwrap();
+    let location = image.find("/jerris.greeter/jerris/test/Greeter.class").unwrap().unwrap();
+    assert_eq!(location, ImageLocation {
+        module: "jerris.greeter".to_string(),
+        parent: "jerris/test".to_string(),
+        base: "Greeter".to_string(),
+        extension: "class".to_string(),
+        offset: 0,
+        compressed_size: 0,
+        uncompressed_size: jmod_class("jerris/test/Greeter.class").len() as u64,
+    });
+    assert!(image.find("/jerris.greeter/jerris/test/Missing.class").unwrap().is_none());
+    assert!(image.find("/java.base/java/lang/Object.class").unwrap().is_none());
+    let names: Vec<_> = image.locations().unwrap().iter().map(ImageLocation::name).collect();
+    assert_eq!(names.len(), 5);
+    assert!(names.contains(&"/packages/jerris.test".to_string()));
+}
+
+#[test]
+fn reads_stored_and_compressed_resources() {
+    let image = JImage::open("tests/greeter.jimage").unwrap();
+    let read = |name: &str| image.read_resource(name).unwrap().unwrap();
+    assert_eq!(read("/jerris.greeter/jerris/test/Greeter.class"), jmod_class("jerris/test/Greeter.class"));
+    assert_eq!(read("/jerris.greeter/module-info.class"), jmod_class("module-info.class"));
+    assert_eq!(read("/jerris.greeter/Main.class"), std::fs::read("tests/Main.class").unwrap());
+    assert_eq!(read("/jerris.greeter/Switch.class"), std::fs::read("tests/Switch.class").unwrap());
+    assert!(image.read_resource("/jerris.greeter/Missing.class").unwrap().is_none());
+}
+
+#[test]
+fn finds_classes_through_packages() {
+    let image = JImage::open("tests/greeter.jimage").unwrap();
+    assert_eq!(image.package_modules("jerris/test").unwrap(), ["jerris.greeter"]);
+    assert!(image.package_modules("java/lang").unwrap().is_empty());
+    assert_eq!(image.find_class("jerris/test/Greeter").unwrap().unwrap(), jmod_class("jerris/test/Greeter.class"));
+    assert!(image.find_class("jerris/test/Missing").unwrap().is_none());
+    assert!(image.find_class("java/lang/Object").unwrap().is_none());
+}
+
+#[test]
+fn rejects_files_that_arent_images() {
+    assert!(matches!(JImage::open("tests/Main.class"), Err(JImageError::InvalidMagicNumber)));
+    let mut truncated = std::fs::read("tests/greeter.jimage").unwrap();
+    truncated.truncate(100);
+    let path = common::temp_dir("jimage_truncated").join("modules");
+    std::fs::write(&path, truncated).unwrap();
+    assert!(matches!(JImage::open(&path), Err(JImageError::IoError(_))));
+}
+
+#[test]
+fn loads_runtime_classes_of_a_jdk() {
+    let class_path = ClassPath::parse("tests/greeter.jimage:tests");
+    assert_eq!(class_path.load("jerris/test/Greeter").unwrap().name().unwrap(), "jerris/test/Greeter");
+    assert_eq!(class_path.find("Main").unwrap().unwrap(), std::fs::read("tests/Main.class").unwrap());
+
+    let image_jdk = common::temp_dir("jimage_image_jdk");
+    std::fs::create_dir(image_jdk.join("lib")).unwrap();
+    std::fs::copy("tests/greeter.jimage", image_jdk.join("lib/modules")).unwrap();
+    let jmods_jdk = common::temp_dir("jimage_jmods_jdk");
+    std::fs::create_dir(jmods_jdk.join("jmods")).unwrap();
+    std::fs::copy("tests/greeter.jmod", jmods_jdk.join("jmods/jerris.greeter.jmod")).unwrap();
+    for jdk in [&image_jdk, &jmods_jdk] {
+        let mut class_path = ClassPath::new();
+        class_path.push_jdk(jdk);
+        class_path.append(ClassPath::parse("tests"));
+        assert_eq!(class_path.paths().count(), 2);
+        assert_eq!(class_path.find("jerris/test/Greeter").unwrap().unwrap(), jmod_class("jerris/test/Greeter.class"));
+        assert!(class_path.find("Switch").unwrap().is_some());
+    }
+}
+
+#[test]
+fn prints_classes_of_a_jdk() {
+    let jdk = common::temp_dir("jimage_cli_jdk");
+    std::fs::create_dir(jdk.join("lib")).unwrap();
+    std::fs::copy("tests/greeter.jimage", jdk.join("lib/modules")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_jerris")).args(["javap", "--system"]).arg(&jdk).args(["-cp", "tests", "jerris.test.Greeter", "Main"]).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("public class jerris.test.Greeter {"));
+    assert!(stdout.contains("class Main {"), "{}", stdout);
+}
@@ -0,0 +1,78 @@
+//! Feeds corrupted class files to the parser, which must return an error instead of panicking
+use std::panic::{self, AssertUnwindSafe};
+
+use jerris::class::Class;
+
+const FIXTURES: [&str; 10] = [
+    "tests/Main.class",
+    "tests/Wide.class",
+    "tests/Unicode.class",
+    "tests/Surrogate.class",
+    "tests/module-info.class",
+    "tests/Lambda.class",
+    "tests/Attributes.class",
+    "tests/Attributes$Point.class",
+    "tests/Attributes$1.class",
+    "tests/TryCatch.class",
+];
+
+fn assert_no_panic(bytes: &[u8], description: impl Fn() -> String) {
+    if panic::catch_unwind(AssertUnwindSafe(|| Class::from_bytes(bytes))).is_err() {
+        panic!("parser panicked on {}", description());
+    }
+}
+
+/// Small deterministic PRNG so failures are reproducible without pulling in a dependency
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn truncated_classes_dont_panic() {
+    for fixture in FIXTURES {
+        let bytes = std::fs::read(fixture).unwrap();
+        for len in 0..bytes.len() {
+            assert_no_panic(&bytes[..len], || format!("{fixture} truncated to {len} bytes"));
+        }
+    }
+}
+
+#[test]
+fn single_byte_corruptions_dont_panic() {
+    for fixture in FIXTURES {
+        let bytes = std::fs::read(fixture).unwrap();
+        for i in 0..bytes.len() {
+            for value in [0x00, 0x01, 0x7f, 0x80, 0xff, bytes[i].wrapping_add(1), bytes[i].wrapping_sub(1)] {
+                let mut corrupted = bytes.clone();
+                corrupted[i] = value;
+                assert_no_panic(&corrupted, || format!("{fixture} with byte {i} set to {value:#04x}"));
+            }
+        }
+    }
+}
+
+#[test]
+fn random_corruptions_dont_panic() {
+    let mut rng = XorShift(0x9e3779b97f4a7c15);
+    for fixture in FIXTURES {
+        let bytes = std::fs::read(fixture).unwrap();
+        for _ in 0..2000 {
+            let mut corrupted = bytes.clone();
+            let mut changes = vec![];
+            for _ in 0..1 + rng.next() % 8 {
+                let i = (rng.next() % bytes.len() as u64) as usize;
+                let value = rng.next() as u8;
+                corrupted[i] = value;
+                changes.push((i, value));
+            }
+            assert_no_panic(&corrupted, || format!("{fixture} with bytes changed to {changes:?}"));
+        }
+    }
+}
@@ -0,0 +1,5 @@
+module jerris.test {
+    requires java.logging;
+    exports jerris.test;
+    opens jerris.test to java.logging;
+}
@@ -1,30 +1,32 @@
+use std::io::Cursor;
 use std::string::ToString;
 
 use jerris::access_flags::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
-use jerris::attribute::Attribute;
-use jerris::class::{Class, JavaVersion};
-use jerris::constant_pool::Constant;
+use jerris::attribute::{Attribute, AttributeInfo, LineNumber};
+use jerris::class::{Class, JavaVersion, ParseClassError, ParseOptions};
+use jerris::code::CodeAttribute;
+use jerris::constant_pool::{Constant, ConstantKind, ConstantPool, ConstantPoolError, ConstantPoolValidationError, InvalidReference, MAX_SLOTS};
 use jerris::field::Field;
 use jerris::method::Method;
+use jerris::reader::SliceCursor;
 
-#[test]
-fn parse_class() {
-    let class = Class {
+fn main_class() -> Class {
+    Class {
         java_version: JavaVersion {
             minor: 0,
             major: 63,
         },
-        constant_pool: vec![
+        constant_pool: ConstantPool::from_iter([
             Constant::Method {
-                class_index: 1,
-                name_and_type_index: 2,
+                class_index: 2,
+                name_and_type_index: 3,
             },
             Constant::Class {
-                name_index: 3,
+                name_index: 4,
             },
             Constant::NameAndType {
-                name_index: 4,
-                descriptor_index: 5,
+                name_index: 5,
+                descriptor_index: 6,
             },
             Constant::UTF8String(
                 "java/lang/Object".to_string(),
@@ -36,15 +38,15 @@ fn parse_class() {
                 "()V".to_string(),
             ),
             Constant::Field {
-                class_index: 7,
-                name_and_type_index: 8,
+                class_index: 8,
+                name_and_type_index: 9,
             },
             Constant::Class {
-                name_index: 9,
+                name_index: 10,
             },
             Constant::NameAndType {
-                name_index: 10,
-                descriptor_index: 11,
+                name_index: 11,
+                descriptor_index: 12,
             },
             Constant::UTF8String(
                 "Main".to_string(),
@@ -56,15 +58,15 @@ fn parse_class() {
                 "I".to_string(),
             ),
             Constant::Field {
-                class_index: 13,
-                name_and_type_index: 14,
+                class_index: 14,
+                name_and_type_index: 15,
             },
             Constant::Class {
-                name_index: 15,
+                name_index: 16,
             },
             Constant::NameAndType {
-                name_index: 16,
-                descriptor_index: 17,
+                name_index: 17,
+                descriptor_index: 18,
             },
             Constant::UTF8String(
                 "java/lang/System".to_string(),
@@ -76,21 +78,21 @@ fn parse_class() {
                 "Ljava/io/PrintStream;".to_string(),
             ),
             Constant::String {
-                string_index: 19,
+                string_index: 20,
             },
             Constant::UTF8String(
                 "Hello World!".to_string(),
             ),
             Constant::Method {
-                class_index: 21,
-                name_and_type_index: 22,
+                class_index: 22,
+                name_and_type_index: 23,
             },
             Constant::Class {
-                name_index: 23,
+                name_index: 24,
             },
             Constant::NameAndType {
-                name_index: 24,
-                descriptor_index: 25,
+                name_index: 25,
+                descriptor_index: 26,
             },
             Constant::UTF8String(
                 "java/io/PrintStream".to_string(),
@@ -119,11 +121,11 @@ fn parse_class() {
             Constant::UTF8String(
                 "Main.java".to_string(),
             ),
-        ],
+        ]),
         access_flags: ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_SUPER,
         this_class: 8,
         super_class: 2,
-        interfaces: vec![],
+        interface_indices: vec![],
         fields: vec![
             Field {
                 access_flags: FieldAccessFlags::ACC_PUBLIC,
@@ -140,46 +142,21 @@ fn parse_class() {
                 attributes: vec![
                     Attribute {
                         attribute_name_index: 27,
-                        info: vec![
-                            0,
-                            2,
-                            0,
-                            1,
-                            0,
-                            0,
-                            0,
-                            10,
-                            42,
-                            183,
-                            0,
-                            1,
-                            42,
-                            4,
-                            181,
-                            0,
-                            7,
-                            177,
-                            0,
-                            0,
-                            0,
-                            1,
-                            0,
-                            28,
-                            0,
-                            0,
-                            0,
-                            10,
-                            0,
-                            2,
-                            0,
-                            0,
-                            0,
-                            1,
-                            0,
-                            4,
-                            0,
-                            2,
-                        ],
+                        info: AttributeInfo::Code(CodeAttribute {
+                            max_stack: 2,
+                            max_locals: 1,
+                            code: vec![42, 183, 0, 1, 42, 4, 181, 0, 7, 177],
+                            exception_table: vec![],
+                            attributes: vec![
+                                Attribute {
+                                    attribute_name_index: 28,
+                                    info: AttributeInfo::LineNumberTable(vec![
+                                        LineNumber { start_pc: 0, line_number: 1 },
+                                        LineNumber { start_pc: 4, line_number: 2 },
+                                    ]),
+                                },
+                            ],
+                        }),
                     },
                 ],
             },
@@ -190,45 +167,21 @@ fn parse_class() {
                 attributes: vec![
                     Attribute {
                         attribute_name_index: 27,
-                        info: vec![
-                            0,
-                            2,
-                            0,
-                            1,
-                            0,
-                            0,
-                            0,
-                            9,
-                            178,
-                            0,
-                            13,
-                            18,
-                            19,
-                            182,
-                            0,
-                            21,
-                            177,
-                            0,
-                            0,
-                            0,
-                            1,
-                            0,
-                            28,
-                            0,
-                            0,
-                            0,
-                            10,
-                            0,
-                            2,
-                            0,
-                            0,
-                            0,
-                            4,
-                            0,
-                            8,
-                            0,
-                            5,
-                        ],
+                        info: AttributeInfo::Code(CodeAttribute {
+                            max_stack: 2,
+                            max_locals: 1,
+                            code: vec![178, 0, 13, 18, 19, 182, 0, 21, 177],
+                            exception_table: vec![],
+                            attributes: vec![
+                                Attribute {
+                                    attribute_name_index: 28,
+                                    info: AttributeInfo::LineNumberTable(vec![
+                                        LineNumber { start_pc: 0, line_number: 4 },
+                                        LineNumber { start_pc: 8, line_number: 5 },
+                                    ]),
+                                },
+                            ],
+                        }),
                     },
                 ],
             },
@@ -236,17 +189,414 @@ fn parse_class() {
         attributes: vec![
             Attribute {
                 attribute_name_index: 31,
-                info: vec![
-                    0,
-                    32,
-                ],
+                info: AttributeInfo::SourceFile { sourcefile_index: 32 },
             },
         ],
-    };
+    }
+}
+
+#[test]
+fn parse_class() {
     match Class::from_file("tests/Main.class") {
         Ok(actual_class) => {
-            assert_eq!(class, actual_class);
+            assert_eq!(main_class(), actual_class);
         }
         Err(_e) => panic!("{_e}")
     };
-}
\ No newline at end of file
+}
+
+#[test]
+fn parse_class_from_bytes() {
+    let bytes = std::fs::read("tests/Main.class").unwrap();
+    assert_eq!(main_class(), Class::from_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn parse_class_from_reader() {
+    let bytes = std::fs::read("tests/Main.class").unwrap();
+    assert_eq!(main_class(), Class::from_reader(Cursor::new(bytes)).unwrap());
+}
+
+#[test]
+fn truncated_class_reports_offset() {
+    let bytes = std::fs::read("tests/Main.class").unwrap();
+    let truncated = &bytes[..bytes.len() - 1];
+    let mut cursor = SliceCursor::new(truncated);
+    match Class::parse(&mut cursor) {
+        Err(ParseClassError::UnexpectedEof { offset }) => assert_eq!(offset, bytes.len() - 2),
+        other => panic!("expected unexpected eof error, got {other:?}"),
+    }
+    assert!(matches!(Class::from_reader(truncated), Err(ParseClassError::UnexpectedEof { .. })));
+}
+#[test]
+fn parse_modified_utf8_constants() {
+    let class = Class::from_file("tests/Unicode.class").unwrap();
+    for expected in ["a\0b", "smile 😀", "café"] {
+        assert!(
+            class.constant_pool.iter().any(|(_, constant)| constant == &Constant::UTF8String(expected.to_string())),
+            "missing {expected:?} in constant pool"
+        );
+    }
+}
+
+#[test]
+fn unpaired_surrogates_are_kept() {
+    let bytes = std::fs::read("tests/Surrogate.class").unwrap();
+    let class = Class::from_bytes(&bytes).unwrap();
+    let lone = Constant::UTF8Bytes { string: "\u{fffd}".to_string(), bytes: vec![0xed, 0xa0, 0x80] };
+    assert!(class.constant_pool.iter().any(|(_, constant)| constant == &lone));
+    assert!(class.constant_pool.iter().any(|(_, constant)| matches!(constant, Constant::UTF8Bytes { string, .. } if string == "a\u{fffd}\u{fffd}b")));
+    assert_eq!(class.to_bytes().unwrap(), bytes);
+}
+
+#[test]
+fn invalid_modified_utf8_reports_offset() {
+    let mut bytes = std::fs::read("tests/Main.class").unwrap();
+    let offset = bytes.windows(16).position(|w| w == b"java/lang/Object").unwrap();
+    bytes[offset + 4] = 0;
+    match Class::from_bytes(&bytes) {
+        Err(ParseClassError::InvalidUTF8Constant { offset: actual, .. }) => assert_eq!(actual, offset + 4),
+        other => panic!("expected invalid utf8 error, got {other:?}"),
+    }
+}
+
+#[test]
+fn main_class_references_resolve() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    assert_eq!(class.name().unwrap(), "Main");
+    assert_eq!(class.super_class_name().unwrap(), Some("java/lang/Object"));
+    let field = &class.fields[0];
+    assert_eq!(field.name(&class.constant_pool).unwrap(), "a");
+    assert_eq!(field.descriptor(&class.constant_pool).unwrap(), "I");
+    let main = &class.methods[1];
+    assert_eq!(main.name(&class.constant_pool).unwrap(), "main");
+    assert_eq!(main.descriptor(&class.constant_pool).unwrap(), "([Ljava/lang/String;)V");
+    assert_eq!(class.attributes[0].name(&class.constant_pool).unwrap(), "SourceFile");
+}
+
+#[test]
+fn long_and_double_take_two_slots() {
+    let class = Class::from_file("tests/Wide.class").unwrap();
+    let pool = &class.constant_pool;
+    assert_eq!(class.name().unwrap(), "Wide");
+    assert_eq!(class.interfaces().unwrap(), vec!["java/lang/Runnable", "java/lang/Comparable"]);
+    let wide: Vec<u16> = pool.iter()
+        .filter(|(_, constant)| constant.is_wide())
+        .map(|(index, _)| index)
+        .collect();
+    assert!(!wide.is_empty());
+    for index in wide {
+        assert_eq!(pool.get(index + 1), Err(ConstantPoolError::UnusableIndex(index + 1)));
+    }
+    assert!(pool.iter().any(|(_, constant)| constant == &Constant::Long(1234567890123)));
+    assert!(pool.iter().any(|(_, constant)| constant == &Constant::Double(std::f64::consts::PI)));
+    let names: Vec<&str> = class.fields.iter().map(|f| f.name(pool).unwrap()).collect();
+    assert_eq!(names, vec!["BIG", "PI", "counter"]);
+    let names: Vec<&str> = class.methods.iter().map(|m| m.name(pool).unwrap()).collect();
+    assert_eq!(names, vec!["<init>", "run", "compareTo", "compareTo"]);
+}
+
+#[test]
+fn constant_pool_accessors_report_errors() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    let pool = &class.constant_pool;
+    assert_eq!(pool.get(0), Err(ConstantPoolError::IndexOutOfRange(0)));
+    assert_eq!(pool.get(pool.len() as u16 + 1), Err(ConstantPoolError::IndexOutOfRange(pool.len() as u16 + 1)));
+    assert_eq!(pool.get_utf8(2), Err(ConstantPoolError::UnexpectedKind {
+        index: 2,
+        expected: ConstantKind::UTF8String,
+        actual: ConstantKind::Class,
+    }));
+    assert_eq!(pool.get_class_name(2), Ok("java/lang/Object"));
+    assert_eq!(pool.get_name_and_type(3), Ok(("<init>", "()V")));
+}
+
+#[test]
+fn constant_pool_push_stops_at_max_slots() {
+    let mut pool: ConstantPool = (1..MAX_SLOTS as i32).map(Constant::Integer).collect();
+    assert_eq!(pool.push(Constant::Long(0)), Err(ConstantPoolError::Full));
+    assert_eq!(pool.len(), MAX_SLOTS - 1);
+    assert_eq!(pool.push(Constant::Integer(0)), Ok(MAX_SLOTS as u16));
+    assert_eq!(pool.push(Constant::Integer(0)), Err(ConstantPoolError::Full));
+}
+
+#[test]
+fn constant_pool_set_keeps_slots() {
+    let mut pool = ConstantPool::from_iter([Constant::Long(1), Constant::Integer(2)]);
+    assert_eq!(pool.set(1, Constant::Double(1.0)), Ok(()));
+    assert_eq!(pool.get(1), Ok(&Constant::Double(1.0)));
+    assert_eq!(pool.set(1, Constant::Integer(1)), Err(ConstantPoolError::SlotsMismatch(1)));
+    assert_eq!(pool.set(2, Constant::Integer(1)), Err(ConstantPoolError::UnusableIndex(2)));
+    assert_eq!(pool.set(3, Constant::Long(3)), Err(ConstantPoolError::SlotsMismatch(3)));
+    assert_eq!(pool.set(4, Constant::Integer(4)), Err(ConstantPoolError::IndexOutOfRange(4)));
+    assert_eq!(pool.set(3, Constant::Unusable), Err(ConstantPoolError::UnusableConstant));
+    assert_eq!(pool.push(Constant::Unusable), Err(ConstantPoolError::UnusableConstant));
+    assert_eq!(pool.len(), 3);
+}
+
+/// Builds a minimal class file around hand-written constant pool entries and class attributes
+fn class_with_attributes(constant_pool_count: u16, constants: &[u8], this_class: u16, attributes: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 61];
+    bytes.extend_from_slice(&constant_pool_count.to_be_bytes());
+    bytes.extend_from_slice(constants);
+    // public super, this_class, no super class, no interfaces, fields or methods
+    bytes.extend_from_slice(&[0x00, 0x21]);
+    bytes.extend_from_slice(&this_class.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    bytes.extend_from_slice(attributes);
+    bytes
+}
+
+fn class_with_constants(constant_pool_count: u16, constants: &[u8], this_class: u16) -> Vec<u8> {
+    class_with_attributes(constant_pool_count, constants, this_class, &[0, 0])
+}
+
+fn utf8_constant(string: &str) -> Vec<u8> {
+    let mut bytes = vec![1];
+    bytes.extend_from_slice(&(string.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+    bytes
+}
+
+#[test]
+fn parse_module_and_package_constants() {
+    let class = Class::from_file("tests/module-info.class").unwrap();
+    let pool = &class.constant_pool;
+    let modules: Vec<&str> = pool.iter()
+        .filter_map(|(_, constant)| match constant {
+            Constant::Module { name_index } => Some(pool.get_utf8(*name_index).unwrap()),
+            _ => None
+        })
+        .collect();
+    assert!(modules.contains(&"jerris.test"));
+    assert!(modules.contains(&"java.logging"));
+    let packages: Vec<&str> = pool.iter()
+        .filter_map(|(_, constant)| match constant {
+            Constant::Package { name_index } => Some(pool.get_utf8(*name_index).unwrap()),
+            _ => None
+        })
+        .collect();
+    assert_eq!(packages, vec!["jerris/test"]);
+}
+
+#[test]
+fn parse_dynamic_constant() {
+    let mut constants = vec![17, 0, 0, 0, 2, 12, 0, 3, 0, 4];
+    constants.extend(utf8_constant("answer"));
+    constants.extend(utf8_constant("I"));
+    constants.extend([7, 0, 6]);
+    constants.extend(utf8_constant("Dyn"));
+    constants.extend([15, 6, 0, 8, 10, 0, 5, 0, 9, 12, 0, 10, 0, 11]);
+    constants.extend(utf8_constant("bootstrap"));
+    constants.extend(utf8_constant("(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/Class;)I"));
+    constants.extend(utf8_constant("BootstrapMethods"));
+    // One BootstrapMethods attribute with a single bootstrap method and no arguments
+    let attributes = [0, 1, 0, 12, 0, 0, 0, 6, 0, 1, 0, 7, 0, 0];
+    let class = Class::from_bytes(&class_with_attributes(13, &constants, 5, &attributes)).unwrap();
+    assert_eq!(class.constant_pool.get(1), Ok(&Constant::Dynamic {
+        bootstrap_method_attr_index: 0,
+        name_and_type_index: 2,
+    }));
+    assert_eq!(class.name().unwrap(), "Dyn");
+}
+
+#[test]
+fn unknown_constant_tag_is_an_error() {
+    let mut constants = utf8_constant("Broken");
+    constants.extend([7, 0, 1, 42]);
+    let bytes = class_with_constants(4, &constants, 2);
+    match Class::from_bytes(&bytes) {
+        Err(ParseClassError::UnknownConstantTag { tag, offset }) => {
+            assert_eq!(tag, 42);
+            assert_eq!(offset, 10 + constants.len() - 1);
+        }
+        other => panic!("expected unknown constant tag error, got {other:?}"),
+    }
+}
+
+#[test]
+fn invalid_references_report_index_and_kinds() {
+    // The class name points at the class itself instead of a utf8 string
+    let bytes = class_with_constants(2, &[7, 0, 1], 1);
+    match Class::from_bytes(&bytes) {
+        Err(ParseClassError::ConstantPoolValidationError(ConstantPoolValidationError::ClassWithInvalidNameIndex(reference))) => {
+            assert_eq!(reference, InvalidReference {
+                index: 1,
+                target: 1,
+                expected: ConstantKind::UTF8String,
+                actual: Some(ConstantKind::Class),
+            });
+        }
+        other => panic!("expected invalid class name error, got {other:?}"),
+    }
+    // A string pointing past the end of the constant pool
+    let mut constants = vec![8, 0, 9];
+    constants.extend(utf8_constant("Broken"));
+    constants.extend([7, 0, 2]);
+    match Class::from_bytes(&class_with_constants(4, &constants, 3)) {
+        Err(ParseClassError::ConstantPoolValidationError(ConstantPoolValidationError::StringWithInvalidUTF8Index(reference))) => {
+            assert_eq!(reference, InvalidReference {
+                index: 1,
+                target: 9,
+                expected: ConstantKind::UTF8String,
+                actual: None,
+            });
+        }
+        other => panic!("expected invalid string error, got {other:?}"),
+    }
+}
+
+#[test]
+fn references_to_unusable_slot_are_rejected() {
+    let mut constants = vec![5, 0, 0, 0, 0, 0, 0, 0, 1, 8, 0, 2];
+    constants.extend(utf8_constant("Broken"));
+    constants.extend([7, 0, 4]);
+    match Class::from_bytes(&class_with_constants(6, &constants, 5)) {
+        Err(ParseClassError::ConstantPoolValidationError(ConstantPoolValidationError::StringWithInvalidUTF8Index(reference))) => {
+            assert_eq!(reference.actual, Some(ConstantKind::Unusable));
+        }
+        other => panic!("expected invalid string error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parse_invoke_dynamic_with_bootstrap_methods() {
+    let class = Class::from_file("tests/Lambda.class").unwrap();
+    let pool = &class.constant_pool;
+    let bootstrap_indices: Vec<u16> = pool.iter()
+        .filter_map(|(_, constant)| match constant {
+            Constant::InvokeDynamic { bootstrap_method_attr_index, .. } => Some(*bootstrap_method_attr_index),
+            _ => None
+        })
+        .collect();
+    assert_eq!(bootstrap_indices, vec![0, 1]);
+}
+
+#[test]
+fn invoke_dynamic_without_bootstrap_methods_is_rejected() {
+    let mut constants = vec![18, 0, 0, 0, 2, 12, 0, 3, 0, 4];
+    constants.extend(utf8_constant("run"));
+    constants.extend(utf8_constant("()V"));
+    constants.extend([7, 0, 6]);
+    constants.extend(utf8_constant("Dyn"));
+    match Class::from_bytes(&class_with_constants(7, &constants, 5)) {
+        Err(ParseClassError::ConstantPoolValidationError(error)) => {
+            assert_eq!(error, ConstantPoolValidationError::InvokeDynamicWithInvalidBootstrapMethodIndex {
+                index: 1,
+                bootstrap_method_attr_index: 0,
+            });
+        }
+        other => panic!("expected invalid bootstrap method index error, got {other:?}"),
+    }
+}
+
+#[test]
+fn bootstrap_method_arguments_must_be_loadable() {
+    let mut bytes = std::fs::read("tests/Lambda.class").unwrap();
+    // First bootstrap method: LambdaMetafactory.metafactory with arguments #38, #39 and #42
+    let entry = [0, 31, 0, 3, 0, 38, 0, 39, 0, 42];
+    let offset = bytes.windows(entry.len()).position(|w| w == entry).unwrap();
+    // Point the last argument at the "BootstrapMethods" utf8 string
+    bytes[offset + entry.len() - 1] = 30;
+    match Class::from_bytes(&bytes) {
+        Err(ParseClassError::ConstantPoolValidationError(error)) => {
+            assert_eq!(error, ConstantPoolValidationError::BootstrapMethodWithInvalidArgument {
+                bootstrap_method: 0,
+                target: 30,
+                actual: Some(ConstantKind::UTF8String),
+            });
+        }
+        other => panic!("expected invalid bootstrap argument error, got {other:?}"),
+    }
+}
+
+/// Changes the version of a class file built by [`class_with_attributes`]
+fn with_version(mut bytes: Vec<u8>, major: u16, minor: u16) -> Vec<u8> {
+    bytes[4..6].copy_from_slice(&minor.to_be_bytes());
+    bytes[6..8].copy_from_slice(&major.to_be_bytes());
+    bytes
+}
+
+#[test]
+fn versions_map_to_java_releases() {
+    let version = |major, minor| JavaVersion { major, minor };
+    assert_eq!(version(45, 3).release(), Some("1.1"));
+    assert_eq!(version(48, 0).release(), Some("1.4"));
+    assert_eq!(version(52, 0).release(), Some("8"));
+    assert_eq!(version(65, 0).release(), Some("21"));
+    assert_eq!(version(66, 0).release(), None);
+    assert_eq!(version(44, 0).release(), None);
+    assert!(version(61, 0xffff).is_preview());
+    assert!(!version(61, 0).is_preview());
+    assert_eq!(version(61, 0xffff).to_string(), "61.65535");
+}
+
+#[test]
+fn unsupported_versions_are_rejected() {
+    let mut constants = utf8_constant("Versioned");
+    constants.extend([7, 0, 1]);
+    let bytes = class_with_constants(3, &constants, 2);
+
+    let preview = Class::from_bytes(&with_version(bytes.clone(), 61, 0xffff)).unwrap();
+    assert!(preview.java_version.is_preview());
+    match Class::from_bytes(&with_version(bytes.clone(), 61, 3)) {
+        Err(ParseClassError::InvalidMinorVersion(version)) => assert_eq!(version, JavaVersion { major: 61, minor: 3 }),
+        other => panic!("expected invalid minor version error, got {other:?}"),
+    }
+    match Class::from_bytes(&with_version(bytes.clone(), 66, 0)) {
+        Err(ParseClassError::UnsupportedVersion { version, max_major }) => {
+            assert_eq!(version, JavaVersion { major: 66, minor: 0 });
+            assert_eq!(max_major, JavaVersion::LATEST_MAJOR);
+        }
+        other => panic!("expected unsupported version error, got {other:?}"),
+    }
+    let newer = ParseOptions { max_major: 66 };
+    assert!(Class::parse_with(&mut SliceCursor::new(&with_version(bytes.clone(), 66, 0)), &newer).is_ok());
+    let older = ParseOptions { max_major: 60 };
+    assert!(matches!(
+        Class::parse_with(&mut SliceCursor::new(&bytes), &older),
+        Err(ParseClassError::UnsupportedVersion { max_major: 60, .. })
+    ));
+    assert!(matches!(Class::from_bytes(&with_version(bytes, 44, 0)), Err(ParseClassError::UnsupportedVersion { .. })));
+}
+
+#[test]
+fn constants_are_gated_by_version() {
+    let mut constants = vec![17, 0, 0, 0, 2, 12, 0, 3, 0, 4];
+    constants.extend(utf8_constant("answer"));
+    constants.extend(utf8_constant("I"));
+    constants.extend([7, 0, 6]);
+    constants.extend(utf8_constant("Dyn"));
+    let bytes = with_version(class_with_constants(7, &constants, 5), 54, 0);
+    match Class::from_bytes(&bytes) {
+        Err(ParseClassError::ConstantPoolValidationError(error)) => {
+            assert_eq!(error, ConstantPoolValidationError::ConstantTooNew { index: 1, kind: ConstantKind::Dynamic, major: 54 });
+            assert_eq!(error.to_string(), "constant #1 is a CONSTANT_Dynamic, which needs version 55 but the class has version 54");
+        }
+        other => panic!("expected constant too new error, got {other:?}"),
+    }
+
+    let mut constants = utf8_constant("NotAModule");
+    constants.extend([7, 0, 1, 19, 0, 1]);
+    match Class::from_bytes(&class_with_constants(4, &constants, 2)) {
+        Err(ParseClassError::ConstantPoolValidationError(error)) => {
+            assert_eq!(error, ConstantPoolValidationError::ConstantOutsideModule { index: 3, kind: ConstantKind::Module });
+        }
+        other => panic!("expected module constant error, got {other:?}"),
+    }
+}
+
+#[test]
+fn newer_attributes_are_unknown_in_older_versions() {
+    let mut constants = utf8_constant("Host");
+    constants.extend([7, 0, 1]);
+    constants.extend(utf8_constant("NestMembers"));
+    // A NestMembers attribute listing the class itself
+    let attributes = [0, 1, 0, 3, 0, 0, 0, 4, 0, 1, 0, 2];
+    let bytes = class_with_attributes(4, &constants, 2, &attributes);
+    let class = Class::from_bytes(&bytes).unwrap();
+    assert_eq!(class.attributes[0].info, AttributeInfo::NestMembers(vec![2]));
+    let class = Class::from_bytes(&with_version(bytes, 54, 0)).unwrap();
+    assert_eq!(class.attributes[0].info, AttributeInfo::Unknown(vec![0, 1, 0, 2]));
+    assert_eq!(class.to_bytes().unwrap(), with_version(class_with_attributes(4, &constants, 2, &attributes), 54, 0));
+}
@@ -0,0 +1,26 @@
+use jerris::class::Class;
+use jerris::signature::{ClassSignature, FieldSignature, MethodSignature};
+
+#[test]
+fn fixture_signatures() {
+    let class = Class::from_file("tests/Generics.class").unwrap();
+    let pool = &class.constant_pool;
+    let class_signature = ClassSignature::parse(class.signature().unwrap().unwrap()).unwrap();
+    assert_eq!(class_signature.java_declaration(false), "<K extends Comparable<K>, V> extends Object implements Serializable");
+
+    let entries = FieldSignature::parse(class.fields[0].signature(pool).unwrap().unwrap()).unwrap();
+    assert_eq!(entries.java_name(false), "Map<K, List<? extends V>>");
+
+    let lookup = class.methods.iter().find(|m| m.name(pool).unwrap() == "lookup").unwrap();
+    let raw = lookup.signature(pool).unwrap().unwrap();
+    let lookup_signature = MethodSignature::parse(raw).unwrap();
+    assert_eq!(lookup_signature.to_string(), raw);
+    assert_eq!(lookup_signature.java_signature("lookup", true), "<E extends java.lang.Exception> V lookup(K, java.lang.Class<? super E>) throws E");
+}
+
+#[test]
+fn plain_members_have_no_signature() {
+    let class = Class::from_file("tests/Main.class").unwrap();
+    assert_eq!(class.signature().unwrap(), None);
+    assert_eq!(class.methods[0].signature(&class.constant_pool).unwrap(), None);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use jerris::attribute::AttributeInfo;
+use jerris::class::Class;
+use jerris::class_hierarchy::ClassHierarchyMap;
+use jerris::constant_pool_builder::ConstantPoolBuilder;
+use jerris::stack_map::{compress_frames, Frame, StackMapFrame, VerificationType};
+
+use common::{CODE_FIXTURES, hierarchy};
+
+fn stack_map_tables(class: &Class) -> Vec<Option<Vec<StackMapFrame>>> {
+    class.methods.iter()
+        .map(|method| method.code()?.attributes.iter().find_map(|attribute| match &attribute.info {
+            AttributeInfo::StackMapTable(frames) => Some(frames.clone()),
+            _ => None,
+        }))
+        .collect()
+}
+
+#[test]
+fn computes_same_frames_as_javac() {
+    let hierarchy = hierarchy();
+    for file in CODE_FIXTURES {
+        let mut class = Class::from_file(file).unwrap();
+        let javac = stack_map_tables(&class);
+        for method in &mut class.methods {
+            if let Some(code) = method.code_mut() {
+                code.attributes.retain(|attribute| !matches!(attribute.info, AttributeInfo::StackMapTable(_)));
+            }
+        }
+        class.compute_stack_maps(&hierarchy).unwrap();
+        assert_eq!(stack_map_tables(&class), javac, "{}", file);
+    }
+}
+
+#[test]
+fn drops_stack_map_table_without_frames() {
+    let hierarchy = ClassHierarchyMap::new();
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let pool_len = class.constant_pool.len();
+    class.compute_stack_maps(&hierarchy).unwrap();
+    assert!(stack_map_tables(&class).iter().all(Option::is_none));
+    assert_eq!(class.constant_pool.len(), pool_len);
+}
+
+#[test]
+fn decodes_frames_by_pc() {
+    let class = Class::from_file("tests/TryCatch.class").unwrap();
+    let pool = &class.constant_pool;
+    let read = class.methods.iter().find(|m| m.name(pool).unwrap() == "read").unwrap();
+    let initial = read.initial_frame(class.name().unwrap(), pool).unwrap();
+    let frames = read.code().unwrap().frames(&initial, pool).unwrap();
+    assert_eq!(frames.keys().copied().collect::<Vec<_>>(), vec![15, 28]);
+    assert_eq!(frames[&28], Frame {
+        locals: vec![VerificationType::Object("java/io/Reader".to_string())],
+        stack: vec![VerificationType::Object("java/lang/Throwable".to_string())],
+    });
+}
+
+#[test]
+fn decoded_frames_compress_back() {
+    for file in CODE_FIXTURES.into_iter().chain(["tests/Attributes.class"]) {
+        let class = Class::from_file(file).unwrap();
+        let name = class.name().unwrap();
+        let mut pool = ConstantPoolBuilder::from_pool(class.constant_pool.clone());
+        for method in &class.methods {
+            let table = match method.code().and_then(|code| code.stack_map_table()) {
+                Some(table) => table,
+                None => continue,
+            };
+            let initial = method.initial_frame(name, &class.constant_pool).unwrap();
+            let frames: Vec<(u32, Frame)> = method.code().unwrap().frames(&initial, &class.constant_pool).unwrap().into_iter().collect();
+            assert_eq!(compress_frames(&initial, &frames, &mut pool).unwrap(), table, "{}", file);
+        }
+        assert_eq!(pool.pool(), &class.constant_pool);
+    }
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use jerris::assembler::Assembler;
+use jerris::attribute::{Attribute, AttributeInfo};
+use jerris::class::Class;
+use jerris::class_hierarchy::ClassHierarchyError;
+use jerris::constant_pool_builder::ConstantPoolBuilder;
+use jerris::instruction::{Instruction, Opcode};
+use jerris::stack_map::{StackMapFrame, VerificationType, VerificationTypeInfo};
+use jerris::verifier::{Expected, VerifyError, VerifyErrorKind};
+
+use common::{CODE_FIXTURES, hierarchy, main_with};
+
+fn error(pc: Option<u32>, kind: VerifyErrorKind) -> VerifyError {
+    VerifyError { class: "Main".to_string(), method: "main([Ljava/lang/String;)V".to_string(), pc, kind }
+}
+
+#[test]
+fn javac_output_verifies() {
+    let hierarchy = hierarchy();
+    for file in CODE_FIXTURES.into_iter().chain(["tests/Attributes.class", "tests/Unicode.class"]) {
+        assert_eq!(Class::from_file(file).unwrap().verify(&hierarchy), vec![], "{}", file);
+    }
+}
+
+#[test]
+fn reports_type_mismatches() {
+    let class = main_with(|asm, _| {
+        asm.emit(Instruction::Aload0);
+        asm.emit(Instruction::Iconst1);
+        asm.emit(Instruction::Iadd);
+        asm.emit(Instruction::Return);
+    }, 2, 1);
+    let errors = class.verify(&hierarchy());
+    assert_eq!(errors, vec![error(Some(2), VerifyErrorKind::TypeMismatch {
+        expected: Expected::Type(VerificationType::Integer),
+        actual: VerificationType::Object("[Ljava/lang/String;".to_string()),
+    })]);
+    assert_eq!(errors[0].to_string(), "Main.main([Ljava/lang/String;)V at pc 2: expected int but found [Ljava/lang/String;");
+}
+
+#[test]
+fn checks_stack_and_locals_limits() {
+    let class = main_with(|asm, _| {
+        asm.emit(Instruction::Iconst0);
+        asm.emit(Instruction::Iconst1);
+        asm.emit(Instruction::Return);
+    }, 1, 1);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(1), VerifyErrorKind::StackOverflow { max_stack: 1 })]);
+
+    let class = main_with(|asm, _| {
+        asm.emit(Instruction::Lconst0);
+        asm.emit(Instruction::Lstore1);
+        asm.emit(Instruction::Return);
+    }, 2, 2);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(1), VerifyErrorKind::LocalOutOfRange { index: 1, max_locals: 2 })]);
+}
+
+#[test]
+fn checks_frames_at_branch_targets() {
+    let class = main_with(|asm, _| {
+        let end = asm.new_label();
+        asm.emit(Instruction::Iconst0);
+        asm.emit(Instruction::Istore1);
+        asm.emit(Instruction::Aload0);
+        asm.branch(Opcode::Ifnull, end).unwrap();
+        asm.emit(Instruction::Iconst0);
+        asm.emit(Instruction::Istore1);
+        asm.bind(end).unwrap();
+        asm.emit(Instruction::Return);
+    }, 1, 2);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(3), VerifyErrorKind::MissingFrame { target: 8 })]);
+
+    // A frame claiming the local is a float, while it holds an int
+    let mut class = class;
+    let mut pool = ConstantPoolBuilder::from_pool(class.constant_pool.clone());
+    let name_index = pool.utf8("StackMapTable").unwrap();
+    class.constant_pool = pool.build();
+    class.methods[1].code_mut().unwrap().attributes.push(Attribute {
+        attribute_name_index: name_index,
+        info: AttributeInfo::StackMapTable(vec![StackMapFrame::AppendFrame {
+            offset_delta: 8,
+            locals: vec![VerificationTypeInfo::Float],
+        }]),
+    });
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(3), VerifyErrorKind::IncompatibleLocal {
+        target: 8,
+        index: 1,
+        expected: VerificationType::Float,
+        actual: VerificationType::Integer,
+    })]);
+}
+
+#[test]
+fn checks_constructor_calls() {
+    let class = main_with(|asm, pool| {
+        let string = pool.class("java/lang/String").unwrap();
+        let object_init = pool.method_ref("java/lang/Object", "<init>", "()V").unwrap();
+        asm.emit(Instruction::New(string));
+        asm.emit(Instruction::Invokespecial(object_init));
+        asm.emit(Instruction::Return);
+    }, 1, 1);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(3), VerifyErrorKind::WrongConstructor {
+        expected: "java/lang/String".to_string(),
+        actual: "java/lang/Object".to_string(),
+    })]);
+
+    let class = main_with(|asm, pool| {
+        let string = pool.class("java/lang/String").unwrap();
+        asm.emit(Instruction::New(string));
+        asm.emit(Instruction::Astore0);
+        asm.emit(Instruction::Aload0);
+        asm.emit(Instruction::Athrow);
+    }, 1, 1);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(5), VerifyErrorKind::TypeMismatch {
+        expected: Expected::Type(VerificationType::Object("java/lang/Throwable".to_string())),
+        actual: VerificationType::Uninitialized(0),
+    })]);
+}
+
+#[test]
+fn constructors_initialize_this_before_returning() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let mut asm = Assembler::new();
+    asm.emit(Instruction::Return);
+    *class.methods[0].code_mut().unwrap() = asm.finish(0, 1).unwrap();
+    assert_eq!(class.verify(&hierarchy()), vec![VerifyError {
+        class: "Main".to_string(),
+        method: "<init>()V".to_string(),
+        pc: Some(0),
+        kind: VerifyErrorKind::UninitializedReturn,
+    }]);
+}
+
+#[test]
+fn checks_return_types() {
+    let class = main_with(|asm, _| {
+        asm.emit(Instruction::Iconst0);
+        asm.emit(Instruction::Ireturn);
+    }, 1, 1);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(1), VerifyErrorKind::WrongReturn { return_type: "void".to_string() })]);
+}
+
+#[test]
+fn subroutines_are_unsupported_before_version_50() {
+    let mut class = main_with(|asm, _| {
+        let subroutine = asm.new_label();
+        asm.branch(Opcode::Jsr, subroutine).unwrap();
+        asm.emit(Instruction::Return);
+        asm.bind(subroutine).unwrap();
+        asm.emit(Instruction::Astore1);
+        asm.emit(Instruction::Ret(1));
+    }, 1, 2);
+    assert_eq!(class.verify(&hierarchy()), vec![error(Some(0), VerifyErrorKind::Subroutine)]);
+    class.java_version.major = 49;
+    let old = Class::from_bytes(&class.to_bytes().unwrap()).unwrap();
+    assert_eq!(old.verify(&hierarchy()), vec![error(None, VerifyErrorKind::UnsupportedSubroutine { pc: 0 })]);
+}
+
+#[test]
+fn cyclic_hierarchies_are_reported() {
+    let class = main_with(|asm, pool| {
+        let a = pool.class("A").unwrap();
+        asm.emit(Instruction::AconstNull);
+        asm.emit(Instruction::Checkcast(a));
+        asm.emit(Instruction::Athrow);
+    }, 1, 1);
+    let mut hierarchy = hierarchy();
+    hierarchy.insert("A", Some("B"), false);
+    hierarchy.insert("B", Some("A"), false);
+    assert_eq!(class.verify(&hierarchy), vec![error(Some(4), VerifyErrorKind::ClassHierarchyError(ClassHierarchyError::CyclicHierarchy("A".to_string())))]);
+}
@@ -0,0 +1,58 @@
+use std::fs;
+
+use jerris::class::Class;
+use jerris::constant_pool::Constant;
+use jerris::constant_pool_builder::ConstantPoolBuilder;
+
+#[test]
+fn unmodified_classes_round_trip() {
+    let mut count = 0;
+    for entry in fs::read_dir("tests").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "class") {
+            continue;
+        }
+        let bytes = fs::read(&path).unwrap();
+        let class = Class::from_bytes(&bytes).unwrap();
+        assert_eq!(class.to_bytes().unwrap(), bytes, "{} doesn't round trip", path.display());
+        count += 1;
+    }
+    assert!(count > 5);
+}
+
+#[test]
+fn write_to_matches_to_bytes() {
+    let class = Class::from_file("tests/Wide.class").unwrap();
+    let mut out = vec![];
+    class.write_to(&mut out).unwrap();
+    assert_eq!(out, class.to_bytes().unwrap());
+}
+
+#[test]
+fn modified_class_parses_back() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let index = class.constant_pool.iter()
+        .find(|(_, constant)| matches!(constant, Constant::UTF8String(s) if s == "Hello World!"))
+        .map(|(index, _)| index)
+        .unwrap();
+    class.constant_pool.set(index, Constant::UTF8String("Hallo, \u{0}Wêlt 🌍".to_string())).unwrap();
+    class.methods[1].code_mut().unwrap().max_stack = 7;
+    let reparsed = Class::from_bytes(&class.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed, class);
+}
+
+#[test]
+fn builder_keeps_existing_indices() {
+    let mut class = Class::from_file("tests/Main.class").unwrap();
+    let original = class.constant_pool.clone();
+    let mut builder = ConstantPoolBuilder::from_pool(class.constant_pool.clone());
+    let object = builder.class("java/lang/Object").unwrap();
+    assert_eq!(original.get_class_name(object), Ok("java/lang/Object"));
+    let added = builder.string("instrumented").unwrap();
+    assert_eq!(added as usize, original.len() + 2);
+    class.constant_pool = builder.build();
+    let reparsed = Class::from_bytes(&class.to_bytes().unwrap()).unwrap();
+    for (index, constant) in original.iter() {
+        assert_eq!(reparsed.constant_pool.get(index), Ok(constant));
+    }
+}